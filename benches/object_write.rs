@@ -0,0 +1,52 @@
+// Writes 1000 small blobs one object at a time and through an ObjectBatch,
+// with and without syncing the batch:
+//
+//     cargo bench --bench object_write
+use mygit::object::{Object, ObjectBatch};
+use std::env;
+use std::fs;
+use std::time::{Duration, Instant};
+
+const BLOBS: usize = 1000;
+
+// Each run writes into a fresh objects directory so none of its blobs are
+// already stored.
+fn time(dir: &std::path::Path, run: &str, write: impl FnOnce(Vec<Object>)) -> Duration {
+    let _ = fs::remove_dir_all(dir.join(".git/objects"));
+    fs::create_dir_all(dir.join(".git/objects")).unwrap();
+    let blobs = (0..BLOBS)
+        .map(|n| Object::Blob(format!("{} blob {}\n", run, n).into_bytes()))
+        .collect();
+    let start = Instant::now();
+    write(blobs);
+    start.elapsed()
+}
+
+fn main() {
+    let dir = env::temp_dir().join(format!("mygit-bench-object-write-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    env::set_current_dir(&dir).unwrap();
+
+    let individually = time(&dir, "individual", |blobs| {
+        for blob in blobs {
+            blob.write().unwrap();
+        }
+    });
+    println!(
+        "{:<14} {} blobs in {:?}",
+        "individually:", BLOBS, individually
+    );
+    for sync in [false, true] {
+        let batched = time(&dir, "batched", |blobs| {
+            let mut batch = ObjectBatch::new();
+            for blob in &blobs {
+                batch.add(blob).unwrap();
+            }
+            batch.flush(sync).unwrap();
+        });
+        let label = if sync { "batched, sync:" } else { "batched:" };
+        println!("{:<14} {} blobs in {:?}", label, BLOBS, batched);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
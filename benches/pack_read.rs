@@ -0,0 +1,72 @@
+// Extracts every object of a large pack, reading it through a memory
+// mapping and through buffered file reads. Run with and without the `mmap`
+// feature:
+//
+//     cargo bench --bench pack_read
+//     cargo bench --bench pack_read --features mmap
+use mygit::object::{self, Object};
+use mygit::pack::{self, Pack, PackIndex};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const OBJECTS: usize = 5000;
+const ROUNDS: usize = 5;
+
+fn extract_all(pack: &Pack, index: &PackIndex, objects: &Path) -> usize {
+    index
+        .hashes()
+        .iter()
+        .map(|hash| pack.read(index, hash, objects).unwrap().1.len())
+        .sum()
+}
+
+// The fastest of a few rounds, so that a cold cache does not count.
+fn fastest(mut round: impl FnMut() -> usize) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(round());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let dir = env::temp_dir().join(format!("mygit-bench-pack-read-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join(".git/objects/pack")).unwrap();
+    env::set_current_dir(&dir).unwrap();
+
+    let hashes: Vec<String> = (0..OBJECTS)
+        .map(|n| {
+            let content = format!("object {}\n", n).repeat(200);
+            Object::Blob(content.into_bytes()).write().unwrap()
+        })
+        .collect();
+    let name = pack::write_pack(&hashes, ".git/objects/pack/pack").unwrap();
+    object::prune_packed(false).unwrap();
+    let index = PackIndex::open(format!(".git/objects/pack/pack-{}.idx", name)).unwrap();
+
+    let objects = Path::new(".git/objects");
+    let buffered = Pack::open_buffered(index.pack_path()).unwrap();
+    let time = fastest(|| extract_all(&buffered, &index, objects));
+    println!("buffered: {} objects in {:?}", OBJECTS, time);
+    if cfg!(feature = "mmap") {
+        let mapped = Pack::open(index.pack_path()).unwrap();
+        let time = fastest(|| extract_all(&mapped, &index, objects));
+        println!("mapped:   {} objects in {:?}", OBJECTS, time);
+    }
+
+    // Through the object store, which opens each pack once per process.
+    let time = fastest(|| {
+        hashes
+            .iter()
+            .map(|hash| object::read_raw(hash).unwrap().1.len())
+            .sum()
+    });
+    println!("read_raw: {} objects in {:?}", OBJECTS, time);
+    fs::remove_dir_all(&dir).unwrap();
+}
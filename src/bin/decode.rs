@@ -1,4 +1,5 @@
 use flate2::read::ZlibDecoder;
+use mygit::repository::Repository;
 use std::env;
 use std::fs;
 use std::io::prelude::*;
@@ -8,9 +9,13 @@ use std::io::Read;
 fn main() {
     let args: Vec<String> = env::args().collect();
     let hash = &args[1];
-    let path = format!(".git/objects/{}/{}", &hash[..2], &hash[2..]);
+    let path = Repository::current()
+        .unwrap()
+        .objects_dir()
+        .join(&hash[..2])
+        .join(&hash[2..]);
     let mut reader = std::io::BufReader::new(ZlibDecoder::new(fs::File::open(path).unwrap()));
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf).unwrap();
-    stdout().write(buf.as_slice()).unwrap();
+    stdout().write_all(buf.as_slice()).unwrap();
 }
@@ -1,84 +1,2261 @@
-use dirs;
 use ini::Ini;
+use mygit::bisect::{self, Step};
+use mygit::checkout;
+use mygit::color::{self, ColorMode};
+use mygit::config;
+use mygit::diff;
+use mygit::editor;
+use mygit::fsck;
 use mygit::ignore::Ignore;
-use mygit::object::create_tree;
+use mygit::index::{Index, IndexEntry};
+use mygit::notes;
+use mygit::object;
+use mygit::object::FileMode;
 use mygit::object::Object;
 use mygit::object::Timestamp;
 use mygit::object::User;
+use mygit::pack::{self, PackIndex};
+use mygit::pager::Pager;
+use mygit::patch;
+use mygit::reflog;
+use mygit::refs;
+use mygit::repository::Repository;
+use mygit::revision::{self, Resolved};
+use mygit::revwalk::{self, Order};
+use mygit::show_branch;
+use mygit::stash;
+use mygit::status;
+use mygit::subtree;
+use mygit::text;
+use mygit::trailer;
+use mygit::transport;
+use std::collections::HashSet;
 use std::env;
+use std::fmt;
 use std::fs;
+use std::io::{self, stdin, stdout, BufRead, IsTerminal, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 
-fn get_user() -> Option<User> {
-    let path = dirs::home_dir().unwrap().join(".gitconfig");
-    let config = Ini::load_from_file(path.to_str().unwrap()).unwrap();
-    let email = config.get_from(Some("user"), "email")?;
-    let name = config.get_from(Some("user"), "name")?;
-    Some(User::new(name, email))
+// A failure to report to the user as `fatal: <message>`.
+#[derive(Debug, Clone)]
+struct Fatal(String);
+
+impl fmt::Display for Fatal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Fatal {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// Arguments a command cannot make sense of, reported with the command's
+// usage line.
+#[derive(Debug, Clone)]
+struct Usage(&'static str);
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "usage: {}", self.0)
+    }
+}
+
+impl std::error::Error for Usage {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// The argument at `i`, or a usage error when the command line is too short.
+fn arg<'a>(args: &'a [String], i: usize, usage: &'static str) -> Result<&'a str, Usage> {
+    args.get(i).map(String::as_str).ok_or(Usage(usage))
+}
+
+// The identity to record: $GIT_AUTHOR_NAME and $GIT_AUTHOR_EMAIL when set,
+// the [user] section of ~/.gitconfig otherwise.
+fn get_user() -> Result<User, Box<dyn std::error::Error + 'static>> {
+    let name = env::var("GIT_AUTHOR_NAME").ok();
+    let email = env::var("GIT_AUTHOR_EMAIL").ok();
+    if let (Some(name), Some(email)) = (&name, &email) {
+        return Ok(User::new(name, email));
+    }
+
+    let home = dirs::home_dir().ok_or(Fatal(
+        "could not find the home directory to read .gitconfig from".to_string(),
+    ))?;
+    let path = home.join(".gitconfig");
+    let config = match Ini::load_from_file(&path) {
+        Ok(config) => config,
+        Err(ini::Error::Io(err)) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(Box::new(Fatal(format!(
+                "{} does not exist; create it with user.name and user.email set",
+                path.display()
+            ))));
+        }
+        Err(err) => {
+            return Err(Box::new(Fatal(format!(
+                "could not read {}: {}",
+                path.display(),
+                err
+            ))));
+        }
+    };
+    let key = |key: &str| {
+        config
+            .get_from(Some("user"), key)
+            .map(str::to_string)
+            .ok_or_else(|| Fatal(format!("user.{} is not set in {}", key, path.display())))
+    };
+    let name = match name {
+        Some(name) => name,
+        None => key("name")?,
+    };
+    let email = match email {
+        Some(email) => email,
+        None => key("email")?,
+    };
+    Ok(User::new(&name, &email))
+}
+
+fn write_commit(
+    out: &mut impl Write,
+    hash: &str,
+    commit: &Object,
+    from: Option<&str>,
+    color: bool,
+) -> io::Result<()> {
+    let Object::Commit {
+        parents,
+        author,
+        author_timestamp,
+        message,
+        ..
+    } = commit
+    else {
+        return Ok(());
+    };
+
+    let mut header = format!("commit {}", hash);
+    if let Some(from) = from {
+        header.push_str(&format!(" (from {})", from));
+    }
+    writeln!(out, "{}", color::paint(&header, color::YELLOW, color))?;
+    if parents.len() > 1 {
+        let short: Vec<&str> = parents.iter().map(|p| &p[..7]).collect();
+        writeln!(out, "Merge: {}", short.join(" "))?;
+    }
+    writeln!(out, "Author: {}", author)?;
+    writeln!(out, "Date:   {}", author_timestamp.date())?;
+    writeln!(out)?;
+    for line in message.lines() {
+        writeln!(out, "    {}", line)?;
+    }
+    Ok(())
+}
+
+// Creates refs/heads/<name> at `start`, or at HEAD, refusing to move an
+// existing branch. Returns the ref and the commit it now points at.
+fn create_branch(
+    name: &str,
+    start: Option<&str>,
+) -> Result<(String, String), Box<dyn std::error::Error + 'static>> {
+    let start = start.unwrap_or("HEAD");
+    let branch = format!("refs/heads/{}", name);
+    if !refs::is_valid_name(&branch) {
+        return Err(Box::new(Fatal(format!(
+            "'{}' is not a valid branch name",
+            name
+        ))));
+    }
+    if refs::read_ref(&branch)?.is_some() {
+        return Err(Box::new(Fatal(format!(
+            "a branch named '{}' already exists",
+            name
+        ))));
+    }
+    let hash = revision::resolve(&format!("{}^{{commit}}", start))?;
+    refs::update_ref(&branch, &hash)?;
+    if let Ok(user) = get_user() {
+        let message = format!("branch: Created from {}", start);
+        reflog::append(&branch, None, &hash, &user, &message)?;
+    }
+    Ok((branch, hash))
 }
 
+// Points HEAD at `hash`, through `branch` when given and detached otherwise,
+// and moves the working directory over to the commit's tree. Local changes
+// in the way stop the switch unless `force` discards them.
+fn switch_to(
+    hash: &str,
+    target: &str,
+    branch: Option<&str>,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let Object::Commit { tree, .. } = Object::from_hash(hash)? else {
+        return Err(Box::new(Fatal(format!("{} is not a commit", target))));
+    };
+    let repo = Repository::open(".")?;
+    let current = match repo.head_commit()? {
+        Some((old, Object::Commit { tree, .. })) => Some((old, tree)),
+        _ => None,
+    };
+    match &current {
+        Some((_, current)) => checkout::switch_tree(current, &tree, force)?,
+        None => checkout::checkout_tree(&tree)?,
+    }
+    Index::reset_to(&tree)?;
+
+    let old_head = refs::read_ref("HEAD")?.unwrap_or_default();
+    let from = old_head
+        .strip_prefix("ref: refs/heads/")
+        .unwrap_or(&old_head)
+        .to_string();
+    if let (Some((old, _)), Some(user)) = (current, get_user().ok()) {
+        let message = format!("checkout: moving from {} to {}", from, target);
+        reflog::append("HEAD", Some(&old), hash, &user, &message)?;
+    }
+    match branch {
+        Some(branch) => refs::update_symbolic_ref("HEAD", branch),
+        None => refs::update_ref("HEAD", hash),
+    }
+}
+
+// Maps a remote name from the config, or a bare path, to the name its
+// remote-tracking refs live under and the path to read from.
+fn remote(arg: &str) -> (String, String) {
+    if let Some(url) = config::get(&format!("remote \"{}\"", arg), "url") {
+        return (arg.to_string(), url);
+    }
+    let name = Path::new(arg.trim_end_matches('/'))
+        .file_name()
+        .map(|name| name.to_string_lossy().trim_end_matches(".git").to_string())
+        .unwrap_or_else(|| arg.to_string());
+    (name, arg.to_string())
+}
+
+// The text the editor starts with when `commit` is run without -m: an empty
+// line for the message, then commented-out instructions and the changes
+// the commit will record. With `scissors` the instructions sit below a
+// scissors line, since comments above it would be kept.
+fn commit_template(
+    status: &status::Status,
+    scissors: bool,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let mut template = if scissors {
+        format!(
+            "\n{}\n# Do not modify or remove the line above.\n\
+             # Everything below it will be ignored.\n#\n",
+            editor::SCISSORS
+        )
+    } else {
+        String::from(
+            "\n# Please enter the commit message for your changes. Lines starting\n\
+             # with '#' will be ignored, and an empty message aborts the commit.\n#\n",
+        )
+    };
+    match refs::read_ref("HEAD")?
+        .unwrap_or_default()
+        .strip_prefix("ref: refs/heads/")
+    {
+        Some(branch) => template.push_str(&format!("# On branch {}\n", branch)),
+        None => template.push_str("# HEAD detached\n"),
+    }
+    template.push_str("# Changes to be committed:\n");
+    let mut changes: Vec<(&str, &String)> = status
+        .modified
+        .iter()
+        .map(|path| ("modified:   ", path))
+        .chain(status.untracked.iter().map(|path| ("new file:   ", path)))
+        .chain(status.deleted.iter().map(|path| ("deleted:    ", path)))
+        .collect();
+    changes.sort_by_key(|(_, path)| *path);
+    for (label, path) in changes {
+        template.push_str(&format!("#\t{}{}\n", label, path));
+    }
+    template.push_str("#\n");
+    Ok(template)
+}
+
+// Creates whatever a git directory is missing, leaving anything already
+// there alone. A new HEAD points at `branch`. Returns whether the
+// directory existed before.
+fn init_gitdir(gitdir: &Path, branch: &str) -> io::Result<bool> {
+    let existed = gitdir.is_dir();
+    for dir in ["objects", "refs/heads", "refs/tags"] {
+        fs::create_dir_all(gitdir.join(dir))?;
+    }
+    let head = gitdir.join("HEAD");
+    if !head.exists() {
+        fs::write(head, format!("ref: refs/heads/{}\n", branch))?;
+    }
+    Ok(existed)
+}
+
+// Adds every entry below `tree` except the trees themselves to `listed`,
+// keyed for sorting by its full path under `prefix`.
+fn list_tree_recursive(
+    tree: &str,
+    prefix: &str,
+    listed: &mut Vec<(String, String, object::Entry)>,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let Object::Tree(entries) = Object::from_hash(tree)? else {
+        return Err(Box::new(Fatal(format!("{} is not a tree", tree))));
+    };
+    for entry in entries {
+        let path = format!("{}/{}", prefix, entry.filename());
+        if entry.is_tree() {
+            list_tree_recursive(entry.hash(), &path, listed)?;
+        } else {
+            listed.push((path.clone(), path, entry));
+        }
+    }
+    Ok(())
+}
+
+// Joins a path given relative to `dir` onto it, resolving `.` and `..`. A
+// path naming a directory, or none at all, keeps a trailing '/' so that the
+// directory's entries are listed rather than the directory itself.
+fn join_path(dir: &str, path: &str) -> String {
+    let mut components: Vec<&str> = dir.split('/').filter(|c| !c.is_empty()).collect();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    let joined = components.join("/");
+    let is_dir = matches!(path.rsplit('/').next(), Some("" | "." | ".."));
+    if is_dir && !joined.is_empty() {
+        joined + "/"
+    } else {
+        joined
+    }
+}
+
+// Spells a path from the root as seen from `dir`, going up with `..` where
+// it lies outside.
+fn relative_path(dir: &str, path: &str) -> String {
+    let dir: Vec<&str> = dir.split('/').filter(|c| !c.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').collect();
+    let common = dir
+        .iter()
+        .zip(&path)
+        .take_while(|(a, b)| a == b)
+        .count()
+        .min(path.len() - 1);
+    let mut relative = vec![".."; dir.len() - common];
+    relative.extend(&path[common..]);
+    relative.join("/")
+}
+
+// Checks out the next commit a bisection wants tested, or reports the first
+// bad commit once the good and bad bounds have met.
+fn bisect_next() -> Result<(), Box<dyn std::error::Error + 'static>> {
+    match bisect::next()? {
+        Step::NeedBounds => {}
+        Step::Test { hash, remaining } => {
+            let steps = usize::BITS - remaining.leading_zeros();
+            println!(
+                "Bisecting: {} revisions left to test after this (roughly {} steps)",
+                remaining, steps
+            );
+            switch_to(&hash, &hash, None, false)?;
+            let Object::Commit { message, .. } = Object::from_hash(&hash)? else {
+                return Err(Box::new(Fatal(format!("{} is not a commit", hash))));
+            };
+            println!("[{}] {}", hash, message.lines().next().unwrap_or(""));
+        }
+        Step::Found(hash) => {
+            println!("{} is the first bad commit", hash);
+            let commit = Object::from_hash(&hash)?;
+            write_commit(&mut stdout(), &hash, &commit, None, false)?;
+        }
+    }
+    Ok(())
+}
+
+fn is_ancestor(
+    ancestor: &str,
+    descendant: &str,
+) -> Result<bool, Box<dyn std::error::Error + 'static>> {
+    Ok(revwalk::walk(&[descendant.to_string()], Order::Topo)?
+        .iter()
+        .any(|(hash, _)| hash == ancestor))
+}
+
+// Upper bounds of the object size buckets printed by `count-objects -H`.
+const SIZE_BUCKETS: [(u64, &str); 5] = [
+    (1 << 10, "1 KiB"),
+    (10 << 10, "10 KiB"),
+    (100 << 10, "100 KiB"),
+    (1 << 20, "1 MiB"),
+    (10 << 20, "10 MiB"),
+];
+
+// Formats a byte count the way git's --human-readable options do.
+fn human_bytes(bytes: u64) -> String {
+    if bytes > 1 << 30 {
+        format!(
+            "{}.{:02} GiB",
+            bytes >> 30,
+            (bytes & ((1 << 30) - 1)) / 10737419
+        )
+    } else if bytes > 1 << 20 {
+        format!(
+            "{}.{:02} MiB",
+            bytes >> 20,
+            (bytes & ((1 << 20) - 1)) / 10486
+        )
+    } else if bytes > 1 << 10 {
+        format!("{}.{:02} KiB", bytes >> 10, (bytes & ((1 << 10) - 1)) / 11)
+    } else if bytes == 1 {
+        "1 byte".to_string()
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+const USAGE: &str =
+    "mygit [--no-pager] [--no-replace-objects] [--dereference-symlinks] <command> [<args>]";
+
+// Usage errors exit with 129 and everything else with 128, as in git.
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args[1] == "init" {
-        fs::create_dir(".git").unwrap();
-        fs::create_dir(".git/objects").unwrap();
-        fs::create_dir(".git/refs").unwrap();
-        fs::write(".git/HEAD", "ref: refs/heads/main\n").unwrap();
-        println!("Initialized git directory")
-    } else if args[1] == "cat-file" && args[2] == "-p" {
-        let object = Object::from_hash(&args[3]).unwrap();
-        match object {
-            Object::Blob(data) => {
-                println!("{}", String::from_utf8(data).unwrap());
+    if let Err(err) = run() {
+        // The reader went away, as `mygit log | head` does; nobody is left
+        // to tell.
+        if err
+            .downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe)
+        {
+            return;
+        }
+        if err.is::<Usage>() {
+            eprintln!("{}", err);
+            std::process::exit(129);
+        }
+        eprintln!("fatal: {}", err);
+        std::process::exit(128);
+    }
+}
+
+// Resolves a cat-file operand, following symlinks inside the tree on the
+// way to a `<tree-ish>:<path>` when asked to.
+fn cat_file_resolve(
+    name: &str,
+    follow_symlinks: bool,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    if follow_symlinks {
+        Ok(revision::parse_following_symlinks(name)?.hash().to_string())
+    } else {
+        revision::resolve(name)
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let mut args: Vec<String> = env::args().collect();
+    let mut paging = true;
+    while args.len() > 1 && args[1].starts_with("--") {
+        if args[1] == "--no-pager" {
+            paging = false;
+        } else if args[1] == "--no-replace-objects" {
+            object::set_replace_objects(false);
+        } else if args[1] == "--dereference-symlinks" {
+            object::set_dereference_symlinks(true);
+        } else {
+            break;
+        }
+        args.remove(1);
+    }
+    let Some(command) = args.get(1).cloned() else {
+        return Err(Box::new(Usage(USAGE)));
+    };
+    // `cat-file --follow-symlinks` may come before or after the mode.
+    let follow_symlinks =
+        command == "cat-file" && args[2..].iter().any(|arg| arg == "--follow-symlinks");
+    args.retain(|arg| !follow_symlinks || arg != "--follow-symlinks");
+    // So may `checkout --force` come before or after `-b`.
+    let force =
+        command == "checkout" && args[2..].iter().any(|arg| arg == "-f" || arg == "--force");
+    args.retain(|arg| !force || (arg != "-f" && arg != "--force"));
+    let sub = args.get(2).map(String::as_str);
+    if command == "init" {
+        const INIT_USAGE: &str = "mygit init [-b <branch>] [<directory>]";
+        let mut branch = None;
+        let mut directory = None;
+        let mut i = 2;
+        while i < args.len() {
+            if args[i] == "-b" {
+                branch = Some(arg(&args, i + 1, INIT_USAGE)?);
+                i += 1;
+            } else if let Some(name) = args[i].strip_prefix("--initial-branch=") {
+                branch = Some(name);
+            } else if directory.is_none() && !args[i].starts_with('-') {
+                directory = Some(args[i].as_str());
+            } else {
+                return Err(Box::new(Usage(INIT_USAGE)));
             }
-            _ => panic!("not a blob"),
+            i += 1;
         }
-    } else if args[1] == "hash-object" && args[2] == "-w" {
-        let filepath = env::current_dir().unwrap().join(&args[3]);
-        let data = fs::read(filepath).unwrap();
-        let hash = Object::Blob(data).write().unwrap();
-        println!("{}", hash);
-    } else if args[1] == "ls-tree" {
-        let object = Object::from_hash(&args[2]).unwrap();
+        if let Some(name) =
+            branch.filter(|name| name.is_empty() || name.contains(char::is_whitespace))
+        {
+            return Err(Box::new(Fatal(format!(
+                "invalid initial branch name: '{}'",
+                name
+            ))));
+        }
+        let gitdir = Path::new(directory.unwrap_or(".")).join(".git");
+        if init_gitdir(&gitdir, branch.unwrap_or("main"))? {
+            if branch.is_some() {
+                eprintln!("warning: re-init: ignored --initial-branch");
+            }
+            println!("Reinitialized existing git directory")
+        } else {
+            println!("Initialized git directory")
+        }
+    } else if command == "clone" {
+        let bare = args[2..].iter().any(|arg| arg == "--bare");
+        let hardlink = !args[2..].iter().any(|arg| arg == "--no-hardlinks");
+        let positional: Vec<&String> = args[2..]
+            .iter()
+            .filter(|arg| !arg.starts_with("--"))
+            .collect();
+        let &[url, dst] = positional.as_slice() else {
+            return Err(Box::new(Usage(
+                "mygit clone [--bare] [--no-hardlinks] <repository> <directory>",
+            )));
+        };
+        let dst = Path::new(dst);
+        let src = transport::gitdir(Path::new(url))?;
+        if dst.exists() && fs::read_dir(dst)?.next().is_some() {
+            return Err(Box::new(Fatal(format!(
+                "destination path '{}' already exists and is not an empty directory",
+                dst.display()
+            ))));
+        }
+        if bare {
+            println!("Cloning into bare repository '{}'...", dst.display());
+        } else {
+            println!("Cloning into '{}'...", dst.display());
+        }
+        // A bare clone is its own git directory and has no working tree.
+        let gitdir = if bare {
+            dst.to_path_buf()
+        } else {
+            dst.join(".git")
+        };
+        init_gitdir(&gitdir, "main")?;
+
+        let remote_refs = transport::list_refs(&src)?;
+        let head = transport::read_ref(&src, "HEAD")?;
+        let mut tips: Vec<String> = remote_refs.iter().map(|(_, hash)| hash.clone()).collect();
+        tips.extend(head.clone());
+        transport::copy_objects(&src, &gitdir, &tips, hardlink)?;
+
+        for (refname, hash) in &remote_refs {
+            let local = match refname.strip_prefix("refs/heads/") {
+                Some(branch) if !bare => format!("refs/remotes/origin/{}", branch),
+                _ => refname.clone(),
+            };
+            refs::update_ref_in(&gitdir, &local, hash)?;
+        }
+
+        let url = fs::canonicalize(url)?.to_string_lossy().to_string();
+        let mut config = Ini::new();
+        config
+            .with_section(Some("remote \"origin\""))
+            .set("url", &url);
+        if bare {
+            config.with_section(Some("core")).set("bare", "true");
+        } else {
+            config
+                .with_section(Some("remote \"origin\""))
+                .set("fetch", "+refs/heads/*:refs/remotes/origin/*");
+        }
+        let branch = transport::head_branch(&src)?
+            .and_then(|target| target.strip_prefix("refs/heads/").map(str::to_string))
+            .filter(|branch| {
+                remote_refs
+                    .iter()
+                    .any(|(name, _)| *name == format!("refs/heads/{}", branch))
+            });
+        match (&branch, &head) {
+            (Some(branch), Some(_)) if bare => {
+                refs::update_symbolic_ref_in(&gitdir, "HEAD", &format!("refs/heads/{}", branch))?
+            }
+            (Some(branch), Some(hash)) => {
+                config
+                    .with_section(Some(format!("branch \"{}\"", branch)))
+                    .set("remote", "origin")
+                    .set("merge", format!("refs/heads/{}", branch));
+                refs::update_symbolic_ref_in(
+                    &gitdir,
+                    "refs/remotes/origin/HEAD",
+                    &format!("refs/remotes/origin/{}", branch),
+                )?;
+                let local = format!("refs/heads/{}", branch);
+                refs::update_ref_in(&gitdir, &local, hash)?;
+                refs::update_symbolic_ref_in(&gitdir, "HEAD", &local)?;
+            }
+            (None, Some(hash)) => refs::update_ref_in(&gitdir, "HEAD", hash)?,
+            (_, None) => println!("warning: You appear to have cloned an empty repository."),
+        }
+        config.write_to_file(gitdir.join("config"))?;
+
+        if let (Some(hash), false) = (head, bare) {
+            env::set_current_dir(dst)?;
+            let Object::Commit { tree, .. } = Object::from_hash(&hash)? else {
+                return Err(Box::new(Fatal(format!("HEAD of {} is not a commit", url))));
+            };
+            checkout::checkout_tree(&tree)?;
+        }
+    } else if command == "branch" {
+        const BRANCH_USAGE: &str =
+            "mygit branch [--set-upstream-to=<upstream>] [<branch> [<start-point>]]";
+        let mut upstream = None;
+        let mut positional = Vec::new();
+        let mut i = 2;
+        while i < args.len() {
+            if let Some(value) = args[i].strip_prefix("--set-upstream-to=") {
+                upstream = Some(value.to_string());
+            } else if args[i] == "-u" || args[i] == "--set-upstream-to" {
+                upstream = Some(arg(&args, i + 1, BRANCH_USAGE)?.to_string());
+                i += 1;
+            } else {
+                positional.push(args[i].clone());
+            }
+            i += 1;
+        }
+        let Some(upstream) = upstream else {
+            match positional.as_slice() {
+                [] => {
+                    let current = match refs::read_ref("HEAD")? {
+                        Some(head) => match refs::parse_target("HEAD", &head)? {
+                            refs::Target::Symbolic(name) => Some(name),
+                            refs::Target::Hash(hash) => {
+                                println!("* (HEAD detached at {})", &hash[..7]);
+                                None
+                            }
+                        },
+                        None => None,
+                    };
+                    for branch in refs::list("refs/heads")? {
+                        let marker = if current.as_ref() == Some(&branch) {
+                            '*'
+                        } else {
+                            ' '
+                        };
+                        println!("{} {}", marker, &branch["refs/heads/".len()..]);
+                    }
+                }
+                [name] => {
+                    create_branch(name, None)?;
+                }
+                [name, start] => {
+                    create_branch(name, Some(start))?;
+                }
+                _ => return Err(Box::new(Usage(BRANCH_USAGE))),
+            }
+            return Ok(());
+        };
+
+        let branch = match positional.first() {
+            Some(branch) => branch.clone(),
+            None => refs::read_ref("HEAD")?
+                .and_then(|head| head.strip_prefix("ref: refs/heads/").map(str::to_string))
+                .ok_or(Fatal("HEAD does not point to a branch".to_string()))?,
+        };
+        if refs::read_ref(&format!("refs/heads/{}", branch))?.is_none() {
+            return Err(Box::new(Fatal(format!(
+                "branch '{}' does not exist",
+                branch
+            ))));
+        }
+        // A remote-tracking branch is tracked through its remote; any other
+        // branch is local, which git spells as the remote `.`.
+        let (remote, merge) = match upstream.split_once('/') {
+            Some((remote, merge))
+                if refs::read_ref(&format!("refs/remotes/{}", upstream))?.is_some() =>
+            {
+                (remote.to_string(), merge.to_string())
+            }
+            _ if refs::read_ref(&format!("refs/heads/{}", upstream))?.is_some() => {
+                (".".to_string(), upstream.clone())
+            }
+            _ => {
+                return Err(Box::new(Fatal(format!(
+                    "the requested upstream branch '{}' does not exist",
+                    upstream
+                ))))
+            }
+        };
+
+        let section = format!("branch \"{}\"", branch);
+        config::set(&section, "remote", &remote)?;
+        config::set(&section, "merge", &format!("refs/heads/{}", merge))?;
+        println!("branch '{}' set up to track '{}'.", branch, upstream);
+    } else if command == "add" && sub == Some("-p") {
+        let mut index = Index::load()?;
+        let pathspecs = &args[3..];
+        let tracked: Vec<IndexEntry> = index
+            .entries()
+            .iter()
+            .filter(|entry| entry.stage() == 0)
+            .filter(|entry| {
+                pathspecs.is_empty()
+                    || pathspecs.iter().any(|spec| {
+                        let spec = spec.trim_end_matches('/');
+                        entry.path == spec || entry.path.starts_with(&format!("{}/", spec))
+                    })
+            })
+            .cloned()
+            .collect();
+
+        let mut answers = stdin().lock().lines();
+        let mut changed = false;
+        'files: for entry in tracked {
+            let Ok(work) = fs::read(&entry.path) else {
+                continue;
+            };
+            let Object::Blob(staged) = Object::from_hash(&entry.hash)? else {
+                return Err(Box::new(Fatal(format!("{} is not a blob", entry.hash))));
+            };
+            if staged == work {
+                continue;
+            }
+            changed = true;
+
+            let (a_lines, b_lines) = (diff::lines(&staged), diff::lines(&work));
+            let edits = diff::diff_lines(&a_lines, &b_lines);
+            let hunks = diff::hunks(&edits, diff::DEFAULT_CONTEXT);
+            let work_hash = Object::Blob(work.clone()).hash()?;
+            println!("diff --git a/{} b/{}", entry.path, entry.path);
+            println!(
+                "index {}..{} {}",
+                &entry.hash[..7],
+                &work_hash[..7],
+                entry.mode()
+            );
+            println!("--- a/{}\n+++ b/{}", entry.path, entry.path);
+
+            let mut chosen = Vec::new();
+            let mut quit = false;
+            for (i, hunk) in hunks.iter().enumerate() {
+                print!("{}", diff::render_hunk(hunk, &a_lines, &b_lines));
+                let answer = loop {
+                    print!("({}/{}) Stage this hunk [y,n,q,?]? ", i + 1, hunks.len());
+                    stdout().flush()?;
+                    let Some(answer) = answers.next().transpose()? else {
+                        println!();
+                        break "q".to_string();
+                    };
+                    match answer.trim() {
+                        answer @ ("y" | "n" | "q") => break answer.to_string(),
+                        _ => println!(
+                            "y - stage this hunk\nn - do not stage this hunk\n\
+                             q - quit; do not stage this hunk or any of the remaining ones"
+                        ),
+                    }
+                };
+                match answer.as_str() {
+                    "y" => chosen.push(hunk),
+                    "q" => {
+                        quit = true;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+
+            if !chosen.is_empty() {
+                let blob = diff::apply_hunks(&a_lines, &b_lines, &edits, &chosen);
+                let hash = Object::Blob(blob).write()?;
+                index.add(IndexEntry::new(&entry.path, &entry.mode(), &hash)?);
+            }
+            if quit {
+                break 'files;
+            }
+        }
+
+        if changed {
+            index.write()?;
+        } else {
+            println!("No changes.");
+        }
+    } else if command == "add" {
+        if args.len() < 3 {
+            return Err(Box::new(Usage("mygit add <pathspec>...")));
+        }
+        let mut index = Index::load()?;
+        let ignore = Ignore::new();
+        let matches = |path: &str, spec: &str| {
+            let spec = spec.trim_start_matches("./").trim_end_matches('/');
+            spec == "."
+                || spec.is_empty()
+                || path == spec
+                || path.starts_with(&format!("{}/", spec))
+        };
+        for spec in &args[2..] {
+            // Only the files the pathspec names are read and hashed.
+            let worktree = object::write_worktree_path(spec, &ignore)?;
+            let mut matched = false;
+            for (path, (mode, hash)) in &worktree {
+                matched = true;
+                // Staging a path also resolves any conflict recorded for it.
+                index.remove(path);
+                let metadata = fs::symlink_metadata(path)?;
+                index.add(IndexEntry::new(path, mode, hash)?.with_metadata(&metadata));
+            }
+            // Tracked files that are gone from the working tree are staged
+            // as removed.
+            let removed: Vec<String> = index
+                .entries()
+                .iter()
+                .filter(|entry| matches(&entry.path, spec) && !worktree.contains_key(&entry.path))
+                .map(|entry| entry.path.clone())
+                .collect();
+            for path in removed {
+                matched = true;
+                index.remove(&path);
+            }
+            if !matched && Path::new(spec).exists() {
+                return Err(Box::new(Fatal(format!(
+                    "'{}' is ignored by one of your .gitignore files",
+                    spec
+                ))));
+            }
+            if !matched {
+                return Err(Box::new(Fatal(format!(
+                    "pathspec '{}' did not match any files",
+                    spec
+                ))));
+            }
+        }
+        index.write()?;
+    } else if command == "cat-file" && sub == Some("--batch-check") {
+        let mut input = String::new();
+        stdin().read_to_string(&mut input)?;
+        let mut out = stdout().lock();
+        for name in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match cat_file_resolve(name, follow_symlinks).and_then(|hash| {
+                let (object_type, size) = object::peek_header(&hash)?;
+                Ok((hash, object_type, size))
+            }) {
+                Ok((hash, object_type, size)) => {
+                    writeln!(out, "{} {} {}", hash, object_type, size)?
+                }
+                Err(_) => writeln!(out, "{} missing", name)?,
+            }
+        }
+    } else if command == "cat-file" && sub == Some("-t") {
+        let name = arg(&args, 3, "mygit cat-file -t [--follow-symlinks] <object>")?;
+        match cat_file_resolve(name, follow_symlinks).and_then(|hash| Ok(Object::from_hash(&hash)?))
+        {
+            Ok(object) => println!("{}", object.object_type()),
+            Err(_) => {
+                return Err(Box::new(Fatal(format!("Not a valid object name {}", name))));
+            }
+        }
+    } else if command == "cat-file" && sub == Some("-s") {
+        let name = arg(&args, 3, "mygit cat-file -s [--follow-symlinks] <object>")?;
+        match cat_file_resolve(name, follow_symlinks).and_then(|hash| object::size(&hash)) {
+            Ok(size) => println!("{}", size),
+            Err(_) => {
+                return Err(Box::new(Fatal(format!("Not a valid object name {}", name))));
+            }
+        }
+    } else if command == "cat-file" && sub == Some("-p") {
+        const CAT_FILE_USAGE: &str =
+            "mygit cat-file -p [--follow-symlinks] <object> [--pack <idx>]";
+        let name = arg(&args, 3, CAT_FILE_USAGE)?;
+        let object = match args.iter().position(|arg| arg == "--pack") {
+            // Bypasses the usual lookup to read the object out of one pack.
+            Some(at) => {
+                let idx = arg(&args, at + 1, CAT_FILE_USAGE)?;
+                let index = PackIndex::open(idx)?;
+                let mut matches = index.hashes().iter().filter(|hash| hash.starts_with(name));
+                let (Some(hash), None) = (matches.next(), matches.next()) else {
+                    return Err(Box::new(Fatal(format!(
+                        "{} does not name exactly one object in {}",
+                        name, idx
+                    ))));
+                };
+                let pack = pack::Pack::open(index.pack_path())?;
+                let objects = Repository::current()?.objects_dir();
+                let (object_type, content) = pack.read(&index, hash, &objects)?;
+                Object::parse(&object_type, &content)?
+            }
+            None => {
+                let hash = cat_file_resolve(name, follow_symlinks)?;
+                if object::peek_header(&hash)?.0 == "blob" {
+                    Object::write_blob_to(&hash, &mut stdout().lock())?;
+                    return Ok(());
+                }
+                Object::from_hash(&hash)?
+            }
+        };
         match object {
+            Object::Blob(data) => {
+                stdout().write_all(&data)?;
+            }
             Object::Tree(entries) => {
+                let mut out = stdout().lock();
                 for entry in entries {
-                    println!("{}", entry.filename());
+                    writeln!(
+                        out,
+                        "{} {} {}\t{}",
+                        entry.mode(),
+                        entry.object_type(),
+                        entry.hash(),
+                        entry.filename()
+                    )?;
                 }
             }
-            _ => panic!("not a tree"),
+            object => {
+                stdout().write_all(&object.content()?)?;
+            }
         }
-    } else if args[1] == "write-tree" {
-        let ignore = Ignore::new();
-        let hash = create_tree(".", &ignore).unwrap();
+    } else if command == "hash-object" {
+        let mut write = false;
+        let mut warn_crlf = false;
+        let mut path = None;
+        for arg in &args[2..] {
+            if arg == "-w" {
+                write = true;
+            } else if arg == "--warn-crlf" {
+                warn_crlf = true;
+            } else {
+                path = Some(arg);
+            }
+        }
+        let path = path.ok_or(Usage("mygit hash-object [-w] [--warn-crlf] <file>"))?;
+        let data = fs::read(env::current_dir()?.join(path))?;
+        if warn_crlf
+            && config::get("core", "autocrlf").is_none()
+            && !text::is_binary(&data)
+            && text::has_crlf(&data)
+        {
+            eprintln!(
+                "warning: {} has CRLF line endings, which will be stored as-is",
+                path
+            );
+        }
+        let blob = Object::Blob(data);
+        let hash = if write { blob.write()? } else { blob.hash()? };
         println!("{}", hash);
-    } else if args[1] == "commit-tree" {
-        let tree_sha = &args[2];
+    } else if command == "ls-tree" {
+        let mut treeish = None;
+        let mut paths = Vec::new();
+        let mut recursive = false;
+        let mut name_only = false;
+        let mut full_tree = false;
+        for arg in &args[2..] {
+            if arg == "-r" {
+                recursive = true;
+            } else if arg == "--name-only" || arg == "--name-status" {
+                name_only = true;
+            } else if arg == "--full-tree" {
+                full_tree = true;
+            } else if treeish.is_none() {
+                treeish = Some(arg);
+            } else {
+                paths.push(arg.as_str());
+            }
+        }
+        let treeish = treeish.ok_or(Usage(
+            "mygit ls-tree [-r] [--name-only] [--full-tree] <tree-ish> [<path>...]",
+        ))?;
+        // Paths, given and listed, are relative to the current directory
+        // unless --full-tree lists from the root.
+        let repo = Repository::discover()?;
+        let prefix = match env::current_dir()?.strip_prefix(repo.workdir()) {
+            Ok(dir) if !full_tree => dir.to_string_lossy().into_owned(),
+            _ => String::new(),
+        };
+        env::set_current_dir(repo.workdir())?;
+        // Resolved before peeling, or `<rev>:<path>` would take the suffix
+        // as part of the path.
+        let tree = revision::resolve(&format!("{}^{{tree}}", revision::resolve(treeish)?))?;
+        if paths.is_empty() {
+            paths.push("");
+        }
+        let paths: Vec<String> = paths.iter().map(|path| join_path(&prefix, path)).collect();
+
+        // A path names that entry itself, while a path ending in '/' (or
+        // none at all) lists the entries of that directory.
+        let mut listed = Vec::new();
+        for path in paths.iter().map(String::as_str) {
+            let (dir, name) = match path.strip_suffix('/') {
+                Some(dir) => (dir, None),
+                None if path.is_empty() => ("", None),
+                None => match path.rsplit_once('/') {
+                    Some((dir, name)) => (dir, Some(name)),
+                    None => ("", Some(path)),
+                },
+            };
+            let subtree = match dir {
+                "" => Resolved::Tree(tree.clone()),
+                dir => match revision::parse(&format!("{}:{}", tree, dir)) {
+                    Ok(resolved) => resolved,
+                    Err(_) => continue,
+                },
+            };
+            let Resolved::Tree(subtree) = subtree else {
+                continue;
+            };
+            let Object::Tree(entries) = Object::from_hash(&subtree)? else {
+                return Err(Box::new(Fatal("not a tree".to_string())));
+            };
+            for entry in entries {
+                if name.is_some_and(|name| name != entry.filename()) {
+                    continue;
+                }
+                let path = match dir {
+                    "" => entry.filename().to_string(),
+                    dir => format!("{}/{}", dir, entry.filename()),
+                };
+                if recursive && entry.is_tree() {
+                    list_tree_recursive(entry.hash(), &path, &mut listed)?;
+                    continue;
+                }
+                // Sort in tree order, where a directory compares as if its
+                // name ended with '/'.
+                let key = format!("{}{}", path, if entry.is_tree() { "/" } else { "" });
+                listed.push((key, path, entry));
+            }
+        }
+        listed.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+        listed.dedup_by(|(a, _, _), (b, _, _)| a == b);
+        for (_, path, entry) in listed {
+            let path = relative_path(&prefix, &path);
+            if name_only {
+                println!("{}", path);
+            } else {
+                println!(
+                    "{} {} {}\t{}",
+                    entry.mode(),
+                    entry.object_type(),
+                    entry.hash(),
+                    path
+                );
+            }
+        }
+    } else if command == "write-tree" {
+        let mut prefix = None;
+        for arg in &args[2..] {
+            match arg.strip_prefix("--prefix=") {
+                Some(dir) => prefix = Some(dir.trim_end_matches('/')),
+                None => return Err(Box::new(Usage("mygit write-tree [--prefix=<dir>]"))),
+            }
+        }
+        let mut hash = Index::load()?.write_tree()?;
+        if let Some(prefix) = prefix.filter(|prefix| !prefix.is_empty()) {
+            // The whole tree is written, as git does, and the prefix's
+            // subtree picked out of it.
+            hash = match revision::parse(&format!("{}:{}", hash, prefix)) {
+                Ok(Resolved::Tree(subtree)) => subtree,
+                _ => {
+                    return Err(Box::new(Fatal(format!(
+                        "git-write-tree: prefix {} not found",
+                        prefix
+                    ))));
+                }
+            };
+        }
+        println!("{}", hash);
+    } else if command == "diff" {
+        let mut context = diff::DEFAULT_CONTEXT;
+        let mut color = ColorMode::from_config();
+        let mut hashes = Vec::new();
+        for arg in &args[2..] {
+            if let Some(n) = diff::parse_context_arg(arg) {
+                context = n;
+            } else if let Some(mode) = ColorMode::parse_arg(arg) {
+                color = mode;
+            } else {
+                hashes.push(arg);
+            }
+        }
+        if let Some(at) = hashes.iter().position(|arg| *arg == "--no-index") {
+            hashes.remove(at);
+            let &[old, new] = hashes.as_slice() else {
+                return Err(Box::new(Usage("mygit diff --no-index <path> <path>")));
+            };
+            let patch = diff::no_index(old, new, context)?;
+            if patch.is_empty() {
+                return Ok(());
+            }
+            {
+                let mut out = Pager::start(paging);
+                let color = color.enabled(out.is_paging() || stdout().is_terminal());
+                write!(out, "{}", color::diff(&patch, color))?;
+            }
+            // Like diff(1), exit with 1 when the paths differ.
+            std::process::exit(1);
+        }
+        let name_status = match hashes.iter().position(|arg| *arg == "--name-status") {
+            Some(at) => {
+                hashes.remove(at);
+                true
+            }
+            None => false,
+        };
+        let binary = match hashes.iter().position(|arg| *arg == "--binary") {
+            Some(at) => {
+                hashes.remove(at);
+                true
+            }
+            None => false,
+        };
+        let &[a, b] = hashes.as_slice() else {
+            return Err(Box::new(Usage(
+                "mygit diff [--name-status | --binary] <blob> <blob> | <tree-ish> <tree-ish>",
+            )));
+        };
+        match (revision::parse(a)?, revision::parse(b)?) {
+            (Resolved::Blob(old_hash), Resolved::Blob(new_hash)) => {
+                let (Object::Blob(old), Object::Blob(new)) =
+                    (Object::from_hash(&old_hash)?, Object::from_hash(&new_hash)?)
+                else {
+                    return Err(Box::new(Fatal("not a blob".to_string())));
+                };
+                if old == new {
+                    return Ok(());
+                }
+                let mut out = Pager::start(paging);
+                if text::is_binary(&old) || text::is_binary(&new) {
+                    writeln!(out, "Binary files a/{} and b/{} differ", a, b)?;
+                    return Ok(());
+                }
+                let color = color.enabled(out.is_paging() || stdout().is_terminal());
+                let text = format!(
+                    "--- a/{}\n+++ b/{}\n{}",
+                    a,
+                    b,
+                    diff::unified(&old, &new, context)
+                );
+                write!(out, "{}", color::diff(&text, color))?;
+            }
+            (Resolved::Blob(_), _) | (_, Resolved::Blob(_)) => {
+                return Err(Box::new(Fatal(
+                    "cannot compare a blob with a tree".to_string(),
+                )));
+            }
+            _ => {
+                let old = revision::resolve(&format!("{}^{{tree}}", a))?;
+                let new = revision::resolve(&format!("{}^{{tree}}", b))?;
+                let changes = diff::diff_trees(Some(&old), Some(&new))?;
+                if changes.is_empty() {
+                    return Ok(());
+                }
+                let mut out = Pager::start(paging);
+                if name_status {
+                    write!(out, "{}", diff::name_status(&changes))?;
+                } else {
+                    let color = color.enabled(out.is_paging() || stdout().is_terminal());
+                    let patch = if binary {
+                        diff::binary_patch(&changes, context)?
+                    } else {
+                        diff::patch(&changes, context)?
+                    };
+                    write!(out, "{}", color::diff(&patch, color))?;
+                }
+            }
+        }
+    } else if command == "apply" {
+        let patch = match args.get(2) {
+            Some(path) => fs::read_to_string(path)?,
+            None => io::read_to_string(stdin())?,
+        };
+        patch::apply(&patch)?;
+    } else if command == "format-patch" {
+        const FORMAT_PATCH_USAGE: &str =
+            "mygit format-patch [--stdout] [-o <dir>] (-<n> | <since>)";
+        let mut to_stdout = false;
+        let mut dir = None;
+        let mut count = None;
+        let mut since = None;
+        let mut i = 2;
+        while i < args.len() {
+            let arg = &args[i];
+            if arg == "--stdout" {
+                to_stdout = true;
+            } else if arg == "-o" {
+                i += 1;
+                dir = Some(args.get(i).ok_or(Usage(FORMAT_PATCH_USAGE))?);
+            } else if let Some(n) = arg.strip_prefix('-').and_then(|n| n.parse::<usize>().ok()) {
+                count = Some(n);
+            } else if since.is_none() && !arg.starts_with('-') {
+                since = Some(revision::resolve(arg)?);
+            } else {
+                return Err(Box::new(Usage(FORMAT_PATCH_USAGE)));
+            }
+            i += 1;
+        }
+
+        // Oldest first, leaving out merges as git does.
+        let head = revision::resolve("HEAD")?;
+        let is_merge =
+            |commit: &Object| matches!(commit, Object::Commit { parents, .. } if parents.len() > 1);
+        let mut commits: Vec<(String, Object)> = match (count, since) {
+            (Some(n), None) => revwalk::walk(&[head], Order::Topo)?
+                .into_iter()
+                .filter(|(_, commit)| !is_merge(commit))
+                .take(n)
+                .collect(),
+            (None, Some(since)) => {
+                let upstream: HashSet<String> = revwalk::walk(&[since], Order::Topo)?
+                    .into_iter()
+                    .map(|(hash, _)| hash)
+                    .collect();
+                revwalk::walk(&[head], Order::Topo)?
+                    .into_iter()
+                    .filter(|(hash, commit)| !upstream.contains(hash) && !is_merge(commit))
+                    .collect()
+            }
+            _ => return Err(Box::new(Usage(FORMAT_PATCH_USAGE))),
+        };
+        commits.reverse();
+
+        let total = commits.len();
+        let mut out = stdout().lock();
+        for (i, (hash, commit)) in commits.iter().enumerate() {
+            let mail = patch::format_patch(hash, commit, (i + 1, total))?;
+            if to_stdout {
+                // A blank line between mails, as in an mbox.
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                write!(out, "{}", mail)?;
+                continue;
+            }
+            let Object::Commit { message, .. } = commit else {
+                continue;
+            };
+            let name = patch::patch_file_name(i + 1, message);
+            let path = match dir {
+                Some(dir) => {
+                    fs::create_dir_all(dir)?;
+                    Path::new(dir).join(name)
+                }
+                None => Path::new(&name).to_path_buf(),
+            };
+            fs::write(&path, mail)?;
+            writeln!(out, "{}", path.display())?;
+        }
+    } else if command == "notes" && sub == Some("add") {
+        const NOTES_ADD_USAGE: &str = "mygit notes add [-f] -m <message> [<object>]";
+        let mut message = Option::<String>::None;
+        let mut force = false;
+        let mut object = Option::<String>::None;
+        let mut i = 3;
+        while i < args.len() {
+            if args[i] == "-m" {
+                let _ = message.insert(arg(&args, i + 1, NOTES_ADD_USAGE)?.to_string());
+                i += 1;
+            } else if args[i] == "-f" {
+                force = true;
+            } else {
+                let _ = object.insert(args[i].to_string());
+            }
+            i += 1;
+        }
+
+        let Some(message) = message else {
+            return Err(Box::new(Usage(NOTES_ADD_USAGE)));
+        };
+        let object = object.unwrap_or_else(|| "HEAD".to_string());
+        let user = get_user()?;
+        notes::add(&object, &message, &user, force)?;
+    } else if command == "notes" && sub == Some("show") {
+        let object = revision::resolve(args.get(3).map_or("HEAD", |object| object.as_str()))?;
+        match notes::show(&object)? {
+            Some(note) => stdout().write_all(&note)?,
+            None => {
+                return Err(Box::new(Fatal(format!(
+                    "no note found for object {}",
+                    object
+                ))))
+            }
+        }
+    } else if command == "interpret-trailers" {
+        let mut message = String::new();
+        stdin().read_to_string(&mut message)?;
+        let mut i = 2;
+        while i < args.len() {
+            if args[i] == "--trailer" {
+                let trailer = arg(
+                    &args,
+                    i + 1,
+                    "mygit interpret-trailers [--trailer <token>=<value>]",
+                )?;
+                let Some((key, value)) = trailer::parse_arg(trailer) else {
+                    return Err(Box::new(Fatal(format!("invalid trailer '{}'", trailer))));
+                };
+                message = trailer::add(&message, &key, &value);
+                i += 1;
+            } else if args[i] == "--trim-empty" {
+                message = trailer::trim_empty(&message);
+            }
+            i += 1;
+        }
+        println!("{}", message.trim_end());
+    } else if command == "rev-parse" {
+        let mut verify = false;
+        let mut short = Option::<usize>::None;
+        let mut revs = Vec::new();
+        for arg in &args[2..] {
+            if arg == "--verify" {
+                verify = true;
+            } else if arg == "--short" {
+                let _ = short.insert(7);
+            } else if let Some(n) = arg.strip_prefix("--short=") {
+                let Ok(n) = n.parse() else {
+                    return Err(Box::new(Fatal(format!("invalid --short length '{}'", n))));
+                };
+                let _ = short.insert(n);
+            } else {
+                revs.push(arg);
+            }
+        }
+
+        if revs.is_empty() && !verify && short.is_none() {
+            return Err(Box::new(Usage(
+                "mygit rev-parse [--verify] [--short[=<n>]] <rev>...",
+            )));
+        }
+        if (verify || short.is_some()) && revs.len() != 1 {
+            return Err(Box::new(Fatal("needed a single revision".to_string())));
+        }
+        for rev in revs {
+            let hash = revision::resolve(rev)?;
+            // The object may be packed, so look for it rather than its file.
+            if verify && object::peek_header(&hash).is_err() {
+                return Err(Box::new(Fatal("needed a single revision".to_string())));
+            }
+            match short {
+                Some(n) => println!("{}", object::abbreviate(&hash, n)?),
+                None => println!("{}", hash),
+            }
+        }
+    } else if command == "log" {
+        let mut order = Order::Topo;
+        let mut color = ColorMode::from_config();
+        let mut first_parent = false;
+        let mut revs = Vec::new();
+        for arg in &args[2..] {
+            if arg == "--first-parent" {
+                first_parent = true;
+            } else if let Some(o) = Order::parse_arg(arg) {
+                order = o;
+            } else if let Some(mode) = ColorMode::parse_arg(arg) {
+                color = mode;
+            } else {
+                revs.push(revision::resolve(arg)?);
+            }
+        }
+        if revs.is_empty() {
+            revs.push(revision::resolve("HEAD")?);
+        }
+
+        let mut out = Pager::start(paging);
+        let color = color.enabled(out.is_paging() || stdout().is_terminal());
+        let commits = if first_parent {
+            revwalk::walk_first_parent(&revs, order)
+        } else {
+            revwalk::walk(&revs, order)
+        };
+        for (i, (hash, commit)) in commits?.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            write_commit(&mut out, &hash, &commit, None, color)?;
+        }
+    } else if command == "show" {
+        let mut stat = false;
+        let mut context = diff::DEFAULT_CONTEXT;
+        let mut color = ColorMode::from_config();
+        let mut rev = "HEAD".to_string();
+        for arg in &args[2..] {
+            if arg == "--stat" {
+                stat = true;
+            } else if let Some(n) = diff::parse_context_arg(arg) {
+                context = n;
+            } else if let Some(mode) = ColorMode::parse_arg(arg) {
+                color = mode;
+            } else {
+                rev = arg.to_string();
+            }
+        }
+
+        let hash = revision::resolve(&rev)?;
+        let mut commit = Object::from_hash(&hash)?;
+        revwalk::graft(&hash, &mut commit, &revwalk::load_grafts()?);
+        let Object::Commit { tree, parents, .. } = &commit else {
+            return Err(Box::new(Fatal("not a commit".to_string())));
+        };
+        // Merges are shown against their first parent, like `git show --first-parent`.
+        let first_parent = match parents.first() {
+            Some(parent) => {
+                let Object::Commit { tree, .. } = Object::from_hash(parent)? else {
+                    return Err(Box::new(Fatal("not a commit".to_string())));
+                };
+                Some((parent.clone(), tree))
+            }
+            None => None,
+        };
+        let from = first_parent
+            .as_ref()
+            .filter(|_| parents.len() > 1)
+            .map(|(parent, _)| parent.as_str());
+        let changes = diff::diff_trees(
+            first_parent.as_ref().map(|(_, tree)| tree.as_str()),
+            Some(tree),
+        )?;
+
+        let mut out = Pager::start(paging);
+        let color = color.enabled(out.is_paging() || stdout().is_terminal());
+        write_commit(&mut out, &hash, &commit, from, color)?;
+        if !changes.is_empty() {
+            writeln!(out)?;
+            if stat {
+                write!(out, "{}", diff::stat(&changes)?)?;
+            } else {
+                write!(
+                    out,
+                    "{}",
+                    color::diff(&diff::patch(&changes, context)?, color)
+                )?;
+            }
+        }
+    } else if command == "show-branch" {
+        let mut order = Order::Topo;
+        let mut names = Vec::new();
+        for arg in &args[2..] {
+            if let Some(o) = Order::parse_arg(arg) {
+                order = o;
+            } else {
+                names.push(arg.to_string());
+            }
+        }
+        if names.is_empty() {
+            for name in refs::list("refs/heads")? {
+                names.push(name.trim_start_matches("refs/heads/").to_string());
+            }
+        }
+
+        let head = refs::read_ref("HEAD")?.unwrap_or_default();
+        let head = names
+            .iter()
+            .position(|name| head == format!("ref: refs/heads/{}", name));
+        let mut tips = Vec::new();
+        for name in names {
+            let hash = revision::resolve(&format!("{}^{{commit}}", name))?;
+            tips.push((name, hash));
+        }
+        let rows = show_branch::show_branch(&tips, head, order)?;
+
+        let mut out = Pager::start(paging);
+        for (i, (name, hash)) in tips.iter().enumerate() {
+            let Object::Commit { message, .. } = Object::from_hash(hash)? else {
+                return Err(Box::new(Fatal("not a commit".to_string())));
+            };
+            let mark = if Some(i) == head { '*' } else { '!' };
+            let subject = message.lines().next().unwrap_or("");
+            writeln!(out, "{}{} [{}] {}", " ".repeat(i), mark, name, subject)?;
+        }
+        writeln!(out, "{}", "-".repeat(tips.len()))?;
+        for row in rows {
+            writeln!(out, "{} [{}] {}", row.marks, row.name, row.subject)?;
+        }
+    } else if command == "replace" {
+        const REPLACE_USAGE: &str = "mygit replace [-d] <object> [<replacement>]";
+        if sub == Some("-d") {
+            let object = revision::resolve(arg(&args, 3, REPLACE_USAGE)?)?;
+            refs::delete_ref(&format!("refs/replace/{}", object))?;
+        } else {
+            let original = revision::resolve(arg(&args, 2, REPLACE_USAGE)?)?;
+            let replacement = revision::resolve(arg(&args, 3, REPLACE_USAGE)?)?;
+            refs::update_ref(&format!("refs/replace/{}", original), &replacement)?;
+        }
+    } else if command == "prune-packed" {
+        let dry_run = args[2..]
+            .iter()
+            .any(|arg| arg == "-n" || arg == "--dry-run");
+        let objects = Repository::current()?.objects_dir();
+        for hash in object::prune_packed(dry_run)? {
+            if dry_run {
+                println!(
+                    "rm -f {}",
+                    objects.join(&hash[..2]).join(&hash[2..]).display()
+                );
+            }
+        }
+    } else if command == "pack-objects" {
+        let mut input = String::new();
+        stdin().read_to_string(&mut input)?;
+        let hashes: Vec<String> = input
+            .split_whitespace()
+            .map(|hash| hash.to_string())
+            .collect();
+        let base = arg(&args, 2, "mygit pack-objects <base-name>")?;
+        println!("{}", pack::write_pack(&hashes, base)?);
+    } else if command == "verify-pack" {
+        for path in &args[2..] {
+            let index = PackIndex::open(path)?;
+            index.verify_pack()?;
+            println!("{}: ok", index.pack_path().display());
+        }
+    } else if command == "fsck" {
+        let problems = fsck::check()?;
+        for problem in &problems {
+            eprintln!("error: {}", problem);
+        }
+        if !problems.is_empty() {
+            std::process::exit(1);
+        }
+    } else if command == "status" {
+        let mut color = ColorMode::from_config();
+        for arg in &args[2..] {
+            if let Some(mode) = ColorMode::parse_arg(arg) {
+                color = mode;
+            }
+        }
+        let color = color.enabled(stdout().is_terminal());
+
+        let changes = status::changes(&Ignore::new())?;
+        match refs::read_ref("HEAD")? {
+            Some(head) => match head.strip_prefix("ref: refs/heads/") {
+                Some(branch) => println!("On branch {}", branch),
+                None => println!("HEAD detached at {}", &head[..7]),
+            },
+            None => return Err(Box::new(Fatal("not a git repository".to_string()))),
+        }
+        for (title, listed, paint) in [
+            ("Changes to be committed:", &changes.staged, color::GREEN),
+            (
+                "Changes not staged for commit:",
+                &changes.unstaged,
+                color::RED,
+            ),
+        ] {
+            if listed.is_empty() {
+                continue;
+            }
+            println!("{}", title);
+            for (change, path) in listed {
+                println!(
+                    "\t{}",
+                    color::paint(&format!("{}{}", change.label(), path), paint, color)
+                );
+            }
+            println!();
+        }
+        if !changes.untracked.is_empty() {
+            println!("Untracked files:");
+            for path in &changes.untracked {
+                println!("\t{}", color::paint(path, color::RED, color));
+            }
+            println!();
+        }
+        if changes.is_clean() {
+            println!("nothing to commit, working tree clean");
+        } else if changes.staged.is_empty() && !changes.unstaged.is_empty() {
+            println!("no changes added to commit (use \"mygit add\")");
+        } else if changes.staged.is_empty() {
+            println!(
+                "nothing added to commit but untracked files present (use \"mygit add\" to track)"
+            );
+        }
+    } else if command == "checkout" && sub == Some("-b") {
+        let name = arg(&args, 3, "mygit checkout [-f] -b <branch> [<start-point>]")?;
+        let (branch, hash) = create_branch(name, args.get(4).map(String::as_str))?;
+        switch_to(&hash, name, Some(&branch), force)?;
+        println!("Switched to a new branch '{}'", name);
+    } else if command == "switch" {
+        const SWITCH_USAGE: &str =
+            "mygit switch [-c <branch> [<start-point>] | --detach [<commit>] | <branch>]";
+        if sub == Some("-c") || sub == Some("--create") {
+            let name = arg(&args, 3, SWITCH_USAGE)?;
+            let (branch, hash) = create_branch(name, args.get(4).map(String::as_str))?;
+            switch_to(&hash, name, Some(&branch), false)?;
+            println!("Switched to a new branch '{}'", name);
+        } else if sub == Some("--detach") || sub == Some("-d") {
+            let rev = args.get(3).map(String::as_str).unwrap_or("HEAD");
+            let hash = revision::resolve(&format!("{}^{{commit}}", rev))?;
+            switch_to(&hash, rev, None, false)?;
+            println!("HEAD is now at {}", &hash[..7]);
+        } else {
+            let name = arg(&args, 2, SWITCH_USAGE)?;
+            let branch = format!("refs/heads/{}", name);
+            let Some(hash) = refs::resolve(&branch)? else {
+                match revision::resolve(name) {
+                    Ok(_) => {
+                        return Err(Box::new(Fatal(format!(
+                            "a branch is expected, got '{}'; use --detach",
+                            name
+                        ))))
+                    }
+                    Err(_) => return Err(Box::new(Fatal(format!("invalid reference: {}", name)))),
+                }
+            };
+            if refs::read_ref("HEAD")? == Some(format!("ref: {}", branch)) {
+                println!("Already on '{}'", name);
+            } else {
+                switch_to(&hash, name, Some(&branch), false)?;
+                println!("Switched to branch '{}'", name);
+            }
+        }
+    } else if command == "checkout" {
+        let name = arg(&args, 2, "mygit checkout [-f] [-b <new-branch>] <branch>")?;
+        let branch = format!("refs/heads/{}", name);
+        if let Some(hash) = refs::resolve(&branch)? {
+            switch_to(&hash, name, Some(&branch), force)?;
+            println!("Switched to branch '{}'", name);
+        } else {
+            let hash = revision::resolve(&format!("{}^{{commit}}", name))?;
+            switch_to(&hash, name, None, force)?;
+            println!("HEAD is now at {}", &hash[..7]);
+        }
+    } else if command == "restore" {
+        let mut source = None;
+        let mut staged = false;
+        let mut worktree = false;
+        let mut paths = Vec::new();
+        let mut i = 2;
+        while i < args.len() {
+            if args[i] == "--source" || args[i] == "-s" {
+                source = Some(
+                    arg(
+                        &args,
+                        i + 1,
+                        "mygit restore [--source=<tree>] [--staged] [--worktree] <paths>...",
+                    )?
+                    .to_string(),
+                );
+                i += 1;
+            } else if let Some(rev) = args[i].strip_prefix("--source=") {
+                source = Some(rev.to_string());
+            } else if args[i] == "--staged" || args[i] == "-S" {
+                staged = true;
+            } else if args[i] == "--worktree" || args[i] == "-W" {
+                worktree = true;
+            } else if args[i] == "--" {
+            } else {
+                paths.push(args[i].to_string());
+            }
+            i += 1;
+        }
+        if paths.is_empty() {
+            return Err(Box::new(Fatal(
+                "you must specify path(s) to restore".to_string(),
+            )));
+        }
+        // The index is restored from HEAD and the working tree from the
+        // index, unless --source names something else.
+        let worktree = worktree || !staged;
+        let mut index = Index::load()?;
+        let tree = match &source {
+            Some(source) => revision::resolve(&format!("{}^{{tree}}", source))?,
+            None if staged => revision::resolve("HEAD^{tree}")?,
+            None => index.write_tree()?,
+        };
+        if staged {
+            checkout::restore_index(&mut index, &tree, &paths)?;
+            index.write()?;
+        }
+        if worktree {
+            checkout::checkout_paths(&tree, &paths)?;
+        }
+    } else if command == "commit" {
+        let mut message = None;
+        let mut allow_empty = false;
+        let mut allow_empty_message = false;
+        let mut signoff = false;
+        let mut cleanup = match config::get("commit", "cleanup") {
+            Some(value) => editor::Cleanup::parse(&value)
+                .ok_or_else(|| Fatal(format!("invalid commit.cleanup '{}'", value)))?,
+            None => editor::Cleanup::Default,
+        };
+        let mut i = 2;
+        while i < args.len() {
+            if args[i] == "-m" {
+                message = Some(
+                    arg(
+                        &args,
+                        i + 1,
+                        "mygit commit [-m <message>] [--cleanup=<mode>]",
+                    )?
+                    .to_string(),
+                );
+                i += 1;
+            } else if let Some(mode) = args[i].strip_prefix("--cleanup=") {
+                cleanup = editor::Cleanup::parse(mode)
+                    .ok_or_else(|| Fatal(format!("Invalid cleanup mode {}", mode)))?;
+            } else if args[i] == "--allow-empty" {
+                allow_empty = true;
+            } else if args[i] == "--allow-empty-message" {
+                allow_empty_message = true;
+            } else if args[i] == "-s" || args[i] == "--signoff" {
+                signoff = true;
+            }
+            i += 1;
+        }
+        let message = message.map(|message| cleanup.apply(&message, false));
+        if message
+            .as_ref()
+            .is_some_and(|message| message.trim().is_empty())
+            && !allow_empty_message
+        {
+            return Err(Box::new(Fatal(
+                "Aborting commit due to empty commit message.".to_string(),
+            )));
+        }
+
+        let repo = Repository::open(".")?;
+        let ignore = Ignore::new();
+        // What is staged gets committed, not the working tree.
+        let tree = Index::load()?.write_tree()?;
+        let mut parents = Vec::new();
+        let parent_tree = match repo.head_commit()? {
+            Some((hash, Object::Commit { tree, .. })) => {
+                parents.push(hash);
+                tree
+            }
+            _ => Object::Tree(Vec::new()).hash()?,
+        };
+        if parent_tree == tree && !allow_empty {
+            let message = if parents.is_empty() {
+                "nothing added to commit"
+            } else {
+                "nothing to commit, working tree clean"
+            };
+            return Err(Box::new(Fatal(message.to_string())));
+        }
+
+        let mut message = match message {
+            Some(message) => message,
+            None => {
+                let template = commit_template(
+                    &status::status(&ignore)?,
+                    cleanup == editor::Cleanup::Scissors,
+                )?;
+                let edited = editor::edit(&repo.gitdir().join("COMMIT_EDITMSG"), &template)?;
+                let message = cleanup.apply(&edited, true);
+                if message.trim().is_empty() && !allow_empty_message {
+                    return Err(Box::new(Fatal(
+                        "Aborting commit due to empty commit message.".to_string(),
+                    )));
+                }
+                message
+            }
+        };
+        let user = get_user()?;
+        if signoff {
+            message = trailer::signoff(&message, &user);
+        }
+        let subject = message.lines().next().unwrap_or("").to_string();
+        let old = parents.first().cloned();
+        let hash = repo.commit_tree(&tree, parents, &user, message)?;
+        refs::update_head(&hash)?;
+
+        let head = refs::read_ref("HEAD")?.unwrap_or_default();
+        let reflog_message = match old {
+            Some(_) => format!("commit: {}", subject),
+            None => format!("commit (initial): {}", subject),
+        };
+        if let Some(branch) = head.strip_prefix("ref: ") {
+            reflog::append(branch, old.as_deref(), &hash, &user, &reflog_message)?;
+        }
+        reflog::append("HEAD", old.as_deref(), &hash, &user, &reflog_message)?;
+        let branch = head
+            .strip_prefix("ref: refs/heads/")
+            .unwrap_or("detached HEAD");
+        println!("[{} {}] {}", branch, &hash[..7], subject);
+    } else if command == "stash" && sub == Some("list") {
+        for (i, entry) in stash::list()?.iter().enumerate() {
+            println!("stash@{{{}}}: {}", i, entry.message);
+        }
+    } else if command == "stash" && sub == Some("show") {
+        let mut show_patch = false;
+        let mut context = diff::DEFAULT_CONTEXT;
+        let mut color = ColorMode::from_config();
+        let mut stash_arg = None;
+        for arg in &args[3..] {
+            if arg == "-p" || arg == "--patch" {
+                show_patch = true;
+            } else if let Some(n) = diff::parse_context_arg(arg) {
+                context = n;
+            } else if let Some(mode) = ColorMode::parse_arg(arg) {
+                color = mode;
+            } else {
+                stash_arg = Some(arg.as_str());
+            }
+        }
+        let n = stash::parse_index(stash_arg).ok_or_else(|| {
+            Fatal(format!(
+                "{} is not a stash reference",
+                stash_arg.unwrap_or_default()
+            ))
+        })?;
+        let (base, tree) = stash::trees(&stash::get(n)?)?;
+        let changes = diff::diff_trees(Some(&base), Some(&tree))?;
+        let mut out = Pager::start(paging);
+        if show_patch {
+            let color = color.enabled(out.is_paging() || stdout().is_terminal());
+            let patch = diff::patch(&changes, context)?;
+            write!(out, "{}", color::diff(&patch, color))?;
+        } else {
+            write!(out, "{}", diff::stat(&changes)?)?;
+        }
+    } else if command == "stash" && sub == Some("drop") {
+        let stash_arg = args.get(3).map(|arg| arg.as_str());
+        let n = stash::parse_index(stash_arg).ok_or_else(|| {
+            Fatal(format!(
+                "{} is not a stash reference",
+                stash_arg.unwrap_or_default()
+            ))
+        })?;
+        let hash = stash::drop(n)?;
+        println!("Dropped refs/stash@{{{}}} ({})", n, hash);
+    } else if command == "stash" {
+        let mut message = None;
+        let mut i = 2;
+        while i < args.len() {
+            if args[i] == "-m" || args[i] == "--message" {
+                message = Some(arg(&args, i + 1, "mygit stash [push] [-m <message>]")?);
+                i += 1;
+            }
+            i += 1;
+        }
+        let user = get_user()?;
+        match stash::push(message, &user)? {
+            Some(_) => {
+                if let Some(entry) = stash::list()?.first() {
+                    println!("Saved working directory state {}", entry.message);
+                }
+            }
+            None => println!("No local changes to save"),
+        }
+    } else if command == "bisect" && sub == Some("start") {
+        bisect::start()?;
+        if let Some(bad) = args.get(3) {
+            bisect::mark_bad(&revision::resolve(&format!("{}^{{commit}}", bad))?)?;
+        }
+        for good in args.iter().skip(4) {
+            bisect::mark_good(&revision::resolve(&format!("{}^{{commit}}", good))?)?;
+        }
+        bisect_next()?;
+    } else if command == "bisect" && (sub == Some("bad") || sub == Some("good")) {
+        let mut revs: Vec<&str> = args[3..].iter().map(|arg| arg.as_str()).collect();
+        if revs.is_empty() {
+            revs.push("HEAD");
+        }
+        for rev in revs {
+            let hash = revision::resolve(&format!("{}^{{commit}}", rev))?;
+            if sub == Some("bad") {
+                bisect::mark_bad(&hash)?;
+            } else {
+                bisect::mark_good(&hash)?;
+            }
+        }
+        bisect_next()?;
+    } else if command == "bisect" && sub == Some("reset") {
+        let head = bisect::start_head()?;
+        match head.strip_prefix("ref: ") {
+            Some(branch) => {
+                let hash = refs::resolve_ref(branch)?;
+                let name = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+                switch_to(&hash, name, Some(branch), false)?;
+            }
+            None => switch_to(&head, &head, None, false)?,
+        }
+        bisect::clear()?;
+    } else if command == "subtree" && sub == Some("split") {
+        const SUBTREE_SPLIT_USAGE: &str = "mygit subtree split --prefix=<dir> [<commit>]";
+        let mut prefix = None;
+        let mut rev = "HEAD";
+        for arg in &args[3..] {
+            if let Some(dir) = arg.strip_prefix("--prefix=") {
+                prefix = Some(dir.trim_matches('/'));
+            } else if arg.starts_with('-') {
+                return Err(Box::new(Usage(SUBTREE_SPLIT_USAGE)));
+            } else {
+                rev = arg;
+            }
+        }
+        let prefix = prefix
+            .filter(|prefix| !prefix.is_empty())
+            .ok_or(Usage(SUBTREE_SPLIT_USAGE))?;
+        let tip = revision::resolve(&format!("{}^{{commit}}", rev))?;
+        let split = subtree::split(&tip, prefix)?
+            .ok_or_else(|| Fatal(format!("no history found for prefix '{}'", prefix)))?;
+        println!("{}", split);
+    } else if command == "reflog" && sub == Some("expire") {
+        let now = Timestamp::now().seconds();
+        let mut cutoff =
+            reflog::default_expire(now).ok_or(Fatal("invalid gc.reflogExpire".to_string()))?;
+        let mut names = Vec::new();
+        for arg in &args[3..] {
+            if let Some(value) = arg.strip_prefix("--expire=") {
+                cutoff = reflog::parse_expire(value, now)
+                    .ok_or_else(|| Fatal(format!("invalid --expire '{}'", value)))?;
+            } else {
+                names.push(reflog::dwim(arg).unwrap_or(arg.to_string()));
+            }
+        }
+        for name in names {
+            reflog::expire(&name, cutoff)?;
+        }
+    } else if command == "count-objects" {
+        let verbose = args[2..]
+            .iter()
+            .any(|arg| arg == "-v" || arg == "--verbose");
+        let human = args[2..]
+            .iter()
+            .any(|arg| arg == "-H" || arg == "--human-readable");
+        let format_size = |bytes: u64| {
+            if human {
+                human_bytes(bytes)
+            } else {
+                (bytes / 1024).to_string()
+            }
+        };
+
+        let repo = Repository::current()?;
+        let loose = object::find_objects("")?;
+        let indexes = pack::indexes()?;
+        let mut size = 0;
+        let mut histogram = [0; SIZE_BUCKETS.len() + 1];
+        for hash in &loose {
+            let path = repo.objects_dir().join(&hash[..2]).join(&hash[2..]);
+            size += fs::metadata(path)?.blocks() * 512;
+            let (_, len) = object::peek_header(hash)?;
+            let bucket = SIZE_BUCKETS
+                .iter()
+                .position(|(limit, _)| (len as u64) < *limit);
+            histogram[bucket.unwrap_or(SIZE_BUCKETS.len())] += 1;
+        }
+
+        if verbose {
+            let mut in_pack = 0;
+            let mut size_pack = 0;
+            for index in &indexes {
+                in_pack += index.hashes().len();
+                size_pack += fs::metadata(index.path())?.blocks() * 512;
+                size_pack += fs::metadata(index.pack_path())?.blocks() * 512;
+            }
+            let prune_packable = loose
+                .iter()
+                .filter(|hash| indexes.iter().any(|index| index.contains(hash)))
+                .count();
+            println!("count: {}", loose.len());
+            println!("size: {}", format_size(size));
+            println!("in-pack: {}", in_pack);
+            println!("packs: {}", indexes.len());
+            println!("size-pack: {}", format_size(size_pack));
+            println!("prune-packable: {}", prune_packable);
+        } else if human {
+            println!("{} objects, {}", loose.len(), format_size(size));
+        } else {
+            println!("{} objects, {} kilobytes", loose.len(), format_size(size));
+        }
+
+        if human {
+            println!("size histogram:");
+            for (i, count) in histogram.iter().enumerate() {
+                let label = match i {
+                    0 => format!("< {}", SIZE_BUCKETS[0].1),
+                    i if i == SIZE_BUCKETS.len() => format!(">= {}", SIZE_BUCKETS[i - 1].1),
+                    i => format!("{} - {}", SIZE_BUCKETS[i - 1].1, SIZE_BUCKETS[i].1),
+                };
+                println!("  {:<18} {}", label, count);
+            }
+        }
+    } else if command == "mktree" {
+        let indexes = pack::indexes()?;
+        let mut entries = Vec::new();
+        for line in stdin().lines() {
+            let line = line?;
+            let Some((meta, name)) = line.split_once('\t') else {
+                return Err(Box::new(Fatal(format!("input format error: {}", line))));
+            };
+            let &[mode, object_type, hash] = meta.split(' ').collect::<Vec<_>>().as_slice() else {
+                return Err(Box::new(Fatal(format!("input format error: {}", line))));
+            };
+            if name.contains('/') {
+                return Err(Box::new(Fatal(format!("path {} contains slash", name))));
+            }
+            let Some(mode) = FileMode::parse(mode) else {
+                return Err(Box::new(Fatal(format!("invalid mode in line: {}", line))));
+            };
+            let expected = mode.object_type();
+            if object_type != expected {
+                return Err(Box::new(Fatal(format!(
+                    "entry '{}' object type ({}) doesn't match mode type ({})",
+                    name, object_type, expected
+                ))));
+            }
+            if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Box::new(Fatal(format!("input format error: {}", line))));
+            }
+            // Submodule commits live in another repository.
+            if expected != "commit" {
+                match object::peek_header(hash) {
+                    Ok((stored, _)) if stored != expected => {
+                        return Err(Box::new(Fatal(format!(
+                            "entry '{}' object {} is a {} but specified type was ({})",
+                            name, hash, stored, expected
+                        ))))
+                    }
+                    Ok(_) => {}
+                    Err(_) if indexes.iter().any(|index| index.contains(hash)) => {}
+                    Err(_) => {
+                        return Err(Box::new(Fatal(format!(
+                            "entry '{}' object {} is unavailable",
+                            name, hash
+                        ))))
+                    }
+                }
+            }
+            entries.push((
+                name.to_string(),
+                mode.as_str().to_string(),
+                hash.to_string(),
+            ));
+        }
+        println!("{}", object::build_tree_from_paths(&entries)?);
+    } else if command == "fetch" {
+        let (name, url) = remote(arg(&args, 2, "mygit fetch <repository>")?);
+        let src = transport::gitdir(Path::new(&url))?;
+        let remote_refs = transport::list_refs(&src)?;
+        let tips: Vec<String> = remote_refs.iter().map(|(_, hash)| hash.clone()).collect();
+        transport::copy_objects(&src, Repository::current()?.gitdir(), &tips, true)?;
+
+        println!("From {}", url);
+        for (refname, hash) in remote_refs {
+            let (local, short, kind) = match refname.strip_prefix("refs/heads/") {
+                Some(branch) => (
+                    format!("refs/remotes/{}/{}", name, branch),
+                    branch.to_string(),
+                    "branch",
+                ),
+                None => (
+                    refname.clone(),
+                    refname.trim_start_matches("refs/tags/").to_string(),
+                    "tag",
+                ),
+            };
+            let to = local
+                .trim_start_matches("refs/remotes/")
+                .trim_start_matches("refs/tags/");
+            match refs::read_ref(&local)? {
+                None => println!(
+                    " * {:<17} {:<10} -> {}",
+                    format!("[new {}]", kind),
+                    short,
+                    to
+                ),
+                Some(old) if old == hash => continue,
+                // Tags are never moved once fetched.
+                Some(_) if kind == "tag" => continue,
+                Some(old) if is_ancestor(&old, &hash)? => {
+                    let range = format!("{}..{}", &old[..7], &hash[..7]);
+                    println!("   {:<17} {:<10} -> {}", range, short, to);
+                }
+                Some(old) => {
+                    let range = format!("{}...{}", &old[..7], &hash[..7]);
+                    println!(" + {:<17} {:<10} -> {}  (forced update)", range, short, to);
+                }
+            }
+            refs::update_ref(&local, &hash)?;
+        }
+    } else if command == "push" {
+        let mut force = false;
+        let mut positional = Vec::new();
+        for arg in &args[2..] {
+            if arg == "-f" || arg == "--force" {
+                force = true;
+            } else {
+                positional.push(arg.as_str());
+            }
+        }
+        let &[repository, refspec] = positional.as_slice() else {
+            return Err(Box::new(Usage("mygit push [-f] <repository> <refspec>")));
+        };
+        let (name, url) = remote(repository);
+        let dst = transport::gitdir(Path::new(&url))?;
+        let (src_ref, dst_ref) = refspec.split_once(':').unwrap_or((refspec, refspec));
+        let full_name = |name: &str| -> Result<String, Box<dyn std::error::Error + 'static>> {
+            Ok(if name.starts_with("refs/") {
+                name.to_string()
+            } else if refs::read_ref(&format!("refs/tags/{}", name))?.is_some() {
+                format!("refs/tags/{}", name)
+            } else {
+                format!("refs/heads/{}", name)
+            })
+        };
+        let (src_ref, dst_ref) = (full_name(src_ref)?, full_name(dst_ref)?);
+        let Some(hash) = refs::resolve(&src_ref)? else {
+            return Err(Box::new(Fatal(format!(
+                "src refspec {} does not match any",
+                src_ref
+            ))));
+        };
+        let short = |name: &str| {
+            name.trim_start_matches("refs/heads/")
+                .trim_start_matches("refs/tags/")
+                .to_string()
+        };
+        let update = format!("{} -> {}", short(&src_ref), short(&dst_ref));
+
+        println!("To {}", url);
+        let old = transport::read_ref(&dst, &dst_ref)?;
+        let summary = match &old {
+            Some(old) if *old == hash => {
+                println!("Everything up-to-date");
+                return Ok(());
+            }
+            None if dst_ref.starts_with("refs/tags/") => " * [new tag]".to_string(),
+            None => " * [new branch]".to_string(),
+            Some(old) if is_ancestor(old, &hash)? => format!("   {}..{}", &old[..7], &hash[..7]),
+            Some(old) if force => format!(" + {}...{}", &old[..7], &hash[..7]),
+            Some(_) => {
+                println!(" ! [rejected]        {} (non-fast-forward)", update);
+                return Err(Box::new(Fatal(format!(
+                    "failed to push some refs to '{}'",
+                    url
+                ))));
+            }
+        };
+        // A non-bare repository's checked-out branch would no longer match
+        // its working tree.
+        let is_bare = !dst.ends_with(".git");
+        if !is_bare && fs::read_to_string(dst.join("HEAD"))?.trim() == format!("ref: {}", dst_ref) {
+            println!(
+                " ! [remote rejected] {} (branch is currently checked out)",
+                update
+            );
+            return Err(Box::new(Fatal(format!(
+                "failed to push some refs to '{}'",
+                url
+            ))));
+        }
+
+        transport::copy_objects(
+            Repository::current()?.gitdir(),
+            &dst,
+            std::slice::from_ref(&hash),
+            true,
+        )?;
+        refs::update_ref_from_in(&dst, &dst_ref, old.as_deref(), &hash)?;
+        if let Some(branch) = dst_ref.strip_prefix("refs/heads/") {
+            refs::update_ref(&format!("refs/remotes/{}/{}", name, branch), &hash)?;
+        }
+        println!("{:<20} {}", summary, update);
+    } else if command == "commit-tree" {
+        const COMMIT_TREE_USAGE: &str = "mygit commit-tree <tree> [-p <parent>]... -m <message>";
+        let mut tree = None;
         let mut parents = Vec::new();
         let mut message = Option::<String>::None;
-        for i in 3..args.len() {
+        let mut signoff = false;
+        let mut i = 2;
+        while i < args.len() {
             if args[i] == "-p" {
-                parents.push(args[i + 1].to_string());
+                let parent = arg(&args, i + 1, COMMIT_TREE_USAGE)?;
+                let Ok(parent) = revision::resolve(&format!("{}^{{commit}}", parent)) else {
+                    return Err(Box::new(Fatal(format!("{} is not a valid commit", parent))));
+                };
+                if parents.contains(&parent) {
+                    eprintln!("error: duplicate parent {} ignored", parent);
+                } else {
+                    parents.push(parent);
+                }
+                i += 1;
             } else if args[i] == "-m" {
-                let _ = message.insert(args[i + 1].to_string());
+                let _ = message.insert(arg(&args, i + 1, COMMIT_TREE_USAGE)?.to_string());
+                i += 1;
+            } else if args[i] == "-s" || args[i] == "--signoff" {
+                signoff = true;
+            } else if tree.is_none() {
+                tree = Some(args[i].as_str());
+            } else {
+                return Err(Box::new(Usage(COMMIT_TREE_USAGE)));
             }
+            i += 1;
         }
+        let Some(tree) = tree else {
+            return Err(Box::new(Usage(COMMIT_TREE_USAGE)));
+        };
+        let Ok(Resolved::Tree(tree)) = revision::parse(&format!("{}^{{tree}}", tree)) else {
+            return Err(Box::new(Fatal(format!(
+                "{} is not a valid 'tree' object",
+                tree
+            ))));
+        };
 
-        if let Some(user) = get_user() {
-            let commit = Object::Commit {
-                tree: tree_sha.to_string(),
-                parents,
-                author: user.clone(),
-                author_timestamp: Timestamp::now(),
-                committer: user.clone(),
-                committer_timestamp: Timestamp::now(),
-                message: message.unwrap(),
-            };
-            let hash = commit.write().unwrap();
-            println!("{}", hash);
-        } else {
-            panic!("could not find user");
+        let Some(mut message) = message else {
+            return Err(Box::new(Usage(COMMIT_TREE_USAGE)));
+        };
+        let user = get_user()?;
+        if signoff {
+            message = trailer::signoff(&message, &user);
         }
+        let hash = Repository::open(".")?.commit_tree(&tree, parents, &user, message)?;
+        println!("{}", hash);
+    } else if command == "tag" {
+        const TAG_USAGE: &str = "mygit tag [-a] [-m <message>] <tagname> [<object>]";
+        let mut annotate = false;
+        let mut message = None;
+        let mut positional = Vec::new();
+        let mut i = 2;
+        while i < args.len() {
+            if args[i] == "-a" {
+                annotate = true;
+            } else if args[i] == "-m" {
+                annotate = true;
+                message = Some(arg(&args, i + 1, TAG_USAGE)?.to_string());
+                i += 1;
+            } else {
+                positional.push(args[i].as_str());
+            }
+            i += 1;
+        }
+        let Some(&name) = positional.first() else {
+            return Err(Box::new(Usage(TAG_USAGE)));
+        };
+        let target = revision::parse(positional.get(1).copied().unwrap_or("HEAD"))?;
+        let refname = format!("refs/tags/{}", name);
+        if refs::read_ref(&refname)?.is_some() {
+            return Err(Box::new(Fatal(format!("tag '{}' already exists", name))));
+        }
+
+        // Without -a or -m the ref points straight at the object.
+        let hash = if annotate {
+            let message = message.ok_or(Fatal("no tag message given, use -m".to_string()))?;
+            let tagger = get_user()?;
+            Object::Tag {
+                object: target.hash().to_string(),
+                tag_type: target.object_type().to_string(),
+                tag: name.to_string(),
+                tagger,
+                tagger_timestamp: Repository::open(".")?.now(),
+                message: object::normalize_message(&editor::stripspace(&message, false)),
+            }
+            .write()?
+        } else {
+            target.hash().to_string()
+        };
+        refs::update_ref(&refname, &hash)?;
     } else {
-        panic!("unknown command");
+        return Err(Box::new(Usage(USAGE)));
     }
+    Ok(())
 }
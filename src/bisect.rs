@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::refs;
+use crate::repository::{NotARepository, Repository};
+use crate::revwalk::{self, Order};
+
+// What HEAD held when the bisection started, so it can be put back.
+const START_FILE: &str = "BISECT_START";
+const BAD_FILE: &str = "BISECT_BAD";
+// One good commit per line.
+const GOOD_FILE: &str = "BISECT_GOOD";
+
+fn state_file(name: &str) -> Result<PathBuf, NotARepository> {
+    Ok(Repository::current()?.gitdir().join(name))
+}
+
+#[derive(Debug, Clone)]
+pub struct NotBisecting;
+
+impl fmt::Display for NotBisecting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "you need to start by \"mygit bisect start\"")
+    }
+}
+
+impl std::error::Error for NotBisecting {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+pub enum Step {
+    // A good or a bad commit has not been given yet.
+    NeedBounds,
+    // The commit to test next and how many are left to test after it.
+    Test { hash: String, remaining: usize },
+    // The bounds have met at the first bad commit.
+    Found(String),
+}
+
+pub fn is_bisecting() -> bool {
+    state_file(START_FILE).is_ok_and(|file| file.is_file())
+}
+
+// Begins a bisection, forgetting any earlier one.
+pub fn start() -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let head = refs::read_ref("HEAD")?.unwrap_or_default();
+    clear()?;
+    fs::write(state_file(START_FILE)?, format!("{}\n", head))?;
+    Ok(())
+}
+
+// The value HEAD had before `start`, as a ref file would hold it.
+pub fn start_head() -> Result<String, Box<dyn std::error::Error + 'static>> {
+    if !is_bisecting() {
+        return Err(Box::new(NotBisecting));
+    }
+    Ok(fs::read_to_string(state_file(START_FILE)?)?
+        .trim()
+        .to_string())
+}
+
+pub fn clear() -> Result<(), Box<dyn std::error::Error + 'static>> {
+    for name in [START_FILE, BAD_FILE, GOOD_FILE] {
+        let file = state_file(name)?;
+        if file.is_file() {
+            fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
+
+// Marking a commit bad replaces the previous bad one, since anything the
+// bisection hands out lies below it.
+pub fn mark_bad(hash: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    if !is_bisecting() {
+        return Err(Box::new(NotBisecting));
+    }
+    fs::write(state_file(BAD_FILE)?, format!("{}\n", hash))?;
+    Ok(())
+}
+
+pub fn mark_good(hash: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    if !is_bisecting() {
+        return Err(Box::new(NotBisecting));
+    }
+    let mut good = goods()?;
+    if !good.iter().any(|known| known == hash) {
+        good.push(hash.to_string());
+    }
+    fs::write(state_file(GOOD_FILE)?, good.join("\n") + "\n")?;
+    Ok(())
+}
+
+fn goods() -> Result<Vec<String>, Box<dyn std::error::Error + 'static>> {
+    if !state_file(GOOD_FILE)?.is_file() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(state_file(GOOD_FILE)?)?
+        .lines()
+        .map(|line| line.to_string())
+        .collect())
+}
+
+// Picks the next commit to test among those reachable from the bad commit
+// but not from any good one. The commit chosen splits them most evenly:
+// the candidates it reaches become the new range if it is bad, the others
+// if it is good.
+pub fn next() -> Result<Step, Box<dyn std::error::Error + 'static>> {
+    if !is_bisecting() {
+        return Err(Box::new(NotBisecting));
+    }
+    let good = goods()?;
+    if good.is_empty() || !state_file(BAD_FILE)?.is_file() {
+        return Ok(Step::NeedBounds);
+    }
+    let bad = fs::read_to_string(state_file(BAD_FILE)?)?
+        .trim()
+        .to_string();
+
+    let excluded: HashSet<String> = revwalk::walk(&good, Order::Topo)?
+        .into_iter()
+        .map(|(hash, _)| hash)
+        .collect();
+    let candidates: Vec<String> = revwalk::walk(std::slice::from_ref(&bad), Order::Topo)?
+        .into_iter()
+        .map(|(hash, _)| hash)
+        .filter(|hash| !excluded.contains(hash))
+        .collect();
+    if candidates.len() <= 1 {
+        return Ok(Step::Found(bad));
+    }
+
+    let all: HashSet<&String> = candidates.iter().collect();
+    let mut best: Option<(usize, &String, usize)> = None;
+    for candidate in &candidates {
+        let reached = revwalk::walk(std::slice::from_ref(candidate), Order::Topo)?
+            .iter()
+            .filter(|(hash, _)| all.contains(hash))
+            .count();
+        let score = reached.min(candidates.len() - reached);
+        if best.map_or(true, |(best_score, _, _)| score > best_score) {
+            best = Some((score, candidate, reached));
+        }
+    }
+    let (_, hash, reached) = best.unwrap();
+    Ok(Step::Test {
+        hash: hash.clone(),
+        remaining: (reached - 1).max(candidates.len() - reached - 1),
+    })
+}
@@ -0,0 +1,317 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::Path;
+
+use crate::config;
+use crate::index::{Index, IndexEntry};
+use crate::object::{self, FileMode, Object};
+
+#[derive(Debug, Clone)]
+pub struct CaseCollision(pub String, pub String);
+
+impl fmt::Display for CaseCollision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "paths '{}' and '{}' collide on a case-insensitive filesystem",
+            self.0, self.1
+        )
+    }
+}
+
+impl std::error::Error for CaseCollision {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PathspecMismatch(pub String);
+
+impl fmt::Display for PathspecMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "pathspec '{}' did not match any file(s) known to git",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for PathspecMismatch {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// Files a checkout would have to overwrite or delete although they hold
+// changes that are not committed.
+#[derive(Debug, Clone)]
+pub struct LocalChanges(pub Vec<String>);
+
+impl fmt::Display for LocalChanges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "your local changes to the following files would be overwritten by checkout:"
+        )?;
+        for path in &self.0 {
+            writeln!(f, "\t{}", path)?;
+        }
+        write!(
+            f,
+            "Please commit your changes or stash them before you switch branches."
+        )
+    }
+}
+
+impl std::error::Error for LocalChanges {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+pub fn ignore_case() -> bool {
+    config::get_bool("core", "ignorecase").unwrap_or(false)
+}
+
+// Finds two paths that would land on the same file once case is folded.
+// Leading directories are compared too, so `Docs/a` collides with a file
+// named `docs`.
+pub fn check_case_collisions<'a>(
+    paths: impl IntoIterator<Item = &'a String>,
+) -> Result<(), CaseCollision> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    for path in paths {
+        let mut end = 0;
+        for component in path.split('/') {
+            end += component.len();
+            let prefix = &path[..end];
+            if let Some(other) = seen.insert(prefix.to_lowercase(), prefix) {
+                if other != prefix {
+                    return Err(CaseCollision(other.to_string(), prefix.to_string()));
+                }
+            }
+            end += 1;
+        }
+    }
+    Ok(())
+}
+
+// Writes every blob of the tree into the working directory. With
+// core.ignorecase set, trees whose paths differ only in case are rejected
+// before anything is written instead of letting one file clobber another.
+pub fn checkout_tree(tree: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let files = object::flatten_tree(tree)?;
+    if ignore_case() {
+        check_case_collisions(files.keys())?;
+    }
+
+    for (path, (mode, hash)) in &files {
+        write_blob(path, mode, hash)?;
+    }
+    Ok(())
+}
+
+// Moves the working directory from tree `old` to tree `new`: files whose
+// entry differs are written, files only `old` has are deleted, and the rest
+// are left alone along with any changes made to them. Unless `force` is
+// set, nothing is touched when one of the files to be written or deleted
+// has changes, staged or not, that `old` does not have. With `force` every
+// file of `new` is written.
+pub fn switch_tree(
+    old: &str,
+    new: &str,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let old_files = object::flatten_tree(old)?;
+    let new_files = object::flatten_tree(new)?;
+    if ignore_case() {
+        check_case_collisions(new_files.keys())?;
+    }
+    let changed: BTreeSet<&String> = old_files
+        .keys()
+        .chain(new_files.keys())
+        .filter(|path| force || old_files.get(*path) != new_files.get(*path))
+        .collect();
+
+    if !force {
+        let index = Index::load()?;
+        let mut dirty = Vec::new();
+        for path in &changed {
+            let committed = old_files.get(*path).map(|(_, hash)| hash.as_str());
+            let staged = index.get(path).map(|entry| entry.hash.as_str());
+            // A directory where a file is wanted holds no file of its own,
+            // and below a file nothing exists: the system reports that as
+            // "not a directory" rather than as missing.
+            let worktree = match fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.is_dir() => None,
+                Ok(_) => Some(object::hash_worktree_blob(Path::new(path))?.1),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(_) if below_a_file(path) => None,
+                Err(err) => return Err(Box::new(err)),
+            };
+            // A file that is already as `new` has it loses nothing.
+            let wanted = new_files.get(*path).map(|(_, hash)| hash.as_str());
+            if worktree.as_deref() == wanted {
+                continue;
+            }
+            if staged != committed || worktree.is_some() && worktree.as_deref() != committed {
+                dirty.push(path.to_string());
+            }
+        }
+        if !dirty.is_empty() {
+            return Err(Box::new(LocalChanges(dirty)));
+        }
+    }
+
+    // Removals come first, deepest first, so that a file can take the
+    // place of a directory it replaces and the other way around.
+    for path in changed.iter().rev() {
+        if !new_files.contains_key(*path) {
+            remove_file(path)?;
+        }
+    }
+    for path in changed {
+        if let Some((mode, hash)) = new_files.get(path) {
+            write_blob(path, mode, hash)?;
+        }
+    }
+    Ok(())
+}
+
+// Whether one of the directories `path` lies in is something else.
+fn below_a_file(path: &str) -> bool {
+    Path::new(path)
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .any(|dir| fs::symlink_metadata(dir).is_ok_and(|metadata| !metadata.is_dir()))
+}
+
+// Deletes a file along with the directories it leaves empty.
+fn remove_file(path: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        result => result?,
+    }
+    let mut dir = Path::new(path).parent();
+    while let Some(parent) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+        if fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+
+fn write_blob(
+    path: &str,
+    mode: &str,
+    hash: &str,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let Object::Blob(content) = Object::from_hash(hash)? else {
+        return Err(Box::new(object::InvalidObjectFormat));
+    };
+    let path = Path::new(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mode = FileMode::parse(mode);
+    // A link cannot be written over, and writing to one would change its
+    // target instead, so links on either side are removed first.
+    if fs::symlink_metadata(path)
+        .is_ok_and(|old| old.is_symlink() || mode == Some(FileMode::Symlink))
+    {
+        fs::remove_file(path)?;
+    }
+    if mode == Some(FileMode::Symlink) {
+        symlink(OsStr::from_bytes(&content), path)?;
+        return Ok(());
+    }
+    fs::write(path, content)?;
+    let permissions = match mode {
+        Some(FileMode::Executable) => 0o755,
+        _ => 0o644,
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(permissions))?;
+    Ok(())
+}
+
+// Writes the files at or under each of `paths` from the tree, leaving the
+// rest of the working directory alone.
+pub fn checkout_paths(
+    tree: &str,
+    paths: &[String],
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let files = object::flatten_tree(tree)?;
+    for pathspec in paths {
+        let pathspec = pathspec.trim_end_matches('/');
+        let matched: Vec<_> = files
+            .iter()
+            .filter(|(path, _)| {
+                path.as_str() == pathspec || path.starts_with(&format!("{}/", pathspec))
+            })
+            .collect();
+        if matched.is_empty() {
+            return Err(Box::new(PathspecMismatch(pathspec.to_string())));
+        }
+        for (path, (mode, hash)) in matched {
+            write_blob(path, mode, hash)?;
+        }
+    }
+    Ok(())
+}
+
+// Makes the index entries under each pathspec match `tree`, staging the
+// tree's version and unstaging paths the tree does not have.
+pub fn restore_index(
+    index: &mut Index,
+    tree: &str,
+    paths: &[String],
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let files = object::flatten_tree(tree)?;
+    for pathspec in paths {
+        let pathspec = pathspec.trim_end_matches('/');
+        let matches = |path: &str| path == pathspec || path.starts_with(&format!("{}/", pathspec));
+        let staged: Vec<String> = index
+            .entries()
+            .iter()
+            .filter(|entry| matches(&entry.path))
+            .map(|entry| entry.path.clone())
+            .collect();
+        let restored: Vec<_> = files.iter().filter(|(path, _)| matches(path)).collect();
+        if staged.is_empty() && restored.is_empty() {
+            return Err(Box::new(PathspecMismatch(pathspec.to_string())));
+        }
+        for path in staged {
+            index.remove(&path);
+        }
+        for (path, (mode, hash)) in restored {
+            index.add(IndexEntry::new(path, mode, hash)?);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collisions_include_leading_directories() {
+        let paths =
+            |paths: &[&str]| -> Vec<String> { paths.iter().map(|p| p.to_string()).collect() };
+        assert!(check_case_collisions(&paths(&["README", "src/readme", "src/main.rs"])).is_ok());
+        assert!(check_case_collisions(&paths(&["Docs/a", "docs/b"])).is_err());
+        let Err(CaseCollision(a, b)) = check_case_collisions(&paths(&["Docs/a", "docs"])) else {
+            panic!("no collision");
+        };
+        assert_eq!((a.as_str(), b.as_str()), ("Docs", "docs"));
+        assert!(check_case_collisions(&paths(&["ä", "Ä"])).is_err());
+    }
+}
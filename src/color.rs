@@ -0,0 +1,90 @@
+use crate::config;
+
+pub const RESET: &str = "\x1b[m";
+pub const BOLD: &str = "\x1b[1m";
+pub const RED: &str = "\x1b[31m";
+pub const GREEN: &str = "\x1b[32m";
+pub const YELLOW: &str = "\x1b[33m";
+pub const CYAN: &str = "\x1b[36m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn parse(value: &str) -> Option<ColorMode> {
+        match value.to_ascii_lowercase().as_str() {
+            "auto" | "true" | "yes" | "on" => Some(ColorMode::Auto),
+            "always" => Some(ColorMode::Always),
+            "never" | "false" | "no" | "off" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+
+    // Parses `--color` / `--color=<mode>`, returning None for any other argument.
+    pub fn parse_arg(arg: &str) -> Option<ColorMode> {
+        if arg == "--color" {
+            return Some(ColorMode::Always);
+        }
+        arg.strip_prefix("--color=").and_then(ColorMode::parse)
+    }
+
+    pub fn from_config() -> ColorMode {
+        config::get("color", "ui")
+            .and_then(|value| ColorMode::parse(&value))
+            .unwrap_or(ColorMode::Auto)
+    }
+
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Auto => is_terminal,
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+pub fn paint(text: &str, color: &str, enabled: bool) -> String {
+    if enabled {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+// Colors a unified diff line by line: headers bold, hunk headers cyan,
+// removals red and additions green.
+pub fn diff(text: &str, enabled: bool) -> String {
+    text.lines()
+        .map(|line| {
+            let meta = [
+                "diff --git ",
+                "index ",
+                "new file mode ",
+                "deleted file mode ",
+                "old mode ",
+                "new mode ",
+                "---",
+                "+++",
+            ];
+            let color = if meta.iter().any(|prefix| line.starts_with(prefix)) {
+                Some(BOLD)
+            } else if line.starts_with("@@") {
+                Some(CYAN)
+            } else if line.starts_with('-') {
+                Some(RED)
+            } else if line.starts_with('+') {
+                Some(GREEN)
+            } else {
+                None
+            };
+            match color {
+                Some(color) => paint(line, color, enabled) + "\n",
+                None => format!("{}\n", line),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,57 @@
+use ini::Ini;
+use std::path::{Path, PathBuf};
+
+use crate::repository::Repository;
+
+// Looks a key up in the repository's .git/config, falling back to the
+// user's ~/.gitconfig. Subsections are spelled as in the file, e.g.
+// `branch "main"`.
+pub fn get(section: &str, key: &str) -> Option<String> {
+    match Repository::current() {
+        Ok(repo) => get_in(repo.gitdir(), section, key),
+        // Outside a repository only ~/.gitconfig applies.
+        Err(_) => lookup(Vec::new(), section, key),
+    }
+}
+
+pub fn get_in(gitdir: &Path, section: &str, key: &str) -> Option<String> {
+    lookup(vec![gitdir.join("config")], section, key)
+}
+
+fn lookup(mut paths: Vec<PathBuf>, section: &str, key: &str) -> Option<String> {
+    if let Some(home) = dirs::home_dir() {
+        paths.push(home.join(".gitconfig"));
+    }
+    paths.iter().find_map(|path| {
+        Ini::load_from_file(path)
+            .ok()?
+            .get_from(Some(section), key)
+            .map(|value| value.to_string())
+    })
+}
+
+pub fn get_bool(section: &str, key: &str) -> Option<bool> {
+    match get(section, key)?.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+// Sets a key in the repository's .git/config, creating the file or section
+// as needed.
+pub fn set(
+    section: &str,
+    key: &str,
+    value: &str,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let path = Repository::current()?.gitdir().join("config");
+    let mut config = if path.is_file() {
+        Ini::load_from_file(&path)?
+    } else {
+        Ini::new()
+    };
+    config.with_section(Some(section)).set(key, value);
+    config.write_to_file(&path)?;
+    Ok(())
+}
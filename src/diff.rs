@@ -0,0 +1,820 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::ignore::Ignore;
+use crate::object::{self, Entry, InvalidObjectFormat, Object};
+use crate::patch;
+use crate::text;
+
+pub const DEFAULT_CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub edits: Vec<Edit>,
+}
+
+pub fn lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|&b| b == b'\n').collect()
+}
+
+// Finds a shortest edit script with Myers' algorithm in its linear-space
+// form, which splits the problem at the middle of an optimal path instead
+// of keeping a table of every pair of lines.
+pub fn diff_lines(a: &[&[u8]], b: &[&[u8]]) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(a.len().max(b.len()));
+    diff_range(a, b, 0, 0, &mut edits);
+    group_changes(&mut edits);
+    edits
+}
+
+// Appends the edits turning `a` into `b`, which start at lines `a_at` and
+// `b_at` of the whole files.
+fn diff_range(a: &[&[u8]], b: &[&[u8]], a_at: usize, b_at: usize, edits: &mut Vec<Edit>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    edits.extend((0..prefix).map(|i| Edit::Equal(a_at + i, b_at + i)));
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (a_mid_at, b_mid_at) = (a_at + prefix, b_at + prefix);
+
+    if a_mid.is_empty() {
+        edits.extend((0..b_mid.len()).map(|j| Edit::Insert(b_mid_at + j)));
+    } else if b_mid.is_empty() {
+        edits.extend((0..a_mid.len()).map(|i| Edit::Delete(a_mid_at + i)));
+    } else {
+        // Both ends differ, so at least two edits are needed and each side
+        // of the snake is a strictly smaller problem.
+        let (x, y, u, v) = middle_snake(a_mid, b_mid);
+        diff_range(&a_mid[..x], &b_mid[..y], a_mid_at, b_mid_at, edits);
+        edits.extend((0..u - x).map(|k| Edit::Equal(a_mid_at + x + k, b_mid_at + y + k)));
+        diff_range(&a_mid[u..], &b_mid[v..], a_mid_at + u, b_mid_at + v, edits);
+    }
+
+    let (a_end, b_end) = (a_at + a.len() - suffix, b_at + b.len() - suffix);
+    edits.extend((0..suffix).map(|k| Edit::Equal(a_end + k, b_end + k)));
+}
+
+// Searches for a shortest path from both corners at once until the two
+// meet, returning the run of equal lines, from (x, y) to (u, v), where the
+// path crosses the middle.
+fn middle_snake(a: &[&[u8]], b: &[&[u8]]) -> (usize, usize, usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    // forward[k] is the furthest x reached on diagonal k = x - y from the
+    // start; backward[k] the same from the end, with both files reversed.
+    let mut forward = vec![0isize; 2 * offset as usize + 1];
+    let mut backward = vec![0isize; 2 * offset as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            let reverse_k = delta - k;
+            if odd && (-(d - 1)..=d - 1).contains(&reverse_k) && x + backward[at(reverse_k)] >= n {
+                return (x0 as usize, y0 as usize, x as usize, y as usize);
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            let forward_k = delta - k;
+            if !odd && (-d..=d).contains(&forward_k) && x + forward[at(forward_k)] >= n {
+                return (
+                    (n - x) as usize,
+                    (m - y) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                );
+            }
+        }
+    }
+    unreachable!("the two searches meet within (n + m + 1) / 2 steps")
+}
+
+// Lists the deletions of each run of changed lines before its insertions,
+// as diff output shows them.
+fn group_changes(edits: &mut [Edit]) {
+    for run in edits.split_mut(|edit| matches!(edit, Edit::Equal(_, _))) {
+        run.sort_by_key(|edit| matches!(edit, Edit::Insert(_)));
+    }
+}
+
+// Groups changed lines into hunks with `context` unchanged lines around them.
+// Changes separated by at most 2 * context unchanged lines share a hunk.
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    let mut positions = Vec::with_capacity(edits.len());
+    let (mut old, mut new) = (0, 0);
+    for edit in edits {
+        positions.push((old, new));
+        match edit {
+            Edit::Equal(_, _) => {
+                old += 1;
+                new += 1;
+            }
+            Edit::Delete(_) => old += 1,
+            Edit::Insert(_) => new += 1,
+        }
+    }
+
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(_, _)))
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut hunks = Vec::new();
+    let mut k = 0;
+    while k < changes.len() {
+        let first = changes[k];
+        let mut last = first;
+        while k + 1 < changes.len() && changes[k + 1] - last - 1 <= 2 * context {
+            k += 1;
+            last = changes[k];
+        }
+        k += 1;
+
+        let start = first.saturating_sub(context);
+        let end = (last + context + 1).min(edits.len());
+        let hunk_edits = edits[start..end].to_vec();
+        let old_len = hunk_edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Insert(_)))
+            .count();
+        let new_len = hunk_edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Delete(_)))
+            .count();
+        let (old_start, new_start) = positions[start];
+        hunks.push(Hunk {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+            edits: hunk_edits,
+        });
+    }
+    hunks
+}
+
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+fn push_line(out: &mut String, prefix: char, line: &[u8]) {
+    out.push(prefix);
+    out.push_str(&String::from_utf8_lossy(line));
+    if !line.ends_with(b"\n") {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+// Renders one hunk of a diff between `a_lines` and `b_lines`.
+pub fn render_hunk(hunk: &Hunk, a_lines: &[&[u8]], b_lines: &[&[u8]]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "@@ -{} +{} @@",
+        range(hunk.old_start, hunk.old_len),
+        range(hunk.new_start, hunk.new_len)
+    );
+    for edit in &hunk.edits {
+        match *edit {
+            Edit::Equal(i, _) => push_line(&mut out, ' ', a_lines[i]),
+            Edit::Delete(i) => push_line(&mut out, '-', a_lines[i]),
+            Edit::Insert(j) => push_line(&mut out, '+', b_lines[j]),
+        }
+    }
+    out
+}
+
+pub fn unified(a: &[u8], b: &[u8], context: usize) -> String {
+    let a_lines = lines(a);
+    let b_lines = lines(b);
+    let edits = diff_lines(&a_lines, &b_lines);
+    hunks(&edits, context)
+        .iter()
+        .map(|hunk| render_hunk(hunk, &a_lines, &b_lines))
+        .collect()
+}
+
+// Rebuilds `a` with only the changes of the chosen hunks applied, for
+// staging part of a file. `edits` is the full script the hunks came from.
+pub fn apply_hunks(
+    a_lines: &[&[u8]],
+    b_lines: &[&[u8]],
+    edits: &[Edit],
+    chosen: &[&Hunk],
+) -> Vec<u8> {
+    let applied: Vec<Edit> = chosen
+        .iter()
+        .flat_map(|hunk| hunk.edits.iter().copied())
+        .filter(|edit| !matches!(edit, Edit::Equal(_, _)))
+        .collect();
+    let mut out = Vec::new();
+    for edit in edits {
+        match *edit {
+            Edit::Equal(i, _) => out.extend_from_slice(a_lines[i]),
+            Edit::Delete(i) if !applied.contains(edit) => out.extend_from_slice(a_lines[i]),
+            Edit::Insert(j) if applied.contains(edit) => out.extend_from_slice(b_lines[j]),
+            _ => {}
+        }
+    }
+    out
+}
+
+// Parses `-U<n>` / `--unified=<n>`, returning None for any other argument.
+pub fn parse_context_arg(arg: &str) -> Option<usize> {
+    arg.strip_prefix("--unified=")
+        .or_else(|| arg.strip_prefix("-U"))
+        .and_then(|n| n.parse().ok())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub path: String,
+    // (mode, hash) on each side; None when the path is absent on that side.
+    pub old: Option<(String, String)>,
+    pub new: Option<(String, String)>,
+}
+
+fn tree_entries(hash: Option<&str>) -> Result<Vec<Entry>, Box<dyn std::error::Error + 'static>> {
+    match hash {
+        Some(hash) => match Object::from_hash(hash)? {
+            Object::Tree(entries) => Ok(entries),
+            _ => Err(Box::new(InvalidObjectFormat)),
+        },
+        None => Ok(Vec::new()),
+    }
+}
+
+fn collect_changes(
+    prefix: &str,
+    old: Option<&str>,
+    new: Option<&str>,
+    changes: &mut Vec<Change>,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let old_entries = tree_entries(old)?;
+    let new_entries = tree_entries(new)?;
+    let mut names: Vec<&str> = old_entries
+        .iter()
+        .chain(&new_entries)
+        .map(|entry| entry.filename())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let path = format!("{}{}", prefix, name);
+        let old_entry = old_entries.iter().find(|entry| entry.filename() == name);
+        let new_entry = new_entries.iter().find(|entry| entry.filename() == name);
+        if let (Some(a), Some(b)) = (old_entry, new_entry) {
+            if a.mode() == b.mode() && a.hash() == b.hash() {
+                continue;
+            }
+        }
+
+        let old_tree = old_entry.filter(|entry| entry.is_tree());
+        let new_tree = new_entry.filter(|entry| entry.is_tree());
+        if old_tree.is_some() || new_tree.is_some() {
+            collect_changes(
+                &format!("{}/", path),
+                old_tree.map(|entry| entry.hash()),
+                new_tree.map(|entry| entry.hash()),
+                changes,
+            )?;
+        }
+
+        let old_blob = old_entry.filter(|entry| !entry.is_tree());
+        let new_blob = new_entry.filter(|entry| !entry.is_tree());
+        if old_blob.is_some() || new_blob.is_some() {
+            changes.push(Change {
+                path,
+                old: old_blob
+                    .map(|entry| (entry.mode().as_str().to_string(), entry.hash().to_string())),
+                new: new_blob
+                    .map(|entry| (entry.mode().as_str().to_string(), entry.hash().to_string())),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Lists the blobs that differ between two trees, recursing into subtrees.
+// A missing tree on either side is treated as empty.
+pub fn diff_trees(
+    old: Option<&str>,
+    new: Option<&str>,
+) -> Result<Vec<Change>, Box<dyn std::error::Error + 'static>> {
+    let mut changes = Vec::new();
+    collect_changes("", old, new, &mut changes)?;
+    Ok(changes)
+}
+
+fn blob(side: &Option<(String, String)>) -> Result<Vec<u8>, Box<dyn std::error::Error + 'static>> {
+    match side {
+        Some((_, hash)) => match Object::from_hash(hash)? {
+            Object::Blob(data) => Ok(data),
+            _ => Err(Box::new(InvalidObjectFormat)),
+        },
+        None => Ok(Vec::new()),
+    }
+}
+
+// The hash of one side of a change for an `index` line: abbreviated, or
+// in full when a binary patch is to be checked against it.
+fn index_hash(side: &Option<(String, String)>, full: bool) -> &str {
+    match side {
+        Some((_, hash)) if full => hash,
+        Some((_, hash)) => &hash[..7],
+        None if full => NULL_HASH,
+        None => &NULL_HASH[..7],
+    }
+}
+
+const NULL_HASH: &str = "0000000000000000000000000000000000000000";
+
+// Renders changes as a git-style patch.
+pub fn patch(
+    changes: &[Change],
+    context: usize,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    render(changes, context, ("", ""), false, |change, new| {
+        blob(if new { &change.new } else { &change.old })
+    })
+}
+
+// Like patch, but with binary files as "GIT binary patch" blocks that
+// `apply` can rebuild them from, and full hashes on their index lines, as
+// `git diff --binary` writes.
+pub fn binary_patch(
+    changes: &[Change],
+    context: usize,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    render(changes, context, ("", ""), true, |change, new| {
+        blob(if new { &change.new } else { &change.old })
+    })
+}
+
+// Writes the patch for `changes`, naming each side by its path below that
+// side's root and reading file contents through `read`, which is told
+// whether the old or the new side is wanted. A file present on one side
+// only is named under that side's root in both places.
+fn render(
+    changes: &[Change],
+    context: usize,
+    (old_root, new_root): (&str, &str),
+    binary: bool,
+    read: impl Fn(&Change, bool) -> Result<Vec<u8>, Box<dyn std::error::Error + 'static>>,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let mut out = String::new();
+    for change in changes {
+        let old = read(change, false)?;
+        let new = read(change, true)?;
+        let is_binary = text::is_binary(&old) || text::is_binary(&new);
+        let full = binary && is_binary;
+        let (old_root, new_root) = match (&change.old, &change.new) {
+            (None, _) => (new_root, new_root),
+            (_, None) => (old_root, old_root),
+            _ => (old_root, new_root),
+        };
+        let old_path = format!("a/{}{}", old_root, change.path);
+        let new_path = format!("b/{}{}", new_root, change.path);
+        let (old_hash, new_hash) = (index_hash(&change.old, full), index_hash(&change.new, full));
+        let _ = writeln!(out, "diff --git {} {}", old_path, new_path);
+        match (&change.old, &change.new) {
+            (None, Some((mode, _))) => {
+                let _ = writeln!(out, "new file mode {}", mode);
+                let _ = writeln!(out, "index {}..{}", old_hash, new_hash);
+            }
+            (Some((mode, _)), None) => {
+                let _ = writeln!(out, "deleted file mode {}", mode);
+                let _ = writeln!(out, "index {}..{}", old_hash, new_hash);
+            }
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode != new_mode => {
+                let _ = writeln!(out, "old mode {}", old_mode);
+                let _ = writeln!(out, "new mode {}", new_mode);
+                if change.old.as_ref().map(|(_, hash)| hash)
+                    != change.new.as_ref().map(|(_, hash)| hash)
+                {
+                    let _ = writeln!(out, "index {}..{}", old_hash, new_hash);
+                }
+            }
+            (Some((mode, _)), Some(_)) => {
+                let _ = writeln!(out, "index {}..{} {}", old_hash, new_hash, mode);
+            }
+            (None, None) => continue,
+        }
+
+        if old == new {
+            continue;
+        }
+        let old_name = match change.old {
+            Some(_) => old_path,
+            None => "/dev/null".to_string(),
+        };
+        let new_name = match change.new {
+            Some(_) => new_path,
+            None => "/dev/null".to_string(),
+        };
+        if is_binary {
+            if binary {
+                out.push_str(&patch::binary_patch(&old, &new)?);
+            } else {
+                let _ = writeln!(out, "Binary files {} and {} differ", old_name, new_name);
+            }
+            continue;
+        }
+        let _ = writeln!(out, "--- {}", old_name);
+        let _ = writeln!(out, "+++ {}", new_name);
+        out.push_str(&unified(&old, &new, context));
+    }
+    Ok(out)
+}
+
+// Diffs two paths on disk, each a file or a directory, without touching
+// the object store. Files in directories are paired by their path below
+// it, and a file compared against a directory is compared against the file
+// of the same name inside it.
+pub fn no_index(
+    old: &str,
+    new: &str,
+    context: usize,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let (mut old_root, mut new_root) = (Path::new(old).to_path_buf(), Path::new(new).to_path_buf());
+    match (old_root.is_dir(), new_root.is_dir()) {
+        (true, false) => old_root.push(new_root.file_name().ok_or(InvalidObjectFormat)?),
+        (false, true) => new_root.push(old_root.file_name().ok_or(InvalidObjectFormat)?),
+        _ => {}
+    }
+
+    let mut changes = Vec::new();
+    let prefixes = if old_root.is_dir() {
+        let scan = |root: &Path| {
+            let root = root.to_str().ok_or(InvalidObjectFormat)?;
+            object::scan_worktree(root, &Ignore::empty())
+        };
+        let old_files = scan(&old_root)?;
+        let new_files = scan(&new_root)?;
+        let mut names: Vec<&String> = old_files.keys().chain(new_files.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let (old_side, new_side) = (old_files.get(name).cloned(), new_files.get(name).cloned());
+            if old_side != new_side {
+                changes.push(Change {
+                    path: name.clone(),
+                    old: old_side,
+                    new: new_side,
+                });
+            }
+        }
+        (
+            format!("{}/", old_root.display()),
+            format!("{}/", new_root.display()),
+        )
+    } else {
+        let side = |path: &Path| -> Result<_, Box<dyn std::error::Error + 'static>> {
+            let metadata = fs::metadata(path)?;
+            let hash = Object::Blob(fs::read(path)?).hash()?;
+            Ok(Some((object::file_mode(&metadata).to_string(), hash)))
+        };
+        let (old_side, new_side) = (side(&old_root)?, side(&new_root)?);
+        if old_side != new_side {
+            changes.push(Change {
+                path: String::new(),
+                old: old_side,
+                new: new_side,
+            });
+        }
+        (
+            old_root.display().to_string(),
+            new_root.display().to_string(),
+        )
+    };
+
+    render(
+        &changes,
+        context,
+        (&prefixes.0, &prefixes.1),
+        false,
+        |change, new| {
+            let (root, side) = if new {
+                (&new_root, &change.new)
+            } else {
+                (&old_root, &change.old)
+            };
+            match side {
+                Some(_) if change.path.is_empty() => Ok(fs::read(root)?),
+                Some(_) => Ok(fs::read(root.join(&change.path))?),
+                None => Ok(Vec::new()),
+            }
+        },
+    )
+}
+
+// Renders `--name-status`: a line per path with A, D or M for whether it
+// was added, deleted or modified.
+pub fn name_status(changes: &[Change]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        let status = match (&change.old, &change.new) {
+            (None, _) => 'A',
+            (_, None) => 'D',
+            _ => 'M',
+        };
+        out.push_str(&format!("{}\t{}\n", status, change.path));
+    }
+    out
+}
+
+// Renders a `--stat` summary: one line per path plus a totals line.
+pub fn stat(changes: &[Change]) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let mut rows = Vec::new();
+    for change in changes {
+        let old = blob(&change.old)?;
+        let new = blob(&change.new)?;
+        if text::is_binary(&old) || text::is_binary(&new) {
+            rows.push((change.path.as_str(), Err((old.len(), new.len()))));
+            continue;
+        }
+        let a_lines = lines(&old);
+        let b_lines = lines(&new);
+        let edits = diff_lines(&a_lines, &b_lines);
+        let insertions = edits
+            .iter()
+            .filter(|e| matches!(e, Edit::Insert(_)))
+            .count();
+        let deletions = edits
+            .iter()
+            .filter(|e| matches!(e, Edit::Delete(_)))
+            .count();
+        rows.push((change.path.as_str(), Ok((insertions, deletions))));
+    }
+
+    let name_width = rows.iter().map(|(path, _)| path.len()).max().unwrap_or(0);
+    let max_changes = rows
+        .iter()
+        .filter_map(|(_, counts)| counts.ok().map(|(i, d)| i + d))
+        .max()
+        .unwrap_or(0);
+    let mut count_width = max_changes.to_string().len();
+    // Binary files show "Bin" in the count column.
+    if rows.iter().any(|(_, counts)| counts.is_err()) {
+        count_width = count_width.max(3);
+    }
+    // Like git, bars are scaled down to fit an 80 column line.
+    let max_bar = 80usize.saturating_sub(name_width + count_width + 6).max(1);
+    let scale = |n: usize| {
+        if n == 0 {
+            0
+        } else {
+            1 + n * (max_bar - 1) / max_changes
+        }
+    };
+
+    let mut out = String::new();
+    let (mut total_insertions, mut total_deletions) = (0, 0);
+    for (path, counts) in &rows {
+        match counts {
+            Ok((insertions, deletions)) => {
+                total_insertions += insertions;
+                total_deletions += deletions;
+                let (mut plus, mut minus) = (*insertions, *deletions);
+                if max_changes > max_bar {
+                    let mut total = scale(plus + minus);
+                    if total < 2 && plus > 0 && minus > 0 {
+                        total = 2;
+                    }
+                    if plus < minus {
+                        plus = scale(plus);
+                        minus = total - plus;
+                    } else {
+                        minus = scale(minus);
+                        plus = total - minus;
+                    }
+                }
+                let _ = writeln!(
+                    out,
+                    " {:name_width$} | {:>count_width$} {}{}",
+                    path,
+                    insertions + deletions,
+                    "+".repeat(plus),
+                    "-".repeat(minus),
+                );
+            }
+            Err((old_size, new_size)) => {
+                let _ = writeln!(
+                    out,
+                    " {:name_width$} | {:>count_width$} {} -> {} bytes",
+                    path, "Bin", old_size, new_size
+                );
+            }
+        }
+    }
+
+    let _ = write!(
+        out,
+        " {} file{} changed",
+        rows.len(),
+        if rows.len() == 1 { "" } else { "s" }
+    );
+    // Like git, a count is left out only when the other is not zero.
+    if total_insertions > 0 || total_deletions == 0 {
+        let _ = write!(
+            out,
+            ", {} insertion{}(+)",
+            total_insertions,
+            if total_insertions == 1 { "" } else { "s" }
+        );
+    }
+    if total_deletions > 0 || total_insertions == 0 {
+        let _ = write!(
+            out,
+            ", {} deletion{}(-)",
+            total_deletions,
+            if total_deletions == 1 { "" } else { "s" }
+        );
+    }
+    out.push('\n');
+    Ok(out)
+}
+
+// Renders `--summary`: a line for each path created, deleted or changing
+// mode.
+pub fn summary(changes: &[Change]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        match (&change.old, &change.new) {
+            (None, Some((mode, _))) => {
+                let _ = writeln!(out, " create mode {} {}", mode, change.path);
+            }
+            (Some((mode, _)), None) => {
+                let _ = writeln!(out, " delete mode {} {}", mode, change.path);
+            }
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode != new_mode => {
+                let _ = writeln!(
+                    out,
+                    " mode change {} => {} {}",
+                    old_mode, new_mode, change.path
+                );
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The length of the longest common subsequence, the slow way.
+    fn lcs_len(a: &[&[u8]], b: &[&[u8]]) -> usize {
+        let mut row = vec![0; b.len() + 1];
+        for x in a {
+            let mut diagonal = 0;
+            for (j, y) in b.iter().enumerate() {
+                let above = row[j + 1];
+                row[j + 1] = if x == y {
+                    diagonal + 1
+                } else {
+                    row[j + 1].max(row[j])
+                };
+                diagonal = above;
+            }
+        }
+        row[b.len()]
+    }
+
+    // Checks that `edits` walks both files in order and that every Equal
+    // pairs equal lines, so it really turns `a` into `b`.
+    fn check_script(a: &[&[u8]], b: &[&[u8]], edits: &[Edit]) {
+        let (mut i, mut j) = (0, 0);
+        for edit in edits {
+            match *edit {
+                Edit::Equal(x, y) => {
+                    assert_eq!((x, y), (i, j));
+                    assert_eq!(a[x], b[y]);
+                    i += 1;
+                    j += 1;
+                }
+                Edit::Delete(x) => {
+                    assert_eq!(x, i);
+                    i += 1;
+                }
+                Edit::Insert(y) => {
+                    assert_eq!(y, j);
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (a.len(), b.len()));
+    }
+
+    #[test]
+    fn edit_scripts_are_shortest() {
+        // A small linear congruential generator keeps the cases repeatable.
+        let mut seed = 12345u64;
+        let mut next = |bound: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+        let words: [&[u8]; 4] = [b"a\n", b"b\n", b"c\n", b"d\n"];
+        for _ in 0..500 {
+            let a: Vec<&[u8]> = (0..next(12)).map(|_| words[next(4) as usize]).collect();
+            let b: Vec<&[u8]> = (0..next(12)).map(|_| words[next(4) as usize]).collect();
+            let edits = diff_lines(&a, &b);
+            check_script(&a, &b, &edits);
+            let equal = edits
+                .iter()
+                .filter(|edit| matches!(edit, Edit::Equal(_, _)))
+                .count();
+            assert_eq!(equal, lcs_len(&a, &b), "{:?} -> {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn changed_runs_delete_before_inserting() {
+        let a: Vec<&[u8]> = vec![b"x\n", b"a\n", b"y\n", b"b\n", b"z\n"];
+        let b: Vec<&[u8]> = vec![b"x\n", b"c\n", b"y\n", b"d\n", b"e\n", b"z\n"];
+        assert_eq!(
+            diff_lines(&a, &b),
+            vec![
+                Edit::Equal(0, 0),
+                Edit::Delete(1),
+                Edit::Insert(1),
+                Edit::Equal(2, 2),
+                Edit::Delete(3),
+                Edit::Insert(3),
+                Edit::Insert(4),
+                Edit::Equal(4, 5),
+            ]
+        );
+    }
+
+    // A table of every pair of lines would need ten billion entries here.
+    #[test]
+    fn large_files_with_few_changes() {
+        let old: Vec<String> = (0..100_000).map(|n| format!("line {}\n", n)).collect();
+        let mut new = old.clone();
+        new[0] = "first\n".to_string();
+        new[50_000] = "middle\n".to_string();
+        new.push("last\n".to_string());
+        let a: Vec<&[u8]> = old.iter().map(|line| line.as_bytes()).collect();
+        let b: Vec<&[u8]> = new.iter().map(|line| line.as_bytes()).collect();
+        let edits = diff_lines(&a, &b);
+        check_script(&a, &b, &edits);
+        let changed = edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Equal(_, _)))
+            .count();
+        assert_eq!(changed, 5);
+    }
+}
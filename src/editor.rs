@@ -0,0 +1,166 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config;
+
+#[derive(Debug, Clone)]
+pub struct EditorFailed(pub String);
+
+impl fmt::Display for EditorFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "there was a problem with the editor '{}'", self.0)
+    }
+}
+
+impl std::error::Error for EditorFailed {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// The editor git would pick: $GIT_EDITOR, core.editor, $VISUAL, $EDITOR,
+// and finally vi.
+pub fn editor() -> String {
+    std::env::var("GIT_EDITOR")
+        .ok()
+        .or_else(|| config::get("core", "editor"))
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|editor| !editor.is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+// Writes `template` to `path`, lets the user edit it and returns what they
+// saved. The editor is run through the shell, so it may carry arguments.
+pub fn edit(path: &Path, template: &str) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    fs::write(path, template)?;
+    let editor = editor();
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(Box::new(EditorFailed(editor)));
+    }
+    Ok(fs::read_to_string(path)?)
+}
+
+// Everything from this line on is dropped under `--cleanup=scissors`.
+pub const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+// How `commit --cleanup=<mode>` tidies a message before it is recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cleanup {
+    // Whitespace cleanup plus dropping `#` comment lines.
+    Strip,
+    // Trailing whitespace and surplus blank lines only.
+    Whitespace,
+    // The message exactly as given.
+    Verbatim,
+    // Whitespace cleanup after cutting an edited message at the scissors
+    // line; the same as Whitespace for messages given with -m.
+    Scissors,
+    // Strip when the message was edited, Whitespace otherwise.
+    Default,
+}
+
+impl Cleanup {
+    pub fn parse(value: &str) -> Option<Cleanup> {
+        match value {
+            "strip" => Some(Cleanup::Strip),
+            "whitespace" => Some(Cleanup::Whitespace),
+            "verbatim" => Some(Cleanup::Verbatim),
+            "scissors" => Some(Cleanup::Scissors),
+            "default" => Some(Cleanup::Default),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, message: &str, edited: bool) -> String {
+        match self {
+            Cleanup::Strip => stripspace(message, true),
+            Cleanup::Whitespace => stripspace(message, false),
+            Cleanup::Verbatim => message.to_string(),
+            Cleanup::Scissors if edited => stripspace(cut_at_scissors(message), false),
+            Cleanup::Scissors => stripspace(message, false),
+            Cleanup::Default => stripspace(message, edited),
+        }
+    }
+}
+
+fn cut_at_scissors(message: &str) -> &str {
+    let mut at = 0;
+    for line in message.split_inclusive('\n') {
+        if line.trim_end_matches('\n') == SCISSORS {
+            return &message[..at];
+        }
+        at += line.len();
+    }
+    message
+}
+
+// Tidies a message the way `git stripspace` does: trailing whitespace is
+// trimmed, runs of blank lines become one and blank lines at either end are
+// removed. With `strip_comments`, `#` lines are dropped first.
+pub fn stripspace(message: &str, strip_comments: bool) -> String {
+    let mut out = String::new();
+    let mut blank = false;
+    for line in message.lines() {
+        if strip_comments && line.starts_with('#') {
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if blank && !out.is_empty() {
+            out.push('\n');
+        }
+        blank = false;
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &str = "\n\nsubject  \n# comment\n\n\n\nbody\t\n\n";
+
+    #[test]
+    fn each_mode_tidies_as_git_does() {
+        assert_eq!(Cleanup::Strip.apply(MESSAGE, false), "subject\n\nbody\n");
+        assert_eq!(
+            Cleanup::Whitespace.apply(MESSAGE, true),
+            "subject\n# comment\n\nbody\n"
+        );
+        assert_eq!(Cleanup::Verbatim.apply(MESSAGE, true), MESSAGE);
+        assert_eq!(
+            Cleanup::Default.apply(MESSAGE, false),
+            "subject\n# comment\n\nbody\n"
+        );
+        assert_eq!(Cleanup::Default.apply(MESSAGE, true), "subject\n\nbody\n");
+        for mode in ["strip", "whitespace", "verbatim", "scissors", "default"] {
+            assert!(Cleanup::parse(mode).is_some(), "{}", mode);
+        }
+        assert!(Cleanup::parse("Strip").is_none());
+    }
+
+    #[test]
+    fn scissors_cut_only_edited_messages() {
+        let message = format!("subject\n# kept\n{}\n# dropped\ntext\n", SCISSORS);
+        assert_eq!(Cleanup::Scissors.apply(&message, true), "subject\n# kept\n");
+        assert_eq!(Cleanup::Scissors.apply(&message, false), message.clone());
+        // Only a line that is the scissors line and nothing else cuts.
+        let message = format!("subject\n {}\n", SCISSORS);
+        assert_eq!(Cleanup::Scissors.apply(&message, true), message.clone());
+        assert_eq!(Cleanup::Scissors.apply(SCISSORS, true), "");
+    }
+}
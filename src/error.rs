@@ -0,0 +1,134 @@
+use std::fmt;
+use std::io;
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+
+use crate::object::InvalidObjectFormat;
+use crate::repository::NotARepository;
+
+// Failures of the object store that callers may want to tell apart.
+#[derive(Debug)]
+pub enum MygitError {
+    // No object is stored under this hash.
+    ObjectNotFound(String),
+    // An abbreviated hash that more than one stored object starts with.
+    AmbiguousObject(String),
+    // The object exists but could not be parsed.
+    InvalidObjectFormat,
+    Io(io::Error),
+    Utf8(Utf8Error),
+    // Anything else, such as a bad ref met while following replacements.
+    Other(Box<dyn std::error::Error + 'static>),
+}
+
+impl fmt::Display for MygitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MygitError::ObjectNotFound(hash) => write!(f, "object {} not found", hash),
+            MygitError::AmbiguousObject(prefix) => {
+                write!(f, "short object ID {} is ambiguous", prefix)
+            }
+            MygitError::InvalidObjectFormat => write!(f, "{}", InvalidObjectFormat),
+            MygitError::Io(err) => write!(f, "{}", err),
+            MygitError::Utf8(err) => write!(f, "{}", err),
+            MygitError::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for MygitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MygitError::Io(err) => Some(err),
+            MygitError::Utf8(err) => Some(err),
+            MygitError::Other(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MygitError {
+    fn from(err: io::Error) -> MygitError {
+        MygitError::Io(err)
+    }
+}
+
+impl From<Utf8Error> for MygitError {
+    fn from(err: Utf8Error) -> MygitError {
+        MygitError::Utf8(err)
+    }
+}
+
+impl From<FromUtf8Error> for MygitError {
+    fn from(err: FromUtf8Error) -> MygitError {
+        MygitError::Utf8(err.utf8_error())
+    }
+}
+
+impl From<NotARepository> for MygitError {
+    fn from(err: NotARepository) -> MygitError {
+        MygitError::Other(Box::new(err))
+    }
+}
+
+impl From<InvalidObjectFormat> for MygitError {
+    fn from(_: InvalidObjectFormat) -> MygitError {
+        MygitError::InvalidObjectFormat
+    }
+}
+
+// Code that still returns boxed errors is funnelled through here, keeping
+// the variants it can recognise.
+impl From<Box<dyn std::error::Error + 'static>> for MygitError {
+    fn from(err: Box<dyn std::error::Error + 'static>) -> MygitError {
+        let err = match err.downcast::<MygitError>() {
+            Ok(err) => return *err,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<io::Error>() {
+            Ok(err) => return MygitError::Io(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<FromUtf8Error>() {
+            Ok(err) => return MygitError::Utf8(err.utf8_error()),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<Utf8Error>() {
+            Ok(err) => return MygitError::Utf8(*err),
+            Err(err) => err,
+        };
+        match err.downcast::<InvalidObjectFormat>() {
+            Ok(_) => MygitError::InvalidObjectFormat,
+            Err(err) => MygitError::Other(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ignore::Ignore;
+    use crate::object::{self, Object};
+
+    #[test]
+    fn missing_objects_are_not_found() {
+        let hash = "0123456789abcdef0123456789abcdef01234567";
+        match Object::from_hash(hash) {
+            Err(MygitError::ObjectNotFound(missing)) => assert_eq!(missing, hash),
+            other => panic!("expected ObjectNotFound, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn boxed_errors_keep_their_variant() {
+        let err: Box<dyn std::error::Error + 'static> = Box::new(InvalidObjectFormat);
+        assert!(matches!(
+            MygitError::from(err),
+            MygitError::InvalidObjectFormat
+        ));
+        assert!(matches!(
+            object::create_tree("no/such/directory", &Ignore::new()),
+            Err(MygitError::Io(err)) if err.kind() == io::ErrorKind::NotFound
+        ));
+    }
+}
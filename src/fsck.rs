@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::object::{self, Object};
+use crate::pack;
+use crate::repository::Repository;
+
+pub enum Problem {
+    // A loose object whose content hashes to something other than its name.
+    HashMismatch {
+        hash: String,
+        actual: String,
+    },
+    // A loose object that could not be inflated or parsed.
+    Corrupt(String),
+    // An object that refers to one that is stored neither loose nor packed.
+    BrokenLink {
+        from_type: &'static str,
+        from: String,
+        to_type: &'static str,
+        to: String,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::HashMismatch { hash, actual } => {
+                write!(
+                    f,
+                    "hash mismatch for {} (content hashes to {})",
+                    hash, actual
+                )
+            }
+            Problem::Corrupt(hash) => write!(f, "object {} is corrupt", hash),
+            Problem::BrokenLink {
+                from_type,
+                from,
+                to_type,
+                to,
+            } => write!(
+                f,
+                "broken link from {} {} to {} {}",
+                from_type, from, to_type, to
+            ),
+        }
+    }
+}
+
+// Checks every loose object: that it hashes to its own name, and that the
+// trees, commits and tags among them only point at objects that exist.
+pub fn check() -> Result<Vec<Problem>, Box<dyn std::error::Error + 'static>> {
+    let objects = Repository::current()?.objects_dir();
+    let loose = object::find_objects_in(&objects, "")?;
+    let mut stored: HashSet<String> = loose.iter().cloned().collect();
+    for index in pack::indexes_in(&objects)? {
+        stored.extend(index.hashes().iter().cloned());
+    }
+
+    let mut problems = Vec::new();
+    for hash in &loose {
+        let Ok((object_type, content)) = object::read_loose(&objects, hash) else {
+            problems.push(Problem::Corrupt(hash.clone()));
+            continue;
+        };
+        let actual = object::hash_raw(&object_type, &content);
+        if actual != *hash {
+            problems.push(Problem::HashMismatch {
+                hash: hash.clone(),
+                actual,
+            });
+            continue;
+        }
+        let Ok(parsed) = Object::parse(&object_type, &content) else {
+            problems.push(Problem::Corrupt(hash.clone()));
+            continue;
+        };
+
+        let links: Vec<(&'static str, &str)> = match &parsed {
+            Object::Blob(_) => Vec::new(),
+            Object::Tree(entries) => entries
+                .iter()
+                // Submodule commits live in another repository.
+                .filter(|entry| entry.object_type() != "commit")
+                .map(|entry| (entry.object_type(), entry.hash()))
+                .collect(),
+            Object::Commit { tree, parents, .. } => std::iter::once(("tree", tree.as_str()))
+                .chain(parents.iter().map(|parent| ("commit", parent.as_str())))
+                .collect(),
+            Object::Tag {
+                object, tag_type, ..
+            } => {
+                let to_type = match tag_type.as_str() {
+                    "tree" => "tree",
+                    "commit" => "commit",
+                    "tag" => "tag",
+                    _ => "blob",
+                };
+                vec![(to_type, object.as_str())]
+            }
+        };
+        for (to_type, to) in links {
+            if !stored.contains(to) {
+                problems.push(Problem::BrokenLink {
+                    from_type: parsed.object_type(),
+                    from: hash.clone(),
+                    to_type,
+                    to: to.to_string(),
+                });
+            }
+        }
+    }
+    Ok(problems)
+}
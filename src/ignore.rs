@@ -1,49 +1,268 @@
-use std::{fs, path};
+use std::fs;
+use std::path::{self, Path, PathBuf};
 
+// One line of a .gitignore file, compiled.
+#[derive(Clone)]
+struct Pattern {
+    glob: String,
+    // A trailing `/` limits the pattern to directories.
+    dir_only: bool,
+    // A pattern with a `/` anywhere but at its end is matched against the
+    // path relative to its .gitignore; otherwise against the last component.
+    anchored: bool,
+    // The directory holding the .gitignore the pattern came from.
+    base: PathBuf,
+    // A leading `!` re-includes what an earlier pattern ignored.
+    negated: bool,
+}
+
+impl Pattern {
+    fn parse(line: &str, base: &Path) -> Option<Pattern> {
+        let line = line.trim_end_matches([' ', '\t', '\r']);
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (line, negated) = match line.strip_prefix('!') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let line = line.strip_prefix('\\').unwrap_or(line);
+        let (line, dir_only) = match line.strip_suffix('/') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let anchored = line.contains('/');
+        let glob = line.strip_prefix('/').unwrap_or(line);
+        if glob.is_empty() {
+            return None;
+        }
+        Some(Pattern {
+            glob: glob.to_string(),
+            dir_only,
+            anchored,
+            base: base.to_path_buf(),
+            negated,
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        let Some(relative) = relative.to_str() else {
+            return false;
+        };
+        if self.anchored {
+            wildmatch(self.glob.as_bytes(), relative.as_bytes())
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            wildmatch(self.glob.as_bytes(), name.as_bytes())
+        }
+    }
+}
+
+// Matches `text` against a gitignore glob: `*` and `?` stop at `/`, `**`
+// crosses directories, and `[...]` is a character class, negated by a
+// leading `!` or `^`.
+fn wildmatch(glob: &[u8], text: &[u8]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // `**/` also matches no directories at all.
+            let rest_after_slash = rest.strip_prefix(b"/").unwrap_or(rest);
+            (0..=text.len())
+                .any(|i| wildmatch(rest, &text[i..]) || wildmatch(rest_after_slash, &text[i..]))
+        }
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| wildmatch(rest, &text[i..])),
+        [b'?', rest @ ..] => match text {
+            [c, text @ ..] if *c != b'/' => wildmatch(rest, text),
+            _ => false,
+        },
+        [b'[', class @ ..] => {
+            let Some(end) = class.iter().skip(1).position(|&c| c == b']').map(|i| i + 1) else {
+                return matches!(text, [b'[', text @ ..] if wildmatch(class, text));
+            };
+            let (set, rest) = (&class[..end], &class[end + 1..]);
+            let (negated, set) = match set {
+                [b'!' | b'^', set @ ..] => (true, set),
+                set => (false, set),
+            };
+            let Some((&c, text)) = text.split_first() else {
+                return false;
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == b'-' {
+                    found |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    found |= set[i] == c;
+                    i += 1;
+                }
+            }
+            found != negated && c != b'/' && wildmatch(rest, text)
+        }
+        [b'\\', c, rest @ ..] | [c, rest @ ..] => match text {
+            [t, text @ ..] if t == c => wildmatch(rest, text),
+            _ => false,
+        },
+    }
+}
+
+// The rules in effect for one directory: those of every .gitignore from the
+// top of the working tree down to it, outermost first.
+#[derive(Clone)]
 pub struct Ignore {
-    entries: Vec<String>,
+    patterns: Vec<Pattern>,
+    // False for `Ignore::empty`, which reads no .gitignore files and treats
+    // `.git` as an ordinary name.
+    in_repository: bool,
+}
+
+impl Default for Ignore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Ignore {
+    // Reads the .gitignore at the top of the working tree. `.git` itself is
+    // always ignored.
     pub fn new() -> Ignore {
-        let mut entries = Vec::new();
-        if let Ok(content) = fs::read(".gitignore") {
-            entries = content
-                .split(|&b| b == b'\n')
-                .filter(|line| !line.starts_with(b"#") && line.len() > 0)
-                .map(|line| {
-                    path::absolute(String::from_utf8(line.to_vec()).unwrap())
-                        .unwrap()
-                        .to_str()
-                        .unwrap()
-                        .trim_end_matches('/')
-                        .to_string()
-                })
-                .collect();
-        }
-
-        entries.push(
-            path::absolute(".git")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-        );
-
-        Ignore { entries }
+        Ignore {
+            patterns: Vec::new(),
+            in_repository: true,
+        }
+        .with_dir(".")
+    }
+
+    // Compiles the lines of a .gitignore in `base`, keeping their order.
+    fn parse(text: &str, base: &Path) -> Vec<Pattern> {
+        text.lines()
+            .filter_map(|line| Pattern::parse(line, base))
+            .collect()
+    }
+
+    // Ignores nothing, for walking directories outside a repository.
+    pub fn empty() -> Ignore {
+        Ignore {
+            patterns: Vec::new(),
+            in_repository: false,
+        }
+    }
+
+    // The rules for walking `dir`: these followed by the ones in
+    // `dir/.gitignore`, which thereby take precedence. `self` is left
+    // untouched, so the subdirectory's rules never apply to its siblings.
+    pub fn with_dir(&self, dir: &str) -> Ignore {
+        let mut ignore = self.clone();
+        if !self.in_repository {
+            return ignore;
+        }
+        let Ok(base) = path::absolute(dir) else {
+            return ignore;
+        };
+        if let Ok(text) = fs::read_to_string(base.join(".gitignore")) {
+            ignore.patterns.extend(Ignore::parse(&text, &base));
+        }
+        ignore
     }
 
     pub fn contains(&self, path: &str) -> bool {
-        let abspath = path::absolute(path);
-        match abspath {
-            Err(_) => return false,
-            Ok(abspath) => {
-                if let Some(abspath) = abspath.to_str() {
-                    return self.entries.contains(&abspath.to_string());
-                } else {
-                    return false;
-                }
-            }
+        let Ok(abspath) = path::absolute(path) else {
+            return false;
+        };
+        if self.in_repository && abspath.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        self.is_ignored(&abspath, Path::new(path).is_dir())
+    }
+
+    // The last pattern matching the path decides, so a later `!pattern`
+    // overrides an earlier one and vice versa.
+    fn is_ignored(&self, abspath: &Path, is_dir: bool) -> bool {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| pattern.matches(abspath, is_dir))
+            .is_some_and(|pattern| !pattern.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negation_reincludes_a_later_match() {
+        let base = Path::new("/repo");
+        let parse = |text| Ignore {
+            patterns: Ignore::parse(text, base),
+            in_repository: true,
+        };
+        let ignore = parse("*.log\n!keep.log\n");
+        assert!(ignore.is_ignored(&base.join("a.log"), false));
+        assert!(!ignore.is_ignored(&base.join("keep.log"), false));
+        assert!(!ignore.is_ignored(&base.join("a.txt"), false));
+
+        let ignore = parse("!keep.log\n*.log\n");
+        assert!(ignore.is_ignored(&base.join("keep.log"), false));
+    }
+
+    #[test]
+    fn globs_match_like_gitignore() {
+        let base = Path::new("/repo");
+        let ignore = Ignore {
+            patterns: Ignore::parse("*.log\nbuild/\n/root-only.txt\nv?.tmp\n", base),
+            in_repository: true,
+        };
+        let ignored = |path: &str, is_dir| ignore.is_ignored(&base.join(path), is_dir);
+        assert!(ignored("a.log", false));
+        assert!(ignored("sub/deeper/b.log", false));
+        assert!(ignored("logs.log", true));
+        assert!(!ignored("a.log.txt", false));
+        assert!(!ignored("log", false));
+
+        assert!(ignored("build", true));
+        assert!(ignored("sub/build", true));
+        assert!(!ignored("build", false));
+        assert!(!ignored("builder", true));
+
+        assert!(ignored("root-only.txt", false));
+        assert!(!ignored("sub/root-only.txt", false));
+
+        assert!(ignored("v1.tmp", false));
+        assert!(!ignored("v10.tmp", false));
+        assert!(!ignore.is_ignored(Path::new("/elsewhere/a.log"), false));
+        assert!(ignore.contains(".git"));
+        assert!(ignore.contains("sub/.git"));
+    }
+
+    #[test]
+    fn nested_rules_stay_in_their_directory() {
+        let root = std::env::temp_dir().join(format!("mygit-ignore-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["a", "b"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join("x.tmp"), "").unwrap();
+        }
+        fs::write(root.join("a/.gitignore"), "*.tmp\n").unwrap();
+        let path = |p: &str| root.join(p).to_str().unwrap().to_string();
+
+        let top = Ignore {
+            patterns: Vec::new(),
+            in_repository: true,
         }
+        .with_dir(&path(""));
+        assert!(top.with_dir(&path("a")).contains(&path("a/x.tmp")));
+        assert!(!top.with_dir(&path("b")).contains(&path("b/x.tmp")));
+        assert!(!top.contains(&path("a/x.tmp")));
+        fs::remove_dir_all(&root).unwrap();
     }
 }
@@ -0,0 +1,444 @@
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::object::{self, InvalidObjectFormat, Object};
+use crate::refs;
+use crate::repository::Repository;
+
+const SIGNATURE: &[u8] = b"DIRC";
+const VERSION: u32 = 2;
+
+// Fixed-size part of an entry: ten 32-bit stat fields, the hash and flags.
+const ENTRY_HEADER_LEN: usize = 62;
+
+// Names at least this long store it in the flags instead of their length.
+const NAME_MASK: u16 = 0x0fff;
+
+#[derive(Debug, Clone)]
+pub struct UnmergedEntry(pub String);
+
+impl fmt::Display for UnmergedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: unmerged (conflicts must be resolved first)", self.0)
+    }
+}
+
+impl std::error::Error for UnmergedEntry {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.input(data);
+    let mut digest = [0; 20];
+    hasher.result(&mut digest);
+    digest
+}
+
+fn be_u32(data: &[u8], at: usize) -> Result<u32, InvalidObjectFormat> {
+    let bytes = data.get(at..at + 4).ok_or(InvalidObjectFormat)?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub ctime: (u32, u32),
+    pub mtime: (u32, u32),
+    pub dev: u32,
+    pub ino: u32,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u32,
+    pub hash: String,
+    pub flags: u16,
+    pub path: String,
+}
+
+impl IndexEntry {
+    // An entry with no stat information, which makes anything comparing
+    // stat data against the working tree look at the file's content.
+    pub fn new(path: &str, mode: &str, hash: &str) -> Result<IndexEntry, InvalidObjectFormat> {
+        Ok(IndexEntry {
+            ctime: (0, 0),
+            mtime: (0, 0),
+            dev: 0,
+            ino: 0,
+            mode: u32::from_str_radix(mode, 8).or(Err(InvalidObjectFormat))?,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            hash: hash.to_string(),
+            flags: 0,
+            path: path.to_string(),
+        })
+    }
+
+    // Records the stat information of the working tree file the entry was
+    // made from. The index keeps only the low 32 bits of each field.
+    pub fn with_metadata(mut self, metadata: &fs::Metadata) -> IndexEntry {
+        self.ctime = (metadata.ctime() as u32, metadata.ctime_nsec() as u32);
+        self.mtime = (metadata.mtime() as u32, metadata.mtime_nsec() as u32);
+        self.dev = metadata.dev() as u32;
+        self.ino = metadata.ino() as u32;
+        self.uid = metadata.uid();
+        self.gid = metadata.gid();
+        self.size = metadata.size() as u32;
+        self
+    }
+
+    // The mode as written in trees, e.g. `100644`.
+    pub fn mode(&self) -> String {
+        format!("{:o}", self.mode)
+    }
+
+    // 0 for a normal entry, 1 to 3 for the sides of a merge conflict.
+    pub fn stage(&self) -> u16 {
+        (self.flags >> 12) & 0x3
+    }
+}
+
+// The staging area, kept in .git/index as a version 2 index file. Entries
+// are sorted by path and then stage. Extensions are not understood and are
+// dropped when the index is written back.
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    entries: Vec<IndexEntry>,
+}
+
+impl Index {
+    // Reads .git/index; a repository without one has an empty index.
+    pub fn read() -> Result<Index, Box<dyn std::error::Error + 'static>> {
+        Index::read_from(&Repository::current()?.gitdir().join("index"))
+    }
+
+    // Reads the index file at `path`, such as another repository's.
+    pub fn read_from(path: &Path) -> Result<Index, Box<dyn std::error::Error + 'static>> {
+        match fs::read(path) {
+            Ok(data) => Index::parse(&data),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Index::default()),
+            Err(err) => Err(Box::new(err)),
+        }
+    }
+
+    // Reads .git/index. Without one yet, staging starts from what HEAD has.
+    pub fn load() -> Result<Index, Box<dyn std::error::Error + 'static>> {
+        Index::load_in(Repository::current()?.gitdir())
+    }
+
+    pub fn load_in(gitdir: &Path) -> Result<Index, Box<dyn std::error::Error + 'static>> {
+        let path = gitdir.join("index");
+        if path.is_file() {
+            return Index::read_from(&path);
+        }
+        let Some(head) = refs::resolve_in(gitdir, "HEAD")? else {
+            return Ok(Index::default());
+        };
+        match Object::read_in(gitdir, &head)? {
+            Object::Commit { tree, .. } => Index::from_tree_in(gitdir, &tree),
+            _ => Err(Box::new(InvalidObjectFormat)),
+        }
+    }
+
+    // Makes an existing index match `tree`, as after a commit or checkout.
+    // A repository that has never staged anything keeps having no index,
+    // which already stands for HEAD's tree.
+    pub fn reset_to(tree: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        Index::reset_to_in(Repository::current()?.gitdir(), tree)
+    }
+
+    pub fn reset_to_in(
+        gitdir: &Path,
+        tree: &str,
+    ) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        if !gitdir.join("index").is_file() {
+            return Ok(());
+        }
+        Index::from_tree_in(gitdir, tree)?.write_in(gitdir)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Index, Box<dyn std::error::Error + 'static>> {
+        if data.len() < 32 || !data.starts_with(SIGNATURE) {
+            return Err(Box::new(InvalidObjectFormat));
+        }
+        let (body, checksum) = data.split_at(data.len() - 20);
+        if sha1(body) != checksum {
+            return Err(Box::new(InvalidObjectFormat));
+        }
+        if be_u32(data, 4)? != VERSION {
+            return Err(Box::new(InvalidObjectFormat));
+        }
+
+        let count = be_u32(data, 8)? as usize;
+        // Every entry takes more than its fixed-size header, so the count
+        // cannot honestly exceed what the data has room for.
+        let mut entries = Vec::with_capacity(count.min(body.len() / ENTRY_HEADER_LEN));
+        let mut at = 12;
+        for _ in 0..count {
+            let field = |i: usize| be_u32(body, at + i * 4);
+            let hash = body
+                .get(at + 40..at + 60)
+                .ok_or(InvalidObjectFormat)?
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            let flags = u16::from_be_bytes(
+                body.get(at + 60..at + 62)
+                    .ok_or(InvalidObjectFormat)?
+                    .try_into()
+                    .unwrap(),
+            );
+            let name_at = at + ENTRY_HEADER_LEN;
+            let name_len = body
+                .get(name_at..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or(InvalidObjectFormat)?;
+            let path = String::from_utf8(body[name_at..name_at + name_len].to_vec())?;
+            entries.push(IndexEntry {
+                ctime: (field(0)?, field(1)?),
+                mtime: (field(2)?, field(3)?),
+                dev: field(4)?,
+                ino: field(5)?,
+                mode: field(6)?,
+                uid: field(7)?,
+                gid: field(8)?,
+                size: field(9)?,
+                hash,
+                flags,
+                path,
+            });
+            // Entries are NUL-padded to a multiple of eight bytes, with at
+            // least one NUL ending the name.
+            at += (ENTRY_HEADER_LEN + name_len + 8) & !7;
+        }
+        if at > body.len() {
+            return Err(Box::new(InvalidObjectFormat));
+        }
+        Ok(Index { entries })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(SIGNATURE);
+        data.extend_from_slice(&VERSION.to_be_bytes());
+        data.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+        for entry in &self.entries {
+            let start = data.len();
+            for field in [
+                entry.ctime.0,
+                entry.ctime.1,
+                entry.mtime.0,
+                entry.mtime.1,
+                entry.dev,
+                entry.ino,
+                entry.mode,
+                entry.uid,
+                entry.gid,
+                entry.size,
+            ] {
+                data.extend_from_slice(&field.to_be_bytes());
+            }
+            for i in (0..entry.hash.len()).step_by(2) {
+                data.push(u8::from_str_radix(&entry.hash[i..i + 2], 16).unwrap_or(0));
+            }
+            let name_len = entry.path.len().min(NAME_MASK as usize) as u16;
+            let flags = (entry.flags & !NAME_MASK) | name_len;
+            data.extend_from_slice(&flags.to_be_bytes());
+            data.extend_from_slice(entry.path.as_bytes());
+            let len = (data.len() - start + 8) & !7;
+            data.resize(start + len, 0);
+        }
+        let checksum = sha1(&data);
+        data.extend_from_slice(&checksum);
+        data
+    }
+
+    // Writes .git/index through a lock file, so a concurrent writer fails
+    // instead of interleaving and a reader never sees a partial index.
+    pub fn write(&self) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        self.write_in(Repository::current()?.gitdir())
+    }
+
+    pub fn write_in(&self, gitdir: &Path) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        let lock = gitdir.join("index.lock");
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock)?;
+        let written = file
+            .write_all(&self.serialize())
+            .and_then(|_| fs::rename(&lock, gitdir.join("index")));
+        if written.is_err() {
+            let _ = fs::remove_file(&lock);
+        }
+        Ok(written?)
+    }
+
+    // An index matching a tree, as a checkout of it would leave behind.
+    pub fn from_tree(tree: &str) -> Result<Index, Box<dyn std::error::Error + 'static>> {
+        Index::from_tree_in(Repository::current()?.gitdir(), tree)
+    }
+
+    pub fn from_tree_in(
+        gitdir: &Path,
+        tree: &str,
+    ) -> Result<Index, Box<dyn std::error::Error + 'static>> {
+        let mut index = Index::default();
+        for (path, (mode, hash)) in object::flatten_tree_in(gitdir, tree)? {
+            index.add(IndexEntry::new(&path, &mode, &hash)?);
+        }
+        Ok(index)
+    }
+
+    // Writes the trees the entries describe and returns the root tree's
+    // hash. An index still holding a merge conflict cannot be written.
+    pub fn write_tree(&self) -> Result<String, Box<dyn std::error::Error + 'static>> {
+        self.write_tree_in(&Repository::current()?.objects_dir())
+    }
+
+    pub fn write_tree_in(
+        &self,
+        objects: &Path,
+    ) -> Result<String, Box<dyn std::error::Error + 'static>> {
+        if let Some(entry) = self.entries.iter().find(|entry| entry.stage() != 0) {
+            return Err(Box::new(UnmergedEntry(entry.path.clone())));
+        }
+        let entries: Vec<(String, String, String)> = self
+            .entries
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.mode(), entry.hash.clone()))
+            .collect();
+        // The index's trees are what commits are made of.
+        object::build_tree_from_paths_in(objects, &entries, true)
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, path: &str) -> Option<&IndexEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.path == path && entry.stage() == 0)
+    }
+
+    // Inserts an entry in sorted position, replacing any entry for the same
+    // path and stage. As in git, a file takes the place of entries under a
+    // directory of its name, and a file in a directory that of any file
+    // named like one of its leading directories.
+    pub fn add(&mut self, entry: IndexEntry) {
+        let under = format!("{}/", entry.path);
+        self.entries.retain(|old| {
+            !old.path.starts_with(&under)
+                && !entry
+                    .path
+                    .strip_prefix(old.path.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+        });
+        let key = |e: &IndexEntry| (e.path.clone(), e.stage());
+        match self.entries.binary_search_by_key(&key(&entry), key) {
+            Ok(i) => self.entries[i] = entry,
+            Err(i) => self.entries.insert(i, entry),
+        }
+    }
+
+    // Removes every stage of `path`, returning whether anything was there.
+    pub fn remove(&mut self, path: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.path != path);
+        self.entries.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_and_directories_replace_each_other() {
+        let hash = "ce013625030ba8dba906f756967f9e9ca394464a";
+        let paths = |index: &Index| -> Vec<String> {
+            index
+                .entries()
+                .iter()
+                .map(|entry| entry.path.clone())
+                .collect()
+        };
+        let mut index = Index::default();
+        for path in ["a", "a-b", "ab/c"] {
+            index.add(IndexEntry::new(path, "100644", hash).unwrap());
+        }
+        index.add(IndexEntry::new("a/b/c", "100644", hash).unwrap());
+        assert_eq!(paths(&index), ["a-b", "a/b/c", "ab/c"]);
+        index.add(IndexEntry::new("a/b/d", "100644", hash).unwrap());
+        index.add(IndexEntry::new("a", "100644", hash).unwrap());
+        assert_eq!(paths(&index), ["a", "a-b", "ab/c"]);
+        index.add(IndexEntry::new("ab", "100644", hash).unwrap());
+        assert_eq!(paths(&index), ["a", "a-b", "ab"]);
+    }
+
+    #[test]
+    fn written_index_reads_back_the_same() {
+        let mut index = Index::default();
+        let mut stat = IndexEntry::new(
+            "src/main.rs",
+            "100755",
+            "ce013625030ba8dba906f756967f9e9ca394464a",
+        )
+        .unwrap();
+        stat.ctime = (1_700_000_000, 123);
+        stat.mtime = (1_700_000_001, 456);
+        stat.dev = 2049;
+        stat.ino = 77;
+        stat.uid = 1000;
+        stat.gid = 1000;
+        stat.size = 6;
+        index.add(stat);
+        // Names of every length up to a full padding block, and one too
+        // long for the flags to hold.
+        for len in 1..=9 {
+            let path = "n".repeat(len);
+            index.add(
+                IndexEntry::new(&path, "100644", "78981922613b2afb6025042ff6bd878ac1994e85")
+                    .unwrap(),
+            );
+        }
+        let long = format!("{}/file", "d".repeat(NAME_MASK as usize));
+        index.add(
+            IndexEntry::new(&long, "100644", "78981922613b2afb6025042ff6bd878ac1994e85").unwrap(),
+        );
+
+        let data = index.serialize();
+        let read = Index::parse(&data).unwrap();
+        assert_eq!(read.entries().len(), 11);
+        for (read, written) in read.entries().iter().zip(index.entries()) {
+            assert_eq!(read.path, written.path);
+            assert_eq!(read.mode(), written.mode());
+            assert_eq!(read.hash, written.hash);
+            assert_eq!((read.ctime, read.mtime), (written.ctime, written.mtime));
+            assert_eq!(
+                (read.dev, read.ino, read.size),
+                (written.dev, written.ino, written.size)
+            );
+            assert_eq!(read.stage(), 0);
+        }
+        assert_eq!(read.serialize(), data);
+    }
+
+    #[test]
+    fn counts_beyond_the_data_are_rejected_without_allocating_for_them() {
+        let mut data = Index::default().serialize();
+        data.truncate(data.len() - 20);
+        data[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        let checksum = sha1(&data);
+        data.extend_from_slice(&checksum);
+        assert!(Index::parse(&data).is_err());
+    }
+}
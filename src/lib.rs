@@ -1,2 +1,29 @@
+pub mod bisect;
+pub mod checkout;
+pub mod clock;
+pub mod color;
+pub mod config;
+pub mod diff;
+pub mod editor;
+pub mod error;
+pub mod fsck;
 pub mod ignore;
+pub mod index;
+pub mod notes;
 pub mod object;
+pub mod pack;
+pub mod pager;
+pub mod patch;
+pub mod reflog;
+pub mod refs;
+pub mod repository;
+pub mod revision;
+pub mod revwalk;
+pub mod show_branch;
+pub mod stash;
+pub mod stat_cache;
+pub mod status;
+pub mod subtree;
+pub mod text;
+pub mod trailer;
+pub mod transport;
@@ -0,0 +1,88 @@
+use std::fmt;
+
+use crate::object::{Entry, FileMode, Object, Timestamp, User};
+use crate::refs;
+use crate::revision;
+
+pub const NOTES_REF: &str = "refs/notes/commits";
+
+#[derive(Debug, Clone)]
+pub struct NoteExists(pub String);
+
+impl fmt::Display for NoteExists {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "found existing notes for object {}", self.0)
+    }
+}
+
+impl std::error::Error for NoteExists {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+fn notes_tree() -> Result<(Option<String>, Vec<Entry>), Box<dyn std::error::Error + 'static>> {
+    let Some(notes_commit) = refs::read_ref(NOTES_REF)? else {
+        return Ok((None, Vec::new()));
+    };
+    let Object::Commit { tree, .. } = Object::from_hash(&notes_commit)? else {
+        return Err(Box::new(crate::object::InvalidObjectFormat));
+    };
+    let Object::Tree(entries) = Object::from_hash(&tree)? else {
+        return Err(Box::new(crate::object::InvalidObjectFormat));
+    };
+    Ok((Some(notes_commit), entries))
+}
+
+// Notes are filed under the full hash of the object a revision names.
+pub fn show(object: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + 'static>> {
+    let object = revision::resolve(object)?;
+    let (_, entries) = notes_tree()?;
+    match entries.iter().find(|entry| entry.filename == object) {
+        Some(entry) => match Object::from_hash(&entry.hash)? {
+            Object::Blob(data) => Ok(Some(data)),
+            _ => Err(Box::new(crate::object::InvalidObjectFormat)),
+        },
+        None => Ok(None),
+    }
+}
+
+pub fn add(
+    object: &str,
+    message: &str,
+    user: &User,
+    force: bool,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let object = &revision::resolve(object)?;
+    let (parent, mut entries) = notes_tree()?;
+    if entries.iter().any(|entry| entry.filename == *object) {
+        if !force {
+            return Err(Box::new(NoteExists(object.to_string())));
+        }
+        entries.retain(|entry| entry.filename != *object);
+    }
+
+    let mut note = message.to_string();
+    if !note.ends_with('\n') {
+        note.push('\n');
+    }
+    entries.push(Entry::new(
+        FileMode::Blob,
+        object,
+        &Object::Blob(note.into_bytes()).write()?,
+    ));
+    entries.sort_by(|a, b| a.filename.cmp(&b.filename));
+
+    let commit = Object::Commit {
+        tree: Object::Tree(entries).write()?,
+        parents: parent.into_iter().collect(),
+        author: user.clone(),
+        author_timestamp: Timestamp::now(),
+        committer: user.clone(),
+        committer_timestamp: Timestamp::now(),
+        message: "Notes added by 'git notes add'\n".to_string(),
+    };
+    let hash = commit.write()?;
+    refs::update_ref(NOTES_REF, &hash)?;
+    Ok(hash)
+}
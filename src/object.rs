@@ -2,14 +2,25 @@ use crypto::digest::Digest;
 use crypto::sha1::Sha1;
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::fmt::Display;
 use std::fs;
 use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::ffi::OsStringExt;
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 
+use crate::error::MygitError;
 use crate::ignore::Ignore;
+use crate::pack;
+use crate::refs;
+use crate::repository::Repository;
+use crate::revision::MIN_ABBREV;
+use crate::stat_cache::StatCache;
+use crate::trailer;
 
 #[derive(Debug, Clone)]
 pub struct InvalidObjectFormat;
@@ -26,6 +37,96 @@ impl std::error::Error for InvalidObjectFormat {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct TagChainError(pub String);
+
+impl fmt::Display for TagChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tag {} does not peel to a non-tag object", self.0)
+    }
+}
+
+impl std::error::Error for TagChainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidParent(pub String);
+
+impl fmt::Display for InvalidParent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid parent {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidParent {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidTree(pub String);
+
+impl fmt::Display for InvalidTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is not a valid 'tree' object", self.0)
+    }
+}
+
+impl std::error::Error for InvalidTree {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// Longest tag-of-a-tag chain followed before giving up on peeling.
+pub const MAX_TAG_DEPTH: usize = 32;
+
+// Replace chains (refs/replace/<a> -> <b>, refs/replace/<b> -> <c>) are
+// followed at most this deep.
+const MAX_REPLACE_DEPTH: usize = 5;
+
+static REPLACE_OBJECTS: AtomicBool = AtomicBool::new(true);
+
+static DEREFERENCE_SYMLINKS: AtomicBool = AtomicBool::new(false);
+
+// Turns refs/replace lookups on or off for this process, like git's
+// --no-replace-objects. GIT_NO_REPLACE_OBJECTS also disables them.
+pub fn set_replace_objects(enabled: bool) {
+    REPLACE_OBJECTS.store(enabled, Ordering::Relaxed);
+}
+
+// Makes trees built from the working directory record what a symlink to a
+// file points at instead of the link itself. Links to directories and
+// dangling links are still recorded as links.
+pub fn set_dereference_symlinks(enabled: bool) {
+    DEREFERENCE_SYMLINKS.store(enabled, Ordering::Relaxed);
+}
+
+fn replacement(
+    gitdir: &Path,
+    hash: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    if !REPLACE_OBJECTS.load(Ordering::Relaxed)
+        || std::env::var_os("GIT_NO_REPLACE_OBJECTS").is_some()
+    {
+        return Ok(None);
+    }
+    let mut replaced = None;
+    for _ in 0..MAX_REPLACE_DEPTH {
+        let current = replaced.as_deref().unwrap_or(hash);
+        match refs::read_ref_in(gitdir, &format!("refs/replace/{}", current))? {
+            Some(target) if is_hash(&target) => replaced = Some(target.to_ascii_lowercase()),
+            Some(_) => return Err(Box::new(InvalidObjectFormat)),
+            None => break,
+        }
+    }
+    Ok(replaced)
+}
+
 #[derive(Clone)]
 pub struct User {
     name: String,
@@ -47,6 +148,7 @@ impl User {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Timestamp {
     seconds: i64,
     offset: i32,
@@ -57,12 +159,36 @@ impl Display for Timestamp {
         let sign = if self.offset < 0 { '-' } else { '+' };
         let offset = self.offset.abs();
         let hours = offset / 3600;
-        let minutes = offset % 3600;
+        let minutes = offset % 3600 / 60;
         write!(f, "{} {sign}{hours:02}{minutes:02}", self.seconds)
     }
 }
 
 impl Timestamp {
+    pub fn new(seconds: i64, offset: i32) -> Timestamp {
+        Timestamp { seconds, offset }
+    }
+
+    // Reads `<seconds> <+|-><hhmm>` as Display writes it and as it appears
+    // in commit and tag headers.
+    pub fn parse(value: &str) -> Result<Timestamp, InvalidObjectFormat> {
+        let (seconds, offset) = value.split_once(' ').ok_or(InvalidObjectFormat)?;
+        if offset.len() != 5 || !offset.is_ascii() {
+            return Err(InvalidObjectFormat);
+        }
+        let sign = match &offset[..1] {
+            "+" => 1,
+            "-" => -1,
+            _ => return Err(InvalidObjectFormat),
+        };
+        let hours: i32 = offset[1..3].parse().or(Err(InvalidObjectFormat))?;
+        let minutes: i32 = offset[3..].parse().or(Err(InvalidObjectFormat))?;
+        Ok(Timestamp {
+            seconds: seconds.parse().or(Err(InvalidObjectFormat))?,
+            offset: sign * (hours * 3600 + minutes * 60),
+        })
+    }
+
     pub fn now() -> Timestamp {
         let now = chrono::Local::now();
         let offset = now.offset().local_minus_utc();
@@ -71,17 +197,121 @@ impl Timestamp {
             offset,
         }
     }
+
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    // Formats the timestamp the way `git log` prints dates by default.
+    pub fn date(&self) -> String {
+        match (
+            chrono::FixedOffset::east_opt(self.offset),
+            chrono::DateTime::from_timestamp(self.seconds, 0),
+        ) {
+            (Some(offset), Some(date)) => date
+                .with_timezone(&offset)
+                .format("%a %b %-d %H:%M:%S %Y %z")
+                .to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    // Formats the timestamp as a mail's Date header carries it.
+    pub fn rfc2822_date(&self) -> String {
+        match (
+            chrono::FixedOffset::east_opt(self.offset),
+            chrono::DateTime::from_timestamp(self.seconds, 0),
+        ) {
+            (Some(offset), Some(date)) => date
+                .with_timezone(&offset)
+                .format("%a, %-d %b %Y %H:%M:%S %z")
+                .to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+// The kinds of entry a tree can hold, named by the modes git gives them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileMode {
+    Blob,
+    Executable,
+    Symlink,
+    // A submodule's commit.
+    Gitlink,
+    Tree,
+}
+
+impl FileMode {
+    // Reads a mode as trees and `mktree` input write it. Leading zeros are
+    // allowed, so `040000` is a tree.
+    pub fn parse(mode: &str) -> Option<FileMode> {
+        match mode.trim_start_matches('0') {
+            "100644" => Some(FileMode::Blob),
+            "100755" => Some(FileMode::Executable),
+            "120000" => Some(FileMode::Symlink),
+            "160000" => Some(FileMode::Gitlink),
+            "40000" => Some(FileMode::Tree),
+            _ => None,
+        }
+    }
+
+    // The mode as it is written into trees, where a tree's has no leading
+    // zero.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileMode::Blob => "100644",
+            FileMode::Executable => "100755",
+            FileMode::Symlink => "120000",
+            FileMode::Gitlink => "160000",
+            FileMode::Tree => "40000",
+        }
+    }
+
+    // The type of object an entry with this mode points at.
+    pub fn object_type(self) -> &'static str {
+        match self {
+            FileMode::Tree => "tree",
+            FileMode::Gitlink => "commit",
+            _ => "blob",
+        }
+    }
+}
+
+// Modes are displayed six digits wide, as `ls-tree` prints them.
+impl Display for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:0>6}", self.as_str())
+    }
 }
 
 pub struct Entry {
-    mode: String,
-    filename: String,
-    hash: String,
+    pub(crate) mode: FileMode,
+    pub(crate) filename: String,
+    pub(crate) hash: String,
+    // The mode as it was stored, when that was a legacy form such as a
+    // zero-padded `040000` or a group-writable `100664` that `mode` has been
+    // normalized from. Writing the tree back uses it so the hash does not
+    // change.
+    pub(crate) stored_mode: Option<String>,
 }
 
 impl Entry {
-    pub fn mode(&self) -> &str {
-        &self.mode
+    pub fn new(mode: FileMode, filename: &str, hash: &str) -> Entry {
+        Entry {
+            mode,
+            filename: filename.to_string(),
+            hash: hash.to_string(),
+            stored_mode: None,
+        }
+    }
+
+    pub fn mode(&self) -> FileMode {
+        self.mode
     }
 
     pub fn filename(&self) -> &str {
@@ -91,6 +321,15 @@ impl Entry {
     pub fn hash(&self) -> &str {
         &self.hash
     }
+
+    pub fn is_tree(&self) -> bool {
+        self.mode == FileMode::Tree
+    }
+
+    // The type of object the entry points at, going by its mode.
+    pub fn object_type(&self) -> &'static str {
+        self.mode.object_type()
+    }
 }
 
 pub enum Object {
@@ -105,19 +344,42 @@ pub enum Object {
         committer_timestamp: Timestamp,
         message: String,
     },
+    Tag {
+        object: String,
+        tag_type: String,
+        tag: String,
+        tagger: User,
+        tagger_timestamp: Timestamp,
+        message: String,
+    },
 }
 
 impl Object {
-    pub fn from_hash(hash: &str) -> Result<Object, Box<dyn std::error::Error + 'static>> {
-        let path = format!(".git/objects/{}/{}", &hash[..2], &hash[2..]);
-        let mut reader = BufReader::new(ZlibDecoder::new(fs::File::open(path)?));
+    pub fn from_hash(hash: &str) -> Result<Object, MygitError> {
+        Repository::current()?.read_object(hash)
+    }
 
-        let mut buf = Vec::new();
-        reader.read_until(b' ', &mut buf)?;
-        buf.pop();
+    // Reads the object stored under a full hash in any git directory,
+    // following its refs/replace/ entries.
+    pub fn read_in(gitdir: &Path, hash: &str) -> Result<Object, MygitError> {
+        let replaced = replacement(gitdir, hash)?;
+        let hash = replaced.as_deref().unwrap_or(hash);
+        let objects = gitdir.join("objects");
+        let (object_type, content) = match read_raw_in(&objects, hash).map_err(MygitError::from) {
+            Err(MygitError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(MygitError::ObjectNotFound(hash.to_string()));
+            }
+            result => result?,
+        };
+        Ok(Object::parse(&object_type, &content)?)
+    }
 
-        let object_type = String::from_utf8(buf)?;
-        match object_type.as_str() {
+    pub fn parse(
+        object_type: &str,
+        content: &[u8],
+    ) -> Result<Object, Box<dyn std::error::Error + 'static>> {
+        let mut reader = content;
+        match object_type {
             "blob" => {
                 let mut data = Vec::new();
                 reader.read_to_end(&mut data)?;
@@ -126,35 +388,75 @@ impl Object {
             "tree" => {
                 let mut entries = Vec::new();
                 while !reader.fill_buf()?.is_empty() {
+                    // A truncated entry may run out before its delimiter, so
+                    // check what was actually read rather than popping blindly.
                     let mut mode = Vec::new();
                     reader.read_until(b' ', &mut mode)?;
-                    mode.pop();
+                    if mode.pop() != Some(b' ')
+                        || mode.is_empty()
+                        || !mode.iter().all(u8::is_ascii_digit)
+                    {
+                        return Err(Box::new(InvalidObjectFormat));
+                    }
 
                     let mut filename = Vec::new();
                     reader.read_until(b'\0', &mut filename)?;
-                    filename.pop();
+                    if filename.pop() != Some(b'\0') || filename.is_empty() {
+                        return Err(Box::new(InvalidObjectFormat));
+                    }
 
                     let mut hash = [0; 20];
                     reader.read_exact(&mut hash)?;
 
+                    let stored_mode = String::from_utf8(mode)?;
+                    let mode = FileMode::parse(&stored_mode)
+                        .or_else(|| legacy_file_mode(&stored_mode))
+                        .ok_or(InvalidObjectFormat)?;
                     entries.push(Entry {
-                        mode: String::from_utf8(mode)?,
+                        mode,
                         filename: String::from_utf8(filename)?,
                         hash: hash.iter().map(|b| format!("{:02x}", b)).collect(),
+                        stored_mode: (mode.as_str() != stored_mode).then_some(stored_mode),
                     });
                 }
                 Ok(Object::Tree(entries))
             }
+            "commit" => {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                parse_commit(&String::from_utf8(data)?)
+            }
+            "tag" => {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                parse_tag(&String::from_utf8(data)?)
+            }
             _ => Err(Box::new(InvalidObjectFormat)),
         }
     }
-    pub fn write(&self) -> Result<String, Box<dyn std::error::Error + 'static>> {
-        let content = match self {
-            Object::Blob(data) => [format!("blob {}\0", data.len()).as_bytes(), &data].concat(),
+
+    pub fn object_type(&self) -> &'static str {
+        match self {
+            Object::Blob(_) => "blob",
+            Object::Tree(_) => "tree",
+            Object::Commit { .. } => "commit",
+            Object::Tag { .. } => "tag",
+        }
+    }
+
+    pub fn content(&self) -> Result<Vec<u8>, Box<dyn std::error::Error + 'static>> {
+        Ok(match self {
+            Object::Blob(data) => data.clone(),
             Object::Tree(entries) => {
                 let mut tree_content = Vec::new();
                 for entry in entries {
-                    tree_content.extend_from_slice(entry.mode.as_bytes());
+                    // A hash of the wrong length would silently shift every
+                    // entry after it.
+                    if !is_hash(&entry.hash) {
+                        return Err(Box::new(InvalidObjectFormat));
+                    }
+                    let mode = entry.stored_mode.as_deref().unwrap_or(entry.mode.as_str());
+                    tree_content.extend_from_slice(mode.as_bytes());
                     tree_content.push(b' ');
                     tree_content.extend_from_slice(entry.filename.as_bytes());
                     tree_content.push(b'\0');
@@ -164,11 +466,7 @@ impl Object {
                         .collect::<Result<Vec<_>, _>>()?;
                     tree_content.extend_from_slice(&hex_bytes);
                 }
-                [
-                    format!("tree {}\0", tree_content.len()).as_bytes(),
-                    &tree_content,
-                ]
-                .concat()
+                tree_content
             }
             Object::Commit {
                 tree,
@@ -178,62 +476,712 @@ impl Object {
                 committer,
                 committer_timestamp,
                 message,
-            } => {
-                let commit_content = format!(
-                    "tree {}\n\
+            } => format!(
+                "tree {}\n\
                   {}\
                   author {} {}\n\
                   committer {} {}\n\n\
-                  {}\n",
-                    tree,
-                    if parents.len() > 0 {
-                        parents
-                            .iter()
-                            .map(|p| format!("parent {}", p))
-                            .collect::<Vec<String>>()
-                            .join("\n")
-                            + "\n"
-                    } else {
-                        "".to_string()
-                    },
-                    author,
-                    author_timestamp,
-                    committer,
-                    committer_timestamp,
-                    message
-                );
+                  {}",
+                tree,
+                if !parents.is_empty() {
+                    parents
+                        .iter()
+                        .map(|p| format!("parent {}", p))
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                        + "\n"
+                } else {
+                    "".to_string()
+                },
+                author,
+                author_timestamp,
+                committer,
+                committer_timestamp,
+                message
+            )
+            .into_bytes(),
+            Object::Tag {
+                object,
+                tag_type,
+                tag,
+                tagger,
+                tagger_timestamp,
+                message,
+            } => format!(
+                "object {}\n\
+                  type {}\n\
+                  tag {}\n\
+                  tagger {} {}\n\n\
+                  {}",
+                object, tag_type, tag, tagger, tagger_timestamp, message
+            )
+            .into_bytes(),
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, Box<dyn std::error::Error + 'static>> {
+        let content = self.content()?;
+        Ok([
+            format!("{} {}\0", self.object_type(), content.len()).as_bytes(),
+            &content,
+        ]
+        .concat())
+    }
+
+    // Computes the object's hash without writing it.
+    pub fn hash(&self) -> Result<String, Box<dyn std::error::Error + 'static>> {
+        Ok(hash_content(&self.encode()?))
+    }
+
+    pub fn write(&self) -> Result<String, MygitError> {
+        Repository::current()?.write_object(self)
+    }
+
+    // Writes the object as a loose object of any objects directory.
+    pub fn write_in(&self, objects: &Path) -> Result<String, MygitError> {
+        if let Object::Commit { tree, parents, .. } = self {
+            check_tree(objects, tree).map_err(|err| MygitError::Other(Box::new(err)))?;
+            check_parents(objects, parents).map_err(|err| MygitError::Other(Box::new(err)))?;
+        }
+        let content = self.encode()?;
+        let hash = hash_content(&content);
+        let dir = objects.join(&hash[..2]);
+        let filepath = dir.join(&hash[2..]);
+        if filepath.exists() {
+            return Ok(hash);
+        }
+
+        fs::create_dir_all(&dir)?;
+        fs::write(filepath, compress(&content)?)?;
+        Ok(hash)
+    }
 
-                [
-                    format!("commit {}\0", commit_content.bytes().len()).as_bytes(),
-                    commit_content.as_bytes(),
-                ]
-                .concat()
+    // Inflates a blob's content straight into `writer`, a buffer at a time,
+    // so large files never have to fit in memory. Returns the number of
+    // bytes written.
+    pub fn write_blob_to(hash: &str, writer: &mut impl Write) -> Result<u64, MygitError> {
+        Object::write_blob_to_in(Repository::current()?.gitdir(), hash, writer)
+    }
+
+    pub fn write_blob_to_in(
+        gitdir: &Path,
+        hash: &str,
+        writer: &mut impl Write,
+    ) -> Result<u64, MygitError> {
+        let objects = gitdir.join("objects");
+        let hash = &resolve_object_in(&objects, hash)?;
+        let replaced = replacement(gitdir, hash)?;
+        let hash = replaced.as_deref().unwrap_or(hash);
+        let path = objects.join(&hash[..2]).join(&hash[2..]);
+        let file = match fs::File::open(path) {
+            // Packed objects are inflated whole, deltas and all.
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let Some(open) = find_packed(&objects, hash)? else {
+                    return Err(MygitError::ObjectNotFound(hash.to_string()));
+                };
+                let (object_type, content) = open.read(hash)?;
+                if object_type != "blob" {
+                    return Err(MygitError::InvalidObjectFormat);
+                }
+                writer.write_all(&content)?;
+                return Ok(content.len() as u64);
             }
+            file => file?,
+        };
+        let mut reader = BufReader::new(ZlibDecoder::new(file));
+
+        let mut header = Vec::new();
+        reader.read_until(b'\0', &mut header)?;
+        if header.pop() != Some(b'\0') {
+            return Err(MygitError::InvalidObjectFormat);
+        }
+        let header = String::from_utf8(header)?;
+        let size = match header.split_once(' ') {
+            Some(("blob", size)) => size
+                .parse::<u64>()
+                .map_err(|_| MygitError::InvalidObjectFormat)?,
+            _ => return Err(MygitError::InvalidObjectFormat),
         };
-        let hash = {
-            let mut hasher = Sha1::new();
-            hasher.input(&content);
-            hasher.result_str()
+        let written = std::io::copy(&mut reader, writer)?;
+        if written != size {
+            return Err(MygitError::InvalidObjectFormat);
+        }
+        Ok(written)
+    }
+
+    // Writes a blob of `size` bytes from `reader` without holding it in
+    // memory: the content goes through the hasher and the compressor a
+    // chunk at a time into a temporary file, which is renamed into place
+    // once the hash is known. Returns the hash.
+    pub fn write_blob_from_reader(reader: &mut impl Read, size: u64) -> Result<String, MygitError> {
+        Object::write_blob_from_reader_in(&Repository::current()?.objects_dir(), reader, size)
+    }
+
+    pub fn write_blob_from_reader_in(
+        objects: &Path,
+        reader: &mut impl Read,
+        size: u64,
+    ) -> Result<String, MygitError> {
+        let tmp = objects.join(format!(
+            "tmp_obj_{}_{}",
+            std::process::id(),
+            TMP_OBJECTS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let hash = match compress_blob(reader, size, &tmp) {
+            Ok(hash) => hash,
+            Err(err) => {
+                let _ = fs::remove_file(&tmp);
+                return Err(err);
+            }
         };
-        let dir = format!(".git/objects/{}", &hash[..2]);
-        let filepath = Path::new(&dir).join(&hash[2..]);
+        let dir = objects.join(&hash[..2]);
+        let filepath = dir.join(&hash[2..]);
         if filepath.exists() {
-            return Ok(hash);
+            fs::remove_file(&tmp)?;
+        } else {
+            fs::create_dir_all(&dir)?;
+            fs::rename(&tmp, filepath)?;
         }
+        Ok(hash)
+    }
 
-        fs::create_dir_all(&dir)?;
-        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
-        encoder.write_all(&content)?;
-        let compressed = encoder.finish()?;
-        fs::write(Path::new(&dir).join(&hash[2..]), &compressed)?;
+    // Follows tag -> tag -> ... until a non-tag object, returning it with its hash.
+    pub fn peel(hash: &str) -> Result<(String, Object), Box<dyn std::error::Error + 'static>> {
+        Object::peel_in(Repository::current()?.gitdir(), hash)
+    }
+
+    pub fn peel_in(
+        gitdir: &Path,
+        hash: &str,
+    ) -> Result<(String, Object), Box<dyn std::error::Error + 'static>> {
+        let objects = gitdir.join("objects");
+        let mut seen = HashSet::new();
+        let mut hash = hash.to_string();
+        loop {
+            if !seen.insert(hash.clone()) {
+                return Err(Box::new(TagChainError(hash)));
+            }
+            match Object::read_in(gitdir, &resolve_object_in(&objects, &hash)?)? {
+                Object::Tag { .. } if seen.len() > MAX_TAG_DEPTH => {
+                    return Err(Box::new(TagChainError(hash)));
+                }
+                Object::Tag { object, .. } => hash = object,
+                object => return Ok((hash, object)),
+            }
+        }
+    }
+
+    pub fn trailers(&self) -> Vec<(String, String)> {
+        match self {
+            Object::Commit { message, .. } => trailer::parse(message),
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Reads an object's type and content without parsing it, from the loose
+// objects or, failing that, from a pack.
+pub fn read_raw(hash: &str) -> Result<(String, Vec<u8>), Box<dyn std::error::Error + 'static>> {
+    read_raw_in(&Repository::current()?.objects_dir(), hash)
+}
+
+pub fn read_raw_in(
+    objects: &Path,
+    hash: &str,
+) -> Result<(String, Vec<u8>), Box<dyn std::error::Error + 'static>> {
+    match read_loose(objects, hash) {
+        Err(err) if is_not_found(err.as_ref()) => match find_packed(objects, hash)? {
+            Some(open) => open.read(hash),
+            None => Err(err),
+        },
+        result => result,
+    }
+}
+
+fn is_not_found(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<std::io::Error>()
+        .is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound)
+}
+
+// The first pack that has the object.
+fn find_packed(
+    objects: &Path,
+    hash: &str,
+) -> Result<Option<Arc<pack::OpenPack>>, Box<dyn std::error::Error + 'static>> {
+    Ok(pack::open_packs_in(objects)?
+        .into_iter()
+        .find(|open| open.index.contains(hash)))
+}
+
+// Reads a loose object out of any objects directory, such as another
+// repository's during a local fetch.
+pub fn read_loose(
+    objects: &Path,
+    hash: &str,
+) -> Result<(String, Vec<u8>), Box<dyn std::error::Error + 'static>> {
+    if !is_hash(hash) {
+        return Err(Box::new(MygitError::ObjectNotFound(hash.to_string())));
+    }
+    let path = objects.join(&hash[..2]).join(&hash[2..]);
+    let mut reader = BufReader::new(ZlibDecoder::new(fs::File::open(path)?));
+
+    let mut buf = Vec::new();
+    reader.read_until(b' ', &mut buf)?;
+    buf.pop();
+    let object_type = String::from_utf8(buf)?;
+
+    let mut size = Vec::new();
+    reader.read_until(b'\0', &mut size)?;
+
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+    Ok((object_type, content))
+}
+
+// Reads just the `<type> <size>` header of a loose object, inflating no
+// more of it than that. A packed object has to be read whole.
+pub fn peek_header(hash: &str) -> Result<(String, usize), Box<dyn std::error::Error + 'static>> {
+    peek_header_in(&Repository::current()?.objects_dir(), hash)
+}
+
+pub fn peek_header_in(
+    objects: &Path,
+    hash: &str,
+) -> Result<(String, usize), Box<dyn std::error::Error + 'static>> {
+    if !is_hash(hash) {
+        return Err(Box::new(MygitError::ObjectNotFound(hash.to_string())));
+    }
+    let path = objects.join(&hash[..2]).join(&hash[2..]);
+    let file = match fs::File::open(path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let Some(open) = find_packed(objects, hash)? else {
+                return Err(Box::new(err));
+            };
+            let (object_type, content) = open.read(hash)?;
+            return Ok((object_type, content.len()));
+        }
+        file => file?,
+    };
+    let mut reader = BufReader::new(ZlibDecoder::new(file));
+
+    let mut header = Vec::new();
+    reader.read_until(b'\0', &mut header)?;
+    if header.pop() != Some(b'\0') {
+        return Err(Box::new(InvalidObjectFormat));
+    }
+    let header = String::from_utf8(header)?;
+    let (object_type, size) = header.split_once(' ').ok_or(InvalidObjectFormat)?;
+    Ok((object_type.to_string(), size.parse()?))
+}
+
+// The content size an object declares in its header, without inflating
+// the content itself.
+pub fn size(hash: &str) -> Result<usize, Box<dyn std::error::Error + 'static>> {
+    Ok(peek_header(hash)?.1)
+}
+
+// Lists the stored objects whose hash starts with `prefix`, in sorted order.
+pub fn find_objects(prefix: &str) -> Result<Vec<String>, Box<dyn std::error::Error + 'static>> {
+    find_objects_in(&Repository::current()?.objects_dir(), prefix)
+}
+
+pub fn find_objects_in(
+    objects: &Path,
+    prefix: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + 'static>> {
+    let prefix = prefix.to_ascii_lowercase();
+    let mut found = Vec::new();
+    for dir in fs::read_dir(objects)? {
+        let dir = dir?;
+        let dirname = dir.file_name().to_string_lossy().to_string();
+        if dirname.len() != 2
+            || !dirname.chars().all(|c| c.is_ascii_hexdigit())
+            || !(dirname.starts_with(&prefix) || prefix.starts_with(&dirname))
+        {
+            continue;
+        }
+        for file in fs::read_dir(dir.path())? {
+            let hash = format!("{}{}", dirname, file?.file_name().to_string_lossy());
+            if hash.len() == 40 && hash.starts_with(&prefix) {
+                found.push(hash);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+// Completes an abbreviated hash, at least MIN_ABBREV characters long, to
+// the one stored object it names. Full hashes are returned as they are.
+pub fn resolve_object(hash: &str) -> Result<String, MygitError> {
+    resolve_object_in(&Repository::current()?.objects_dir(), hash)
+}
+
+pub fn resolve_object_in(objects: &Path, hash: &str) -> Result<String, MygitError> {
+    if is_hash(hash) {
+        return Ok(hash.to_ascii_lowercase());
+    }
+    if hash.len() < MIN_ABBREV || hash.len() > 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(MygitError::ObjectNotFound(hash.to_string()));
+    }
+    let prefix = hash.to_ascii_lowercase();
+    let mut matches = find_objects_in(objects, &prefix)?;
+    for open in pack::open_packs_in(objects)? {
+        matches.extend(
+            open.index
+                .hashes()
+                .iter()
+                .filter(|hash| hash.starts_with(&prefix))
+                .cloned(),
+        );
+    }
+    matches.sort();
+    matches.dedup();
+    match matches.len() {
+        0 => Err(MygitError::ObjectNotFound(hash.to_string())),
+        1 => Ok(matches.remove(0)),
+        _ => Err(MygitError::AmbiguousObject(hash.to_string())),
+    }
+}
+
+// Deletes loose objects that are already stored in a pack, returning the
+// removed hashes. With `dry_run` nothing is deleted.
+pub fn prune_packed(dry_run: bool) -> Result<Vec<String>, Box<dyn std::error::Error + 'static>> {
+    prune_packed_in(&Repository::current()?.objects_dir(), dry_run)
+}
+
+pub fn prune_packed_in(
+    objects: &Path,
+    dry_run: bool,
+) -> Result<Vec<String>, Box<dyn std::error::Error + 'static>> {
+    let indexes = pack::indexes_in(objects)?;
+    for index in &indexes {
+        index.verify_pack()?;
+    }
+    let mut pruned = Vec::new();
+    for hash in find_objects_in(objects, "")? {
+        if !indexes.iter().any(|index| index.contains(&hash)) {
+            continue;
+        }
+        if !dry_run {
+            let dir = objects.join(&hash[..2]);
+            fs::remove_file(dir.join(&hash[2..]))?;
+            if fs::read_dir(&dir)?.next().is_none() {
+                fs::remove_dir(&dir)?;
+            }
+        }
+        pruned.push(hash);
+    }
+    Ok(pruned)
+}
+
+// Shortens `hash` to the fewest characters (at least `min_len`) that no
+// other stored object shares.
+pub fn abbreviate(
+    hash: &str,
+    min_len: usize,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    abbreviate_in(&Repository::current()?.objects_dir(), hash, min_len)
+}
+
+pub fn abbreviate_in(
+    objects: &Path,
+    hash: &str,
+    min_len: usize,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let mut neighbours = find_objects_in(objects, &hash[..2])?;
+    for open in pack::open_packs_in(objects)? {
+        neighbours.extend(
+            open.index
+                .hashes()
+                .iter()
+                .filter(|packed| packed.starts_with(&hash[..2]))
+                .cloned(),
+        );
+    }
+    neighbours.sort();
+    neighbours.dedup();
+    for len in min_len.clamp(4, hash.len())..hash.len() {
+        let prefix = &hash[..len];
+        if neighbours.iter().filter(|h| h.starts_with(prefix)).count() <= 1 {
+            return Ok(prefix.to_string());
+        }
+    }
+    Ok(hash.to_string())
+}
+
+// Temporary files of streamed writes are numbered so that two in one
+// process never collide.
+static TMP_OBJECTS: AtomicUsize = AtomicUsize::new(0);
+
+const STREAM_CHUNK: usize = 64 * 1024;
+
+// Hashes a blob of `size` bytes from `reader` a chunk at a time, as
+// write_blob_from_reader does but without writing it.
+pub fn hash_blob_from_reader(reader: &mut impl Read, size: u64) -> Result<String, MygitError> {
+    stream_blob(reader, size, &mut std::io::sink())
+}
+
+fn compress_blob(reader: &mut impl Read, size: u64, tmp: &Path) -> Result<String, MygitError> {
+    let mut encoder = ZlibEncoder::new(fs::File::create(tmp)?, flate2::Compression::default());
+    let hash = stream_blob(reader, size, &mut encoder)?;
+    encoder.finish()?;
+    Ok(hash)
+}
+
+// Passes the blob's header and content on to `out` while hashing them.
+fn stream_blob(
+    reader: &mut impl Read,
+    size: u64,
+    out: &mut impl Write,
+) -> Result<String, MygitError> {
+    let header = format!("blob {}\0", size);
+    let mut hasher = Sha1::new();
+    hasher.input(header.as_bytes());
+    out.write_all(header.as_bytes())?;
+
+    let mut reader = reader.take(size);
+    let mut buf = vec![0; STREAM_CHUNK];
+    let mut total = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        hasher.input(&buf[..n]);
+        out.write_all(&buf[..n])?;
+        total += n as u64;
+    }
+    // A file that shrank while being read no longer matches its header.
+    if total != size {
+        return Err(MygitError::Io(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("expected {} bytes of blob content, read {}", size, total),
+        )));
+    }
+    Ok(hasher.result_str())
+}
+
+fn hash_content(content: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.input(content);
+    hasher.result_str()
+}
+
+// The hash of an object with this type and content, header included.
+pub fn hash_raw(object_type: &str, content: &[u8]) -> String {
+    hash_content(
+        &[
+            format!("{} {}\0", object_type, content.len()).as_bytes(),
+            content,
+        ]
+        .concat(),
+    )
+}
+
+pub(crate) fn compress(content: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(content)?;
+    encoder.finish()
+}
+
+// Collects objects and writes them together, so bulk operations create
+// each fan-out directory once and can sync everything in a single pass at
+// the end instead of paying for durability per object.
+#[derive(Default)]
+pub struct ObjectBatch {
+    pending: BTreeMap<String, Vec<u8>>,
+}
+
+impl ObjectBatch {
+    pub fn new() -> ObjectBatch {
+        ObjectBatch {
+            pending: BTreeMap::new(),
+        }
+    }
+
+    // Pending objects are keyed by hash, so content met again within the
+    // batch, such as two identical sibling directories, is compressed and
+    // written only once.
+    pub fn add(&mut self, object: &Object) -> Result<String, Box<dyn std::error::Error + 'static>> {
+        let content = object.encode()?;
+        let hash = hash_content(&content);
+        if !self.pending.contains_key(&hash) {
+            self.pending.insert(hash.clone(), compress(&content)?);
+        }
         Ok(hash)
     }
+
+    pub fn flush(&mut self, sync: bool) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        self.flush_into(&Repository::current()?.objects_dir(), sync)
+    }
+
+    pub fn flush_into(
+        &mut self,
+        objects: &Path,
+        sync: bool,
+    ) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        let mut written = Vec::new();
+        let mut dirs = Vec::new();
+        for (hash, compressed) in std::mem::take(&mut self.pending) {
+            let dir = objects.join(&hash[..2]);
+            if dirs.last() != Some(&dir) {
+                fs::create_dir_all(&dir)?;
+                dirs.push(dir.clone());
+            }
+            let filepath = dir.join(&hash[2..]);
+            if filepath.exists() {
+                continue;
+            }
+            let mut file = fs::File::create(filepath)?;
+            file.write_all(&compressed)?;
+            written.push(file);
+        }
+
+        if sync {
+            for file in written {
+                file.sync_data()?;
+            }
+            for dir in dirs {
+                fs::File::open(dir)?.sync_all()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn parse_signature(value: &str) -> Result<(User, Timestamp), InvalidObjectFormat> {
+    let (user, timestamp) = value.rsplit_once("> ").ok_or(InvalidObjectFormat)?;
+    let (name, email) = user.split_once(" <").ok_or(InvalidObjectFormat)?;
+    Ok((User::new(name, email), Timestamp::parse(timestamp)?))
+}
+
+// A commit's tree must be a tree already in the store.
+fn check_tree(objects: &Path, tree: &str) -> Result<(), InvalidTree> {
+    match peek_header_in(objects, tree) {
+        Ok((object_type, _)) if object_type == "tree" => Ok(()),
+        _ => Err(InvalidTree(tree.to_string())),
+    }
+}
+
+// Each parent of a commit must be a commit already in the store, and none
+// may be named twice, which would make a malformed merge.
+fn check_parents(objects: &Path, parents: &[String]) -> Result<(), InvalidParent> {
+    for (i, parent) in parents.iter().enumerate() {
+        let is_commit =
+            peek_header_in(objects, parent).is_ok_and(|(object_type, _)| object_type == "commit");
+        if !is_commit || parents[..i].contains(parent) {
+            return Err(InvalidParent(parent.clone()));
+        }
+    }
+    Ok(())
+}
+
+// Commit and tag messages are written byte for byte, so parsing and writing
+// an existing object gives back the same hash. New messages go through this
+// first to end in a newline, as `git commit-tree -m` writes them: one is
+// added when missing, and any the caller gave are kept. An empty message
+// stays empty.
+pub fn normalize_message(message: &str) -> String {
+    if message.is_empty() || message.ends_with('\n') {
+        message.to_string()
+    } else {
+        format!("{}\n", message)
+    }
+}
+
+fn is_hash(value: &str) -> bool {
+    value.len() == 40 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn parse_commit(content: &str) -> Result<Object, Box<dyn std::error::Error + 'static>> {
+    let (headers, message) = content.split_once("\n\n").ok_or(InvalidObjectFormat)?;
+
+    let mut tree = None;
+    let mut parents = Vec::new();
+    let mut author = None;
+    let mut committer = None;
+    for line in headers.lines() {
+        match line.split_once(' ') {
+            Some(("tree", value)) if is_hash(value) => tree = Some(value.to_string()),
+            Some(("parent", value)) if is_hash(value) => parents.push(value.to_string()),
+            Some(("tree" | "parent", _)) => return Err(Box::new(InvalidObjectFormat)),
+            Some(("author", value)) => author = Some(parse_signature(value)?),
+            Some(("committer", value)) => committer = Some(parse_signature(value)?),
+            _ => {}
+        }
+    }
+
+    let (author, author_timestamp) = author.ok_or(InvalidObjectFormat)?;
+    let (committer, committer_timestamp) = committer.ok_or(InvalidObjectFormat)?;
+    Ok(Object::Commit {
+        tree: tree.ok_or(InvalidObjectFormat)?,
+        parents,
+        author,
+        author_timestamp,
+        committer,
+        committer_timestamp,
+        message: message.to_string(),
+    })
+}
+
+fn parse_tag(content: &str) -> Result<Object, Box<dyn std::error::Error + 'static>> {
+    let (headers, message) = content.split_once("\n\n").ok_or(InvalidObjectFormat)?;
+
+    let mut object = None;
+    let mut tag_type = None;
+    let mut tag = None;
+    let mut tagger = None;
+    for line in headers.lines() {
+        match line.split_once(' ') {
+            Some(("object", value)) if is_hash(value) => object = Some(value.to_string()),
+            Some(("object", _)) => return Err(Box::new(InvalidObjectFormat)),
+            Some(("type", value)) => tag_type = Some(value.to_string()),
+            Some(("tag", value)) => tag = Some(value.to_string()),
+            Some(("tagger", value)) => tagger = Some(parse_signature(value)?),
+            _ => {}
+        }
+    }
+
+    let (tagger, tagger_timestamp) = tagger.ok_or(InvalidObjectFormat)?;
+    Ok(Object::Tag {
+        object: object.ok_or(InvalidObjectFormat)?,
+        tag_type: tag_type.ok_or(InvalidObjectFormat)?,
+        tag: tag.ok_or(InvalidObjectFormat)?,
+        tagger,
+        tagger_timestamp,
+        message: message.to_string(),
+    })
+}
+
+pub fn create_tree(path: &str, ignore: &Ignore) -> Result<String, MygitError> {
+    Ok(build_tree(path, ignore)?.0)
 }
 
-pub fn create_tree(
+// Writes the tree of the directory at `path` like create_tree, and also
+// returns the root tree itself, so callers can look at what was written
+// without reading it back.
+pub fn build_tree(path: &str, ignore: &Ignore) -> Result<(String, Object), MygitError> {
+    let repo = Repository::current()?;
+    let objects = repo.objects_dir();
+    let mut batch = ObjectBatch::new();
+    let mut cache = StatCache::load(repo.gitdir());
+    let tree = write_dir_tree(path, ignore, &objects, &mut batch, &mut cache)?;
+    // Stash commits these trees, so they must be on disk before it does.
+    batch.flush_into(&objects, true)?;
+    cache.save(repo.gitdir())?;
+    Ok(tree)
+}
+
+fn write_dir_tree(
     path: &str,
     ignore: &Ignore,
-) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    objects: &Path,
+    batch: &mut ObjectBatch,
+    cache: &mut StatCache,
+) -> Result<(String, Object), Box<dyn std::error::Error + 'static>> {
     let fs_entries = fs::read_dir(path)?;
     let mut entries = Vec::new();
 
@@ -251,29 +1199,721 @@ pub fn create_tree(
             continue;
         }
 
-        if fs_entry.file_type()?.is_dir() {
+        // The link itself is inspected, so a symlink to a directory is not
+        // recursed into, even one pointing back up the tree.
+        let metadata = path.symlink_metadata()?;
+        if metadata.is_dir() {
             entries.push(Entry {
-                mode: "040000".to_string(),
+                mode: FileMode::Tree,
                 filename,
-                hash: create_tree(filepath, ignore)?,
+                hash: write_dir_tree(filepath, &ignore.with_dir(filepath), objects, batch, cache)?
+                    .0,
+                stored_mode: None,
             });
             continue;
         }
 
-        let metadata = fs_entry.metadata()?;
-        if metadata.is_symlink() {
-            continue;
+        // An unchanged file is not read again, provided its blob is still
+        // stored loose.
+        let cached = cache
+            .get(filepath, &metadata)
+            .filter(|_| metadata.is_file())
+            .filter(|hash| objects.join(&hash[..2]).join(&hash[2..]).is_file());
+        let (mode, hash) = match cached {
+            Some(hash) => (file_mode(&metadata), hash),
+            None => write_worktree_blob(&path)?,
+        };
+        if metadata.is_file() {
+            cache.insert(filepath, &metadata, &hash);
         }
-
-        let is_executable = metadata.permissions().mode() & 0o111 != 0;
         entries.push(Entry {
-            mode: if is_executable { "100755" } else { "100644" }.to_string(),
+            mode,
             filename,
-            hash: Object::Blob(fs::read(path).or(Err(InvalidObjectFormat))?).write()?,
+            hash,
+            stored_mode: None,
         });
     }
 
-    entries.sort_by(|a, b| a.filename.cmp(&b.filename));
+    sort_entries(&mut entries);
+
+    let tree = Object::Tree(entries);
+    Ok((batch.add(&tree)?, tree))
+}
+
+// Very old versions of git recorded regular files with their permission
+// bits, such as `100664`; they are read as the blob or executable they are.
+fn legacy_file_mode(mode: &str) -> Option<FileMode> {
+    let mode = u32::from_str_radix(mode, 8).ok()?;
+    match (mode & 0o170000, mode & 0o100) {
+        (0o100000, 0) => Some(FileMode::Blob),
+        (0o100000, _) => Some(FileMode::Executable),
+        _ => None,
+    }
+}
+
+// Orders entries as git does, comparing a subtree's name as if it ended
+// with '/', so a directory `a` sorts after a file `a.txt` but before `a0`.
+pub(crate) fn sort_entries(entries: &mut [Entry]) {
+    entries.sort_by(|a, b| {
+        let key = |entry: &Entry| {
+            let mut key = entry.filename.as_bytes().to_vec();
+            if entry.is_tree() {
+                key.push(b'/');
+            }
+            key
+        };
+        key(a).cmp(&key(b))
+    });
+}
+
+// Builds nested trees from (path, mode, blob hash) triples without looking
+// at the working directory, returning the root tree's hash.
+pub fn build_tree_from_paths(
+    entries: &[(String, String, String)],
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    build_tree_from_paths_in(&Repository::current()?.objects_dir(), entries, false)
+}
+
+// With `sync`, the trees are on disk before this returns, as they must be
+// before a commit refers to them.
+pub fn build_tree_from_paths_in(
+    objects: &Path,
+    entries: &[(String, String, String)],
+    sync: bool,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let entries: Vec<(&str, &str, &str)> = entries
+        .iter()
+        .map(|(path, mode, hash)| (path.as_str(), mode.as_str(), hash.as_str()))
+        .collect();
+    let mut batch = ObjectBatch::new();
+    let hash = tree_from_paths(&entries, &mut batch)?;
+    batch.flush_into(objects, sync)?;
+    Ok(hash)
+}
+
+fn tree_from_paths(
+    entries: &[(&str, &str, &str)],
+    batch: &mut ObjectBatch,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let mut files = Vec::new();
+    let mut dirs: BTreeMap<&str, Vec<(&str, &str, &str)>> = BTreeMap::new();
+    for &(path, mode, hash) in entries {
+        if !is_hash(hash) {
+            return Err(Box::new(InvalidObjectFormat));
+        }
+        match path.split_once('/') {
+            Some((dir, rest)) if !dir.is_empty() && !rest.is_empty() => {
+                dirs.entry(dir).or_default().push((rest, mode, hash));
+            }
+            None if !path.is_empty() => files.push(Entry::new(
+                FileMode::parse(mode).ok_or(InvalidObjectFormat)?,
+                path,
+                hash,
+            )),
+            _ => return Err(Box::new(InvalidObjectFormat)),
+        }
+    }
+    for (name, children) in dirs {
+        files.push(Entry::new(
+            FileMode::Tree,
+            name,
+            &tree_from_paths(&children, batch)?,
+        ));
+    }
+
+    // A name listed twice, or as both a file and a directory, cannot be
+    // represented in a tree.
+    let mut names = HashSet::new();
+    if !files
+        .iter()
+        .all(|entry| names.insert(entry.filename.as_str()))
+    {
+        return Err(Box::new(InvalidObjectFormat));
+    }
+    sort_entries(&mut files);
+    batch.add(&Object::Tree(files))
+}
+
+pub(crate) fn file_mode(metadata: &fs::Metadata) -> FileMode {
+    if metadata.permissions().mode() & 0o111 != 0 {
+        FileMode::Executable
+    } else {
+        FileMode::Blob
+    }
+}
 
-    Object::Tree(entries).write()
+// The mode and blob hash a tree records for a file in the working
+// directory, streaming its content rather than reading it whole. A symlink
+// is stored as git stores it, as a blob holding the path it points at.
+pub fn hash_worktree_blob(
+    path: &Path,
+) -> Result<(FileMode, String), Box<dyn std::error::Error + 'static>> {
+    match worktree_file(path)? {
+        Some(metadata) => {
+            let mut file = fs::File::open(path)?;
+            let hash = hash_blob_from_reader(&mut file, metadata.len())?;
+            Ok((file_mode(&metadata), hash))
+        }
+        None => Ok((FileMode::Symlink, hash_raw("blob", &link_target(path)?))),
+    }
+}
+
+// Like hash_worktree_blob, but also writes the blob.
+pub fn write_worktree_blob(
+    path: &Path,
+) -> Result<(FileMode, String), Box<dyn std::error::Error + 'static>> {
+    match worktree_file(path)? {
+        Some(metadata) => {
+            let mut file = fs::File::open(path)?;
+            let hash = Object::write_blob_from_reader(&mut file, metadata.len())?;
+            Ok((file_mode(&metadata), hash))
+        }
+        None => Ok((FileMode::Symlink, Object::Blob(link_target(path)?).write()?)),
+    }
+}
+
+// The metadata of the file whose content `path` records, or None when it
+// is a symlink recorded as a link.
+fn worktree_file(
+    path: &Path,
+) -> Result<Option<fs::Metadata>, Box<dyn std::error::Error + 'static>> {
+    if !fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Ok(Some(fs::metadata(path)?));
+    }
+    if DEREFERENCE_SYMLINKS.load(Ordering::Relaxed) {
+        return Ok(fs::metadata(path).ok().filter(|target| target.is_file()));
+    }
+    Ok(None)
+}
+
+fn link_target(path: &Path) -> Result<Vec<u8>, std::io::Error> {
+    Ok(fs::read_link(path)?.into_os_string().into_vec())
+}
+
+// Maps every file under `path` to its (mode, blob hash), the way
+// create_tree would record it, without writing anything to the store.
+pub fn scan_worktree(
+    path: &str,
+    ignore: &Ignore,
+) -> Result<BTreeMap<String, (String, String)>, Box<dyn std::error::Error + 'static>> {
+    let mut files = BTreeMap::new();
+    scan_dir(path, "", ignore, &mut files, &hash_worktree_blob)?;
+    Ok(files)
+}
+
+// Writes the blobs of what `spec` names, a file or a directory and all it
+// holds, and maps their paths to (mode, hash) like scan_worktree. Nothing
+// outside `spec` is read, and an ignored or missing `spec` yields nothing.
+pub fn write_worktree_path(
+    spec: &str,
+    ignore: &Ignore,
+) -> Result<BTreeMap<String, (String, String)>, Box<dyn std::error::Error + 'static>> {
+    let mut files = BTreeMap::new();
+    let spec = spec.trim_start_matches("./").trim_end_matches('/');
+    if spec.is_empty() || spec == "." {
+        scan_dir(".", "", ignore, &mut files, &write_worktree_blob)?;
+        return Ok(files);
+    }
+    // Each directory on the way down brings its own .gitignore.
+    let mut ignore = ignore.clone();
+    let mut filepath = ".".to_string();
+    let mut components = spec.split('/').peekable();
+    while let Some(component) = components.next() {
+        filepath = format!("{}/{}", filepath, component);
+        if ignore.contains(&filepath) {
+            return Ok(files);
+        }
+        if components.peek().is_some() {
+            ignore = ignore.with_dir(&filepath);
+        }
+    }
+    match fs::symlink_metadata(&filepath) {
+        Ok(metadata) if metadata.is_dir() => scan_dir(
+            &filepath,
+            &format!("{}/", spec),
+            &ignore.with_dir(&filepath),
+            &mut files,
+            &write_worktree_blob,
+        )?,
+        Ok(_) => {
+            let (mode, hash) = write_worktree_blob(Path::new(&filepath))?;
+            files.insert(spec.to_string(), (mode.to_string(), hash));
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(Box::new(err)),
+    }
+    Ok(files)
+}
+
+// Walks the directory at `path`, recording each file as `blob` reads it.
+fn scan_dir(
+    path: &str,
+    prefix: &str,
+    ignore: &Ignore,
+    files: &mut BTreeMap<String, (String, String)>,
+    blob: &impl Fn(&Path) -> Result<(FileMode, String), Box<dyn std::error::Error + 'static>>,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    for fs_entry in fs::read_dir(path)? {
+        let fs_entry = fs_entry?;
+        let path = fs_entry.path();
+        let filepath = path.to_str().ok_or(InvalidObjectFormat)?;
+        let filename = fs_entry.file_name();
+        let name = format!(
+            "{}{}",
+            prefix,
+            filename.to_str().ok_or(InvalidObjectFormat)?
+        );
+
+        if ignore.contains(filepath) {
+            continue;
+        }
+
+        if path.symlink_metadata()?.is_dir() {
+            scan_dir(
+                filepath,
+                &format!("{}/", name),
+                &ignore.with_dir(filepath),
+                files,
+                blob,
+            )?;
+            continue;
+        }
+
+        let (mode, hash) = blob(&path)?;
+        files.insert(name, (mode.to_string(), hash));
+    }
+    Ok(())
+}
+
+// Maps every blob reachable from the tree `hash` to its (mode, hash),
+// keyed by its slash-separated path.
+pub fn flatten_tree(
+    hash: &str,
+) -> Result<BTreeMap<String, (String, String)>, Box<dyn std::error::Error + 'static>> {
+    flatten_tree_in(Repository::current()?.gitdir(), hash)
+}
+
+pub fn flatten_tree_in(
+    gitdir: &Path,
+    hash: &str,
+) -> Result<BTreeMap<String, (String, String)>, Box<dyn std::error::Error + 'static>> {
+    let mut files = BTreeMap::new();
+    flatten_into(gitdir, hash, "", &mut files)?;
+    Ok(files)
+}
+
+fn flatten_into(
+    gitdir: &Path,
+    hash: &str,
+    prefix: &str,
+    files: &mut BTreeMap<String, (String, String)>,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let Object::Tree(entries) = Object::read_in(gitdir, hash)? else {
+        return Err(Box::new(InvalidObjectFormat));
+    };
+    for entry in entries {
+        let path = format!("{}{}", prefix, entry.filename);
+        if entry.is_tree() {
+            flatten_into(gitdir, &entry.hash, &format!("{}/", path), files)?;
+        } else {
+            files.insert(path, (entry.mode.as_str().to_string(), entry.hash));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::FromUtf8Error;
+
+    #[test]
+    fn offsets_print_as_hours_and_minutes() {
+        assert_eq!(
+            Timestamp::new(1700000000, 0).to_string(),
+            "1700000000 +0000"
+        );
+        assert_eq!(
+            Timestamp::new(1700000000, 19800).to_string(),
+            "1700000000 +0530"
+        );
+        assert_eq!(
+            Timestamp::new(1700000000, -28800).to_string(),
+            "1700000000 -0800"
+        );
+        assert_eq!(
+            Timestamp::new(1700000000, -12600).to_string(),
+            "1700000000 -0330"
+        );
+    }
+
+    #[test]
+    fn tree_modes_are_written_as_before() {
+        let hash = "78981922613b2afb6025042ff6bd878ac1994e85";
+        let raw = |mode: &str, name: &str| {
+            let mut entry = format!("{} {}\0", mode, name).into_bytes();
+            entry.extend(
+                (0..40)
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hash[i..i + 2], 16).unwrap()),
+            );
+            entry
+        };
+
+        let tree = Object::Tree(vec![
+            Entry::new(FileMode::Blob, "a", hash),
+            Entry::new(FileMode::Executable, "b", hash),
+            Entry::new(FileMode::Symlink, "c", hash),
+            Entry::new(FileMode::Gitlink, "d", hash),
+            Entry::new(FileMode::Tree, "e", hash),
+        ]);
+        let expected = [
+            raw("100644", "a"),
+            raw("100755", "b"),
+            raw("120000", "c"),
+            raw("160000", "d"),
+            raw("40000", "e"),
+        ]
+        .concat();
+        assert_eq!(tree.content().unwrap(), expected);
+        assert_eq!(FileMode::Tree.to_string(), "040000");
+
+        // Legacy modes are understood but written back as they were stored.
+        let legacy = [raw("100664", "a"), raw("040000", "e")].concat();
+        let Object::Tree(entries) = Object::parse("tree", &legacy).unwrap() else {
+            panic!("expected a tree");
+        };
+        assert_eq!(entries[0].mode(), FileMode::Blob);
+        assert_eq!(entries[1].mode(), FileMode::Tree);
+        assert_eq!(Object::Tree(entries).content().unwrap(), legacy);
+        assert!(Object::parse("tree", &raw("170000", "x")).is_err());
+    }
+
+    #[test]
+    fn trees_with_malformed_hashes_are_not_written() {
+        let hash = "78981922613b2afb6025042ff6bd878ac1994e85";
+        for bad in [
+            &hash[..39],
+            &hash[..38],
+            "zz981922613b2afb6025042ff6bd878ac1994e85",
+        ] {
+            let tree = Object::Tree(vec![Entry::new(FileMode::Blob, "a", bad)]);
+            assert!(
+                matches!(tree.write(), Err(MygitError::InvalidObjectFormat)),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn parse_reads_back_what_display_writes() {
+        for (text, offset) in [
+            ("1700000000 +0000", 0),
+            ("1700000000 -0800", -28800),
+            ("1700000000 +0545", 20700),
+        ] {
+            let timestamp = Timestamp::parse(text).unwrap();
+            assert_eq!(timestamp, Timestamp::new(1700000000, offset));
+            assert_eq!(timestamp.to_string(), text);
+        }
+        for text in [
+            "1700000000",
+            "1700000000 0530",
+            "1700000000 +5:30",
+            "x +0000",
+        ] {
+            assert!(Timestamp::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn identical_subtrees_are_written_once() {
+        let (a, b) = (
+            "78981922613b2afb6025042ff6bd878ac1994e85",
+            "61780798228d17af2d34fce4cfbdf35556832472",
+        );
+        let entries = [
+            ("x/a", "100644", a),
+            ("y/a", "100644", a),
+            ("z/deeper/a", "100644", a),
+            ("z/b", "100644", b),
+        ];
+        let mut batch = ObjectBatch::new();
+        let root = tree_from_paths(&entries, &mut batch).unwrap();
+        // The root, the shared `a` directory, and `z`.
+        assert_eq!(batch.pending.len(), 3);
+        assert!(batch.pending.contains_key(&root));
+
+        let objects = std::env::temp_dir().join(format!("mygit-subtrees-{}", std::process::id()));
+        batch.flush_into(&objects, false).unwrap();
+        assert!(batch.pending.is_empty());
+        let written: usize = fs::read_dir(&objects)
+            .unwrap()
+            .map(|dir| fs::read_dir(dir.unwrap().path()).unwrap().count())
+            .sum();
+        assert_eq!(written, 3);
+        fs::remove_dir_all(&objects).unwrap();
+    }
+
+    #[test]
+    fn commits_read_back_as_git_wrote_them() {
+        let tree = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+        let (a, b) = (
+            "693ba8b8ded38baf9791b4d23123263b48d76349",
+            "90f3acc78c3922e953596b27881569315ea31e16",
+        );
+        // As `git commit-tree` wrote it, with two parents and a message
+        // ending in a blank line.
+        let raw = format!(
+            "tree {}\nparent {}\nparent {}\n\
+             author A U Thor <author@example.com> 1700000000 +0530\n\
+             committer C O Mitter <committer@example.com> 1700000100 -0800\n\
+             \nmerge\n\nbody line\n\n",
+            tree, a, b
+        );
+        assert_eq!(
+            hash_raw("commit", raw.as_bytes()),
+            "5bc6d45823f4394077ac5ee5b729b00211a3cede"
+        );
+        let commit = Object::parse("commit", raw.as_bytes()).unwrap();
+        let Object::Commit {
+            tree: parsed_tree,
+            parents,
+            author,
+            author_timestamp,
+            committer,
+            committer_timestamp,
+            message,
+        } = &commit
+        else {
+            panic!("not a commit");
+        };
+        assert_eq!(parsed_tree, tree);
+        assert_eq!(parents, &[a, b]);
+        assert_eq!(author.to_string(), "A U Thor <author@example.com>");
+        assert_eq!(*author_timestamp, Timestamp::new(1700000000, 19800));
+        assert_eq!(committer.to_string(), "C O Mitter <committer@example.com>");
+        assert_eq!(*committer_timestamp, Timestamp::new(1700000100, -28800));
+        assert_eq!(message, "merge\n\nbody line\n\n");
+        assert_eq!(commit.content().unwrap(), raw.as_bytes());
+
+        for parents in [Vec::new(), vec![a], vec![a, b, a]] {
+            for message in ["", "subject", "subject\n", "subject\n\n\n", "\n\nbody\n"] {
+                let user = User::new("Test", "test@example.com");
+                let commit = Object::Commit {
+                    tree: tree.to_string(),
+                    parents: parents.iter().map(|parent| parent.to_string()).collect(),
+                    author: user.clone(),
+                    author_timestamp: Timestamp::new(1700000000, 0),
+                    committer: user,
+                    committer_timestamp: Timestamp::new(1700000000, 0),
+                    message: message.to_string(),
+                };
+                let content = commit.content().unwrap();
+                let parsed = Object::parse("commit", &content).unwrap();
+                assert_eq!(parsed.content().unwrap(), content, "{:?}", message);
+                assert_eq!(parsed.hash().unwrap(), commit.hash().unwrap());
+                let Object::Commit {
+                    parents: read_parents,
+                    message: read_message,
+                    ..
+                } = parsed
+                else {
+                    panic!("not a commit");
+                };
+                assert_eq!(read_parents, parents);
+                assert_eq!(read_message, message);
+            }
+        }
+    }
+
+    #[test]
+    fn tags_read_back_as_git_wrote_them() {
+        let commit = "693ba8b8ded38baf9791b4d23123263b48d76349";
+        // As `git tag -a` wrote it.
+        let raw = format!(
+            "object {}\ntype commit\ntag v1.0\n\
+             tagger C O Mitter <committer@example.com> 1700000200 +0900\n\
+             \nrelease 1.0\n\nnotes here\n",
+            commit
+        );
+        assert_eq!(
+            hash_raw("tag", raw.as_bytes()),
+            "0fd85776add4dc5df5bc8d18b575da5ed553cd31"
+        );
+        let tag = Object::parse("tag", raw.as_bytes()).unwrap();
+        let Object::Tag {
+            object,
+            tag_type,
+            tag: name,
+            tagger,
+            tagger_timestamp,
+            message,
+        } = &tag
+        else {
+            panic!("not a tag");
+        };
+        assert_eq!(object, commit);
+        assert_eq!(tag_type, "commit");
+        assert_eq!(name, "v1.0");
+        assert_eq!(tagger.to_string(), "C O Mitter <committer@example.com>");
+        assert_eq!(*tagger_timestamp, Timestamp::new(1700000200, 32400));
+        assert_eq!(message, "release 1.0\n\nnotes here\n");
+        assert_eq!(tag.object_type(), "tag");
+        assert_eq!(tag.content().unwrap(), raw.as_bytes());
+        assert_eq!(
+            tag.hash().unwrap(),
+            "0fd85776add4dc5df5bc8d18b575da5ed553cd31"
+        );
+
+        for tag_type in ["blob", "tree", "tag"] {
+            let raw = raw.replace("type commit", &format!("type {}", tag_type));
+            let tag = Object::parse("tag", raw.as_bytes()).unwrap();
+            assert_eq!(tag.content().unwrap(), raw.as_bytes());
+        }
+        for bad in [
+            raw.replace("object ", "objekt "),
+            raw.replace("type commit\n", ""),
+            raw.replace("tag v1.0\n", ""),
+            raw.replace("1700000200 +0900", "soon"),
+        ] {
+            assert!(Object::parse("tag", bad.as_bytes()).is_err(), "{:?}", bad);
+        }
+    }
+
+    // A small linear congruential generator keeps the cases repeatable.
+    fn generator(mut seed: u64) -> impl FnMut(u64) -> u64 {
+        move |bound| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        }
+    }
+
+    fn samples() -> Vec<(&'static str, Vec<u8>)> {
+        let hash = "78981922613b2afb6025042ff6bd878ac1994e85";
+        let user = User::new("A U Thor", "author@example.com");
+        let objects = [
+            Object::Tree(vec![
+                Entry::new(FileMode::Blob, "a", hash),
+                Entry::new(FileMode::Executable, "b c", hash),
+                Entry::new(FileMode::Tree, "d", hash),
+            ]),
+            Object::Commit {
+                tree: hash.to_string(),
+                parents: vec![hash.to_string(), hash.to_string()],
+                author: user.clone(),
+                author_timestamp: Timestamp::new(1700000000, 3600),
+                committer: user.clone(),
+                committer_timestamp: Timestamp::new(1700000001, -3600),
+                message: "subject\n\nbody\n".to_string(),
+            },
+            Object::Tag {
+                object: hash.to_string(),
+                tag_type: "commit".to_string(),
+                tag: "v1".to_string(),
+                tagger: user,
+                tagger_timestamp: Timestamp::new(1700000000, 0),
+                message: "release\n".to_string(),
+            },
+        ];
+        objects
+            .iter()
+            .map(|object| (object.object_type(), object.content().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn truncated_objects_are_rejected() {
+        for (object_type, content) in samples() {
+            assert_eq!(
+                Object::parse(object_type, &content)
+                    .unwrap()
+                    .content()
+                    .unwrap(),
+                content
+            );
+            // A tree cut between entries is still a tree, and a commit or
+            // tag cut in its message is still a commit or tag.
+            let message = content.windows(2).position(|w| w == b"\n\n");
+            for len in 0..content.len() {
+                let valid = match message {
+                    Some(at) => len >= at + 2,
+                    None => [0, 29, 60].contains(&len),
+                };
+                let result = Object::parse(object_type, &content[..len]);
+                assert_eq!(
+                    result.is_ok(),
+                    valid,
+                    "{} cut to {} bytes",
+                    object_type,
+                    len
+                );
+            }
+        }
+        let commit = b"tree 78981922613b2afb6025042ff6bd878ac1994e85\n\
+            author A <a@example.com> 0 +0000\n\
+            committer A <a@example.com> 0 +0000\n";
+        assert!(Object::parse("commit", commit).is_err());
+        assert!(Object::parse("tree", b"100644 \0").is_err());
+        assert!(Object::parse("tree", b" a\0").is_err());
+        assert!(Object::parse("tree", b"10x644 a\0").is_err());
+    }
+
+    // Corrupted objects must fail to parse, or parse to something that is
+    // written back unchanged, but never panic.
+    #[test]
+    fn corrupted_objects_do_not_panic() {
+        let mut next = generator(226);
+        let alphabet = b" \0\n<>+-0123456789abcdefghijklmnoprstuvwxyz\xff";
+        for (object_type, content) in samples() {
+            for _ in 0..2000 {
+                let mut corrupted = content.clone();
+                for _ in 0..=next(3) {
+                    let at = next(corrupted.len() as u64 + 1) as usize;
+                    let byte = alphabet[next(alphabet.len() as u64) as usize];
+                    match next(3) {
+                        0 if at < corrupted.len() => corrupted[at] = byte,
+                        1 if at < corrupted.len() => {
+                            corrupted.remove(at);
+                        }
+                        _ => corrupted.insert(at, byte),
+                    }
+                }
+                if let Ok(object) = Object::parse(object_type, &corrupted) {
+                    let written = object.content().unwrap();
+                    if object_type == "tree" {
+                        assert_eq!(written, corrupted);
+                    }
+                    let reparsed = Object::parse(object_type, &written).unwrap();
+                    assert_eq!(reparsed.content().unwrap(), written);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_trees_round_trip() {
+        let mut next = generator(1);
+        let modes = ["100644", "100755", "120000", "160000", "40000"];
+        for _ in 0..200 {
+            let mut content = Vec::new();
+            for _ in 0..next(6) {
+                content.extend_from_slice(modes[next(5) as usize].as_bytes());
+                content.push(b' ');
+                for _ in 0..=next(8) {
+                    content.push(b"ab. -_x\xc3\xa4"[next(9) as usize]);
+                }
+                content.push(0);
+                content.extend((0..20).map(|_| next(256) as u8));
+            }
+            match Object::parse("tree", &content) {
+                Ok(tree) => assert_eq!(tree.content().unwrap(), content),
+                // A name cut in the middle of a multi-byte character.
+                Err(err) => assert!(err.is::<FromUtf8Error>(), "{}", err),
+            }
+        }
+    }
 }
@@ -0,0 +1,761 @@
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+use flate2::bufread::ZlibDecoder;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use crate::object::{self, InvalidObjectFormat};
+use crate::repository::Repository;
+
+const IDX_MAGIC: &[u8] = b"\xfftOc";
+const PACK_SIGNATURE: &[u8] = b"PACK";
+
+#[derive(Debug, Clone)]
+pub struct ChecksumMismatch(pub PathBuf);
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "checksum mismatch in {}", self.0.display())
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// The lookup table (.idx) that accompanies a pack, mapping each object's
+// hash to its offset in the .pack file.
+pub struct PackIndex {
+    path: PathBuf,
+    hashes: Vec<String>,
+    offsets: Vec<u64>,
+    pack_checksum: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.input(data);
+    let mut digest = [0; 20];
+    hasher.result(&mut digest);
+    digest
+}
+
+// Packs and their indexes end with the SHA-1 of everything before it.
+fn has_valid_trailer(data: &[u8]) -> bool {
+    data.len() >= 20 && sha1(&data[..data.len() - 20]) == data[data.len() - 20..]
+}
+
+fn be_u32(data: &[u8], at: usize) -> Result<u32, InvalidObjectFormat> {
+    let bytes = data.get(at..at + 4).ok_or(InvalidObjectFormat)?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn be_u64(data: &[u8], at: usize) -> Result<u64, InvalidObjectFormat> {
+    let bytes = data.get(at..at + 8).ok_or(InvalidObjectFormat)?;
+    Ok(u64::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+impl PackIndex {
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<PackIndex, Box<dyn std::error::Error + 'static>> {
+        let path = path.as_ref().to_path_buf();
+        let data = fs::read(&path)?;
+        if data.len() < 40 || !has_valid_trailer(&data) {
+            return Err(Box::new(ChecksumMismatch(path)));
+        }
+        let pack_checksum = hex(&data[data.len() - 40..data.len() - 20]);
+        let (hashes, offsets) = if data.starts_with(IDX_MAGIC) {
+            if be_u32(&data, 4)? != 2 {
+                return Err(Box::new(InvalidObjectFormat));
+            }
+            let count = be_u32(&data, 8 + 255 * 4)? as usize;
+            let hashes_at = 8 + 256 * 4;
+            let offsets_at = hashes_at + count * 24;
+            let large_at = offsets_at + count * 4;
+
+            // Each object takes at least 28 bytes of the file, which bounds
+            // how many a truthful count can claim.
+            let mut hashes = Vec::with_capacity(count.min(data.len() / 28));
+            let mut offsets = Vec::with_capacity(count.min(data.len() / 28));
+            for i in 0..count {
+                let hash = data
+                    .get(hashes_at + i * 20..hashes_at + (i + 1) * 20)
+                    .ok_or(InvalidObjectFormat)?;
+                hashes.push(hex(hash));
+                let offset = be_u32(&data, offsets_at + i * 4)?;
+                offsets.push(if offset & 0x8000_0000 != 0 {
+                    be_u64(&data, large_at + (offset & 0x7fff_ffff) as usize * 8)?
+                } else {
+                    offset as u64
+                });
+            }
+            (hashes, offsets)
+        } else {
+            // Version 1: fan-out table followed by (offset, hash) pairs.
+            let count = be_u32(&data, 255 * 4)? as usize;
+            let mut hashes = Vec::with_capacity(count.min(data.len() / 24));
+            let mut offsets = Vec::with_capacity(count.min(data.len() / 24));
+            for i in 0..count {
+                let at = 256 * 4 + i * 24;
+                offsets.push(be_u32(&data, at)? as u64);
+                hashes.push(hex(data.get(at + 4..at + 24).ok_or(InvalidObjectFormat)?));
+            }
+            (hashes, offsets)
+        };
+        Ok(PackIndex {
+            path,
+            hashes,
+            offsets,
+            pack_checksum,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn pack_path(&self) -> PathBuf {
+        self.path.with_extension("pack")
+    }
+
+    pub fn hashes(&self) -> &[String] {
+        &self.hashes
+    }
+
+    pub fn find(&self, hash: &str) -> Option<u64> {
+        self.hashes
+            .binary_search_by(|probe| probe.as_str().cmp(hash))
+            .ok()
+            .map(|i| self.offsets[i])
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.find(hash).is_some()
+    }
+
+    // Checks the pack's trailing checksum, and that it is the pack this
+    // index was built for.
+    pub fn verify_pack(&self) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        let path = self.pack_path();
+        let data = fs::read(&path)?;
+        if !data.starts_with(PACK_SIGNATURE)
+            || data.len() < 32
+            || !has_valid_trailer(&data)
+            || hex(&data[data.len() - 20..]) != self.pack_checksum
+        {
+            return Err(Box::new(ChecksumMismatch(path)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NotInPack(pub String);
+
+impl fmt::Display for NotInPack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "object {} is not in the pack", self.0)
+    }
+}
+
+impl std::error::Error for NotInPack {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+
+// Delta chains longer than this are treated as corrupt rather than followed.
+const MAX_DELTA_DEPTH: usize = 4096;
+
+// The most that is allocated up front for an entry on the word of its
+// header; larger entries grow their buffer as they are inflated.
+const MAX_PREALLOC: usize = 1 << 20;
+
+// With the `mmap` feature the pack is mapped once and entries are read
+// straight out of memory; otherwise each read is a buffered read of the
+// open file.
+enum PackData {
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap),
+    Buffered(fs::File),
+}
+
+// Reads a file from an offset on without moving the file's own cursor, so
+// that readers sharing one open pack do not disturb each other.
+struct FileAt<'a> {
+    file: &'a fs::File,
+    offset: u64,
+}
+
+impl Read for FileAt<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read_at(buf, self.offset)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+// A .pack file opened for reading objects out of it.
+pub struct Pack {
+    data: PackData,
+}
+
+impl Pack {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Pack, Box<dyn std::error::Error + 'static>> {
+        #[cfg(feature = "mmap")]
+        {
+            let file = fs::File::open(path)?;
+            // Safety: packs are written once under their final name and
+            // never modified in place, so the mapping does not change.
+            let map = unsafe { Mmap::map(&file)? };
+            Ok(Pack {
+                data: PackData::Mapped(map),
+            })
+        }
+        #[cfg(not(feature = "mmap"))]
+        Pack::open_buffered(path)
+    }
+
+    // Opens the pack for buffered reads whether or not `mmap` is enabled.
+    pub fn open_buffered<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<Pack, Box<dyn std::error::Error + 'static>> {
+        Ok(Pack {
+            data: PackData::Buffered(fs::File::open(path)?),
+        })
+    }
+
+    fn reader_at(
+        &self,
+        offset: u64,
+    ) -> Result<Box<dyn BufRead + '_>, Box<dyn std::error::Error + 'static>> {
+        match &self.data {
+            #[cfg(feature = "mmap")]
+            PackData::Mapped(map) => {
+                let rest = usize::try_from(offset)
+                    .ok()
+                    .and_then(|offset| map.get(offset..))
+                    .ok_or(InvalidObjectFormat)?;
+                Ok(Box::new(rest))
+            }
+            PackData::Buffered(file) => Ok(Box::new(BufReader::new(FileAt { file, offset }))),
+        }
+    }
+
+    // Reads the entry at `offset`: its type and inflated size, the base of
+    // a delta, and then the compressed data.
+    fn entry_at(
+        &self,
+        offset: u64,
+        index: &PackIndex,
+    ) -> Result<PackEntry, Box<dyn std::error::Error + 'static>> {
+        let mut reader = self.reader_at(offset)?;
+        let mut byte = read_byte(&mut reader)?;
+        let type_code = (byte >> 4) & 0x07;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = read_byte(&mut reader)?;
+            size |= ((byte & 0x7f) as usize)
+                .checked_shl(shift)
+                .ok_or(InvalidObjectFormat)?;
+            shift += 7;
+        }
+
+        let base = match type_code {
+            OFS_DELTA => {
+                // A big-endian base-128 distance back to the base entry,
+                // with one added per continuation byte.
+                let mut byte = read_byte(&mut reader)?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = read_byte(&mut reader)?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                }
+                let base = offset.checked_sub(distance).ok_or(InvalidObjectFormat)?;
+                Some(Base::Offset(base))
+            }
+            REF_DELTA => {
+                let mut hash = [0; 20];
+                reader.read_exact(&mut hash)?;
+                let hash = hex(&hash);
+                match index.find(&hash) {
+                    Some(base) => Some(Base::Offset(base)),
+                    None => Some(Base::Loose(hash)),
+                }
+            }
+            _ => None,
+        };
+
+        // The size is only as good as the pack: allocate for at most
+        // MAX_PREALLOC up front, and inflate no more than one byte past it.
+        let mut data = Vec::with_capacity(size.min(MAX_PREALLOC));
+        ZlibDecoder::new(reader)
+            .take(size as u64 + 1)
+            .read_to_end(&mut data)?;
+        if data.len() != size {
+            return Err(Box::new(InvalidObjectFormat));
+        }
+        Ok(PackEntry {
+            type_code,
+            base,
+            data,
+        })
+    }
+
+    // Reads the object at `offset`, applying any chain of deltas to the base
+    // they start from. A REF_DELTA base missing from the pack is looked for
+    // in `objects`, the objects directory the pack belongs to.
+    pub fn read_at(
+        &self,
+        index: &PackIndex,
+        offset: u64,
+        objects: &Path,
+    ) -> Result<(String, Vec<u8>), Box<dyn std::error::Error + 'static>> {
+        let mut deltas = Vec::new();
+        let mut offset = offset;
+        let (object_type, mut content) = loop {
+            if deltas.len() > MAX_DELTA_DEPTH {
+                return Err(Box::new(InvalidObjectFormat));
+            }
+            let PackEntry {
+                type_code,
+                base,
+                data,
+            } = self.entry_at(offset, index)?;
+            match base {
+                None => break (type_name(type_code)?.to_string(), data),
+                Some(Base::Offset(base)) => {
+                    deltas.push(data);
+                    offset = base;
+                }
+                Some(Base::Loose(hash)) => {
+                    deltas.push(data);
+                    break object::read_raw_in(objects, &hash)?;
+                }
+            }
+        };
+        for delta in deltas.iter().rev() {
+            content = apply_delta(&content, delta)?;
+        }
+        Ok((object_type, content))
+    }
+
+    pub fn read(
+        &self,
+        index: &PackIndex,
+        hash: &str,
+        objects: &Path,
+    ) -> Result<(String, Vec<u8>), Box<dyn std::error::Error + 'static>> {
+        let offset = index
+            .find(hash)
+            .ok_or_else(|| NotInPack(hash.to_string()))?;
+        self.read_at(index, offset, objects)
+    }
+}
+
+struct PackEntry {
+    type_code: u8,
+    base: Option<Base>,
+    data: Vec<u8>,
+}
+
+// Where a delta's base is: another entry of the same pack, or a loose object.
+enum Base {
+    Offset(u64),
+    Loose(String),
+}
+
+fn read_byte(reader: &mut impl Read) -> std::io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+// Delta sizes are little-endian base-128.
+fn delta_size(delta: &[u8], at: &mut usize) -> Result<usize, InvalidObjectFormat> {
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *delta.get(*at).ok_or(InvalidObjectFormat)?;
+        *at += 1;
+        size |= ((byte & 0x7f) as usize)
+            .checked_shl(shift)
+            .ok_or(InvalidObjectFormat)?;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+// Rebuilds an object from its base and a delta: a run of instructions that
+// either copy a range of the base or insert literal bytes.
+fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, InvalidObjectFormat> {
+    let mut at = 0;
+    if delta_size(delta, &mut at)? != base.len() {
+        return Err(InvalidObjectFormat);
+    }
+    let size = delta_size(delta, &mut at)?;
+    // Most of a result is copied from the base or inserted from the delta,
+    // so their sizes bound what is worth allocating before the claimed
+    // size has been seen to be true.
+    let mut result = Vec::with_capacity(size.min(base.len() + delta.len()));
+    while at < delta.len() {
+        let op = delta[at];
+        at += 1;
+        if op & 0x80 != 0 {
+            // The low four bits say which offset bytes follow, the next
+            // three which size bytes; absent bytes are zero.
+            let mut offset = 0;
+            for bit in 0..4 {
+                if op & (1 << bit) != 0 {
+                    offset |= (*delta.get(at).ok_or(InvalidObjectFormat)? as usize) << (bit * 8);
+                    at += 1;
+                }
+            }
+            let mut len = 0;
+            for bit in 0..3 {
+                if op & (0x10 << bit) != 0 {
+                    len |= (*delta.get(at).ok_or(InvalidObjectFormat)? as usize) << (bit * 8);
+                    at += 1;
+                }
+            }
+            let len = if len == 0 { 0x10000 } else { len };
+            let copied = base.get(offset..offset + len).ok_or(InvalidObjectFormat)?;
+            result.extend_from_slice(copied);
+        } else if op != 0 {
+            let inserted = delta.get(at..at + op as usize).ok_or(InvalidObjectFormat)?;
+            result.extend_from_slice(inserted);
+            at += op as usize;
+        } else {
+            return Err(InvalidObjectFormat);
+        }
+        if result.len() > size {
+            return Err(InvalidObjectFormat);
+        }
+    }
+    if result.len() != size {
+        return Err(InvalidObjectFormat);
+    }
+    Ok(result)
+}
+
+fn type_name(type_code: u8) -> Result<&'static str, InvalidObjectFormat> {
+    match type_code {
+        1 => Ok("commit"),
+        2 => Ok("tree"),
+        3 => Ok("blob"),
+        4 => Ok("tag"),
+        _ => Err(InvalidObjectFormat),
+    }
+}
+
+fn type_code(object_type: &str) -> Result<u8, InvalidObjectFormat> {
+    match object_type {
+        "commit" => Ok(1),
+        "tree" => Ok(2),
+        "blob" => Ok(3),
+        "tag" => Ok(4),
+        _ => Err(InvalidObjectFormat),
+    }
+}
+
+// Each pack entry starts with its type and inflated size: 3 type bits and
+// the low 4 size bits in the first byte, then 7 size bits per byte.
+fn entry_header(type_code: u8, size: usize) -> Vec<u8> {
+    let mut header = Vec::new();
+    let mut byte = (type_code << 4) | (size & 0x0f) as u8;
+    let mut size = size >> 4;
+    while size != 0 {
+        header.push(byte | 0x80);
+        byte = (size & 0x7f) as u8;
+        size >>= 7;
+    }
+    header.push(byte);
+    header
+}
+
+// Writes the given loose objects, undeltified, to `<base>-<checksum>.pack`
+// with a matching version 2 `.idx`, returning the checksum.
+pub fn write_pack(
+    hashes: &[String],
+    base: &str,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    write_pack_in(&Repository::current()?.objects_dir(), hashes, base)
+}
+
+// Like write_pack, taking the objects from any objects directory.
+pub fn write_pack_in(
+    objects: &Path,
+    hashes: &[String],
+    base: &str,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let mut hashes = hashes.to_vec();
+    hashes.sort();
+    hashes.dedup();
+
+    let mut pack = Vec::new();
+    pack.extend_from_slice(PACK_SIGNATURE);
+    pack.extend_from_slice(&2u32.to_be_bytes());
+    pack.extend_from_slice(&(hashes.len() as u32).to_be_bytes());
+
+    let mut crcs = Vec::with_capacity(hashes.len());
+    let mut offsets = Vec::with_capacity(hashes.len());
+    for hash in &hashes {
+        let (object_type, content) = object::read_raw_in(objects, hash)?;
+        let mut entry = entry_header(type_code(&object_type)?, content.len());
+        entry.extend(object::compress(&content)?);
+        offsets.push(pack.len() as u64);
+        crcs.push(crc32fast::hash(&entry));
+        pack.extend(entry);
+    }
+    let checksum = sha1(&pack);
+    pack.extend_from_slice(&checksum);
+
+    let mut idx = Vec::new();
+    idx.extend_from_slice(IDX_MAGIC);
+    idx.extend_from_slice(&2u32.to_be_bytes());
+    let mut raw_hashes = Vec::with_capacity(hashes.len());
+    for hash in &hashes {
+        let bytes = (0..hash.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hash[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()?;
+        raw_hashes.push(bytes);
+    }
+    for first_byte in 0..=255u8 {
+        let count = raw_hashes
+            .iter()
+            .filter(|hash| hash[0] <= first_byte)
+            .count();
+        idx.extend_from_slice(&(count as u32).to_be_bytes());
+    }
+    for hash in &raw_hashes {
+        idx.extend_from_slice(hash);
+    }
+    for crc in &crcs {
+        idx.extend_from_slice(&crc.to_be_bytes());
+    }
+    let mut large_offsets = Vec::new();
+    for offset in &offsets {
+        if *offset < 0x8000_0000 {
+            idx.extend_from_slice(&(*offset as u32).to_be_bytes());
+        } else {
+            idx.extend_from_slice(&(0x8000_0000 | large_offsets.len() as u32).to_be_bytes());
+            large_offsets.push(*offset);
+        }
+    }
+    for offset in large_offsets {
+        idx.extend_from_slice(&offset.to_be_bytes());
+    }
+    idx.extend_from_slice(&checksum);
+    let idx_checksum = sha1(&idx);
+    idx.extend_from_slice(&idx_checksum);
+
+    let name = hex(&checksum);
+    fs::write(format!("{}-{}.pack", base, name), pack)?;
+    fs::write(format!("{}-{}.idx", base, name), idx)?;
+    Ok(name)
+}
+
+// Opens every pack index under .git/objects/pack.
+pub fn indexes() -> Result<Vec<PackIndex>, Box<dyn std::error::Error + 'static>> {
+    indexes_in(&Repository::current()?.objects_dir())
+}
+
+// Opens the pack indexes of any objects directory.
+pub fn indexes_in(objects: &Path) -> Result<Vec<PackIndex>, Box<dyn std::error::Error + 'static>> {
+    index_paths(objects)?
+        .into_iter()
+        .map(PackIndex::open)
+        .collect()
+}
+
+fn index_paths(objects: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error + 'static>> {
+    let dir = objects.join("pack");
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "idx"));
+    paths.sort();
+    Ok(paths)
+}
+
+// A pack index together with its pack, opened for reading, and the
+// objects directory they are in.
+pub struct OpenPack {
+    pub index: PackIndex,
+    pub pack: Pack,
+    pub objects: PathBuf,
+}
+
+impl OpenPack {
+    pub fn read(
+        &self,
+        hash: &str,
+    ) -> Result<(String, Vec<u8>), Box<dyn std::error::Error + 'static>> {
+        self.pack.read(&self.index, hash, &self.objects)
+    }
+}
+
+// An index file's size and modification time, which tell a cached open
+// pack from one that has since been replaced.
+type Stamp = (u64, SystemTime);
+
+// Packs opened so far in this process, by index path. Parsing an index
+// checks its whole checksum, so this is done once per pack rather than on
+// every object lookup.
+static OPEN_PACKS: Mutex<BTreeMap<PathBuf, (Stamp, Arc<OpenPack>)>> = Mutex::new(BTreeMap::new());
+
+// The packs of any objects directory, opening only those not seen before.
+pub fn open_packs_in(
+    objects: &Path,
+) -> Result<Vec<Arc<OpenPack>>, Box<dyn std::error::Error + 'static>> {
+    let paths = index_paths(objects)?;
+    let mut cache = OPEN_PACKS.lock().unwrap_or_else(PoisonError::into_inner);
+    // Forget packs that have been deleted from this directory.
+    let dir = objects.join("pack");
+    cache.retain(|path, _| path.parent() != Some(dir.as_path()) || paths.contains(path));
+
+    let mut packs = Vec::with_capacity(paths.len());
+    for path in paths {
+        let metadata = fs::metadata(&path)?;
+        let stamp = (metadata.len(), metadata.modified()?);
+        let open = match cache.get(&path) {
+            Some((cached, open)) if *cached == stamp => Arc::clone(open),
+            _ => {
+                let index = PackIndex::open(&path)?;
+                let pack = Pack::open(index.pack_path())?;
+                let open = Arc::new(OpenPack {
+                    index,
+                    pack,
+                    objects: objects.to_path_buf(),
+                });
+                cache.insert(path, (stamp, Arc::clone(&open)));
+                open
+            }
+        };
+        packs.push(open);
+    }
+    Ok(packs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Base-128 the way delta and entry headers spell sizes.
+    fn delta_header(size: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut size = size;
+        while size >= 0x80 {
+            bytes.push((size & 0x7f) as u8 | 0x80);
+            size >>= 7;
+        }
+        bytes.push(size as u8);
+        bytes
+    }
+
+    #[test]
+    fn deltas_claiming_huge_results_are_rejected() {
+        let base = b"base";
+        let mut delta = delta_header(base.len());
+        delta.extend(delta_header(usize::MAX >> 1));
+        // Copy the whole base, then insert a few bytes.
+        delta.extend([0x90, base.len() as u8, 3, b'a', b'b', b'c']);
+        assert!(apply_delta(base, &delta).is_err());
+
+        let mut delta = delta_header(base.len());
+        delta.extend(delta_header(7));
+        delta.extend([0x90, base.len() as u8, 3, b'a', b'b', b'c']);
+        assert_eq!(apply_delta(base, &delta).unwrap(), b"baseabc");
+    }
+
+    // The base of a REF_DELTA missing from the pack comes from the pack's
+    // own objects directory, not the current repository's.
+    #[test]
+    fn missing_bases_are_read_from_the_packs_objects() {
+        let dir = std::env::temp_dir().join(format!("mygit-thin-pack-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let objects = dir.join("objects");
+        fs::create_dir_all(&objects).unwrap();
+        let base = format!("a base only {} has\n", dir.display());
+        let base_hash = object::Object::Blob(base.clone().into_bytes())
+            .write_in(&objects)
+            .unwrap();
+
+        let mut delta = delta_header(base.len());
+        delta.extend(delta_header(base.len() + 3));
+        delta.extend([0x90, base.len() as u8, 3, b'a', b'b', b'c']);
+        let mut pack = entry_header(REF_DELTA, delta.len());
+        for i in (0..40).step_by(2) {
+            pack.push(u8::from_str_radix(&base_hash[i..i + 2], 16).unwrap());
+        }
+        pack.extend(object::compress(&delta).unwrap());
+        let path = dir.join("thin.pack");
+        fs::write(&path, &pack).unwrap();
+        let index = PackIndex {
+            path: path.clone(),
+            hashes: Vec::new(),
+            offsets: Vec::new(),
+            pack_checksum: String::new(),
+        };
+
+        let opened = Pack::open(&path).unwrap();
+        assert_eq!(
+            opened.read_at(&index, 0, &objects).unwrap(),
+            ("blob".to_string(), format!("{}abc", base).into_bytes())
+        );
+        assert!(opened.read_at(&index, 0, &dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entries_claiming_huge_sizes_are_rejected() {
+        let content = b"hello\n";
+        let mut pack = entry_header(3, usize::MAX >> 4);
+        pack.extend(object::compress(content).unwrap());
+        let path = std::env::temp_dir().join(format!("mygit-huge-entry-{}", std::process::id()));
+        fs::write(&path, &pack).unwrap();
+        let index = PackIndex {
+            path: path.clone(),
+            hashes: Vec::new(),
+            offsets: Vec::new(),
+            pack_checksum: String::new(),
+        };
+        let opened = Pack::open(&path).unwrap();
+        assert!(opened.read_at(&index, 0, &std::env::temp_dir()).is_err());
+
+        let mut pack = entry_header(3, content.len());
+        pack.extend(object::compress(content).unwrap());
+        fs::write(&path, &pack).unwrap();
+        let opened = Pack::open(&path).unwrap();
+        assert_eq!(
+            opened.read_at(&index, 0, &std::env::temp_dir()).unwrap(),
+            ("blob".to_string(), content.to_vec())
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
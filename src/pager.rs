@@ -0,0 +1,68 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Child, Command, Stdio};
+
+pub const DEFAULT_PAGER: &str = "less -FRX";
+
+// Writes to the user's pager when stdout is a terminal, and straight to
+// stdout otherwise. The pager is waited on when this is dropped.
+pub struct Pager {
+    child: Option<Child>,
+}
+
+fn pager_command() -> Option<String> {
+    let command = env::var("GIT_PAGER")
+        .or_else(|_| env::var("PAGER"))
+        .unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+    match command.trim() {
+        "" | "cat" => None,
+        command => Some(command.to_string()),
+    }
+}
+
+impl Pager {
+    pub fn start(enabled: bool) -> Pager {
+        let child = if enabled && io::stdout().is_terminal() {
+            pager_command().and_then(|command| {
+                Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .ok()
+            })
+        } else {
+            None
+        };
+        Pager { child }
+    }
+
+    pub fn is_paging(&self) -> bool {
+        self.child.is_some()
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.child.as_mut().and_then(|child| child.stdin.as_mut()) {
+            Some(stdin) => stdin.write(buf),
+            None => io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.child.as_mut().and_then(|child| child.stdin.as_mut()) {
+            Some(stdin) => stdin.flush(),
+            None => io::stdout().flush(),
+        }
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            drop(child.stdin.take());
+            let _ = child.wait();
+        }
+    }
+}
@@ -0,0 +1,636 @@
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Component, Path};
+
+use crate::diff;
+use crate::object::{self, FileMode, Object};
+
+#[derive(Debug, Clone)]
+pub struct InvalidPatchFormat;
+
+impl fmt::Display for InvalidPatchFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid patch format")
+    }
+}
+
+impl std::error::Error for InvalidPatchFormat {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PatchDoesNotApply(pub String);
+
+impl fmt::Display for PatchDoesNotApply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "patch does not apply to {}", self.0)
+    }
+}
+
+impl std::error::Error for PatchDoesNotApply {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BinaryPatchMissing(pub String);
+
+impl fmt::Display for BinaryPatchMissing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot apply binary patch to '{}' without full index line",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for BinaryPatchMissing {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BeyondSymlink(pub String);
+
+impl fmt::Display for BeyondSymlink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "affected file '{}' is beyond a symbolic link", self.0)
+    }
+}
+
+impl std::error::Error for BeyondSymlink {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+const BASE85: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+// Bytes of deflated data carried by a single line of a binary patch.
+const LINE_BYTES: usize = 52;
+
+pub fn encode_base85(data: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in data.chunks(4) {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        let mut acc = u32::from_be_bytes(word);
+        let mut encoded = [0u8; 5];
+        for c in encoded.iter_mut().rev() {
+            *c = BASE85[(acc % 85) as usize];
+            acc /= 85;
+        }
+        out.push_str(std::str::from_utf8(&encoded).unwrap());
+    }
+    out
+}
+
+pub fn decode_base85(text: &[u8], len: usize) -> Result<Vec<u8>, InvalidPatchFormat> {
+    if text.len() % 5 != 0 {
+        return Err(InvalidPatchFormat);
+    }
+    let mut out = Vec::with_capacity(text.len() / 5 * 4);
+    for chunk in text.chunks(5) {
+        let mut acc: u64 = 0;
+        for c in chunk {
+            let digit = BASE85
+                .iter()
+                .position(|b| b == c)
+                .ok_or(InvalidPatchFormat)?;
+            acc = acc * 85 + digit as u64;
+        }
+        let word = u32::try_from(acc).or(Err(InvalidPatchFormat))?;
+        out.extend_from_slice(&word.to_be_bytes());
+    }
+    if len > out.len() {
+        return Err(InvalidPatchFormat);
+    }
+    out.truncate(len);
+    Ok(out)
+}
+
+fn literal_hunk(data: &[u8]) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    // Deflated as fast as possible, as git does, so the patches match.
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(data)?;
+    let deflated = encoder.finish()?;
+
+    let mut out = format!("literal {}\n", data.len());
+    for line in deflated.chunks(LINE_BYTES) {
+        let len = line.len() as u8;
+        out.push(if len <= 26 {
+            (b'A' + len - 1) as char
+        } else {
+            (b'a' + len - 27) as char
+        });
+        out.push_str(&encode_base85(line));
+        out.push('\n');
+    }
+    out.push('\n');
+    Ok(out)
+}
+
+// Emits a "GIT binary patch" block in the literal form: a forward hunk
+// carrying `new` followed by a reverse hunk carrying `old`.
+pub fn binary_patch(
+    old: &[u8],
+    new: &[u8],
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    Ok(format!(
+        "GIT binary patch\n{}{}",
+        literal_hunk(new)?,
+        literal_hunk(old)?
+    ))
+}
+
+fn parse_literal_hunk<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + 'static>> {
+    let header = lines.next().ok_or(InvalidPatchFormat)?;
+    let size: usize = header
+        .strip_prefix("literal ")
+        .ok_or(InvalidPatchFormat)?
+        .parse()?;
+
+    let mut deflated = Vec::new();
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+        let line = line.as_bytes();
+        let len = match line[0] {
+            b'A'..=b'Z' => line[0] - b'A' + 1,
+            b'a'..=b'z' => line[0] - b'a' + 27,
+            _ => return Err(Box::new(InvalidPatchFormat)),
+        };
+        deflated.extend(decode_base85(&line[1..], len as usize)?);
+    }
+
+    let mut data = Vec::new();
+    ZlibDecoder::new(&deflated[..]).read_to_end(&mut data)?;
+    if data.len() != size {
+        return Err(Box::new(InvalidPatchFormat));
+    }
+    Ok(data)
+}
+
+// Reconstructs the postimage from a "GIT binary patch" block, or the
+// preimage when `reverse` is set.
+pub fn apply_binary_patch(
+    patch: &str,
+    reverse: bool,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + 'static>> {
+    let mut lines = patch
+        .lines()
+        .skip_while(|line| *line != "GIT binary patch")
+        .skip(1);
+    let forward = parse_literal_hunk(&mut lines)?;
+    if !reverse {
+        return Ok(forward);
+    }
+    parse_literal_hunk(&mut lines)
+}
+
+// A commit message's subject as a mail carries it: its first paragraph
+// on one line.
+fn subject(message: &str) -> String {
+    message
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .take_while(|line| !line.trim().is_empty())
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Writes a commit as `git format-patch` does: a mail with the commit's
+// subject, numbered `number` of `total` when there are several, and its
+// changes against its first parent as a stat and a patch that carries
+// binary files too.
+pub fn format_patch(
+    hash: &str,
+    commit: &Object,
+    (number, total): (usize, usize),
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let Object::Commit {
+        tree,
+        parents,
+        author,
+        author_timestamp,
+        message,
+        ..
+    } = commit
+    else {
+        return Err(Box::new(InvalidPatchFormat));
+    };
+    let parent_tree = match parents.first() {
+        Some(parent) => match Object::from_hash(parent)? {
+            Object::Commit { tree, .. } => Some(tree),
+            _ => return Err(Box::new(InvalidPatchFormat)),
+        },
+        None => None,
+    };
+    let changes = diff::diff_trees(parent_tree.as_deref(), Some(tree))?;
+
+    let numbering = if total > 1 {
+        format!("PATCH {}/{}", number, total)
+    } else {
+        "PATCH".to_string()
+    };
+    let mut out = format!(
+        "From {} Mon Sep 17 00:00:00 2001\nFrom: {}\nDate: {}\nSubject: [{}] {}\n\n",
+        hash,
+        author,
+        author_timestamp.rfc2822_date(),
+        numbering,
+        subject(message)
+    );
+    let body: Vec<&str> = message
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .skip_while(|line| !line.trim().is_empty())
+        .skip_while(|line| line.trim().is_empty())
+        .collect();
+    for line in body {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str("---\n");
+    if !changes.is_empty() {
+        out.push_str(&diff::stat(&changes)?);
+        out.push_str(&diff::summary(&changes));
+        out.push('\n');
+        out.push_str(&diff::binary_patch(&changes, diff::DEFAULT_CONTEXT)?);
+    }
+    out.push_str(&format!("-- \n{}\n\n", env!("CARGO_PKG_VERSION")));
+    Ok(out)
+}
+
+// The file `format-patch` writes a commit's mail to: its number and its
+// subject with anything but letters, digits, `.` and `_` turned into
+// dashes, cut short like git's.
+pub fn patch_file_name(number: usize, message: &str) -> String {
+    let mut sanitized = String::new();
+    let mut dash = false;
+    let mut last = None;
+    for c in subject(message).chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            if dash && !sanitized.is_empty() {
+                sanitized.push('-');
+            }
+            dash = false;
+            // Runs of dots become one.
+            if !(c == '.' && last == Some('.')) {
+                sanitized.push(c);
+            }
+        } else {
+            dash = true;
+        }
+        last = Some(c);
+    }
+    let mut name = format!("{:04}-{}", number, sanitized.trim_end_matches(['.', '-']));
+    name.truncate(57);
+    name + ".patch"
+}
+
+// What one file's part of a patch leaves in the working tree: the file
+// with its new content and mode, or nothing when it is deleted.
+struct Outcome {
+    path: String,
+    content: Option<Vec<u8>>,
+    mode: Option<FileMode>,
+}
+
+// Applies a patch, as `diff --binary` writes one, to the working tree.
+// Text hunks must match the files exactly, binary files are rebuilt from
+// their literal hunks, and each file's index line is checked against what
+// is there before and after. Nothing is written unless every file applies.
+// Returns the paths written or deleted.
+pub fn apply(patch: &str) -> Result<Vec<String>, Box<dyn std::error::Error + 'static>> {
+    // Lines keep everything but their '\n', so a CR is content.
+    let lines: Vec<&str> = patch
+        .split_inclusive('\n')
+        .map(|line| line.strip_suffix('\n').unwrap_or(line))
+        .collect();
+    let starts: Vec<usize> = (0..lines.len())
+        .filter(|&i| lines[i].starts_with("diff --git "))
+        .chain([lines.len()])
+        .collect();
+    if starts.len() < 2 {
+        return Err(Box::new(InvalidPatchFormat));
+    }
+    let outcomes = starts
+        .windows(2)
+        .map(|range| apply_file(&lines[range[0]..range[1]]))
+        .collect::<Result<Vec<_>, _>>()?;
+    // Nor through a symlink the patch itself creates.
+    for outcome in &outcomes {
+        let beyond = leading_dirs(&outcome.path).any(|dir| {
+            outcomes.iter().any(|other| {
+                Path::new(&other.path) == dir
+                    && other.content.is_some()
+                    && other.mode == Some(FileMode::Symlink)
+            })
+        });
+        if beyond {
+            return Err(Box::new(BeyondSymlink(outcome.path.clone())));
+        }
+    }
+
+    for outcome in &outcomes {
+        let path = Path::new(&outcome.path);
+        let Some(content) = &outcome.content else {
+            fs::remove_file(path)?;
+            continue;
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mode = outcome.mode.or_else(|| {
+            fs::symlink_metadata(path)
+                .ok()
+                .map(|metadata| object::file_mode(&metadata))
+        });
+        if fs::symlink_metadata(path).is_ok_and(|old| old.is_symlink())
+            || mode == Some(FileMode::Symlink)
+        {
+            let _ = fs::remove_file(path);
+        }
+        if mode == Some(FileMode::Symlink) {
+            symlink(OsStr::from_bytes(content), path)?;
+            continue;
+        }
+        fs::write(path, content)?;
+        let permissions = match mode {
+            Some(FileMode::Executable) => 0o755,
+            _ => 0o644,
+        };
+        fs::set_permissions(path, fs::Permissions::from_mode(permissions))?;
+    }
+    Ok(outcomes.into_iter().map(|outcome| outcome.path).collect())
+}
+
+// Works out what one `diff --git` section does, without writing anything.
+fn apply_file(lines: &[&str]) -> Result<Outcome, Box<dyn std::error::Error + 'static>> {
+    let names = lines[0]
+        .strip_prefix("diff --git a/")
+        .ok_or(InvalidPatchFormat)?;
+    let (_, path) = names.rsplit_once(" b/").ok_or(InvalidPatchFormat)?;
+    // Patches come from elsewhere: they may not write outside the tree.
+    if !Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(Box::new(InvalidPatchFormat));
+    }
+    // Writing through a symlink would land outside the tree as well, as
+    // git's "beyond a symbolic link" check knows.
+    if leading_dirs(path)
+        .any(|dir| fs::symlink_metadata(dir).is_ok_and(|metadata| metadata.is_symlink()))
+    {
+        return Err(Box::new(BeyondSymlink(path.to_string())));
+    }
+
+    let (mut created, mut deleted) = (false, false);
+    let mut mode = None;
+    let mut hashes = None;
+    let mut body = lines.len();
+    for (i, line) in lines.iter().enumerate().skip(1) {
+        if line.starts_with("--- ")
+            || line.starts_with("Binary files ")
+            || *line == "GIT binary patch"
+        {
+            body = i;
+            break;
+        }
+        let (key, value) = line.rsplit_once(' ').ok_or(InvalidPatchFormat)?;
+        match key {
+            "new file mode" => {
+                created = true;
+                mode = FileMode::parse(value);
+            }
+            "deleted file mode" => deleted = true,
+            "new mode" => mode = FileMode::parse(value),
+            "old mode" => {}
+            _ => {
+                let (index, value) = line.split_once(' ').ok_or(InvalidPatchFormat)?;
+                if index != "index" {
+                    return Err(Box::new(InvalidPatchFormat));
+                }
+                let (range, file_mode) = match value.split_once(' ') {
+                    Some((range, file_mode)) => (range, FileMode::parse(file_mode)),
+                    None => (value, None),
+                };
+                mode = mode.or(file_mode);
+                let (old, new) = range.split_once("..").ok_or(InvalidPatchFormat)?;
+                hashes = Some((old, new));
+            }
+        }
+    }
+
+    let old = if created {
+        if fs::symlink_metadata(path).is_ok() {
+            return Err(Box::new(PatchDoesNotApply(path.to_string())));
+        }
+        Vec::new()
+    } else {
+        worktree_content(path).map_err(|_| PatchDoesNotApply(path.to_string()))?
+    };
+    // The index line names the blobs before and after, abbreviated or in
+    // full; the all-zero hash stands for a missing side.
+    let matches = |hash: &str, content: &[u8]| {
+        hash.chars().all(|c| c == '0') || object::hash_raw("blob", content).starts_with(hash)
+    };
+    if let Some((old_hash, _)) = hashes {
+        if !created && !matches(old_hash, &old) {
+            return Err(Box::new(PatchDoesNotApply(path.to_string())));
+        }
+    }
+
+    let body = &lines[body..];
+    let new = match body.first() {
+        Some(&"GIT binary patch") => {
+            if !hashes.is_some_and(|(old, new)| old.len() == 40 && new.len() == 40) {
+                return Err(Box::new(BinaryPatchMissing(path.to_string())));
+            }
+            apply_binary_patch(&body.join("\n"), false)?
+        }
+        Some(line) if line.starts_with("Binary files ") => {
+            return Err(Box::new(BinaryPatchMissing(path.to_string())));
+        }
+        Some(_) => {
+            let hunks = body.get(2..).ok_or(InvalidPatchFormat)?;
+            apply_text_hunks(&old, hunks).ok_or_else(|| PatchDoesNotApply(path.to_string()))?
+        }
+        None => old,
+    };
+    if deleted {
+        if !new.is_empty() {
+            return Err(Box::new(PatchDoesNotApply(path.to_string())));
+        }
+        return Ok(Outcome {
+            path: path.to_string(),
+            content: None,
+            mode: None,
+        });
+    }
+    if let Some((_, new_hash)) = hashes {
+        if !matches(new_hash, &new) {
+            return Err(Box::new(PatchDoesNotApply(path.to_string())));
+        }
+    }
+    Ok(Outcome {
+        path: path.to_string(),
+        content: Some(new),
+        mode,
+    })
+}
+
+// The directories `path` lies in, deepest first.
+fn leading_dirs(path: &str) -> impl Iterator<Item = &Path> {
+    Path::new(path)
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+}
+
+// A file's content as a blob records it: a symlink holds its target.
+fn worktree_content(path: &str) -> Result<Vec<u8>, std::io::Error> {
+    if fs::symlink_metadata(path)?.is_symlink() {
+        return Ok(fs::read_link(path)?.into_os_string().into_encoded_bytes());
+    }
+    fs::read(path)
+}
+
+// Applies unified diff hunks to `old`, each at exactly the lines its
+// header names. None when a context or removed line does not match. Each
+// hunk ends where its header's line counts say, so whatever follows the
+// last one, like the signature of a mail, is left alone.
+fn apply_text_hunks(old: &[u8], lines: &[&str]) -> Option<Vec<u8>> {
+    let old_lines = diff::lines(old);
+    let parse_range = |range: &str| match range.split_once(',') {
+        Some((start, len)) => Some((start.parse::<usize>().ok()?, len.parse::<usize>().ok()?)),
+        None => Some((range.parse::<usize>().ok()?, 1)),
+    };
+    let mut out = Vec::new();
+    let mut at = 0;
+    let mut i = 0;
+    loop {
+        let (old_range, new_range) = lines.get(i)?.strip_prefix("@@ -")?.split_once(" +")?;
+        let (start, mut old_left) = parse_range(old_range)?;
+        let (_, mut new_left) = parse_range(new_range.split(' ').next()?)?;
+        // An empty range names the line before it.
+        let start = if old_left == 0 {
+            start
+        } else {
+            start.checked_sub(1)?
+        };
+        out.extend(old_lines.get(at..start)?.concat());
+        at = start;
+        i += 1;
+
+        while old_left > 0 || new_left > 0 {
+            let (kind, text) = lines.get(i)?.split_at_checked(1)?;
+            i += 1;
+            let mut line = text.as_bytes().to_vec();
+            if lines.get(i).is_some_and(|next| next.starts_with('\\')) {
+                i += 1;
+            } else {
+                line.push(b'\n');
+            }
+            match kind {
+                " " | "-" => {
+                    if *old_lines.get(at)? != line.as_slice() {
+                        return None;
+                    }
+                    at += 1;
+                    old_left = old_left.checked_sub(1)?;
+                    if kind == " " {
+                        new_left = new_left.checked_sub(1)?;
+                        out.extend(line);
+                    }
+                }
+                "+" => {
+                    new_left = new_left.checked_sub(1)?;
+                    out.extend(line);
+                }
+                _ => return None,
+            }
+        }
+        if !lines.get(i).is_some_and(|line| line.starts_with("@@ ")) {
+            break;
+        }
+    }
+    out.extend(old_lines.get(at..)?.concat());
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base85_round_trips() {
+        for len in 0..=9usize {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37 + 255) as u8).collect();
+            let encoded = encode_base85(&data);
+            assert_eq!(encoded.len(), len.div_ceil(4) * 5);
+            assert_eq!(decode_base85(encoded.as_bytes(), len).unwrap(), data);
+        }
+        assert!(decode_base85(b"0000", 3).is_err());
+        assert!(decode_base85(b"\"0000", 4).is_err());
+    }
+
+    #[test]
+    fn binary_patches_carry_both_sides() {
+        let old: Vec<u8> = (0..=255).collect();
+        let new: Vec<u8> = (0..1000).map(|i| (i % 7) as u8).collect();
+        let patch = binary_patch(&old, &new).unwrap();
+        assert!(patch.starts_with("GIT binary patch\nliteral 1000\n"));
+        assert_eq!(apply_binary_patch(&patch, false).unwrap(), new);
+        assert_eq!(apply_binary_patch(&patch, true).unwrap(), old);
+        assert_eq!(
+            apply_binary_patch(&binary_patch(b"", b"").unwrap(), true).unwrap(),
+            b""
+        );
+    }
+
+    #[test]
+    fn text_hunks_apply_only_where_they_match() {
+        let old = b"a\nb\nc\nd\n";
+        let hunks = [
+            "@@ -2,2 +2,2 @@",
+            " b",
+            "-c",
+            "+C",
+            "@@ -4 +4 @@",
+            "-d",
+            "+D",
+            "\\ No newline at end of file",
+        ];
+        assert_eq!(apply_text_hunks(old, &hunks).unwrap(), b"a\nb\nC\nD");
+        assert!(apply_text_hunks(b"a\nx\nc\nd\n", &hunks).is_none());
+        assert_eq!(
+            apply_text_hunks(b"", &["@@ -0,0 +1 @@", "+new"]).unwrap(),
+            b"new\n"
+        );
+        // A mail's signature after the last hunk is not part of it.
+        assert_eq!(
+            apply_text_hunks(b"a\n", &["@@ -1 +1 @@", "-a", "+b", "-- ", "2.39.5", ""]).unwrap(),
+            b"b\n"
+        );
+        assert!(apply_text_hunks(b"a\nb\n", &["@@ -1,2 +1 @@", "-a"]).is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::object::{self, InvalidObjectFormat, Timestamp, User};
+use crate::refs;
+use crate::repository::Repository;
+
+// Written as the old value of a ref that did not exist before.
+pub const ZERO_HASH: &str = "0000000000000000000000000000000000000000";
+
+// How long entries are kept when neither --expire nor gc.reflogExpire is given.
+pub const DEFAULT_EXPIRE_DAYS: i64 = 90;
+
+pub struct Entry {
+    pub old: String,
+    pub new: String,
+    pub committer: User,
+    pub timestamp: Timestamp,
+    pub message: String,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Result<Entry, InvalidObjectFormat> {
+        let (header, message) = line.split_once('\t').unwrap_or((line, ""));
+        let (old, rest) = header.split_once(' ').ok_or(InvalidObjectFormat)?;
+        let (new, signature) = rest.split_once(' ').ok_or(InvalidObjectFormat)?;
+        let (committer, timestamp) = object::parse_signature(signature)?;
+        Ok(Entry {
+            old: old.to_string(),
+            new: new.to_string(),
+            committer,
+            timestamp,
+            message: message.to_string(),
+        })
+    }
+
+    fn format(&self) -> String {
+        format!(
+            "{} {} {} {}\t{}\n",
+            self.old, self.new, self.committer, self.timestamp, self.message
+        )
+    }
+}
+
+fn path(gitdir: &Path, name: &str) -> PathBuf {
+    gitdir.join("logs").join(name)
+}
+
+// Maps a short name such as `main` to the ref whose log exists.
+pub fn dwim(name: &str) -> Option<String> {
+    dwim_in(Repository::current().ok()?.gitdir(), name)
+}
+
+pub fn dwim_in(gitdir: &Path, name: &str) -> Option<String> {
+    [
+        name.to_string(),
+        format!("refs/{}", name),
+        format!("refs/tags/{}", name),
+        format!("refs/heads/{}", name),
+        format!("refs/remotes/{}", name),
+    ]
+    .into_iter()
+    .find(|candidate| refs::is_valid_name(candidate) && path(gitdir, candidate).is_file())
+}
+
+// Reads a ref's log, oldest entry first. A ref without a log has no entries.
+pub fn read(name: &str) -> Result<Vec<Entry>, Box<dyn std::error::Error + 'static>> {
+    read_in(Repository::current()?.gitdir(), name)
+}
+
+pub fn read_in(
+    gitdir: &Path,
+    name: &str,
+) -> Result<Vec<Entry>, Box<dyn std::error::Error + 'static>> {
+    let path = path(gitdir, name);
+    if !refs::is_valid_name(name) || !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        entries.push(Entry::parse(line)?);
+    }
+    Ok(entries)
+}
+
+pub fn append(
+    name: &str,
+    old: Option<&str>,
+    new: &str,
+    committer: &User,
+    message: &str,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    append_in(
+        Repository::current()?.gitdir(),
+        name,
+        old,
+        new,
+        committer,
+        message,
+    )
+}
+
+pub fn append_in(
+    gitdir: &Path,
+    name: &str,
+    old: Option<&str>,
+    new: &str,
+    committer: &User,
+    message: &str,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let path = path(gitdir, name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let entry = Entry {
+        old: old.unwrap_or(ZERO_HASH).to_string(),
+        new: new.to_string(),
+        committer: committer.clone(),
+        timestamp: Timestamp::now(),
+        message: message.lines().next().unwrap_or("").to_string(),
+    };
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(entry.format().as_bytes())?;
+    Ok(())
+}
+
+// Parses an expiry such as `90.days.ago`, `2 weeks`, `now`, `all`, `never`
+// or a unix timestamp into the cutoff before which entries are dropped.
+pub fn parse_expire(value: &str, now: i64) -> Option<i64> {
+    match value {
+        "all" => return Some(i64::MAX),
+        "now" => return Some(now),
+        "never" | "false" => return Some(i64::MIN),
+        _ => {}
+    }
+    if let Ok(seconds) = value.parse() {
+        return Some(seconds);
+    }
+
+    let words: Vec<&str> = value.split(['.', ' ']).filter(|w| !w.is_empty()).collect();
+    let (count, unit) = match words[..] {
+        [count, unit] | [count, unit, "ago"] => (count.parse::<i64>().ok()?, unit),
+        _ => return None,
+    };
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        "month" => 30 * 24 * 60 * 60,
+        "year" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    now.checked_sub(count.checked_mul(seconds)?)
+}
+
+// The cutoff used when `reflog expire` is not given one: gc.reflogExpire,
+// or 90 days.
+pub fn default_expire(now: i64) -> Option<i64> {
+    match config::get("gc", "reflogExpire") {
+        Some(value) => parse_expire(&value, now),
+        None => Some(now - DEFAULT_EXPIRE_DAYS * 24 * 60 * 60),
+    }
+}
+
+// Drops the entries written before `cutoff` and rewrites the log, returning
+// how many were removed.
+pub fn expire(name: &str, cutoff: i64) -> Result<usize, Box<dyn std::error::Error + 'static>> {
+    expire_in(Repository::current()?.gitdir(), name, cutoff)
+}
+
+pub fn expire_in(
+    gitdir: &Path,
+    name: &str,
+    cutoff: i64,
+) -> Result<usize, Box<dyn std::error::Error + 'static>> {
+    let entries = read_in(gitdir, name)?;
+    let total = entries.len();
+    let kept: Vec<Entry> = entries
+        .into_iter()
+        .filter(|entry| entry.timestamp.seconds() >= cutoff)
+        .collect();
+    let removed = total - kept.len();
+    if removed > 0 {
+        write_in(gitdir, name, &kept)?;
+    }
+    Ok(removed)
+}
+
+// Replaces a ref's log with `entries`, oldest first, through a lock file so
+// readers never see it half-written.
+pub fn write(name: &str, entries: &[Entry]) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    write_in(Repository::current()?.gitdir(), name, entries)
+}
+
+pub fn write_in(
+    gitdir: &Path,
+    name: &str,
+    entries: &[Entry],
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let path = path(gitdir, name);
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".lock");
+    let content: String = entries.iter().map(|entry| entry.format()).collect();
+    fs::write(&tmp, content)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+pub fn delete(name: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    delete_in(Repository::current()?.gitdir(), name)
+}
+
+pub fn delete_in(gitdir: &Path, name: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    let path = path(gitdir, name);
+    if path.is_file() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,464 @@
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::repository::Repository;
+
+#[derive(Debug, Clone)]
+pub struct RefNotFound(pub String);
+
+impl fmt::Display for RefNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ref {} does not exist", self.0)
+    }
+}
+
+impl std::error::Error for RefNotFound {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidRefName(pub String);
+
+impl fmt::Display for InvalidRefName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "'{}' is not a valid ref name", self.0)
+    }
+}
+
+impl std::error::Error for InvalidRefName {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidRefContent(pub String);
+
+impl fmt::Display for InvalidRefContent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ref {} holds neither a hash nor a symbolic ref", self.0)
+    }
+}
+
+impl std::error::Error for InvalidRefContent {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StaleRef(pub String);
+
+impl fmt::Display for StaleRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} changed while it was being updated", self.0)
+    }
+}
+
+impl std::error::Error for StaleRef {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// Symbolic refs pointing at symbolic refs are followed at most this deep.
+pub const MAX_SYMREF_DEPTH: usize = 5;
+
+// Applies the rules of `git check-ref-format --allow-onelevel`, which also
+// keep a name from reaching outside the git directory: no component may
+// be empty or start with `.`, or end with `.lock`; the name may not
+// contain `..`, `@{`, control characters, spaces or any of `~^:?*[\`,
+// end with `.` or be `@` alone.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "@"
+        && !name.ends_with('.')
+        && !name.contains("..")
+        && !name.contains("@{")
+        && !name
+            .chars()
+            .any(|c| c.is_control() || " ~^:?*[\\".contains(c))
+        && name
+            .split('/')
+            .all(|part| !part.is_empty() && !part.starts_with('.') && !part.ends_with(".lock"))
+}
+
+// What a ref file holds: a hash, or another ref's name after `ref: `.
+pub enum Target {
+    Hash(String),
+    Symbolic(String),
+}
+
+// Checks the content of the ref `name` before anything uses it.
+pub fn parse_target(name: &str, content: &str) -> Result<Target, InvalidRefContent> {
+    match content.strip_prefix("ref: ") {
+        Some(target) if is_valid_name(target) => Ok(Target::Symbolic(target.to_string())),
+        None if content.len() == 40 && content.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            Ok(Target::Hash(content.to_ascii_lowercase()))
+        }
+        _ => Err(InvalidRefContent(name.to_string())),
+    }
+}
+
+pub fn read_ref(name: &str) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    read_ref_in(Repository::current()?.gitdir(), name)
+}
+
+// Reads a ref of any git directory.
+pub fn read_ref_in(
+    gitdir: &Path,
+    name: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    // An invalid name cannot be a ref, and may even point outside gitdir.
+    if !is_valid_name(name) {
+        return Ok(None);
+    }
+    let path = gitdir.join(name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    Ok(Some(fs::read_to_string(path)?.trim().to_string()))
+}
+
+// Points a ref at `hash`. The new value is written to `<ref>.lock` and
+// renamed over the ref, so a crash never leaves it half-written, and the
+// lock is created exclusively so concurrent updates cannot interleave.
+pub fn update_ref(name: &str, hash: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    update_ref_in(Repository::current()?.gitdir(), name, hash)
+}
+
+pub fn update_ref_in(
+    gitdir: &Path,
+    name: &str,
+    hash: &str,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    write_ref(gitdir, name, hash, None)
+}
+
+// Like update_ref_in, but only moves the ref if it still holds `old` (None
+// meaning it must not exist yet) once the lock is taken, so of two
+// concurrent updates from the same value only one succeeds.
+pub fn update_ref_from_in(
+    gitdir: &Path,
+    name: &str,
+    old: Option<&str>,
+    hash: &str,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    write_ref(gitdir, name, hash, Some(old))
+}
+
+fn write_ref(
+    gitdir: &Path,
+    name: &str,
+    content: &str,
+    expected: Option<Option<&str>>,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    if !is_valid_name(name) {
+        return Err(Box::new(InvalidRefName(name.to_string())));
+    }
+    let path = gitdir.join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut lock = path.clone().into_os_string();
+    lock.push(".lock");
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock)?;
+    if let Some(old) = expected {
+        let current = match stored_value(gitdir, name) {
+            Ok(current) => current,
+            Err(err) => {
+                let _ = fs::remove_file(&lock);
+                return Err(err);
+            }
+        };
+        if current.as_deref() != old {
+            fs::remove_file(&lock)?;
+            return Err(Box::new(StaleRef(name.to_string())));
+        }
+    }
+    let written = file
+        .write_all(format!("{}\n", content).as_bytes())
+        .and_then(|_| file.sync_all())
+        .and_then(|_| fs::rename(&lock, &path));
+    if written.is_err() {
+        let _ = fs::remove_file(&lock);
+    }
+    Ok(written?)
+}
+
+// What the ref `name` itself holds, from its own file or else from
+// packed-refs.
+fn stored_value(
+    gitdir: &Path,
+    name: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    if let Some(content) = read_ref_in(gitdir, name)? {
+        return Ok(Some(content));
+    }
+    Ok(packed_refs_in(gitdir)?
+        .into_iter()
+        .find(|(packed, _)| packed == name)
+        .map(|(_, hash)| hash))
+}
+
+// The `(name, hash)` pairs listed in a git directory's packed-refs file.
+pub fn packed_refs_in(
+    gitdir: &Path,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + 'static>> {
+    let path = gitdir.join("packed-refs");
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.starts_with(['#', '^']))
+        .filter_map(|line| line.split_once(' '))
+        .map(|(hash, name)| (name.to_string(), hash.to_string()))
+        .collect())
+}
+
+// Points the symbolic ref `name`, usually HEAD, at the ref `target`,
+// through the same lock file as update_ref.
+pub fn update_symbolic_ref(
+    name: &str,
+    target: &str,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    update_symbolic_ref_in(Repository::current()?.gitdir(), name, target)
+}
+
+pub fn update_symbolic_ref_in(
+    gitdir: &Path,
+    name: &str,
+    target: &str,
+) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    if !is_valid_name(target) {
+        return Err(Box::new(InvalidRefName(target.to_string())));
+    }
+    update_ref_in(gitdir, name, &format!("ref: {}", target))
+}
+
+pub fn delete_ref(name: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    if !is_valid_name(name) {
+        return Err(Box::new(InvalidRefName(name.to_string())));
+    }
+    let path = Repository::current()?.gitdir().join(name);
+    if path.is_file() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+// Lists the refs under `prefix`, e.g. `refs/heads`, sorted by full name.
+pub fn list(prefix: &str) -> Result<Vec<String>, Box<dyn std::error::Error + 'static>> {
+    list_in(Repository::current()?.gitdir(), prefix)
+}
+
+pub fn list_in(
+    gitdir: &Path,
+    prefix: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error + 'static>> {
+    fn visit(
+        dir: &Path,
+        name: &str,
+        refs: &mut Vec<String>,
+    ) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let child = format!("{}/{}", name, entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                visit(&entry.path(), &child, refs)?;
+            } else {
+                refs.push(child);
+            }
+        }
+        Ok(())
+    }
+
+    let mut refs = Vec::new();
+    visit(&gitdir.join(prefix), prefix, &mut refs)?;
+    refs.sort();
+    Ok(refs)
+}
+
+// Reads a ref and follows `ref: <name>` indirections down to a hash.
+// Returns None when the ref, or the ref it points at, does not exist.
+pub fn resolve(name: &str) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    resolve_in(Repository::current()?.gitdir(), name)
+}
+
+pub fn resolve_in(
+    gitdir: &Path,
+    name: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    let mut name = name.to_string();
+    for _ in 0..=MAX_SYMREF_DEPTH {
+        match read_ref_in(gitdir, &name)? {
+            Some(content) => match parse_target(&name, &content)? {
+                Target::Symbolic(target) => name = target,
+                Target::Hash(hash) => return Ok(Some(hash)),
+            },
+            None => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+// Like `resolve`, but a missing ref, a dangling symbolic ref or a chain of
+// symbolic refs deeper than MAX_SYMREF_DEPTH is an error naming `name`.
+pub fn resolve_ref(name: &str) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    Repository::current()?.resolve_ref(name)
+}
+
+// Moves whatever HEAD points at to `hash`: the current branch when HEAD is
+// symbolic, even if that branch does not exist yet, or HEAD itself when
+// detached.
+pub fn update_head(hash: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    match read_ref("HEAD")?
+        .as_deref()
+        .and_then(|head| head.strip_prefix("ref: "))
+    {
+        Some(branch) => update_ref(branch, hash),
+        None => update_ref("HEAD", hash),
+    }
+}
+
+// Resolves a short ref name the way git does, trying `<name>`, `refs/<name>`,
+// `refs/tags/<name>`, `refs/heads/<name>`, `refs/remotes/<name>` and
+// `refs/remotes/<name>/HEAD` in that order.
+pub fn dwim(name: &str) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    dwim_in(Repository::current()?.gitdir(), name)
+}
+
+pub fn dwim_in(
+    gitdir: &Path,
+    name: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    let candidates = [
+        name.to_string(),
+        format!("refs/{}", name),
+        format!("refs/tags/{}", name),
+        format!("refs/heads/{}", name),
+        format!("refs/remotes/{}", name),
+        format!("refs/remotes/{}/HEAD", name),
+    ];
+    for candidate in candidates {
+        if let Some(hash) = resolve_in(gitdir, &candidate)? {
+            return Ok(Some(hash));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_follow_check_ref_format() {
+        for name in [
+            "HEAD",
+            "refs/heads/main",
+            "refs/heads/feature/x-1",
+            "refs/tags/v1.0",
+        ] {
+            assert!(is_valid_name(name), "{}", name);
+        }
+        for name in [
+            "",
+            "@",
+            "../x",
+            "refs/heads/../../x",
+            "/refs/heads/x",
+            "refs/heads/x/",
+            "refs//heads",
+            "refs/heads/.hidden",
+            "refs/heads/x.lock",
+            "refs/heads/x.",
+            "refs/heads/a@{1}",
+            "refs/heads/a b",
+            "refs/heads/a\tb",
+            "refs/heads/a~1",
+            "refs/heads/a:b",
+            "refs/heads/a\\b",
+        ] {
+            assert!(!is_valid_name(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn contents_must_be_a_hash_or_a_symbolic_ref() {
+        let hash = "0123456789ABCDEF0123456789abcdef01234567";
+        assert!(matches!(
+            parse_target("HEAD", hash),
+            Ok(Target::Hash(h)) if h == hash.to_ascii_lowercase()
+        ));
+        assert!(matches!(
+            parse_target("HEAD", "ref: refs/heads/main"),
+            Ok(Target::Symbolic(name)) if name == "refs/heads/main"
+        ));
+        for content in ["a\n", "ref: ../../x", &hash[1..], "ref:refs/heads/main"] {
+            assert!(parse_target("HEAD", content).is_err(), "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn symbolic_refs_must_name_a_ref() {
+        let gitdir =
+            std::env::temp_dir().join(format!("mygit-symbolic-ref-{}", std::process::id()));
+        fs::create_dir_all(&gitdir).unwrap();
+        update_symbolic_ref_in(&gitdir, "HEAD", "refs/heads/main").unwrap();
+        assert_eq!(
+            fs::read_to_string(gitdir.join("HEAD")).unwrap(),
+            "ref: refs/heads/main\n"
+        );
+        assert!(update_symbolic_ref_in(&gitdir, "HEAD", "../x").is_err());
+        assert!(update_symbolic_ref_in(&gitdir, "../HEAD", "refs/heads/main").is_err());
+        assert_eq!(
+            fs::read_to_string(gitdir.join("HEAD")).unwrap(),
+            "ref: refs/heads/main\n"
+        );
+        assert!(!gitdir.join("HEAD.lock").exists());
+        fs::remove_dir_all(&gitdir).unwrap();
+    }
+
+    #[test]
+    fn checked_updates_refuse_stale_values() {
+        let gitdir = std::env::temp_dir().join(format!("mygit-stale-ref-{}", std::process::id()));
+        fs::create_dir_all(&gitdir).unwrap();
+        let (a, b, c) = ("a".repeat(40), "b".repeat(40), "c".repeat(40));
+        let name = "refs/heads/main";
+        let stored = || fs::read_to_string(gitdir.join(name)).unwrap();
+
+        update_ref_from_in(&gitdir, name, None, &a).unwrap();
+        assert_eq!(stored(), format!("{}\n", a));
+        for old in [None, Some(b.as_str())] {
+            let err = update_ref_from_in(&gitdir, name, old, &c).unwrap_err();
+            assert!(err.is::<StaleRef>());
+            assert_eq!(stored(), format!("{}\n", a));
+        }
+        update_ref_from_in(&gitdir, name, Some(&a), &b).unwrap();
+        assert_eq!(stored(), format!("{}\n", b));
+
+        // A ref only in packed-refs holds its packed value.
+        fs::write(
+            gitdir.join("packed-refs"),
+            format!("# pack-refs with: peeled\n{} refs/tags/v1\n", a),
+        )
+        .unwrap();
+        assert!(update_ref_from_in(&gitdir, "refs/tags/v1", None, &c).is_err());
+        update_ref_from_in(&gitdir, "refs/tags/v1", Some(&a), &c).unwrap();
+        assert!(!gitdir.join("refs/heads/main.lock").exists());
+        assert!(!gitdir.join("refs/tags/v1.lock").exists());
+        fs::remove_dir_all(&gitdir).unwrap();
+    }
+}
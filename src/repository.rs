@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::MygitError;
+use crate::index::Index;
+use crate::object::{self, InvalidObjectFormat, Object, Timestamp, User};
+use crate::pack;
+use crate::refs;
+use crate::revision::{self, Resolved};
+use crate::revwalk::{self, Order};
+
+#[derive(Debug, Clone)]
+pub struct NotARepository;
+
+impl fmt::Display for NotARepository {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "not a git repository")
+    }
+}
+
+impl std::error::Error for NotARepository {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// A working tree and the .git directory inside it. Objects, refs and the
+// index are read from `gitdir`, so a repository need not be the one in the
+// current directory. The free functions elsewhere in the crate act on
+// `Repository::current()`; most also have an `_in` variant taking the git
+// or objects directory instead.
+pub struct Repository {
+    gitdir: PathBuf,
+    workdir: PathBuf,
+    clock: Box<dyn Clock>,
+}
+
+impl Repository {
+    // Opens the repository whose working tree is `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Repository, NotARepository> {
+        let workdir = path.as_ref().to_path_buf();
+        let gitdir = workdir.join(".git");
+        if !gitdir.is_dir() {
+            return Err(NotARepository);
+        }
+        Ok(Repository {
+            gitdir,
+            workdir,
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    // The repository in the current directory, which the free functions
+    // elsewhere in the crate act on.
+    pub fn current() -> Result<Repository, NotARepository> {
+        let gitdir = PathBuf::from(".git");
+        if !gitdir.is_dir() {
+            return Err(NotARepository);
+        }
+        Ok(Repository {
+            gitdir,
+            workdir: PathBuf::from("."),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    // Opens the repository enclosing the current directory, looking in it
+    // and then in each of its parents for a .git directory.
+    pub fn discover() -> Result<Repository, NotARepository> {
+        let cwd = std::env::current_dir().map_err(|_| NotARepository)?;
+        cwd.ancestors()
+            .find(|dir| dir.join(".git").is_dir())
+            .ok_or(NotARepository)
+            .and_then(Repository::open)
+    }
+
+    pub fn gitdir(&self) -> &Path {
+        &self.gitdir
+    }
+
+    pub fn workdir(&self) -> &Path {
+        &self.workdir
+    }
+
+    pub fn objects_dir(&self) -> PathBuf {
+        self.gitdir.join("objects")
+    }
+
+    // Reads the object a full or abbreviated hash names, loose or packed.
+    pub fn read_object(&self, hash: &str) -> Result<Object, MygitError> {
+        Object::read_in(
+            &self.gitdir,
+            &object::resolve_object_in(&self.objects_dir(), hash)?,
+        )
+    }
+
+    pub fn write_object(&self, object: &Object) -> Result<String, MygitError> {
+        object.write_in(&self.objects_dir())
+    }
+
+    // Follows a ref such as `HEAD` or `refs/heads/main` down to a hash.
+    pub fn resolve_ref(&self, name: &str) -> Result<String, Box<dyn std::error::Error + 'static>> {
+        refs::resolve_in(&self.gitdir, name)?
+            .ok_or_else(|| Box::new(refs::RefNotFound(name.to_string())).into())
+    }
+
+    // Writes the trees the index describes, as `git write-tree` does, and
+    // returns the root tree's hash.
+    pub fn write_tree(&self) -> Result<String, Box<dyn std::error::Error + 'static>> {
+        Index::load_in(&self.gitdir)?.write_tree_in(&self.objects_dir())
+    }
+
+    // Replaces the clock new commits are stamped with.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Repository {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn now(&self) -> Timestamp {
+        self.clock.now()
+    }
+
+    // Writes a commit authored and committed by `user` at the repository
+    // clock's current time, returning its hash.
+    pub fn commit_tree(
+        &self,
+        tree: &str,
+        parents: Vec<String>,
+        user: &User,
+        message: String,
+    ) -> Result<String, Box<dyn std::error::Error + 'static>> {
+        let now = self.now();
+        let commit = Object::Commit {
+            tree: tree.to_string(),
+            parents,
+            author: user.clone(),
+            author_timestamp: now,
+            committer: user.clone(),
+            committer_timestamp: now,
+            message: object::normalize_message(&message),
+        };
+        Ok(self.write_object(&commit)?)
+    }
+
+    // Resolves HEAD to the commit it names. An unborn branch, where HEAD
+    // points at a ref that does not exist yet, yields None.
+    pub fn head_commit(
+        &self,
+    ) -> Result<Option<(String, Object)>, Box<dyn std::error::Error + 'static>> {
+        let Some(hash) = refs::resolve_in(&self.gitdir, "HEAD")? else {
+            return Ok(None);
+        };
+        match self.read_object(&hash)? {
+            commit @ Object::Commit { .. } => Ok(Some((hash, commit))),
+            _ => Err(Box::new(InvalidObjectFormat)),
+        }
+    }
+
+    pub fn resolve(&self, rev: &str) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+        revision::parse_in(&self.gitdir, rev)
+    }
+
+    // Builds nested trees from (path, mode, blob hash) triples, without
+    // touching the working directory, and returns the root tree's hash.
+    pub fn build_tree_from_paths(
+        &self,
+        entries: &[(String, String, String)],
+    ) -> Result<String, Box<dyn std::error::Error + 'static>> {
+        object::build_tree_from_paths_in(&self.objects_dir(), entries, false)
+    }
+
+    // Rewrites every commit reachable from `refname`, oldest first, giving
+    // each the tree `mapper` returns for its original tree and pointing it
+    // at its parents' rewritten counterparts. Authors, dates and messages
+    // are kept, and commits that come out unchanged keep their hashes. The
+    // ref is moved to the rewritten tip, and its old value saved under
+    // refs/original/ as filter-branch does. Returns the new tip.
+    pub fn rewrite_history(
+        &self,
+        refname: &str,
+        mut mapper: impl FnMut(&str) -> Result<String, Box<dyn std::error::Error + 'static>>,
+    ) -> Result<String, Box<dyn std::error::Error + 'static>> {
+        let tip = self.resolve_ref(refname)?;
+        let mut rewritten: HashMap<String, String> = HashMap::new();
+        let commits = revwalk::walk_in(&self.gitdir, std::slice::from_ref(&tip), Order::Topo)?;
+        for (hash, commit) in commits.into_iter().rev() {
+            let Object::Commit {
+                tree,
+                parents,
+                author,
+                author_timestamp,
+                committer,
+                committer_timestamp,
+                message,
+            } = commit
+            else {
+                return Err(Box::new(InvalidObjectFormat));
+            };
+            let commit = Object::Commit {
+                tree: mapper(&tree)?,
+                parents: parents
+                    .iter()
+                    .map(|parent| rewritten.get(parent).unwrap_or(parent).clone())
+                    .collect(),
+                author,
+                author_timestamp,
+                committer,
+                committer_timestamp,
+                message,
+            };
+            rewritten.insert(hash, self.write_object(&commit)?);
+        }
+
+        let new_tip = rewritten.remove(&tip).unwrap_or(tip.clone());
+        refs::update_ref_in(&self.gitdir, &format!("refs/original/{}", refname), &tip)?;
+        refs::update_ref_in(&self.gitdir, refname, &new_tip)?;
+        Ok(new_tip)
+    }
+
+    // Checks many hashes at once by listing the loose objects and loading
+    // the pack indexes a single time, instead of probing the object store
+    // per hash.
+    pub fn contains_all(
+        &self,
+        hashes: &[&str],
+    ) -> Result<Vec<bool>, Box<dyn std::error::Error + 'static>> {
+        let objects = self.objects_dir();
+        let loose: HashSet<String> = object::find_objects_in(&objects, "")?.into_iter().collect();
+        let packs = pack::open_packs_in(&objects)?;
+        Ok(hashes
+            .iter()
+            .map(|hash| {
+                let hash = hash.to_ascii_lowercase();
+                loose.contains(&hash) || packs.iter().any(|open| open.index.contains(&hash))
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,496 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::config;
+use crate::error::MygitError;
+use crate::index::Index;
+use crate::object::{self, FileMode, InvalidObjectFormat, Object};
+use crate::pack::ChecksumMismatch;
+use crate::reflog;
+use crate::refs;
+use crate::repository::Repository;
+
+#[derive(Debug, Clone)]
+pub struct UnknownRevision(pub String);
+
+impl fmt::Display for UnknownRevision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown revision {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownRevision {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AmbiguousRevision(pub String);
+
+impl fmt::Display for AmbiguousRevision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "short object ID {} is ambiguous", self.0)
+    }
+}
+
+impl std::error::Error for AmbiguousRevision {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReflogTooShort(pub String, pub usize);
+
+impl fmt::Display for ReflogTooShort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "log for '{}' only has {} entries", self.0, self.1)
+    }
+}
+
+impl std::error::Error for ReflogTooShort {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NoUpstream(pub String);
+
+impl fmt::Display for NoUpstream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no upstream configured for branch '{}'", self.0)
+    }
+}
+
+impl std::error::Error for NoUpstream {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SymlinkLoop(pub String);
+
+impl fmt::Display for SymlinkLoop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "too many levels of symbolic links in {}", self.0)
+    }
+}
+
+impl std::error::Error for SymlinkLoop {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SymlinkOutsideTree(pub String);
+
+impl fmt::Display for SymlinkOutsideTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} leads outside the tree", self.0)
+    }
+}
+
+impl std::error::Error for SymlinkOutsideTree {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// Shortest abbreviation git accepts for an object name.
+pub const MIN_ABBREV: usize = 4;
+
+// What a revision names, together with the object's hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolved {
+    Commit(String),
+    Tree(String),
+    Blob(String),
+    Tag(String),
+}
+
+impl Resolved {
+    fn new(object_type: &str, hash: String) -> Result<Resolved, InvalidObjectFormat> {
+        match object_type {
+            "commit" => Ok(Resolved::Commit(hash)),
+            "tree" => Ok(Resolved::Tree(hash)),
+            "blob" => Ok(Resolved::Blob(hash)),
+            "tag" => Ok(Resolved::Tag(hash)),
+            _ => Err(InvalidObjectFormat),
+        }
+    }
+
+    pub fn from_hash(hash: &str) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+        Resolved::from_hash_in(Repository::current()?.gitdir(), hash)
+    }
+
+    pub fn from_hash_in(
+        gitdir: &Path,
+        hash: &str,
+    ) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+        let (object_type, _) = object::peek_header_in(&gitdir.join("objects"), hash)?;
+        Ok(Resolved::new(&object_type, hash.to_string())?)
+    }
+
+    pub fn hash(&self) -> &str {
+        match self {
+            Resolved::Commit(hash)
+            | Resolved::Tree(hash)
+            | Resolved::Blob(hash)
+            | Resolved::Tag(hash) => hash,
+        }
+    }
+
+    pub fn object_type(&self) -> &'static str {
+        match self {
+            Resolved::Commit(_) => "commit",
+            Resolved::Tree(_) => "tree",
+            Resolved::Blob(_) => "blob",
+            Resolved::Tag(_) => "tag",
+        }
+    }
+}
+
+// The branch HEAD points at, or HEAD itself when detached.
+fn current_branch(gitdir: &Path) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let head = refs::read_ref_in(gitdir, "HEAD")?.unwrap_or_default();
+    Ok(head.strip_prefix("ref: ").unwrap_or("HEAD").to_string())
+}
+
+// The value `name` had `n` updates ago, read from its reflog.
+fn reflog_entry(
+    gitdir: &Path,
+    name: &str,
+    n: usize,
+    rev: &str,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let name = match name {
+        "" => current_branch(gitdir)?,
+        "@" => "HEAD".to_string(),
+        name => reflog::dwim_in(gitdir, name).ok_or_else(|| UnknownRevision(rev.to_string()))?,
+    };
+    let entries = reflog::read_in(gitdir, &name)?;
+    match n
+        .checked_add(1)
+        .and_then(|count| entries.len().checked_sub(count))
+    {
+        Some(i) => Ok(entries[i].new.clone()),
+        None => Err(Box::new(ReflogTooShort(name, entries.len()))),
+    }
+}
+
+// The ref a branch tracks, from its `branch.<name>.remote` and `merge`
+// config. A remote of `.` means the upstream is another local branch.
+fn upstream(
+    gitdir: &Path,
+    name: &str,
+    rev: &str,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let branch = match name {
+        "" | "@" | "HEAD" => current_branch(gitdir)?,
+        name => format!("refs/heads/{}", name.trim_start_matches("refs/heads/")),
+    };
+    let Some(short) = branch.strip_prefix("refs/heads/") else {
+        return Err(Box::new(UnknownRevision(rev.to_string())));
+    };
+    let section = format!("branch \"{}\"", short);
+    let (Some(remote), Some(merge)) = (
+        config::get_in(gitdir, &section, "remote"),
+        config::get_in(gitdir, &section, "merge"),
+    ) else {
+        return Err(Box::new(NoUpstream(short.to_string())));
+    };
+    match merge.strip_prefix("refs/heads/") {
+        _ if remote == "." => Ok(merge),
+        Some(merge) => Ok(format!("refs/remotes/{}/{}", remote, merge)),
+        None => Err(Box::new(NoUpstream(short.to_string()))),
+    }
+}
+
+// The branch checked out before the `n`th most recent switch, found from
+// the checkout messages in HEAD's reflog.
+fn previous_branch(
+    gitdir: &Path,
+    n: usize,
+    rev: &str,
+) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    reflog::read_in(gitdir, "HEAD")?
+        .iter()
+        .rev()
+        .filter_map(|entry| entry.message.strip_prefix("checkout: moving from "))
+        .filter_map(|moved| moved.split_once(" to ").map(|(from, _)| from.to_string()))
+        .nth(n - 1)
+        .ok_or_else(|| Box::new(UnknownRevision(rev.to_string())).into())
+}
+
+// Looks up a bare name: a ref, `@` for HEAD, an abbreviated hash, or any of
+// these followed by `@{<n>}` for an earlier value from the reflog or
+// `@{upstream}` (`@{u}`) for the branch it tracks. `@{-<n>}` names the branch
+// checked out `n` switches ago.
+fn resolve_name(gitdir: &Path, name: &str) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    if let Some((base, selector)) = name
+        .strip_suffix('}')
+        .and_then(|name| name.rsplit_once("@{"))
+    {
+        if let Some(n) = selector.strip_prefix('-') {
+            let n = n.parse().map_err(|_| UnknownRevision(name.to_string()))?;
+            if !base.is_empty() || n == 0 {
+                return Err(Box::new(UnknownRevision(name.to_string())));
+            }
+            return resolve_name(gitdir, &previous_branch(gitdir, n, name)?);
+        }
+        if let Ok(n) = selector.parse() {
+            return reflog_entry(gitdir, base, n, name);
+        }
+        if selector == "u" || selector == "upstream" {
+            let upstream = upstream(gitdir, base, name)?;
+            return refs::resolve_in(gitdir, &upstream)?
+                .ok_or_else(|| Box::new(refs::RefNotFound(upstream)).into());
+        }
+        return Err(Box::new(UnknownRevision(name.to_string())));
+    }
+
+    let name = if name == "@" { "HEAD" } else { name };
+    if let Some(hash) = refs::dwim_in(gitdir, name)? {
+        return Ok(hash);
+    }
+
+    let objects = gitdir.join("objects");
+    // A damaged pack is reported as such, not as a missing object.
+    match object::resolve_object_in(&objects, &name.to_ascii_lowercase()) {
+        // A full hash is taken as it is by resolve_object, so check that
+        // something is stored under it.
+        Ok(hash) => match object::peek_header_in(&objects, &hash) {
+            Ok(_) => Ok(hash),
+            Err(err) if err.is::<ChecksumMismatch>() => Err(err),
+            Err(_) => Err(Box::new(UnknownRevision(name.to_string()))),
+        },
+        Err(MygitError::AmbiguousObject(_)) => Err(Box::new(AmbiguousRevision(name.to_string()))),
+        Err(MygitError::Other(err)) if err.is::<ChecksumMismatch>() => Err(err),
+        Err(_) => Err(Box::new(UnknownRevision(name.to_string()))),
+    }
+}
+
+// Peels tags, and commits down to their tree, until an object of
+// `object_type` is reached.
+fn peel_to(
+    gitdir: &Path,
+    resolved: Resolved,
+    object_type: &str,
+    rev: &str,
+) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+    if resolved.object_type() == object_type {
+        return Ok(resolved);
+    }
+    let (hash, object) = Object::peel_in(gitdir, resolved.hash())?;
+    match object {
+        _ if object.object_type() == object_type => Ok(Resolved::new(object_type, hash)?),
+        Object::Commit { tree, .. } if object_type == "tree" => Ok(Resolved::Tree(tree)),
+        _ => Err(Box::new(UnknownRevision(rev.to_string()))),
+    }
+}
+
+fn parent(
+    gitdir: &Path,
+    resolved: Resolved,
+    n: usize,
+    rev: &str,
+) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+    let commit = peel_to(gitdir, resolved, "commit", rev)?;
+    let Object::Commit { parents, .. } = Object::read_in(gitdir, commit.hash())? else {
+        return Err(Box::new(InvalidObjectFormat));
+    };
+    match parents.get(n - 1) {
+        Some(hash) => Ok(Resolved::Commit(hash.clone())),
+        None => Err(Box::new(UnknownRevision(rev.to_string()))),
+    }
+}
+
+fn lookup_path(
+    gitdir: &Path,
+    tree: Resolved,
+    path: &str,
+    rev: &str,
+) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+    let mut resolved = tree;
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        let Object::Tree(entries) = Object::read_in(gitdir, resolved.hash())? else {
+            return Err(Box::new(UnknownRevision(rev.to_string())));
+        };
+        let entry = entries
+            .into_iter()
+            .find(|entry| entry.filename() == component)
+            .ok_or_else(|| UnknownRevision(rev.to_string()))?;
+        resolved = match entry.mode() {
+            FileMode::Gitlink => Resolved::Commit(entry.hash().to_string()),
+            _ if entry.is_tree() => Resolved::Tree(entry.hash().to_string()),
+            _ => Resolved::Blob(entry.hash().to_string()),
+        };
+    }
+    Ok(resolved)
+}
+
+// Symlinks followed while resolving one path before giving up, the same
+// limit Linux puts on path lookups.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+// Walks `path` through `tree` like lookup_path, but a symlink on the way,
+// including the last component, is replaced by its target, read relative
+// to the directory holding it. Targets that are absolute or climb above
+// the tree's root are refused.
+fn lookup_path_following_symlinks(
+    gitdir: &Path,
+    tree: Resolved,
+    path: &str,
+    rev: &str,
+) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+    // The trees entered so far, root first, for resolving `..`.
+    let mut dirs = vec![tree.hash().to_string()];
+    // Components still to walk, the next one last.
+    let mut pending: Vec<String> = path.split('/').rev().map(str::to_string).collect();
+    let mut hops = 0;
+    let mut resolved = tree;
+    while let Some(component) = pending.pop() {
+        if !matches!(resolved, Resolved::Tree(_)) {
+            return Err(Box::new(UnknownRevision(rev.to_string())));
+        }
+        match component.as_str() {
+            "" | "." => continue,
+            ".." => {
+                if dirs.len() == 1 {
+                    return Err(Box::new(SymlinkOutsideTree(rev.to_string())));
+                }
+                dirs.pop();
+                resolved = Resolved::Tree(dirs[dirs.len() - 1].clone());
+                continue;
+            }
+            _ => {}
+        }
+
+        let Object::Tree(entries) = Object::read_in(gitdir, resolved.hash())? else {
+            return Err(Box::new(InvalidObjectFormat));
+        };
+        let entry = entries
+            .into_iter()
+            .find(|entry| entry.filename() == component)
+            .ok_or_else(|| UnknownRevision(rev.to_string()))?;
+        match entry.mode() {
+            FileMode::Symlink => {
+                hops += 1;
+                if hops > MAX_SYMLINK_HOPS {
+                    return Err(Box::new(SymlinkLoop(rev.to_string())));
+                }
+                let Object::Blob(target) = Object::read_in(gitdir, entry.hash())? else {
+                    return Err(Box::new(InvalidObjectFormat));
+                };
+                let target = String::from_utf8(target)?;
+                if target.starts_with('/') {
+                    return Err(Box::new(SymlinkOutsideTree(rev.to_string())));
+                }
+                pending.extend(target.split('/').rev().map(str::to_string));
+            }
+            FileMode::Gitlink => resolved = Resolved::Commit(entry.hash().to_string()),
+            _ if entry.is_tree() => {
+                dirs.push(entry.hash().to_string());
+                resolved = Resolved::Tree(entry.hash().to_string());
+            }
+            _ => resolved = Resolved::Blob(entry.hash().to_string()),
+        }
+    }
+    Ok(resolved)
+}
+
+// Parses the revision syntax commands accept: a ref name or abbreviated
+// hash, optionally with an `@{...}` reflog selector, followed by any
+// number of `~<n>`, `^<n>`, `^{}` and `^{<type>}` suffixes, optionally
+// followed by `:<path>` to name an entry in the resulting tree.
+pub fn parse(rev: &str) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+    Repository::current()?.resolve(rev)
+}
+
+pub fn parse_in(
+    gitdir: &Path,
+    rev: &str,
+) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+    if let Some((treeish, path)) = rev.split_once(':') {
+        if treeish.is_empty() {
+            // `:<path>` names the blob staged at that path.
+            let index = Index::load_in(gitdir)?;
+            let entry = index
+                .get(path.trim_start_matches("./"))
+                .ok_or_else(|| UnknownRevision(rev.to_string()))?;
+            return Ok(match FileMode::parse(&entry.mode()) {
+                Some(FileMode::Gitlink) => Resolved::Commit(entry.hash.clone()),
+                _ => Resolved::Blob(entry.hash.clone()),
+            });
+        }
+        let tree = peel_to(gitdir, parse_in(gitdir, treeish)?, "tree", rev)?;
+        return lookup_path(gitdir, tree, path, rev);
+    }
+
+    let (name, mut suffix) = rev.split_at(rev.find(['~', '^']).unwrap_or(rev.len()));
+    let mut resolved = Resolved::from_hash_in(gitdir, &resolve_name(gitdir, name)?)?;
+    while !suffix.is_empty() {
+        if let Some(rest) = suffix.strip_prefix("^{") {
+            let (object_type, rest) = rest
+                .split_once('}')
+                .ok_or_else(|| UnknownRevision(rev.to_string()))?;
+            resolved = match object_type {
+                "" => {
+                    let (hash, object) = Object::peel_in(gitdir, resolved.hash())?;
+                    Resolved::new(object.object_type(), hash)?
+                }
+                _ => peel_to(gitdir, resolved, object_type, rev)?,
+            };
+            suffix = rest;
+            continue;
+        }
+        if !suffix.starts_with(['~', '^']) {
+            return Err(Box::new(UnknownRevision(rev.to_string())));
+        }
+
+        let digits = suffix[1..].len()
+            - suffix[1..]
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let n = match &suffix[1..1 + digits] {
+            "" => 1,
+            n => n.parse()?,
+        };
+        // `~0` and `^0` both name the commit a tag points at.
+        if n == 0 {
+            resolved = peel_to(gitdir, resolved, "commit", rev)?;
+        } else if suffix.starts_with('~') {
+            for _ in 0..n {
+                resolved = parent(gitdir, resolved, 1, rev)?;
+            }
+        } else {
+            resolved = parent(gitdir, resolved, n, rev)?;
+        }
+        suffix = &suffix[1 + digits..];
+    }
+    Ok(resolved)
+}
+
+// Like `parse`, but symlinks inside the tree are followed when resolving
+// `<tree-ish>:<path>`, as `cat-file --follow-symlinks` does.
+pub fn parse_following_symlinks(
+    rev: &str,
+) -> Result<Resolved, Box<dyn std::error::Error + 'static>> {
+    match rev.split_once(':') {
+        Some((treeish, path)) if !treeish.is_empty() => {
+            let repo = Repository::current()?;
+            let gitdir = repo.gitdir();
+            let tree = peel_to(gitdir, parse_in(gitdir, treeish)?, "tree", rev)?;
+            lookup_path_following_symlinks(gitdir, tree, path, rev)
+        }
+        _ => parse(rev),
+    }
+}
+
+pub fn resolve(rev: &str) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    Ok(parse(rev)?.hash().to_string())
+}
@@ -0,0 +1,195 @@
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use crate::object::Object;
+use crate::repository::Repository;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    // Depth-first, so lines of history are not interleaved.
+    Topo,
+    // Newest committer date first.
+    Date,
+    // Newest author date first.
+    AuthorDate,
+}
+
+impl Order {
+    pub fn parse_arg(arg: &str) -> Option<Order> {
+        match arg {
+            "--topo-order" => Some(Order::Topo),
+            "--date-order" => Some(Order::Date),
+            "--author-date-order" => Some(Order::AuthorDate),
+            _ => None,
+        }
+    }
+}
+
+fn date_key(object: &Object, order: Order) -> i64 {
+    match (object, order) {
+        (
+            Object::Commit {
+                author_timestamp, ..
+            },
+            Order::AuthorDate,
+        ) => author_timestamp.seconds(),
+        (
+            Object::Commit {
+                committer_timestamp,
+                ..
+            },
+            _,
+        ) => committer_timestamp.seconds(),
+        _ => 0,
+    }
+}
+
+fn parents(object: &Object, first_parent: bool) -> &[String] {
+    match object {
+        Object::Commit { parents, .. } if first_parent => &parents[..parents.len().min(1)],
+        Object::Commit { parents, .. } => parents,
+        _ => &[],
+    }
+}
+
+// Parent overrides from .git/info/grafts (`<commit> <parent>...` per line)
+// and .git/shallow, whose commits are treated as roots because their
+// parents were never fetched.
+pub fn load_grafts() -> Result<HashMap<String, Vec<String>>, Box<dyn std::error::Error + 'static>> {
+    load_grafts_in(Repository::current()?.gitdir())
+}
+
+pub fn load_grafts_in(
+    gitdir: &Path,
+) -> Result<HashMap<String, Vec<String>>, Box<dyn std::error::Error + 'static>> {
+    let mut grafts = HashMap::new();
+    if let Ok(content) = fs::read_to_string(gitdir.join("info/grafts")) {
+        for line in content.lines().filter(|line| !line.starts_with('#')) {
+            let mut hashes = line.split_whitespace().map(|hash| hash.to_string());
+            if let Some(commit) = hashes.next() {
+                grafts.insert(commit, hashes.collect());
+            }
+        }
+    }
+    if let Ok(content) = fs::read_to_string(gitdir.join("shallow")) {
+        for line in content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            grafts.insert(line.to_string(), Vec::new());
+        }
+    }
+    Ok(grafts)
+}
+
+// Applies a graft or shallow boundary to a freshly read commit.
+pub fn graft(hash: &str, object: &mut Object, grafts: &HashMap<String, Vec<String>>) {
+    if let (Object::Commit { parents, .. }, Some(grafted)) = (object, grafts.get(hash)) {
+        *parents = grafted.clone();
+    }
+}
+
+// Returns every commit reachable from `tips`, never listing a commit before
+// all of its children. Parents missing from the object store are skipped.
+pub fn walk(
+    tips: &[String],
+    order: Order,
+) -> Result<Vec<(String, Object)>, Box<dyn std::error::Error + 'static>> {
+    walk_in(Repository::current()?.gitdir(), tips, order)
+}
+
+pub fn walk_in(
+    gitdir: &Path,
+    tips: &[String],
+    order: Order,
+) -> Result<Vec<(String, Object)>, Box<dyn std::error::Error + 'static>> {
+    walk_parents(gitdir, tips, order, false)
+}
+
+// Like `walk`, but only follows the first parent of each merge, giving the
+// mainline history of a branch that merges others in.
+pub fn walk_first_parent(
+    tips: &[String],
+    order: Order,
+) -> Result<Vec<(String, Object)>, Box<dyn std::error::Error + 'static>> {
+    walk_parents(Repository::current()?.gitdir(), tips, order, true)
+}
+
+fn walk_parents(
+    gitdir: &Path,
+    tips: &[String],
+    order: Order,
+    first_parent: bool,
+) -> Result<Vec<(String, Object)>, Box<dyn std::error::Error + 'static>> {
+    let grafts = load_grafts_in(gitdir)?;
+    let mut commits = HashMap::new();
+    let mut queue: VecDeque<String> = tips.iter().cloned().collect();
+    while let Some(hash) = queue.pop_front() {
+        if commits.contains_key(&hash) {
+            continue;
+        }
+        let Ok(mut object) = Object::read_in(gitdir, &hash) else {
+            continue;
+        };
+        graft(&hash, &mut object, &grafts);
+        if !matches!(object, Object::Commit { .. }) {
+            continue;
+        }
+        queue.extend(parents(&object, first_parent).iter().cloned());
+        commits.insert(hash, object);
+    }
+
+    let mut children: HashMap<&str, usize> = HashMap::new();
+    for object in commits.values() {
+        for parent in parents(object, first_parent) {
+            if commits.contains_key(parent) {
+                *children.entry(parent.as_str()).or_default() += 1;
+            }
+        }
+    }
+
+    let mut seen = Vec::new();
+    let mut stack = Vec::new();
+    let mut heap = BinaryHeap::new();
+    for tip in tips.iter().rev() {
+        if commits.contains_key(tip) && !children.contains_key(tip.as_str()) && !seen.contains(tip)
+        {
+            seen.push(tip.clone());
+            stack.push(tip.clone());
+            heap.push((date_key(&commits[tip], order), tip.clone()));
+        }
+    }
+
+    let mut sorted = Vec::new();
+    loop {
+        let next = match order {
+            Order::Topo => stack.pop(),
+            Order::Date | Order::AuthorDate => heap.pop().map(|(_, hash)| hash),
+        };
+        let Some(hash) = next else {
+            break;
+        };
+
+        for parent in parents(&commits[&hash], first_parent) {
+            let Some(count) = children.get_mut(parent.as_str()) else {
+                continue;
+            };
+            *count -= 1;
+            if *count == 0 {
+                stack.push(parent.clone());
+                heap.push((date_key(&commits[parent], order), parent.clone()));
+            }
+        }
+        sorted.push(hash);
+    }
+
+    Ok(sorted
+        .into_iter()
+        .map(|hash| {
+            let object = commits.remove(&hash).unwrap();
+            (hash, object)
+        })
+        .collect())
+}
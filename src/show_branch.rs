@@ -0,0 +1,223 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::object::Object;
+use crate::revwalk::{self, Order};
+
+#[derive(Debug, Clone)]
+pub struct TooManyBranches;
+
+impl fmt::Display for TooManyBranches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot handle more than {} refs", MAX_REVS)
+    }
+}
+
+impl std::error::Error for TooManyBranches {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// Each branch takes one bit of a commit's reachability flags.
+pub const MAX_REVS: usize = 26;
+
+// One line of the matrix: a marker per branch, then the commit's name
+// relative to the branches and its subject.
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub marks: String,
+    pub name: String,
+    pub subject: String,
+}
+
+fn subject(object: &Object) -> String {
+    match object {
+        Object::Commit { message, .. } => message.lines().next().unwrap_or("").to_string(),
+        _ => String::new(),
+    }
+}
+
+fn parents(object: &Object) -> &[String] {
+    match object {
+        Object::Commit { parents, .. } => parents,
+        _ => &[],
+    }
+}
+
+fn display_name(head_name: &str, generation: usize) -> String {
+    match generation {
+        0 => head_name.to_string(),
+        1 => format!("{}^", head_name),
+        n => format!("{}~{}", head_name, n),
+    }
+}
+
+// Names commits the way `git show-branch` does: tips by their branch, first
+// parents as `<name>~<n>`, and other parents as `<name>^<n>`, preferring
+// first-parent chains over side branches.
+fn name_commits(
+    list: &[&String],
+    tips: &[(String, String)],
+    commits: &HashMap<&str, &Object>,
+) -> HashMap<String, (String, usize)> {
+    let mut names: HashMap<String, (String, usize)> = HashMap::new();
+    for hash in list {
+        if let Some((name, _)) = tips.iter().find(|(_, tip)| tip == *hash) {
+            names.entry(hash.to_string()).or_insert((name.clone(), 0));
+        }
+    }
+
+    let name_first_parent_chain = |names: &mut HashMap<String, (String, usize)>,
+                                   hash: &String|
+     -> bool {
+        let mut named = false;
+        let mut hash = hash.clone();
+        while let Some((head_name, generation)) = names.get(&hash).cloned() {
+            let Some(parent) = commits.get(hash.as_str()).and_then(|c| parents(c).first()) else {
+                break;
+            };
+            if names.contains_key(parent) {
+                break;
+            }
+            names.insert(parent.clone(), (head_name, generation + 1));
+            named = true;
+            hash = parent.clone();
+        }
+        named
+    };
+
+    loop {
+        let mut named = false;
+        for hash in list {
+            named |= name_first_parent_chain(&mut names, hash);
+        }
+        if !named {
+            break;
+        }
+    }
+
+    loop {
+        let mut named = false;
+        for hash in list {
+            let Some((head_name, generation)) = names.get(*hash).cloned() else {
+                continue;
+            };
+            let Some(commit) = commits.get(hash.as_str()) else {
+                continue;
+            };
+            for (nth, parent) in parents(commit).iter().enumerate() {
+                if names.contains_key(parent) {
+                    continue;
+                }
+                let mut name = display_name(&head_name, generation);
+                if nth == 0 {
+                    name.push('^');
+                } else {
+                    name.push_str(&format!("^{}", nth + 1));
+                }
+                names.insert(parent.clone(), (name, 0));
+                name_first_parent_chain(&mut names, parent);
+                named = true;
+            }
+        }
+        if !named {
+            break;
+        }
+    }
+    names
+}
+
+// Lists the commits on the given `(name, hash)` branches, newest first,
+// down to the first commit reachable from all of them. Each row marks the
+// branches that reach the commit: `*` for the branch at `head`, `+` for
+// the others and `-` for merges. Merges reachable from a single branch
+// are left out unless they are a tip.
+pub fn show_branch(
+    tips: &[(String, String)],
+    head: Option<usize>,
+    order: Order,
+) -> Result<Vec<Row>, Box<dyn std::error::Error + 'static>> {
+    if tips.len() > MAX_REVS {
+        return Err(Box::new(TooManyBranches));
+    }
+    let hashes: Vec<String> = tips.iter().map(|(_, hash)| hash.clone()).collect();
+    // Like git, start from the most recently committed tip so its line of
+    // history is listed first.
+    let mut starts = Vec::new();
+    for hash in &hashes {
+        let seconds = match Object::from_hash(hash)? {
+            Object::Commit {
+                committer_timestamp,
+                ..
+            } => committer_timestamp.seconds(),
+            _ => 0,
+        };
+        starts.push((Reverse(seconds), hash.clone()));
+    }
+    starts.sort_by_key(|(seconds, _)| *seconds);
+    let starts: Vec<String> = starts.into_iter().map(|(_, hash)| hash).collect();
+    let sorted = revwalk::walk(&starts, order)?;
+
+    // Children come before parents in walk order, so one pass is enough to
+    // push every tip's bit down to all of its ancestors.
+    let mut flags: HashMap<String, u32> = HashMap::new();
+    for (i, hash) in hashes.iter().enumerate() {
+        *flags.entry(hash.clone()).or_default() |= 1 << i;
+    }
+    for (hash, commit) in &sorted {
+        let flag = flags.get(hash).copied().unwrap_or(0);
+        for parent in parents(commit) {
+            *flags.entry(parent.clone()).or_default() |= flag;
+        }
+    }
+
+    let all = (1u32 << tips.len()) - 1;
+    let mut list = Vec::new();
+    for (hash, _) in &sorted {
+        list.push(hash);
+        if flags[hash] == all {
+            break;
+        }
+    }
+    let commits: HashMap<&str, &Object> = sorted
+        .iter()
+        .map(|(hash, commit)| (hash.as_str(), commit))
+        .collect();
+    let names = name_commits(&list, tips, &commits);
+
+    let mut rows = Vec::new();
+    for hash in list {
+        let commit = commits[hash.as_str()];
+        let flag = flags[hash];
+        let is_merge = parents(commit).len() > 1;
+        if tips.len() > 1 && is_merge && !hashes.contains(hash) && flag.count_ones() == 1 {
+            continue;
+        }
+
+        let marks = (0..tips.len())
+            .map(|i| {
+                if flag & (1 << i) == 0 {
+                    ' '
+                } else if is_merge {
+                    '-'
+                } else if Some(i) == head {
+                    '*'
+                } else {
+                    '+'
+                }
+            })
+            .collect();
+        let name = match names.get(hash) {
+            Some((head_name, generation)) => display_name(head_name, *generation),
+            None => hash[..7].to_string(),
+        };
+        rows.push(Row {
+            marks,
+            name,
+            subject: subject(commit),
+        });
+    }
+    Ok(rows)
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::checkout;
+use crate::ignore::Ignore;
+use crate::index::Index;
+use crate::object::{self, Object, User};
+use crate::reflog;
+use crate::refs;
+use crate::repository::Repository;
+
+// The stack of stashes is refs/stash together with its reflog: the ref is
+// the newest stash and the log holds every stash, oldest first.
+pub const STASH_REF: &str = "refs/stash";
+
+#[derive(Debug, Clone)]
+pub struct NoStashEntry(pub usize);
+
+impl fmt::Display for NoStashEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "stash@{{{}}} is not a valid reference", self.0)
+    }
+}
+
+impl std::error::Error for NoStashEntry {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NoInitialCommit;
+
+impl fmt::Display for NoInitialCommit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "you do not have the initial commit yet")
+    }
+}
+
+impl std::error::Error for NoInitialCommit {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// Records the working tree as a commit on top of HEAD, pushes it onto the
+// stash and puts HEAD's files back. Returns None when there is nothing to
+// stash.
+pub fn push(
+    message: Option<&str>,
+    user: &User,
+) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    let repo = Repository::open(".")?;
+    let Some((
+        head,
+        Object::Commit {
+            tree: head_tree,
+            message: head_message,
+            ..
+        },
+    )) = repo.head_commit()?
+    else {
+        return Err(Box::new(NoInitialCommit));
+    };
+    let tree = object::create_tree(".", &Ignore::new())?;
+    if tree == head_tree {
+        return Ok(None);
+    }
+
+    let head_ref = refs::read_ref("HEAD")?.unwrap_or_default();
+    let branch = head_ref
+        .strip_prefix("ref: refs/heads/")
+        .unwrap_or("(no branch)");
+    let message = match message {
+        Some(message) => format!("On {}: {}", branch, message),
+        None => format!(
+            "WIP on {}: {} {}",
+            branch,
+            &head[..7],
+            head_message.lines().next().unwrap_or("")
+        ),
+    };
+    let hash = repo.commit_tree(&tree, vec![head], user, message.clone())?;
+    let old = refs::read_ref(STASH_REF)?;
+    refs::update_ref(STASH_REF, &hash)?;
+    reflog::append(STASH_REF, old.as_deref(), &hash, user, &message)?;
+
+    // Files only the stash has would otherwise be left behind.
+    let head_files = object::flatten_tree(&head_tree)?;
+    for path in object::flatten_tree(&tree)?.keys() {
+        if !head_files.contains_key(path) {
+            fs::remove_file(path)?;
+            let mut dir = Path::new(path).parent();
+            while let Some(parent) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+                if fs::remove_dir(parent).is_err() {
+                    break;
+                }
+                dir = parent.parent();
+            }
+        }
+    }
+    checkout::checkout_tree(&head_tree)?;
+    Index::reset_to(&head_tree)?;
+    Ok(Some(hash))
+}
+
+// The stashes, newest first, so that the n-th is `stash@{n}`.
+pub fn list() -> Result<Vec<reflog::Entry>, Box<dyn std::error::Error + 'static>> {
+    let mut entries = reflog::read(STASH_REF)?;
+    entries.reverse();
+    Ok(entries)
+}
+
+pub fn get(n: usize) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    list()?
+        .into_iter()
+        .nth(n)
+        .map(|entry| entry.new)
+        .ok_or_else(|| Box::new(NoStashEntry(n)) as Box<dyn std::error::Error + 'static>)
+}
+
+// The tree a stash was made on top of and the tree it recorded.
+pub fn trees(hash: &str) -> Result<(String, String), Box<dyn std::error::Error + 'static>> {
+    let Object::Commit { tree, parents, .. } = Object::from_hash(hash)? else {
+        return Err(Box::new(object::InvalidObjectFormat));
+    };
+    let base = parents.first().ok_or(object::InvalidObjectFormat)?;
+    let Object::Commit {
+        tree: base_tree, ..
+    } = Object::from_hash(base)?
+    else {
+        return Err(Box::new(object::InvalidObjectFormat));
+    };
+    Ok((base_tree, tree))
+}
+
+// Removes `stash@{n}` by rewriting the stash reflog, moving refs/stash to
+// the newest stash left or deleting it once the last one is gone. Returns
+// the dropped stash's hash.
+pub fn drop(n: usize) -> Result<String, Box<dyn std::error::Error + 'static>> {
+    let mut entries = reflog::read(STASH_REF)?;
+    if n >= entries.len() {
+        return Err(Box::new(NoStashEntry(n)));
+    }
+    let dropped = entries.remove(entries.len() - 1 - n);
+    match entries.last() {
+        Some(newest) => {
+            refs::update_ref(STASH_REF, &newest.new)?;
+            reflog::write(STASH_REF, &entries)?;
+        }
+        None => {
+            refs::delete_ref(STASH_REF)?;
+            reflog::delete(STASH_REF)?;
+        }
+    }
+    Ok(dropped.new)
+}
+
+// Parses `<n>` or `stash@{<n>}`, defaulting to the newest stash.
+pub fn parse_index(arg: Option<&str>) -> Option<usize> {
+    let Some(arg) = arg else {
+        return Some(0);
+    };
+    arg.strip_prefix("stash@{")
+        .and_then(|rest| rest.strip_suffix('}'))
+        .unwrap_or(arg)
+        .parse()
+        .ok()
+}
@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Blob hashes of working-tree files as of the last tree built from them,
+// one `<mtime> <mtime-nsec> <size> <ino> <hash>\t<path>` line per file.
+const CACHE_FILE: &str = "mygit-stat-cache";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stat {
+    mtime: (i64, i64),
+    size: u64,
+    ino: u64,
+}
+
+impl Stat {
+    fn of(metadata: &fs::Metadata) -> Stat {
+        Stat {
+            mtime: (metadata.mtime(), metadata.mtime_nsec()),
+            size: metadata.size(),
+            ino: metadata.ino(),
+        }
+    }
+}
+
+// A file whose stat data still matches what was cached is taken to hold
+// the same content, so its blob need not be read and hashed again.
+pub struct StatCache {
+    entries: BTreeMap<String, (Stat, String)>,
+    // Only the paths looked up or added are saved, so files that are gone
+    // drop out of the cache.
+    used: HashSet<String>,
+    // Files modified in this second may change again without their mtime
+    // moving, so they are not cached until a later run.
+    started: i64,
+}
+
+impl StatCache {
+    pub fn new() -> StatCache {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs() as i64);
+        StatCache {
+            entries: BTreeMap::new(),
+            used: HashSet::new(),
+            started,
+        }
+    }
+
+    // Reads the cache. It only saves work, so a missing or unreadable file
+    // and malformed lines are treated as nothing cached.
+    pub fn load(gitdir: &Path) -> StatCache {
+        let mut cache = StatCache::new();
+        let path = gitdir.join(CACHE_FILE);
+        let content = fs::read_to_string(path).unwrap_or_default();
+        for line in content.lines() {
+            let Some((fields, path)) = line.split_once('\t') else {
+                continue;
+            };
+            let fields: Vec<&str> = fields.split(' ').collect();
+            let &[secs, nsecs, size, ino, hash] = fields.as_slice() else {
+                continue;
+            };
+            if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                continue;
+            }
+            let (Ok(secs), Ok(nsecs), Ok(size), Ok(ino)) =
+                (secs.parse(), nsecs.parse(), size.parse(), ino.parse())
+            else {
+                continue;
+            };
+            let stat = Stat {
+                mtime: (secs, nsecs),
+                size,
+                ino,
+            };
+            cache
+                .entries
+                .insert(path.to_string(), (stat, hash.to_string()));
+        }
+        cache
+    }
+
+    // The cached hash of `path`, if the file is unchanged since it was
+    // cached.
+    pub fn get(&mut self, path: &str, metadata: &fs::Metadata) -> Option<String> {
+        let (stat, hash) = self.entries.get(path)?;
+        if *stat != Stat::of(metadata) {
+            return None;
+        }
+        self.used.insert(path.to_string());
+        Some(hash.clone())
+    }
+
+    pub fn insert(&mut self, path: &str, metadata: &fs::Metadata, hash: &str) {
+        if metadata.mtime() >= self.started {
+            return;
+        }
+        self.entries
+            .insert(path.to_string(), (Stat::of(metadata), hash.to_string()));
+        self.used.insert(path.to_string());
+    }
+
+    // Writes the cache through a lock file, like the refs.
+    pub fn save(&self, gitdir: &Path) -> Result<(), std::io::Error> {
+        let mut content = String::new();
+        for (path, (stat, hash)) in &self.entries {
+            if self.used.contains(path) {
+                content.push_str(&format!(
+                    "{} {} {} {} {}\t{}\n",
+                    stat.mtime.0, stat.mtime.1, stat.size, stat.ino, hash, path
+                ));
+            }
+        }
+        let path = gitdir.join(CACHE_FILE);
+        let lock = path.with_extension("lock");
+        let written = fs::write(&lock, content).and_then(|_| fs::rename(&lock, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&lock);
+        }
+        written
+    }
+}
+
+impl Default for StatCache {
+    fn default() -> StatCache {
+        StatCache::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn changed_files_miss_and_untouched_files_hit() {
+        let dir = std::env::temp_dir().join(format!("mygit-stat-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        for path in [&a, &b] {
+            fs::write(path, "old\n").unwrap();
+            let file = fs::File::options().write(true).open(path).unwrap();
+            file.set_modified(an_hour_ago).unwrap();
+        }
+
+        let mut cache = StatCache::new();
+        cache.insert("a", &fs::metadata(&a).unwrap(), "hash of a");
+        cache.insert("b", &fs::metadata(&b).unwrap(), "hash of b");
+        fs::write(&b, "new\n").unwrap();
+
+        assert_eq!(
+            cache.get("a", &fs::metadata(&a).unwrap()).as_deref(),
+            Some("hash of a")
+        );
+        assert_eq!(cache.get("b", &fs::metadata(&b).unwrap()), None);
+
+        // A file changed just now is not cached yet.
+        cache.insert("b", &fs::metadata(&b).unwrap(), "hash of new b");
+        assert_eq!(cache.get("b", &fs::metadata(&b).unwrap()), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
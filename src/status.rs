@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+
+use crate::ignore::Ignore;
+use crate::index::Index;
+use crate::object::{self, Object};
+use crate::repository::Repository;
+
+#[derive(Debug, Default)]
+pub struct Status {
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+    pub untracked: Vec<String>,
+}
+
+impl Status {
+    pub fn is_clean(&self) -> bool {
+        self.modified.is_empty() && self.deleted.is_empty() && self.untracked.is_empty()
+    }
+}
+
+// Compares the working tree against HEAD's tree. A file counts as modified
+// when either its content or its mode differs, so flipping only the
+// executable bit is reported too.
+pub fn status(ignore: &Ignore) -> Result<Status, Box<dyn std::error::Error + 'static>> {
+    let head = match Repository::open(".")?.head_commit()? {
+        Some((_, Object::Commit { tree, .. })) => object::flatten_tree(&tree)?,
+        _ => Default::default(),
+    };
+    let worktree = object::scan_worktree(".", ignore)?;
+
+    let mut status = Status::default();
+    for (path, (mode, hash)) in &worktree {
+        match head.get(path) {
+            Some((head_mode, head_hash)) => {
+                if head_mode != mode || head_hash != hash {
+                    status.modified.push(path.clone());
+                }
+            }
+            None => status.untracked.push(path.clone()),
+        }
+    }
+    status.deleted = head
+        .keys()
+        .filter(|path| !worktree.contains_key(*path))
+        .cloned()
+        .collect();
+    Ok(status)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    // The label `status` shows before the path.
+    pub fn label(self) -> &'static str {
+        match self {
+            Change::Added => "new file:   ",
+            Change::Modified => "modified:   ",
+            Change::Deleted => "deleted:    ",
+        }
+    }
+}
+
+// What `status` reports once there is an index in between HEAD and the
+// working tree. Paths are sorted within each list.
+#[derive(Debug, Default)]
+pub struct Changes {
+    // The index against HEAD's tree: what a commit of the index would record.
+    pub staged: Vec<(Change, String)>,
+    // The working tree against the index.
+    pub unstaged: Vec<(Change, String)>,
+    // Working tree files the index does not have.
+    pub untracked: Vec<String>,
+}
+
+impl Changes {
+    pub fn is_clean(&self) -> bool {
+        self.staged.is_empty() && self.unstaged.is_empty() && self.untracked.is_empty()
+    }
+}
+
+// Compares HEAD's tree, the index and the working tree. Entries still in
+// conflict count as tracked but are not compared.
+pub fn changes(ignore: &Ignore) -> Result<Changes, Box<dyn std::error::Error + 'static>> {
+    let head = match Repository::open(".")?.head_commit()? {
+        Some((_, Object::Commit { tree, .. })) => object::flatten_tree(&tree)?,
+        _ => Default::default(),
+    };
+    let index = Index::load()?;
+    let worktree = object::scan_worktree(".", ignore)?;
+
+    let mut changes = Changes::default();
+    let mut tracked = HashSet::new();
+    for entry in index.entries() {
+        tracked.insert(entry.path.as_str());
+        if entry.stage() != 0 {
+            continue;
+        }
+        let mode = entry.mode();
+        match head.get(&entry.path) {
+            None => changes.staged.push((Change::Added, entry.path.clone())),
+            Some((head_mode, head_hash)) if *head_mode != mode || *head_hash != entry.hash => {
+                changes.staged.push((Change::Modified, entry.path.clone()))
+            }
+            Some(_) => {}
+        }
+        match worktree.get(&entry.path) {
+            None => changes.unstaged.push((Change::Deleted, entry.path.clone())),
+            Some((work_mode, work_hash)) if *work_mode != mode || *work_hash != entry.hash => {
+                changes
+                    .unstaged
+                    .push((Change::Modified, entry.path.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for path in head.keys() {
+        if !tracked.contains(path.as_str()) {
+            changes.staged.push((Change::Deleted, path.clone()));
+        }
+    }
+    changes.staged.sort_by(|(_, a), (_, b)| a.cmp(b));
+    changes.untracked = worktree
+        .keys()
+        .filter(|path| !tracked.contains(path.as_str()))
+        .cloned()
+        .collect();
+    Ok(changes)
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use crate::object::{InvalidObjectFormat, Object};
+use crate::revwalk::{self, Order};
+
+// The tree at `prefix` inside `tree`, or None when there is no directory
+// there.
+fn subtree_of(
+    tree: &str,
+    prefix: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    let mut hash = tree.to_string();
+    for component in prefix.split('/').filter(|c| !c.is_empty()) {
+        let Object::Tree(entries) = Object::from_hash(&hash)? else {
+            return Err(Box::new(InvalidObjectFormat));
+        };
+        match entries
+            .into_iter()
+            .find(|entry| entry.filename() == component && entry.is_tree())
+        {
+            Some(entry) => hash = entry.hash().to_string(),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(hash))
+}
+
+// Rewrites the history reachable from `tip` into one whose commits hold
+// only what was under `prefix`, with that directory as their root. Commits
+// that left the directory alone are dropped, and the rewritten commits keep
+// their authors, dates and messages, so splitting the same history twice
+// gives the same commits. Returns the rewritten tip, or None when `prefix`
+// never existed.
+pub fn split(
+    tip: &str,
+    prefix: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    // Each original commit's counterpart in the split history, with the
+    // counterpart's tree.
+    let mut split: HashMap<String, Option<(String, String)>> = HashMap::new();
+    let commits = revwalk::walk(&[tip.to_string()], Order::Topo)?;
+    for (hash, commit) in commits.into_iter().rev() {
+        let Object::Commit {
+            tree,
+            parents,
+            author,
+            author_timestamp,
+            committer,
+            committer_timestamp,
+            message,
+        } = commit
+        else {
+            return Err(Box::new(InvalidObjectFormat));
+        };
+        let mut new_parents: Vec<(String, String)> = Vec::new();
+        for parent in &parents {
+            if let Some(Some(new_parent)) = split.get(parent) {
+                if !new_parents.contains(new_parent) {
+                    new_parents.push(new_parent.clone());
+                }
+            }
+        }
+
+        let rewritten = match subtree_of(&tree, prefix)? {
+            // Before the directory appeared, or after it was removed, the
+            // history carries on from the parents'.
+            None => new_parents.into_iter().next(),
+            Some(subtree) => match new_parents.as_slice() {
+                [(parent, parent_tree)] if *parent_tree == subtree => {
+                    Some((parent.clone(), subtree))
+                }
+                _ => {
+                    let new_hash = Object::Commit {
+                        tree: subtree.clone(),
+                        parents: new_parents.into_iter().map(|(hash, _)| hash).collect(),
+                        author,
+                        author_timestamp,
+                        committer,
+                        committer_timestamp,
+                        message,
+                    }
+                    .write()?;
+                    Some((new_hash, subtree))
+                }
+            },
+        };
+        split.insert(hash, rewritten);
+    }
+    Ok(split.remove(tip).flatten().map(|(hash, _)| hash))
+}
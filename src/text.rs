@@ -0,0 +1,43 @@
+// How much of a file git looks at when deciding whether it is binary.
+pub const FIRST_FEW_BYTES: usize = 8000;
+
+// Treats data as binary when a NUL byte appears near the start, the same
+// heuristic git uses for diffs and line-ending conversion.
+pub fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(FIRST_FEW_BYTES).any(|&b| b == 0)
+}
+
+pub fn has_crlf(data: &[u8]) -> bool {
+    data.windows(2).any(|pair| pair == b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_nul_near_the_start_means_binary() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary(b"plain text\nover two lines\n"));
+        assert!(!is_binary("naïve café, 日本語\n".as_bytes()));
+        assert!(!is_binary(b"\xff\xfe not UTF-8 at all\r\n"));
+        assert!(is_binary(b"\0"));
+        assert!(is_binary(b"text with an\0embedded NUL\n"));
+
+        let mut late = vec![b'x'; FIRST_FEW_BYTES - 1];
+        late.push(0);
+        assert!(is_binary(&late));
+        late.insert(0, b'x');
+        assert!(!is_binary(&late));
+    }
+
+    #[test]
+    fn crlf_needs_both_bytes_in_order() {
+        assert!(has_crlf(b"one\r\ntwo"));
+        assert!(has_crlf(b"\r\n"));
+        assert!(!has_crlf(b"one\ntwo\n"));
+        assert!(!has_crlf(b"one\rtwo\r"));
+        assert!(!has_crlf(b"one\n\rtwo"));
+        assert!(!has_crlf(b""));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::object::User;
+
+fn split_trailer(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim_end();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+// Trailers live in the final paragraph of a message, which must consist
+// only of `Key: value` lines (values may continue on indented lines). The
+// first paragraph is the subject and is never treated as a trailer block.
+fn block_start(lines: &[&str]) -> Option<usize> {
+    let start = lines.iter().rposition(|line| line.trim().is_empty())? + 1;
+    let block = &lines[start..];
+    let valid = block.iter().enumerate().all(|(i, line)| {
+        if line.starts_with([' ', '\t']) {
+            i > 0
+        } else {
+            split_trailer(line).is_some()
+        }
+    });
+    (valid && !block.is_empty()).then_some(start)
+}
+
+fn group(block: &[&str]) -> Vec<(String, String, Vec<String>)> {
+    let mut trailers: Vec<(String, String, Vec<String>)> = Vec::new();
+    for line in block {
+        match split_trailer(line) {
+            Some((key, value)) if !line.starts_with([' ', '\t']) => {
+                trailers.push((key.to_string(), value.to_string(), vec![line.to_string()]));
+            }
+            _ => {
+                if let Some((_, value, lines)) = trailers.last_mut() {
+                    if !value.is_empty() {
+                        value.push(' ');
+                    }
+                    value.push_str(line.trim());
+                    lines.push(line.to_string());
+                }
+            }
+        }
+    }
+    trailers
+}
+
+pub fn parse(message: &str) -> Vec<(String, String)> {
+    let lines: Vec<&str> = message.trim_end().lines().collect();
+    match block_start(&lines) {
+        Some(start) => group(&lines[start..])
+            .into_iter()
+            .map(|(key, value, _)| (key, value))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+pub fn append(message: &str, key: &str, value: &str) -> String {
+    let body = message.trim_end();
+    let separator = if body.is_empty() {
+        ""
+    } else if parse(body).is_empty() {
+        "\n\n"
+    } else {
+        "\n"
+    };
+    format!("{}{}{}: {}", body, separator, key, value)
+}
+
+// Appends the trailer unless the block already carries the same key and value.
+pub fn add(message: &str, key: &str, value: &str) -> String {
+    if parse(message)
+        .iter()
+        .any(|(k, v)| k.eq_ignore_ascii_case(key) && v == value)
+    {
+        return message.to_string();
+    }
+    append(message, key, value)
+}
+
+pub fn signoff(message: &str, user: &User) -> String {
+    add(message, "Signed-off-by", &user.to_string())
+}
+
+pub fn trim_empty(message: &str) -> String {
+    let lines: Vec<&str> = message.trim_end().lines().collect();
+    let Some(start) = block_start(&lines) else {
+        return message.to_string();
+    };
+
+    let kept: Vec<String> = group(&lines[start..])
+        .into_iter()
+        .filter(|(_, value, _)| !value.is_empty())
+        .flat_map(|(_, _, lines)| lines)
+        .collect();
+    let body = lines[..start].join("\n");
+    if kept.is_empty() {
+        body.trim_end().to_string()
+    } else {
+        format!("{}\n{}", body, kept.join("\n"))
+    }
+}
+
+// Parses a `--trailer` argument given as `key: value` or `key=value`.
+pub fn parse_arg(arg: &str) -> Option<(String, String)> {
+    let at = arg.find([':', '='])?;
+    let key = arg[..at].trim();
+    if key.is_empty() {
+        return None;
+    }
+    Some((key.to_string(), arg[at + 1..].trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailers_come_from_the_final_paragraph() {
+        let message = "Subject\n\nBody text.\n\nSigned-off-by: A <a@example.com>\nCo-authored-by: B\n  <b@example.com>\n";
+        assert_eq!(
+            parse(message),
+            vec![
+                ("Signed-off-by".to_string(), "A <a@example.com>".to_string()),
+                (
+                    "Co-authored-by".to_string(),
+                    "B <b@example.com>".to_string()
+                ),
+            ]
+        );
+
+        for message in [
+            "",
+            "Subject: with a colon\n",
+            "Subject\n\nJust a body.\n",
+            "Subject\n\nFixes: 1\nand some prose\n",
+            "Subject\n\nNot a key: value\n",
+        ] {
+            assert_eq!(parse(message), Vec::new(), "{:?}", message);
+        }
+    }
+
+    #[test]
+    fn appended_trailers_join_an_existing_block() {
+        assert_eq!(append("Subject\n", "Fixes", "1"), "Subject\n\nFixes: 1");
+        assert_eq!(
+            append("Subject\n\nFixes: 1\n", "Fixes", "2"),
+            "Subject\n\nFixes: 1\nFixes: 2"
+        );
+        assert_eq!(append("", "Fixes", "1"), "Fixes: 1");
+        assert_eq!(
+            add("Subject\n\nfixes: 1\n", "Fixes", "1"),
+            "Subject\n\nfixes: 1\n"
+        );
+        assert_eq!(
+            parse_arg("Fixes = 1"),
+            Some(("Fixes".to_string(), "1".to_string()))
+        );
+        assert_eq!(parse_arg(": 1"), None);
+    }
+}
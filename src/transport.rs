@@ -0,0 +1,225 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::object::{self, FileMode, Object};
+use crate::pack::{self, PackIndex};
+use crate::refs;
+use crate::repository::NotARepository;
+
+#[derive(Debug, Clone)]
+pub struct MissingObject(pub String);
+
+impl fmt::Display for MissingObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "object {} is missing from the source repository", self.0)
+    }
+}
+
+impl std::error::Error for MissingObject {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// Finds the git directory of a local repository given either its working
+// tree or, for a bare repository, the git directory itself.
+pub fn gitdir(path: &Path) -> Result<PathBuf, NotARepository> {
+    let dotgit = path.join(".git");
+    if dotgit.join("objects").is_dir() {
+        return Ok(dotgit);
+    }
+    if path.join("objects").is_dir() && path.join("HEAD").is_file() {
+        return Ok(path.to_path_buf());
+    }
+    Err(NotARepository)
+}
+
+// Reads a ref of another repository, following symbolic refs and falling
+// back to its packed-refs file.
+pub fn read_ref(
+    gitdir: &Path,
+    name: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    let mut name = name.to_string();
+    for _ in 0..=refs::MAX_SYMREF_DEPTH {
+        if !refs::is_valid_name(&name) {
+            return Ok(None);
+        }
+        let path = gitdir.join(&name);
+        let content = if path.is_file() {
+            fs::read_to_string(path)?.trim().to_string()
+        } else {
+            match refs::packed_refs_in(gitdir)?
+                .into_iter()
+                .find(|(n, _)| *n == name)
+            {
+                Some((_, hash)) => hash,
+                None => return Ok(None),
+            }
+        };
+        match refs::parse_target(&name, &content)? {
+            refs::Target::Symbolic(target) => name = target,
+            refs::Target::Hash(hash) => return Ok(Some(hash)),
+        }
+    }
+    Ok(None)
+}
+
+// The branch another repository's HEAD points at, or None when its HEAD is
+// detached.
+pub fn head_branch(gitdir: &Path) -> Result<Option<String>, Box<dyn std::error::Error + 'static>> {
+    let head = fs::read_to_string(gitdir.join("HEAD"))?;
+    Ok(head
+        .trim()
+        .strip_prefix("ref: ")
+        .map(|target| target.to_string()))
+}
+
+// Lists another repository's branches and tags with the hashes they point
+// at, sorted by name.
+pub fn list_refs(
+    gitdir: &Path,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + 'static>> {
+    fn visit(
+        dir: &Path,
+        name: &str,
+        refs: &mut Vec<String>,
+    ) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let child = format!("{}/{}", name, entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                visit(&entry.path(), &child, refs)?;
+            } else {
+                refs.push(child);
+            }
+        }
+        Ok(())
+    }
+
+    let mut names = Vec::new();
+    for prefix in ["refs/heads", "refs/tags"] {
+        visit(&gitdir.join(prefix), prefix, &mut names)?;
+    }
+    for (name, _) in refs::packed_refs_in(gitdir)? {
+        if name.starts_with("refs/heads/") || name.starts_with("refs/tags/") {
+            names.push(name);
+        }
+    }
+    names.sort();
+    names.dedup();
+
+    let mut refs = Vec::new();
+    for name in names {
+        if let Some(hash) = read_ref(gitdir, &name)? {
+            refs.push((name, hash));
+        }
+    }
+    Ok(refs)
+}
+
+fn loose_path(objects: &Path, hash: &str) -> PathBuf {
+    objects.join(&hash[..2]).join(&hash[2..])
+}
+
+// The hashes an object points at: a commit's tree and parents, a tree's
+// entries other than submodules, and a tag's target.
+fn links(object: &Object) -> Vec<String> {
+    match object {
+        Object::Commit { tree, parents, .. } => {
+            let mut links = vec![tree.clone()];
+            links.extend(parents.iter().cloned());
+            links
+        }
+        Object::Tree(entries) => entries
+            .iter()
+            .filter(|entry| entry.mode() != FileMode::Gitlink)
+            .map(|entry| entry.hash().to_string())
+            .collect(),
+        Object::Tag { object, .. } => vec![object.clone()],
+        Object::Blob(_) => Vec::new(),
+    }
+}
+
+// Object files are never modified once written, so a hardlink is as good as
+// a copy. Linking fails across filesystems, in which case the bytes are
+// copied instead.
+fn link_or_copy(src: &Path, dst: &Path, hardlink: bool) -> std::io::Result<()> {
+    if hardlink && fs::hard_link(src, dst).is_ok() {
+        return Ok(());
+    }
+    fs::copy(src, dst).map(|_| ())
+}
+
+// Copies every object reachable from `tips` that `dst` lacks from the git
+// directory `src`, hardlinking files when `hardlink` is set. Packs `dst`
+// does not have are copied whole first, and anything `dst` already held is
+// assumed to come with its history, so the walk stops there. The copied
+// packs are walked through instead, since a pack need not hold everything
+// its objects point at. Returns the number of loose objects copied.
+pub fn copy_objects(
+    src: &Path,
+    dst: &Path,
+    tips: &[String],
+    hardlink: bool,
+) -> Result<usize, Box<dyn std::error::Error + 'static>> {
+    let src_objects = src.join("objects");
+    let dst_objects = dst.join("objects");
+    let held: Vec<PackIndex> = pack::indexes_in(&dst_objects)?;
+
+    let mut copied_packs = Vec::new();
+    for index in pack::indexes_in(&src_objects)? {
+        let idx_name = index
+            .path()
+            .file_name()
+            .ok_or(object::InvalidObjectFormat)?;
+        let pack_path = index.pack_path();
+        let pack_name = pack_path.file_name().ok_or(object::InvalidObjectFormat)?;
+        let dst_pack = dst_objects.join("pack");
+        if dst_pack.join(idx_name).exists() {
+            continue;
+        }
+        fs::create_dir_all(&dst_pack)?;
+        // The index goes last so a reader never sees it without its pack.
+        link_or_copy(&pack_path, &dst_pack.join(pack_name), hardlink)?;
+        link_or_copy(index.path(), &dst_pack.join(idx_name), hardlink)?;
+        copied_packs.push(index);
+    }
+
+    let mut seen = HashSet::new();
+    let mut stack: Vec<String> = tips.to_vec();
+    let mut copied = 0;
+    while let Some(hash) = stack.pop() {
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        let dst_path = loose_path(&dst_objects, &hash);
+        if dst_path.exists() || held.iter().any(|index| index.contains(&hash)) {
+            continue;
+        }
+        if copied_packs.iter().any(|index| index.contains(&hash)) {
+            let (object_type, content) = object::read_raw_in(&src_objects, &hash)?;
+            stack.extend(links(&Object::parse(&object_type, &content)?));
+            continue;
+        }
+
+        let src_path = loose_path(&src_objects, &hash);
+        if !src_path.is_file() {
+            return Err(Box::new(MissingObject(hash)));
+        }
+        let (object_type, content) = object::read_loose(&src_objects, &hash)?;
+        stack.extend(links(&Object::parse(&object_type, &content)?));
+
+        if let Some(parent) = dst_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        link_or_copy(&src_path, &dst_path, hardlink)?;
+        copied += 1;
+    }
+    Ok(copied)
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::repository;
+use mygit::error::MygitError;
+use mygit::object::{self, Object};
+use mygit::pack;
+use mygit::repository::Repository;
+use std::collections::HashMap;
+use std::fs;
+
+#[test]
+fn reads_complete_unique_prefixes() {
+    let dir = repository("abbreviated-hash");
+    let repo = Repository::open(&dir).unwrap();
+    let objects = repo.objects_dir();
+
+    // Hash blobs until two share their first four characters, which also
+    // puts them in the same objects/<xx>/ directory.
+    let mut seen: HashMap<String, Object> = HashMap::new();
+    let (first, second) = (0..)
+        .find_map(|i| {
+            let blob = Object::Blob(format!("{}\n", i).into_bytes());
+            let hash = blob.hash().unwrap();
+            match seen.remove(&hash[..4]) {
+                Some(other) => Some((other, blob)),
+                None => {
+                    seen.insert(hash[..4].to_string(), blob);
+                    None
+                }
+            }
+        })
+        .unwrap();
+    let first_hash = repo.write_object(&first).unwrap();
+    let second_hash = repo.write_object(&second).unwrap();
+
+    let shared = first_hash
+        .chars()
+        .zip(second_hash.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    match repo.read_object(&first_hash[..shared]) {
+        Err(MygitError::AmbiguousObject(prefix)) => assert_eq!(prefix, &first_hash[..shared]),
+        other => panic!("expected AmbiguousObject, got {:?}", other.err()),
+    }
+    for hash in [&first_hash, &second_hash] {
+        let Object::Blob(data) = repo.read_object(&hash[..shared + 1]).unwrap() else {
+            panic!("not a blob");
+        };
+        assert_eq!(Object::Blob(data).hash().unwrap(), *hash);
+        assert_eq!(object::resolve_object_in(&objects, hash).unwrap(), *hash);
+    }
+
+    assert!(matches!(
+        repo.read_object(&first_hash[..2]),
+        Err(MygitError::ObjectNotFound(_))
+    ));
+    // Extends the shared prefix with a character neither object has next.
+    let next = |hash: &str| hash.chars().nth(shared).unwrap();
+    let other = "0123456789abcdef"
+        .chars()
+        .find(|&c| c != next(&first_hash) && c != next(&second_hash))
+        .unwrap();
+    let missing = format!("{}{}", &first_hash[..shared], other);
+    assert!(matches!(
+        object::resolve_object_in(&objects, &missing),
+        Err(MygitError::ObjectNotFound(_))
+    ));
+
+    // Packed objects count as neighbours too.
+    assert_eq!(
+        object::abbreviate_in(&objects, &first_hash, 4)
+            .unwrap()
+            .len(),
+        shared + 1
+    );
+    fs::create_dir_all(objects.join("pack")).unwrap();
+    let base = objects.join("pack/pack");
+    pack::write_pack_in(
+        &objects,
+        std::slice::from_ref(&second_hash),
+        base.to_str().unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        object::prune_packed_in(&objects, false).unwrap(),
+        vec![second_hash.clone()]
+    );
+    assert_eq!(
+        object::abbreviate_in(&objects, &first_hash, 4)
+            .unwrap()
+            .len(),
+        shared + 1
+    );
+    assert_eq!(
+        object::abbreviate_in(&objects, &second_hash, 4)
+            .unwrap()
+            .len(),
+        shared + 1
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
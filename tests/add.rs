@@ -0,0 +1,136 @@
+mod common;
+
+use common::{command, mygit, mygit_with_input, repository, run};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn staged_paths(dir: &Path) -> String {
+    let tree = mygit(dir, &["write-tree"]);
+    mygit(dir, &["ls-tree", "-r", "--name-only", tree.trim()])
+}
+
+#[test]
+fn only_the_pathspec_is_staged() {
+    let dir = repository("add-pathspec");
+    fs::create_dir_all(dir.join("sub/deeper")).unwrap();
+    fs::write(dir.join("sub/b"), "b\n").unwrap();
+    fs::write(dir.join("sub/b.log"), "log\n").unwrap();
+    fs::write(dir.join("sub/deeper/c"), "c\n").unwrap();
+    fs::write(dir.join("sub/.gitignore"), "*.log\n").unwrap();
+    fs::write(dir.join("a"), "changed\n").unwrap();
+    mygit(&dir, &["add", "sub/deeper/c"]);
+    mygit(&dir, &["add", "./sub/"]);
+
+    assert_eq!(
+        staged_paths(&dir),
+        ".gitignore\na\nsub/.gitignore\nsub/b\nsub/deeper/c\n"
+    );
+    let head_a = mygit(&dir, &["rev-parse", "HEAD:a"]);
+    assert_eq!(mygit(&dir, &["rev-parse", ":a"]), head_a);
+
+    let output = run(&dir, &["add", "sub/b.log"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("ignored"));
+    assert!(!run(&dir, &["add", "missing"]).status.success());
+
+    // Deleted files under the pathspec are staged as removed.
+    fs::remove_file(dir.join("sub/b")).unwrap();
+    mygit(&dir, &["add", "sub"]);
+    assert!(!staged_paths(&dir).contains("sub/b\n"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Opening a FIFO blocks until something writes to it, so `add` would hang
+// if it read any file besides the ones it was asked to stage.
+#[test]
+fn files_outside_the_pathspec_are_not_read() {
+    let dir = repository("add-unread");
+    let status = Command::new("mkfifo")
+        .arg(dir.join("fifo"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    fs::write(dir.join("a"), "changed\n").unwrap();
+
+    let mut child = command(&dir, &["add", "a"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break Some(status);
+        }
+        if start.elapsed() > Duration::from_secs(10) {
+            child.kill().unwrap();
+            child.wait().unwrap();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    assert!(status.is_some_and(|status| status.success()), "add hung");
+    assert_eq!(mygit(&dir, &["cat-file", "-p", ":a"]), "changed\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn hunks_are_staged_one_by_one() {
+    let dir = repository("add-patch");
+    let lines: Vec<String> = (1..=20).map(|n| format!("{}\n", n)).collect();
+    fs::write(dir.join("a"), lines.concat()).unwrap();
+    fs::write(dir.join("b"), "b\n").unwrap();
+    mygit(&dir, &["add", "a", "b"]);
+    mygit(&dir, &["commit", "-m", "numbers"]);
+    // Far enough apart to land in separate hunks.
+    let mut edited = lines.clone();
+    edited[0] = "one\n".to_string();
+    edited[19] = "twenty\n".to_string();
+    fs::write(dir.join("a"), edited.concat()).unwrap();
+    fs::write(dir.join("b"), "B\n").unwrap();
+
+    let output = mygit_with_input(&dir, &["add", "-p", "a"], "y\n?\nn\n");
+    assert!(output.starts_with("diff --git a/a b/a\n"));
+    assert!(output.contains("@@ -1,4 +1,4 @@\n-1\n+one\n"));
+    assert!(output.contains("(1/2) Stage this hunk [y,n,q,?]? "));
+    assert!(output.contains("y - stage this hunk\n"));
+    assert!(output.contains("(2/2) Stage this hunk [y,n,q,?]? "));
+    let mut staged = lines.clone();
+    staged[0] = "one\n".to_string();
+    assert_eq!(mygit(&dir, &["cat-file", "-p", ":a"]), staged.concat());
+    // Only the pathspec was offered.
+    assert_eq!(mygit(&dir, &["cat-file", "-p", ":b"]), "b\n");
+
+    // Quitting keeps what was chosen so far and skips the other files.
+    mygit(&dir, &["add", "-p"]);
+    mygit_with_input(&dir, &["add", "-p"], "y\nq\n");
+    assert_eq!(mygit(&dir, &["cat-file", "-p", ":a"]), edited.concat());
+    assert_eq!(mygit(&dir, &["cat-file", "-p", ":b"]), "b\n");
+    mygit_with_input(&dir, &["add", "-p", "b"], "y\n");
+    assert_eq!(mygit(&dir, &["cat-file", "-p", ":b"]), "B\n");
+    assert_eq!(mygit(&dir, &["add", "-p"]), "No changes.\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn files_and_directories_replace_each_other() {
+    let dir = repository("add-file-directory");
+    fs::remove_file(dir.join("a")).unwrap();
+    fs::create_dir(dir.join("a")).unwrap();
+    fs::write(dir.join("a/b"), "b\n").unwrap();
+    mygit(&dir, &["add", "a/b"]);
+    assert_eq!(staged_paths(&dir), ".gitignore\na/b\n");
+    mygit(&dir, &["commit", "-m", "directory"]);
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "HEAD:a/b"]), "b\n");
+
+    fs::remove_dir_all(dir.join("a")).unwrap();
+    fs::write(dir.join("a"), "a\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    assert_eq!(staged_paths(&dir), ".gitignore\na\n");
+    mygit(&dir, &["commit", "-m", "file"]);
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "HEAD:a"]), "a\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
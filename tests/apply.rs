@@ -0,0 +1,148 @@
+mod common;
+
+use common::{commit, mygit, repository, run};
+use std::fs;
+
+// Commits `files` on top of HEAD, each written whole, or deleted for None.
+
+#[test]
+fn binary_changes_round_trip_through_diff_and_apply() {
+    let dir = repository("apply-binary");
+    let old: Vec<u8> = (0..=255).cycle().take(3000).collect();
+    let mut new = old.clone();
+    new[1000..1100].fill(0);
+    new.extend(b"\0tail");
+    let added = b"\0\x01\x02 added\n".to_vec();
+    commit(
+        &dir,
+        &[
+            ("image", Some(&old)),
+            ("gone", Some(b"\0gone")),
+            ("text", Some(b"one\ntwo\nthree\n")),
+        ],
+        "second",
+    );
+    commit(
+        &dir,
+        &[
+            ("image", Some(&new)),
+            ("gone", None),
+            ("added", Some(&added)),
+            ("text", Some(b"one\n2\nthree")),
+        ],
+        "third",
+    );
+
+    let patch = mygit(&dir, &["diff", "--binary", "HEAD~1", "HEAD"]);
+    assert!(patch.contains("GIT binary patch\nliteral 3005\n"));
+    let image = mygit(&dir, &["rev-parse", "HEAD~1:image"]);
+    assert!(patch.contains(&format!("index {}..", image.trim())));
+    fs::write(dir.join("patch"), &patch).unwrap();
+
+    // Put the working tree back the way HEAD~1 had it, then patch it up.
+    fs::write(dir.join("image"), &old).unwrap();
+    fs::write(dir.join("gone"), b"\0gone").unwrap();
+    fs::write(dir.join("text"), b"one\ntwo\nthree\n").unwrap();
+    fs::remove_file(dir.join("added")).unwrap();
+    mygit(&dir, &["apply", "patch"]);
+    assert_eq!(fs::read(dir.join("image")).unwrap(), new);
+    assert_eq!(fs::read(dir.join("added")).unwrap(), added);
+    assert_eq!(fs::read(dir.join("text")).unwrap(), b"one\n2\nthree");
+    assert!(!dir.join("gone").exists());
+
+    // The patch no longer applies to what it produced.
+    assert!(!run(&dir, &["apply", "patch"]).status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn patches_that_do_not_apply_change_nothing() {
+    let dir = repository("apply-nothing");
+    commit(
+        &dir,
+        &[("image", Some(b"\0old")), ("text", Some(b"a\nb\n"))],
+        "second",
+    );
+    commit(
+        &dir,
+        &[("image", Some(b"\0new")), ("text", Some(b"a\nc\n"))],
+        "third",
+    );
+
+    // Without --binary there is nothing to rebuild the binary file from.
+    let patch = mygit(&dir, &["diff", "HEAD~1", "HEAD"]);
+    assert!(patch.contains("Binary files a/image and b/image differ"));
+    fs::write(dir.join("patch"), &patch).unwrap();
+    fs::write(dir.join("image"), b"\0old").unwrap();
+    fs::write(dir.join("text"), b"a\nb\n").unwrap();
+    let output = run(&dir, &["apply", "patch"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("without full index line"));
+    assert_eq!(fs::read(dir.join("text")).unwrap(), b"a\nb\n");
+
+    // A file that is not what the patch was made from is left alone.
+    let patch = mygit(&dir, &["diff", "--binary", "HEAD~1", "HEAD"]);
+    fs::write(dir.join("patch"), &patch).unwrap();
+    fs::write(dir.join("image"), b"\0other").unwrap();
+    let output = run(&dir, &["apply", "patch"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("patch does not apply to image"));
+    assert_eq!(fs::read(dir.join("text")).unwrap(), b"a\nb\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn nothing_is_written_beyond_a_symlink() {
+    let dir = repository("apply-symlink");
+    let outside = dir.with_extension("outside");
+    fs::create_dir_all(&outside).unwrap();
+    let target = outside.to_str().unwrap();
+    let add = |path: &str| {
+        format!(
+            "diff --git a/{0} b/{0}\n\
+             new file mode 100644\n\
+             --- /dev/null\n\
+             +++ b/{0}\n\
+             @@ -0,0 +1 @@\n\
+             +pwned\n",
+            path
+        )
+    };
+    let link = format!(
+        "diff --git a/link b/link\n\
+         new file mode 120000\n\
+         --- /dev/null\n\
+         +++ b/link\n\
+         @@ -0,0 +1 @@\n\
+         +{}\n\
+         \\ No newline at end of file\n",
+        target
+    );
+
+    // A link the patch creates, in either order.
+    for patch in [link.clone() + &add("link/pwned"), add("link/pwned") + &link] {
+        fs::write(dir.join("patch"), patch).unwrap();
+        let output = run(&dir, &["apply", "patch"]);
+        assert_eq!(output.status.code(), Some(128));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "fatal: affected file 'link/pwned' is beyond a symbolic link\n"
+        );
+        assert!(fs::symlink_metadata(dir.join("link")).is_err());
+    }
+
+    // A link already in the working tree, however deep.
+    std::os::unix::fs::symlink(&outside, dir.join("out")).unwrap();
+    for path in ["out/pwned", "out/deeper/pwned"] {
+        fs::write(dir.join("patch"), add(path)).unwrap();
+        let output = run(&dir, &["apply", "patch"]);
+        assert_eq!(output.status.code(), Some(128), "{}", path);
+    }
+    assert!(fs::read_dir(&outside).unwrap().next().is_none());
+
+    // The link itself is fine.
+    fs::write(dir.join("patch"), &link).unwrap();
+    mygit(&dir, &["apply", "patch"]);
+    assert_eq!(fs::read_link(dir.join("link")).unwrap(), outside);
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_dir_all(&outside).unwrap();
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::{mygit, repository};
+use std::fs;
+
+#[test]
+fn scripted_bisect_finds_first_bad_commit() {
+    // Commits 2 through 8 on top of "first"; the bug appears in commit 5.
+    let dir = repository("bisect");
+    for i in 2..=8 {
+        fs::write(dir.join("a"), format!("{}\n", i)).unwrap();
+        mygit(&dir, &["add", "."]);
+        mygit(&dir, &["commit", "-m", &format!("commit {}", i)]);
+    }
+    let first_bad = mygit(&dir, &["rev-parse", "HEAD~3"]).trim().to_string();
+    let first = mygit(&dir, &["rev-parse", "HEAD~7"]).trim().to_string();
+
+    mygit(&dir, &["bisect", "start"]);
+    mygit(&dir, &["bisect", "bad"]);
+    let mut output = mygit(&dir, &["bisect", "good", &first]);
+    let mut tested = 0;
+    while !output.contains("is the first bad commit") {
+        assert!(output.starts_with("Bisecting: "), "{}", output);
+        tested += 1;
+        assert!(tested <= 3, "bisect did not converge");
+        let a = fs::read_to_string(dir.join("a")).unwrap();
+        let verdict = match a.trim() {
+            "a" => "good",
+            n if n.parse::<u32>().unwrap() >= 5 => "bad",
+            _ => "good",
+        };
+        output = mygit(&dir, &["bisect", verdict]);
+    }
+    assert!(output.starts_with(&format!("{} is the first bad commit", first_bad)));
+
+    mygit(&dir, &["bisect", "reset"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "8\n");
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/HEAD")).unwrap(),
+        "ref: refs/heads/main\n"
+    );
+    assert!(!dir.join(".git/BISECT_START").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+
+#[test]
+fn branches_are_listed_and_created() {
+    let dir = repository("branch");
+    assert_eq!(mygit(&dir, &["branch"]), "* main\n");
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "second"]);
+
+    mygit(&dir, &["branch", "feature"]);
+    mygit(&dir, &["branch", "old", "HEAD~1"]);
+    assert_eq!(mygit(&dir, &["branch"]), "  feature\n* main\n  old\n");
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "feature"]),
+        mygit(&dir, &["rev-parse", "HEAD"])
+    );
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "old"]),
+        mygit(&dir, &["rev-parse", "HEAD~1"])
+    );
+
+    let output = run(&dir, &["branch", "feature", "old"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: a branch named 'feature' already exists\n"
+    );
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "feature"]),
+        mygit(&dir, &["rev-parse", "HEAD"])
+    );
+    assert_eq!(
+        run(&dir, &["branch", "nope", "no-such-rev"]).status.code(),
+        Some(128)
+    );
+    assert!(!dir.join(".git/refs/heads/nope").exists());
+
+    let old = mygit(&dir, &["rev-parse", "old"]);
+    mygit(&dir, &["switch", "--detach", "old"]);
+    assert_eq!(
+        mygit(&dir, &["branch"]),
+        format!(
+            "* (HEAD detached at {})\n  feature\n  main\n  old\n",
+            &old[..7]
+        )
+    );
+
+    fs::write(dir.join(".git/HEAD"), "ab").unwrap();
+    let output = run(&dir, &["branch"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: ref HEAD holds neither a hash nor a symbolic ref\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn upstreams_are_written_to_config() {
+    let dir = repository("branch-upstream");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    fs::create_dir_all(dir.join(".git/refs/remotes/origin/topic")).unwrap();
+    fs::write(dir.join(".git/refs/remotes/origin/main"), &head).unwrap();
+    fs::write(dir.join(".git/refs/remotes/origin/topic/x"), &head).unwrap();
+    mygit(&dir, &["branch", "feature"]);
+    // Settings already there are kept.
+    fs::write(dir.join(".git/config"), "[core]\n\tignorecase = false\n").unwrap();
+
+    assert_eq!(
+        mygit(&dir, &["branch", "--set-upstream-to=origin/main"]),
+        "branch 'main' set up to track 'origin/main'.\n"
+    );
+    assert_eq!(
+        mygit(&dir, &["branch", "-u", "origin/topic/x", "feature"]),
+        "branch 'feature' set up to track 'origin/topic/x'.\n"
+    );
+    let config = fs::read_to_string(dir.join(".git/config")).unwrap();
+    assert!(config.contains("ignorecase=false"), "{}", config);
+    assert!(
+        config.contains("[branch \"main\"]\nremote=origin\nmerge=refs/heads/main\n"),
+        "{}",
+        config
+    );
+    assert!(
+        config.contains("[branch \"feature\"]\nremote=origin\nmerge=refs/heads/topic/x\n"),
+        "{}",
+        config
+    );
+    // Read back the way revisions are.
+    assert_eq!(mygit(&dir, &["rev-parse", "feature@{u}"]), head);
+
+    // A local branch is tracked through the remote `.`.
+    mygit(&dir, &["branch", "--set-upstream-to", "main", "feature"]);
+    let config = fs::read_to_string(dir.join(".git/config")).unwrap();
+    assert!(
+        config.contains("[branch \"feature\"]\nremote=.\nmerge=refs/heads/main\n"),
+        "{}",
+        config
+    );
+    assert_eq!(mygit(&dir, &["rev-parse", "feature@{upstream}"]), head);
+
+    for (args, message) in [
+        (
+            &["branch", "-u", "origin/missing"][..],
+            "fatal: the requested upstream branch 'origin/missing' does not exist\n",
+        ),
+        (
+            &["branch", "-u", "main", "missing"],
+            "fatal: branch 'missing' does not exist\n",
+        ),
+    ] {
+        let output = run(&dir, args);
+        assert_eq!(output.status.code(), Some(128), "{:?}", args);
+        assert_eq!(String::from_utf8_lossy(&output.stderr), message);
+    }
+    mygit(&dir, &["switch", "--detach", "main"]);
+    let output = run(&dir, &["branch", "-u", "main"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: HEAD does not point to a branch\n"
+    );
+    assert_eq!(run(&dir, &["branch", "-u"]).status.code(), Some(129));
+    fs::remove_dir_all(&dir).unwrap();
+}
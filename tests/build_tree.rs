@@ -0,0 +1,75 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::ignore::Ignore;
+use mygit::object;
+use mygit::repository::Repository;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn path_lists_hash_like_the_working_directory() {
+    let dir = repository("build-tree");
+    fs::create_dir_all(dir.join("lib/sub")).unwrap();
+    fs::write(dir.join("lib/b"), "b\n").unwrap();
+    fs::write(dir.join("lib/sub/c"), "c\n").unwrap();
+    // Sorts between "lib" and "lib/" only when trees sort as git does.
+    fs::write(dir.join("lib.txt"), "txt\n").unwrap();
+    fs::write(dir.join("lib-x"), "x\n").unwrap();
+    fs::write(dir.join("run.sh"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(dir.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    // Listed out of order; the builder sorts them.
+    let paths = [
+        ("run.sh", "100755"),
+        ("lib/sub/c", "100644"),
+        ("a", "100644"),
+        ("lib.txt", "100644"),
+        ("lib/b", "100644"),
+        (".gitignore", "100644"),
+        ("lib-x", "100644"),
+    ];
+    let entries: Vec<(String, String, String)> = paths
+        .iter()
+        .map(|(path, mode)| {
+            let hash = mygit(&dir, &["hash-object", "-w", path]);
+            (path.to_string(), mode.to_string(), hash.trim().to_string())
+        })
+        .collect();
+    let repo = Repository::open(&dir).unwrap();
+    let tree = repo.build_tree_from_paths(&entries).unwrap();
+
+    // The only test in this binary, so changing directory disturbs no other.
+    env::set_current_dir(&dir).unwrap();
+    assert_eq!(object::create_tree(".", &Ignore::new()).unwrap(), tree);
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "-r", "--name-only", &tree]),
+        ".gitignore\na\nlib-x\nlib.txt\nlib/b\nlib/sub/c\nrun.sh\n"
+    );
+    mygit(&dir, &["add", "."]);
+    assert_eq!(mygit(&dir, &["write-tree"]).trim(), tree);
+
+    // Nothing of the working directory is read.
+    fs::remove_dir_all(dir.join("lib")).unwrap();
+    assert_eq!(repo.build_tree_from_paths(&entries).unwrap(), tree);
+
+    let blob = entries[2].2.clone();
+    let invalid = [
+        vec![("a", "100644", blob.as_str()), ("a", "100644", &blob)],
+        vec![("a", "100644", &blob), ("a/b", "100644", &blob)],
+        vec![("a", "100644", "nothex")],
+        vec![("a", "100600", &blob)],
+        vec![("a//b", "100644", &blob)],
+        vec![("/a", "100644", &blob)],
+        vec![("", "100644", &blob)],
+    ];
+    for list in invalid {
+        let list: Vec<(String, String, String)> = list
+            .iter()
+            .map(|(path, mode, hash)| (path.to_string(), mode.to_string(), hash.to_string()))
+            .collect();
+        assert!(repo.build_tree_from_paths(&list).is_err(), "{:?}", list);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,259 @@
+mod common;
+
+use common::{mygit, mygit_with_input, repository, run};
+use mygit::object::read_raw_in;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn blob(dir: &Path, content: &str) -> String {
+    fs::write(dir.join("blob"), content).unwrap();
+    let hash = mygit(dir, &["hash-object", "-w", "blob"])
+        .trim()
+        .to_string();
+    fs::remove_file(dir.join("blob")).unwrap();
+    hash
+}
+
+#[test]
+fn follow_symlinks_resolves_within_the_tree() {
+    let dir = repository("cat-file-symlinks");
+    // sub/up points back out of sub, so `link` only resolves if each
+    // target is read relative to the directory holding the link.
+    let sub = mygit_with_input(
+        &dir,
+        &["mktree"],
+        &format!("120000 blob {}\tup\n", blob(&dir, "../target.txt")),
+    );
+    let tree = mygit_with_input(
+        &dir,
+        &["mktree"],
+        &format!(
+            "120000 blob {}\tescape\n\
+             120000 blob {}\tlink\n\
+             120000 blob {}\tloop\n\
+             040000 tree {}\tsub\n\
+             100644 blob {}\ttarget.txt\n",
+            blob(&dir, "../outside"),
+            blob(&dir, "sub/up"),
+            blob(&dir, "loop"),
+            sub.trim(),
+            blob(&dir, "hello\n"),
+        ),
+    );
+    let tree = tree.trim();
+
+    let link = format!("{}:link", tree);
+    assert_eq!(mygit(&dir, &["cat-file", "-p", &link]), "sub/up");
+    assert_eq!(
+        mygit(&dir, &["cat-file", "-p", "--follow-symlinks", &link]),
+        "hello\n"
+    );
+    assert_eq!(
+        mygit(&dir, &["cat-file", "--follow-symlinks", "-s", &link]),
+        "6\n"
+    );
+
+    for path in ["loop", "escape"] {
+        let output = run(
+            &dir,
+            &[
+                "cat-file",
+                "-p",
+                "--follow-symlinks",
+                &format!("{}:{}", tree, path),
+            ],
+        );
+        assert_eq!(output.status.code(), Some(128));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn malformed_forty_character_names_are_not_found() {
+    let dir = repository("cat-file-malformed");
+    let a = mygit(&dir, &["rev-parse", "HEAD:a"]).trim().to_string();
+    for flag in ["-p", "-t", "-s"] {
+        let output = run(&dir, &["cat-file", flag, "aéééééééééééééééééééb"]);
+        assert_eq!(output.status.code(), Some(128), "cat-file {}", flag);
+        let output = run(&dir, &["cat-file", flag, &"z".repeat(40)]);
+        assert_eq!(output.status.code(), Some(128), "cat-file {}", flag);
+    }
+    // Full hashes are not case sensitive.
+    assert_eq!(
+        mygit(&dir, &["cat-file", "-p", &a.to_ascii_uppercase()]),
+        "a\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn trees_are_printed_like_ls_tree() {
+    let dir = repository("cat-file-tree");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/b"), "b\n").unwrap();
+    mygit(&dir, &["add", "sub/b"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+
+    let listing = mygit(&dir, &["cat-file", "-p", "HEAD^{tree}"]);
+    assert_eq!(listing, mygit(&dir, &["ls-tree", "HEAD"]));
+    let sub = mygit(&dir, &["rev-parse", "HEAD:sub"]).trim().to_string();
+    let b = mygit(&dir, &["rev-parse", "HEAD:sub/b"]).trim().to_string();
+    assert!(listing.contains(&format!("040000 tree {}\tsub\n", sub)));
+    assert_eq!(
+        mygit(&dir, &["cat-file", "-p", "HEAD:sub"]),
+        format!("100644 blob {}\tb\n", b)
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tags_are_printed_as_stored() {
+    let dir = repository("cat-file-tag");
+    mygit(&dir, &["tag", "-a", "-m", "release\n\nnotes", "v1"]);
+    let tag = mygit(&dir, &["rev-parse", "refs/tags/v1"])
+        .trim()
+        .to_string();
+    let (object_type, stored) = read_raw_in(&dir.join(".git/objects"), &tag).unwrap();
+    assert_eq!(object_type, "tag");
+
+    let printed = mygit(&dir, &["cat-file", "-p", &tag]);
+    assert_eq!(printed.as_bytes(), stored);
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    assert!(printed.starts_with(&format!(
+        "object {}type commit\ntag v1\ntagger Test <test@example.com> ",
+        head
+    )));
+    assert!(printed.ends_with("\n\nrelease\n\nnotes\n"));
+    assert_eq!(mygit(&dir, &["cat-file", "-t", &tag]), "tag\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn batch_check_prints_metadata() {
+    let dir = repository("cat-file-batch-check");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"])
+        .trim()
+        .to_string();
+    let a = mygit(&dir, &["rev-parse", "HEAD:a"]).trim().to_string();
+    let commit_size = mygit(&dir, &["cat-file", "-s", &head]);
+    let tree_size = mygit(&dir, &["cat-file", "-s", &tree]);
+
+    let input = format!("{}\n{}\n\n  HEAD:a  \n{}\nnope\n", head, &tree[..7], &a);
+    assert_eq!(
+        mygit_with_input(&dir, &["cat-file", "--batch-check"], &input),
+        format!(
+            "{} commit {}{} tree {}{} blob 2\n{} blob 2\nnope missing\n",
+            head, commit_size, tree, tree_size, a, a
+        )
+    );
+    assert_eq!(
+        mygit_with_input(&dir, &["cat-file", "--batch-check"], ""),
+        ""
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn blobs_are_printed_byte_for_byte() {
+    let dir = repository("cat-file-blob-bytes");
+    for content in [
+        "no newline",
+        "",
+        "\n",
+        "two\n\n",
+        "crlf\r\n",
+        "last line\nlacks one",
+    ] {
+        let hash = blob(&dir, content);
+        assert_eq!(mygit(&dir, &["cat-file", "-p", &hash]), content);
+        assert_eq!(
+            mygit(&dir, &["cat-file", "-s", &hash]),
+            format!("{}\n", content.len())
+        );
+    }
+
+    let bytes = b"\0\xff\xfe binary\n\0";
+    fs::write(dir.join("binary"), bytes).unwrap();
+    let hash = mygit(&dir, &["hash-object", "-w", "binary"]);
+    let output = run(&dir, &["cat-file", "-p", hash.trim()]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, bytes);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn types_are_printed_by_name() {
+    let dir = repository("cat-file-types");
+    mygit(&dir, &["tag", "-a", "-m", "release", "v1"]);
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    for (name, object_type) in [
+        (head.trim(), "commit"),
+        (&head[..7], "commit"),
+        ("HEAD", "commit"),
+        ("HEAD^{tree}", "tree"),
+        ("HEAD:a", "blob"),
+        ("v1", "tag"),
+        ("v1^{}", "commit"),
+    ] {
+        assert_eq!(
+            mygit(&dir, &["cat-file", "-t", name]),
+            format!("{}\n", object_type),
+            "{}",
+            name
+        );
+    }
+
+    let missing = "0".repeat(40);
+    let output = run(&dir, &["cat-file", "-t", &missing]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("fatal: Not a valid object name {}\n", missing)
+    );
+    assert_eq!(run(&dir, &["cat-file", "-t"]).status.code(), Some(129));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// The sizes are cross-checked with git where it is installed.
+#[test]
+fn sizes_match_git() {
+    let dir = repository("cat-file-sizes");
+    mygit(&dir, &["tag", "-a", "-m", "release", "v1"]);
+    let large = blob(&dir, &"x".repeat(100_000));
+    let empty = blob(&dir, "");
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(&dir)
+            .env("HOME", &dir)
+            .output()
+    };
+    let has_git = git(&["--version"]).is_ok_and(|output| output.status.success());
+
+    for (name, size) in [
+        ("HEAD:a", Some(2)),
+        (&large, Some(100_000)),
+        (&empty, Some(0)),
+        // "100644 .gitignore\0" and "100644 a\0", each with a 20-byte hash.
+        ("HEAD^{tree}", Some(38 + 29)),
+        ("HEAD", None),
+        ("v1", None),
+    ] {
+        let output = mygit(&dir, &["cat-file", "-s", name]);
+        let hash = mygit(&dir, &["rev-parse", name]);
+        let (_, content) = read_raw_in(&dir.join(".git/objects"), hash.trim()).unwrap();
+        assert_eq!(output, format!("{}\n", content.len()), "{}", name);
+        if let Some(size) = size {
+            assert_eq!(output, format!("{}\n", size), "{}", name);
+        }
+        if has_git {
+            let expected = git(&["cat-file", "-s", hash.trim()]).unwrap();
+            assert!(expected.status.success(), "{}", name);
+            assert_eq!(output.as_bytes(), expected.stdout, "{}", name);
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,251 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+fn mode(path: &Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[test]
+fn checkout_writes_and_removes_files() {
+    let dir = repository("checkout");
+    mygit(&dir, &["branch", "first"]);
+    fs::write(dir.join("a"), "main\n").unwrap();
+    fs::create_dir_all(dir.join("bin/tools")).unwrap();
+    fs::write(dir.join("bin/tools/run.sh"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(
+        dir.join("bin/tools/run.sh"),
+        fs::Permissions::from_mode(0o755),
+    )
+    .unwrap();
+    fs::write(dir.join("b"), "b\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "second"]);
+
+    // Files only `main` has go away, directories and all.
+    mygit(&dir, &["checkout", "first"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\n");
+    assert!(!dir.join("b").exists());
+    assert!(!dir.join("bin").exists());
+
+    mygit(&dir, &["checkout", "main"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "main\n");
+    assert_eq!(fs::read_to_string(dir.join("b")).unwrap(), "b\n");
+    assert_eq!(mode(&dir.join("bin/tools/run.sh")), 0o755);
+    let tree = mygit(&dir, &["rev-parse", "HEAD:"]);
+    mygit(&dir, &["add", "."]);
+    assert_eq!(mygit(&dir, &["write-tree"]), tree);
+
+    // A commit checked out into an empty directory comes back whole.
+    let clone = dir.join("clone");
+    mygit(&dir, &["clone", ".", "clone"]);
+    assert_eq!(fs::read_to_string(clone.join("a")).unwrap(), "main\n");
+    assert_eq!(fs::read_to_string(clone.join("b")).unwrap(), "b\n");
+    assert_eq!(mode(&clone.join("bin/tools/run.sh")), 0o755);
+    fs::remove_dir_all(&clone).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn checkout_keeps_local_changes_unless_forced() {
+    let dir = repository("checkout-local-changes");
+    mygit(&dir, &["branch", "first"]);
+    fs::write(dir.join("a"), "main\n").unwrap();
+    fs::write(dir.join("b"), "b\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "second"]);
+
+    // `a` differs between the branches, so its change would be lost.
+    fs::write(dir.join("a"), "local\n").unwrap();
+    let output = run(&dir, &["checkout", "first"]);
+    assert_eq!(output.status.code(), Some(128));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("would be overwritten by checkout:\n\ta\n"),
+        "{}",
+        stderr
+    );
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "local\n");
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/HEAD")).unwrap(),
+        "ref: refs/heads/main\n"
+    );
+
+    // A change to a file both branches share comes along.
+    fs::write(dir.join("a"), "main\n").unwrap();
+    fs::write(dir.join(".gitignore"), ".gitconfig\n*.log\n").unwrap();
+    mygit(&dir, &["checkout", "first"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\n");
+    assert_eq!(
+        fs::read_to_string(dir.join(".gitignore")).unwrap(),
+        ".gitconfig\n*.log\n"
+    );
+
+    fs::write(dir.join("a"), "local\n").unwrap();
+    mygit(&dir, &["checkout", "--force", "main"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "main\n");
+    assert_eq!(
+        fs::read_to_string(dir.join(".gitignore")).unwrap(),
+        ".gitconfig\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn checkout_restores_modes_and_symlinks() {
+    let dir = repository("checkout-modes");
+    mygit(&dir, &["branch", "first"]);
+    fs::write(dir.join("run.sh"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(dir.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(dir.join("private"), "p\n").unwrap();
+    fs::set_permissions(dir.join("private"), fs::Permissions::from_mode(0o600)).unwrap();
+    std::os::unix::fs::symlink("run.sh", dir.join("link")).unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "modes"]);
+    let tree = mygit(&dir, &["rev-parse", "HEAD:"]);
+
+    mygit(&dir, &["checkout", "first"]);
+    assert!(fs::symlink_metadata(dir.join("link")).is_err());
+    mygit(&dir, &["checkout", "main"]);
+
+    assert_eq!(mode(&dir.join("run.sh")), 0o755);
+    assert_eq!(mode(&dir.join("private")), 0o644);
+    assert_eq!(
+        fs::read_link(dir.join("link")).unwrap(),
+        Path::new("run.sh")
+    );
+    mygit(&dir, &["add", "."]);
+    assert_eq!(mygit(&dir, &["write-tree"]), tree);
+
+    // Files that become links, and links that become files, are replaced.
+    fs::remove_file(dir.join("link")).unwrap();
+    fs::write(dir.join("link"), "now a file\n").unwrap();
+    fs::remove_file(dir.join("a")).unwrap();
+    std::os::unix::fs::symlink("run.sh", dir.join("a")).unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "swapped"]);
+    mygit(&dir, &["checkout", "first"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\n");
+    assert!(!fs::symlink_metadata(dir.join("a")).unwrap().is_symlink());
+    assert!(!dir.join("run.sh").exists());
+    mygit(&dir, &["checkout", "main"]);
+    assert_eq!(fs::read_link(dir.join("a")).unwrap(), Path::new("run.sh"));
+    assert_eq!(
+        fs::read_to_string(dir.join("link")).unwrap(),
+        "now a file\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("run.sh")).unwrap(),
+        "#!/bin/sh\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn case_collisions_are_rejected_when_ignoring_case() {
+    let dir = repository("checkout-ignorecase");
+    fs::write(dir.join("README"), "upper\n").unwrap();
+    fs::write(dir.join("readme"), "lower\n").unwrap();
+    mygit(&dir, &["add", "README", "readme"]);
+    mygit(&dir, &["commit", "-m", "both"]);
+    mygit(&dir, &["checkout", "-b", "old", "HEAD~1"]);
+    assert!(!dir.join("README").exists() && !dir.join("readme").exists());
+
+    fs::write(dir.join(".git/config"), "[core]\n\tignorecase = true\n").unwrap();
+    let output = run(&dir, &["checkout", "main"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: paths 'README' and 'readme' collide on a case-insensitive filesystem\n"
+    );
+    // Nothing was written and HEAD did not move.
+    assert!(!dir.join("README").exists() && !dir.join("readme").exists());
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/HEAD")).unwrap(),
+        "ref: refs/heads/old\n"
+    );
+
+    fs::write(dir.join(".git/config"), "[core]\n\tignorecase = false\n").unwrap();
+    mygit(&dir, &["checkout", "main"]);
+    assert_eq!(fs::read_to_string(dir.join("README")).unwrap(), "upper\n");
+    assert_eq!(fs::read_to_string(dir.join("readme")).unwrap(), "lower\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn new_branches_leave_main_alone() {
+    let dir = repository("checkout-new-branch");
+    let main = mygit(&dir, &["rev-parse", "main"]);
+    assert_eq!(
+        mygit(&dir, &["checkout", "-b", "feature"]),
+        "Switched to a new branch 'feature'\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/HEAD")).unwrap(),
+        "ref: refs/heads/feature\n"
+    );
+    assert_eq!(mygit(&dir, &["rev-parse", "feature"]), main);
+
+    fs::write(dir.join("a"), "feature\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "on feature"]);
+    assert_eq!(mygit(&dir, &["rev-parse", "main"]), main);
+    assert_eq!(mygit(&dir, &["rev-parse", "feature~1"]), main);
+
+    // A start point is checked out as the new branch's tree.
+    mygit(&dir, &["checkout", "-b", "other", "main"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\n");
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD"]), main);
+
+    let output = run(&dir, &["checkout", "-b", "feature"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    assert!(!run(&dir, &["checkout", "-b", "bad..name"]).status.success());
+    assert!(!run(&dir, &["checkout", "-b", "nowhere", "missing"])
+        .status
+        .success());
+    assert!(!dir.join(".git/refs/heads/nowhere").exists());
+    assert_eq!(run(&dir, &["checkout", "-b"]).status.code(), Some(129));
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/HEAD")).unwrap(),
+        "ref: refs/heads/other\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// `a` is a file on one branch and a directory on the other.
+#[test]
+fn files_and_directories_replace_each_other() {
+    let dir = repository("checkout-file-directory");
+    mygit(&dir, &["branch", "file"]);
+    fs::remove_file(dir.join("a")).unwrap();
+    fs::create_dir(dir.join("a")).unwrap();
+    fs::write(dir.join("a/b"), "b\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "directory"]);
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "-r", "--name-only", "HEAD"]),
+        ".gitignore\na/b\n"
+    );
+
+    mygit(&dir, &["checkout", "file"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\n");
+    mygit(&dir, &["checkout", "main"]);
+    assert_eq!(fs::read_to_string(dir.join("a/b")).unwrap(), "b\n");
+    mygit(&dir, &["checkout", "file"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\n");
+
+    // Changes to the file are still kept.
+    fs::write(dir.join("a"), "changed\n").unwrap();
+    let output = run(&dir, &["checkout", "main"]);
+    assert!(!output.status.success());
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "changed\n");
+    mygit(&dir, &["checkout", "-f", "main"]);
+    fs::write(dir.join("a/b"), "changed\n").unwrap();
+    assert!(!run(&dir, &["checkout", "file"]).status.success());
+    assert_eq!(fs::read_to_string(dir.join("a/b")).unwrap(), "changed\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+
+#[test]
+fn missing_arguments_print_usage() {
+    let dir = repository("cli-usage");
+    for args in [
+        &[][..],
+        &["cat-file", "-p"],
+        &["checkout"],
+        &["push", "origin"],
+        &["no-such-command"],
+    ] {
+        let output = run(&dir, args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(129), "{:?}: {}", args, stderr);
+        assert!(stderr.starts_with("usage: mygit"), "{:?}: {}", args, stderr);
+        assert!(!stderr.contains("panicked"), "{:?}: {}", args, stderr);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failures_are_reported_as_fatal() {
+    let dir = repository("cli-fatal");
+    mygit(&dir, &["tag", "v1"]);
+    for (args, message) in [
+        (&["cat-file", "-p", "0000000"][..], "fatal: "),
+        (
+            &["cat-file", "-t", "nope"],
+            "fatal: Not a valid object name nope\n",
+        ),
+        (&["switch", "nope"], "fatal: invalid reference: nope\n"),
+        (&["tag", "v1"], "fatal: tag 'v1' already exists\n"),
+    ] {
+        let output = run(&dir, args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(128), "{:?}: {}", args, stderr);
+        assert!(stderr.starts_with(message), "{:?}: {}", args, stderr);
+        assert!(!stderr.contains("panicked"), "{:?}: {}", args, stderr);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
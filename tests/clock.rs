@@ -0,0 +1,66 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::clock::{Clock, FixedClock, SystemClock};
+use mygit::object::{Object, Timestamp, User};
+use mygit::repository::Repository;
+use std::cell::Cell;
+use std::fs;
+
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+// Moves on a minute, and to another time zone, each time it is read.
+struct Ticking(Cell<i64>);
+
+impl Clock for Ticking {
+    fn now(&self) -> Timestamp {
+        let seconds = self.0.get();
+        self.0.set(seconds + 60);
+        Timestamp::new(seconds, -9000)
+    }
+}
+
+// The hashes are git's for the same commits.
+#[test]
+fn pinned_clocks_give_known_hashes() {
+    let dir = repository("clock");
+    let user = User::new("Test", "test@example.com");
+    let repo = Repository::open(&dir)
+        .unwrap()
+        .with_clock(FixedClock(Timestamp::new(1700000000, 3600)));
+    let tree = repo.write_object(&Object::Tree(Vec::new())).unwrap();
+    assert_eq!(tree, EMPTY_TREE);
+
+    let first = repo
+        .commit_tree(&tree, Vec::new(), &user, "first".to_string())
+        .unwrap();
+    assert_eq!(first, "693ba8b8ded38baf9791b4d23123263b48d76349");
+    assert!(mygit(&dir, &["cat-file", "-p", &first])
+        .contains("\nauthor Test <test@example.com> 1700000000 +0100\n"));
+    // A fixed clock never moves, so the same commit comes out again.
+    assert_eq!(
+        repo.commit_tree(&tree, Vec::new(), &user, "first".to_string())
+            .unwrap(),
+        first
+    );
+
+    let repo = repo.with_clock(Ticking(Cell::new(1700000060)));
+    let second = repo
+        .commit_tree(&tree, vec![first.clone()], &user, "second".to_string())
+        .unwrap();
+    assert_eq!(second, "90f3acc78c3922e953596b27881569315ea31e16");
+    assert_ne!(
+        repo.commit_tree(&tree, vec![first], &user, "second".to_string())
+            .unwrap(),
+        second
+    );
+
+    let before = Timestamp::now().seconds();
+    let now = Repository::open(&dir)
+        .unwrap()
+        .with_clock(SystemClock)
+        .now()
+        .seconds();
+    assert!(now >= before && now <= Timestamp::now().seconds());
+    fs::remove_dir_all(&dir).unwrap();
+}
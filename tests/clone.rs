@@ -0,0 +1,252 @@
+mod common;
+
+use common::{mygit, mygit_with_input, repository, run};
+use mygit::object::read_raw_in;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+fn read(path: impl AsRef<Path>) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn clones_match_their_source() {
+    let dir = repository("clone-source");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/b"), "b\n").unwrap();
+    mygit(&dir, &["add", "sub"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    mygit(&dir, &["tag", "v1"]);
+    mygit(&dir, &["switch", "-c", "feature"]);
+    fs::write(dir.join("a"), "feature\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "on feature"]);
+    mygit(&dir, &["switch", "main"]);
+    let main = mygit(&dir, &["rev-parse", "main"]);
+    let feature = mygit(&dir, &["rev-parse", "feature"]);
+
+    let clone = dir.with_extension("clone");
+    let path = clone.display().to_string();
+    assert_eq!(
+        mygit(&dir, &["clone", ".", &path]),
+        format!("Cloning into '{}'...\n", path)
+    );
+    assert_eq!(read(clone.join(".git/HEAD")), "ref: refs/heads/main\n");
+    assert_eq!(read(clone.join(".git/refs/heads/main")), main);
+    assert!(!clone.join(".git/refs/heads/feature").exists());
+    assert_eq!(read(clone.join(".git/refs/remotes/origin/main")), main);
+    assert_eq!(
+        read(clone.join(".git/refs/remotes/origin/feature")),
+        feature
+    );
+    assert_eq!(
+        read(clone.join(".git/refs/remotes/origin/HEAD")),
+        "ref: refs/remotes/origin/main\n"
+    );
+    assert_eq!(read(clone.join(".git/refs/tags/v1")), main);
+    let config = read(clone.join(".git/config"));
+    let url = fs::canonicalize(&dir).unwrap().display().to_string();
+    for line in [
+        "[remote \"origin\"]".to_string(),
+        format!("url={}", url),
+        "fetch=+refs/heads/*:refs/remotes/origin/*".to_string(),
+        "[branch \"main\"]".to_string(),
+        "remote=origin".to_string(),
+        "merge=refs/heads/main".to_string(),
+    ] {
+        assert!(config.lines().any(|l| l == line), "{}: {}", line, config);
+    }
+
+    // The working tree is the source's main, and the index agrees with it.
+    assert_eq!(read(clone.join("a")), "a\n");
+    assert_eq!(read(clone.join("sub/b")), "b\n");
+    assert!(!clone.join(".gitconfig").exists());
+    assert_eq!(
+        mygit(&clone, &["write-tree"]),
+        mygit(&dir, &["rev-parse", "main^{tree}"])
+    );
+    assert_eq!(
+        mygit(&clone, &["cat-file", "-p", "origin/feature:a"]),
+        "feature\n"
+    );
+    assert_eq!(
+        mygit(&clone, &["rev-parse", "origin/feature~2"]),
+        mygit(&dir, &["rev-parse", "main~1"])
+    );
+
+    let output = run(&dir, &["clone", ".", &path]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "fatal: destination path '{}' already exists and is not an empty directory\n",
+            path
+        )
+    );
+    fs::remove_dir_all(&clone).unwrap();
+
+    // Whatever the source has checked out is checked out.
+    mygit(&dir, &["switch", "feature"]);
+    mygit(&dir, &["clone", ".", &path]);
+    assert_eq!(read(clone.join(".git/HEAD")), "ref: refs/heads/feature\n");
+    assert_eq!(read(clone.join("a")), "feature\n");
+    fs::remove_dir_all(&clone).unwrap();
+    mygit(&dir, &["switch", "--detach", "v1"]);
+    mygit(&dir, &["clone", ".", &path]);
+    assert_eq!(read(clone.join(".git/HEAD")), main);
+    assert_eq!(read(clone.join("a")), "a\n");
+    fs::remove_dir_all(&clone).unwrap();
+
+    let empty = dir.with_extension("empty");
+    fs::create_dir(&empty).unwrap();
+    mygit(&empty, &["init"]);
+    assert_eq!(
+        mygit(&dir, &["clone", &empty.display().to_string(), &path]),
+        format!(
+            "Cloning into '{}'...\nwarning: You appear to have cloned an empty repository.\n",
+            path
+        )
+    );
+    assert!(!run(&dir, &["clone", "/no/such/repository", "elsewhere"])
+        .status
+        .success());
+    assert_eq!(run(&dir, &["clone", "."]).status.code(), Some(129));
+    fs::remove_dir_all(&empty).unwrap();
+    fs::remove_dir_all(&clone).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bare_clones_have_no_working_tree() {
+    let dir = repository("clone-bare");
+    mygit(&dir, &["branch", "feature"]);
+    mygit(&dir, &["tag", "v1"]);
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    let bare = dir.with_extension("git");
+    let path = bare.display().to_string();
+    assert_eq!(
+        mygit(&dir, &["clone", "--bare", ".", &path]),
+        format!("Cloning into bare repository '{}'...\n", path)
+    );
+
+    // Branches are copied as they are, not as remote-tracking ones.
+    assert_eq!(read(bare.join("HEAD")), "ref: refs/heads/main\n");
+    assert_eq!(read(bare.join("refs/heads/main")), head);
+    assert_eq!(read(bare.join("refs/heads/feature")), head);
+    assert_eq!(read(bare.join("refs/tags/v1")), head);
+    assert!(!bare.join("refs/remotes").exists());
+    let config = read(bare.join("config"));
+    assert!(config.lines().any(|line| line == "bare=true"), "{}", config);
+    assert!(!config.contains("fetch"), "{}", config);
+
+    for path in ["a", ".gitignore", ".git", "index"] {
+        assert!(!bare.join(path).exists(), "{}", path);
+    }
+    for rev in ["HEAD", "HEAD^{tree}", "HEAD:a"] {
+        let hash = mygit(&dir, &["rev-parse", rev]);
+        let (_, content) = read_raw_in(&bare.join("objects"), hash.trim()).unwrap();
+        let (_, original) = read_raw_in(&dir.join(".git/objects"), hash.trim()).unwrap();
+        assert_eq!(content, original, "{}", rev);
+    }
+
+    // It serves as a source like any other repository.
+    let clone = dir.with_extension("clone");
+    mygit(&dir, &["clone", &path, &clone.display().to_string()]);
+    assert_eq!(read(clone.join("a")), "a\n");
+    assert_eq!(read(clone.join(".git/refs/remotes/origin/feature")), head);
+    fs::remove_dir_all(&clone).unwrap();
+    fs::remove_dir_all(&bare).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Every object file, loose or packed, under a git directory.
+fn object_files(gitdir: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    for dir in fs::read_dir(gitdir.join("objects")).unwrap() {
+        let dir = dir.unwrap().path();
+        for file in fs::read_dir(&dir).unwrap() {
+            let name = file.unwrap().file_name();
+            files.push(format!(
+                "{}/{}",
+                dir.file_name().unwrap().to_string_lossy(),
+                name.to_string_lossy()
+            ));
+        }
+    }
+    files.sort();
+    files
+}
+
+#[test]
+fn objects_are_hardlinked_unless_asked_not_to() {
+    let dir = repository("clone-hardlinks");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    fs::create_dir_all(dir.join(".git/objects/pack")).unwrap();
+    mygit_with_input(&dir, &["pack-objects", ".git/objects/pack/pack"], &head);
+    // The packed commit is not copied loose as well.
+    let loose_head = format!("{}/{}", &head[..2], head[2..].trim());
+    let files: Vec<String> = object_files(&dir.join(".git"))
+        .into_iter()
+        .filter(|file| *file != loose_head)
+        .collect();
+    assert!(files.iter().any(|file| file.ends_with(".pack")));
+    let objects = dir.join(".git/objects");
+
+    let linked = dir.with_extension("linked");
+    mygit(&dir, &["clone", ".", &linked.display().to_string()]);
+    let copied = dir.with_extension("copied");
+    mygit(
+        &dir,
+        &[
+            "clone",
+            "--no-hardlinks",
+            ".",
+            &copied.display().to_string(),
+        ],
+    );
+    assert_eq!(object_files(&linked.join(".git")), files);
+    assert_eq!(object_files(&copied.join(".git")), files);
+    for file in &files {
+        let original = fs::metadata(objects.join(file)).unwrap();
+        let link = fs::metadata(linked.join(".git/objects").join(file)).unwrap();
+        let copy = fs::metadata(copied.join(".git/objects").join(file)).unwrap();
+        assert_eq!(link.ino(), original.ino(), "{}", file);
+        assert_ne!(copy.ino(), original.ino(), "{}", file);
+        assert_eq!(original.nlink(), 2, "{}", file);
+        assert_eq!(
+            fs::read(copied.join(".git/objects").join(file)).unwrap(),
+            fs::read(objects.join(file)).unwrap()
+        );
+    }
+    assert_eq!(read(copied.join("a")), "a\n");
+
+    // Fetching links what it copies too.
+    fs::write(dir.join("a"), "second\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    let second = mygit(&dir, &["rev-parse", "HEAD"]);
+    let second = format!("{}/{}", &second[..2], second[2..].trim());
+    mygit(&linked, &["fetch", "origin"]);
+    assert_eq!(
+        fs::metadata(linked.join(".git/objects").join(&second))
+            .unwrap()
+            .ino(),
+        fs::metadata(objects.join(&second)).unwrap().ino()
+    );
+
+    // Across filesystems, where links cannot be made, the files are copied.
+    let shm = Path::new("/dev/shm");
+    let other_device =
+        fs::metadata(shm).is_ok_and(|shm| shm.dev() != fs::metadata(&dir).unwrap().dev());
+    if other_device {
+        let elsewhere = shm.join(linked.file_name().unwrap());
+        mygit(&dir, &["clone", ".", &elsewhere.display().to_string()]);
+        assert_eq!(object_files(&elsewhere.join(".git")).len(), files.len() + 3);
+        assert_eq!(read(elsewhere.join("a")), "second\n");
+        fs::remove_dir_all(&elsewhere).unwrap();
+    }
+    fs::remove_dir_all(&linked).unwrap();
+    fs::remove_dir_all(&copied).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
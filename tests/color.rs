@@ -0,0 +1,70 @@
+mod common;
+
+use common::{mygit, repository};
+use std::fs;
+use std::io::Write;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+
+#[test]
+fn color_flag_and_config() {
+    let dir = repository("color");
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    fs::write(dir.join("a"), "c\n").unwrap();
+    fs::write(dir.join("new"), "new\n").unwrap();
+    mygit(&dir, &["add", "new"]);
+
+    let diff = |flag: &str| mygit(&dir, &["--no-pager", "diff", flag, "HEAD~1", "HEAD"]);
+    let status = |flag: &str| mygit(&dir, &["status", flag]);
+    // Output to a pipe is plain unless asked otherwise.
+    for output in [
+        diff("--color=never"),
+        diff("--color=auto"),
+        status("--color=never"),
+    ] {
+        assert!(!output.contains('\x1b'), "{:?}", output);
+    }
+    let colored = diff("--color=always");
+    assert!(colored.contains(&format!("{}-a\x1b[m\n", RED)));
+    assert!(colored.contains(&format!("{}+b\x1b[m\n", GREEN)));
+    assert_eq!(strip(&colored), diff("--color=never"));
+    assert_eq!(diff("--color"), colored);
+
+    let colored = status("--color=always");
+    assert!(
+        colored.contains(GREEN) && colored.contains(RED),
+        "{:?}",
+        colored
+    );
+    assert_eq!(strip(&colored), status("--color=never"));
+
+    // color.ui applies when no flag is given, and the flag wins over it.
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(".git/config"))
+        .unwrap()
+        .write_all(b"[color]\n\tui = always\n")
+        .unwrap();
+    assert_eq!(
+        mygit(&dir, &["--no-pager", "diff", "HEAD~1", "HEAD"]),
+        diff("--color=always")
+    );
+    assert!(!diff("--color=never").contains('\x1b'));
+    assert!(mygit(&dir, &["status"]).contains(GREEN));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Drops every escape sequence.
+fn strip(text: &str) -> String {
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find('\x1b') {
+        plain.push_str(&rest[..at]);
+        rest = &rest[at + rest[at..].find('m').unwrap() + 1..];
+    }
+    plain + rest
+}
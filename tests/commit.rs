@@ -0,0 +1,469 @@
+mod common;
+
+use common::{command, mygit, repository, run};
+use mygit::object::User;
+use mygit::repository::Repository;
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+#[test]
+fn only_staged_changes_are_committed() {
+    let dir = repository("commit-index");
+    fs::write(dir.join("a"), "staged\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    fs::write(dir.join("a"), "unstaged\n").unwrap();
+    fs::write(dir.join("untracked"), "u\n").unwrap();
+    mygit(&dir, &["commit", "-m", "second"]);
+
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "HEAD:a"]), "staged\n");
+    assert!(!run(&dir, &["rev-parse", "HEAD:untracked"]).status.success());
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "unstaged\n");
+    let output = run(&dir, &["commit", "-m", "nothing staged"]);
+    assert!(!output.status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn restore_staged_and_index_paths() {
+    let dir = repository("commit-restore");
+    let head_a = mygit(&dir, &["rev-parse", "HEAD:a"]);
+    fs::write(dir.join("a"), "staged\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    let staged_a = mygit(&dir, &["rev-parse", ":a"]);
+    assert_ne!(staged_a, head_a);
+    assert_eq!(mygit(&dir, &["cat-file", "-p", ":a"]), "staged\n");
+
+    // The working tree is restored from the index by default.
+    fs::write(dir.join("a"), "scribbled\n").unwrap();
+    mygit(&dir, &["restore", "a"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "staged\n");
+
+    // --staged puts HEAD's version back in the index, leaving the file.
+    mygit(&dir, &["restore", "--staged", "a"]);
+    assert_eq!(mygit(&dir, &["rev-parse", ":a"]), head_a);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "staged\n");
+    assert!(!run(&dir, &["rev-parse", ":missing"]).status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parents_are_distinct_commits() {
+    let dir = repository("commit-parents");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"])
+        .trim()
+        .to_string();
+
+    // Repeated -p flags collapse to one parent.
+    let output = run(
+        &dir,
+        &[
+            "commit-tree",
+            &tree,
+            "-p",
+            "HEAD",
+            "-p",
+            &head,
+            "-m",
+            "merge",
+        ],
+    );
+    assert!(output.status.success());
+    let commit = String::from_utf8(output.stdout).unwrap();
+    let parents = mygit(&dir, &["cat-file", "-p", commit.trim()]);
+    assert_eq!(parents.matches("\nparent ").count(), 1);
+
+    let repo = Repository::open(&dir).unwrap();
+    let user = User::new("Test", "test@example.com");
+    let commit_with = |parents: &[&str]| {
+        let parents = parents.iter().map(|parent| parent.to_string()).collect();
+        repo.commit_tree(&tree, parents, &user, "second".to_string())
+    };
+    assert!(commit_with(&[&head]).is_ok());
+    assert!(commit_with(&[&head, &head]).is_err());
+    assert!(commit_with(&[&tree]).is_err());
+    assert!(commit_with(&[&"0".repeat(40)]).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn trees_must_be_trees() {
+    let dir = repository("commit-tree-tree");
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"]);
+    let blob = mygit(&dir, &["rev-parse", "HEAD:a"]).trim().to_string();
+
+    // Options come before the tree as well as after it.
+    let output = run(&dir, &["commit-tree", "-p", "HEAD", "-m", "dir"]);
+    assert_eq!(output.status.code(), Some(129));
+    let hash = mygit(&dir, &["commit-tree", "-p", "HEAD", "-m", "dir", "HEAD"]);
+    assert_eq!(
+        mygit(&dir, &["rev-parse", &format!("{}^{{tree}}", hash.trim())]),
+        tree
+    );
+    assert_eq!(
+        mygit(&dir, &["rev-parse", &format!("{}^", hash.trim())]),
+        mygit(&dir, &["rev-parse", "HEAD"])
+    );
+
+    let output = run(&dir, &["commit-tree", &blob, "-m", "blob"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("fatal: {} is not a valid 'tree' object\n", blob)
+    );
+    assert_eq!(
+        run(&dir, &["commit-tree", "missing", "-m", "missing"])
+            .status
+            .code(),
+        Some(128)
+    );
+
+    let repo = Repository::open(&dir).unwrap();
+    let user = User::new("Test", "test@example.com");
+    assert!(repo
+        .commit_tree(&blob, Vec::new(), &user, "blob".to_string())
+        .is_err());
+    assert!(repo
+        .commit_tree(&"0".repeat(40), Vec::new(), &user, "none".to_string())
+        .is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn head_message(dir: &Path) -> String {
+    let commit = mygit(dir, &["cat-file", "-p", "HEAD"]);
+    commit.split_once("\n\n").unwrap().1.to_string()
+}
+
+#[test]
+fn signoff_is_appended_once() {
+    let dir = repository("commit-signoff");
+    let signoff = "Signed-off-by: Test <test@example.com>";
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-s", "-m", "second"]);
+    assert_eq!(head_message(&dir), format!("second\n\n{}\n", signoff));
+
+    // An existing block is extended, and an existing sign-off kept as is.
+    for (message, expected) in [
+        (
+            "third\n\nFixes: 1".to_string(),
+            format!("third\n\nFixes: 1\n{}\n", signoff),
+        ),
+        (
+            format!("fourth\n\n{}", signoff),
+            format!("fourth\n\n{}\n", signoff),
+        ),
+    ] {
+        mygit(
+            &dir,
+            &["commit", "--allow-empty", "--signoff", "-m", &message],
+        );
+        assert_eq!(head_message(&dir), expected);
+    }
+
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    let commit = Repository::open(&dir)
+        .unwrap()
+        .read_object(head.trim())
+        .unwrap();
+    assert_eq!(
+        commit.trailers(),
+        vec![(
+            "Signed-off-by".to_string(),
+            "Test <test@example.com>".to_string()
+        )]
+    );
+
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"]);
+    let hash = mygit(&dir, &["commit-tree", tree.trim(), "-s", "-m", "tree"]);
+    let commit = mygit(&dir, &["cat-file", "-p", hash.trim()]);
+    assert!(commit.ends_with(&format!("\n\ntree\n\n{}\n", signoff)));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn empty_messages_and_empty_commits_need_flags() {
+    let dir = repository("commit-empty");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+
+    for message in ["", "  \n\n"] {
+        let output = run(&dir, &["commit", "-m", message]);
+        assert_eq!(output.status.code(), Some(128));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "fatal: Aborting commit due to empty commit message.\n"
+        );
+    }
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD"]), head);
+    mygit(&dir, &["commit", "--allow-empty-message", "-m", ""]);
+    assert_eq!(head_message(&dir), "");
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD~1"]), head);
+
+    // Nothing staged since.
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    let output = run(&dir, &["commit", "-m", "nothing"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: nothing to commit, working tree clean\n"
+    );
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD"]), head);
+    mygit(&dir, &["commit", "--allow-empty", "-m", "nothing"]);
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD~1"]), head);
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "HEAD^{tree}"]),
+        mygit(&dir, &["rev-parse", "HEAD~1^{tree}"])
+    );
+    assert_eq!(head_message(&dir), "nothing\n");
+
+    // Each flag allows only what it names.
+    assert!(!run(&dir, &["commit", "--allow-empty", "-m", ""])
+        .status
+        .success());
+    assert!(!run(&dir, &["commit", "--allow-empty-message", "-m", "x"])
+        .status
+        .success());
+    mygit(
+        &dir,
+        &["commit", "--allow-empty", "--allow-empty-message", "-m", ""],
+    );
+    assert_eq!(head_message(&dir), "");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn commits_move_the_branch_head_points_at() {
+    let dir = repository("commit-branch");
+    let rev = |name: &str| mygit(&dir, &["rev-parse", name]).trim().to_string();
+    // The first commit created the branch and has no parent.
+    let first = rev("HEAD");
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/refs/heads/main")).unwrap(),
+        format!("{}\n", first)
+    );
+    assert!(!mygit(&dir, &["cat-file", "-p", &first]).contains("\nparent "));
+
+    fs::write(dir.join("a"), "second\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    let second = rev("main");
+    assert_ne!(second, first);
+    assert_eq!(rev("main~1"), first);
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/HEAD")).unwrap(),
+        "ref: refs/heads/main\n"
+    );
+    assert!(!dir.join(".git/refs/heads/main.lock").exists());
+
+    // A detached HEAD moves by itself.
+    fs::write(dir.join(".git/HEAD"), format!("{}\n", first)).unwrap();
+    fs::write(dir.join("a"), "detached\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "detached"]);
+    assert_eq!(rev("HEAD~1"), first);
+    assert_eq!(rev("main"), second);
+
+    // While another update holds the lock, the branch is left alone.
+    fs::write(dir.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::write(dir.join(".git/refs/heads/main.lock"), "").unwrap();
+    fs::write(dir.join("a"), "locked\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    assert!(!run(&dir, &["commit", "-m", "locked"]).status.success());
+    assert_eq!(rev("main"), second);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Runs `commit` with `script` as the editor. The script gets the message
+// file as $1 and the directory it lives in as $SCRIPTS, which is outside
+// the repository so that nothing it leaves shows up as a change.
+fn commit_with_editor(dir: &Path, args: &[&str], script: &str) -> Output {
+    let scripts = dir.with_extension("editor");
+    fs::create_dir_all(&scripts).unwrap();
+    fs::write(scripts.join("editor.sh"), script).unwrap();
+    command(dir, args)
+        .env("SCRIPTS", &scripts)
+        .env("GIT_EDITOR", "sh \"$SCRIPTS/editor.sh\"")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn messages_are_written_in_the_editor() {
+    let dir = repository("commit-editor");
+    let scripts = dir.with_extension("editor");
+    fs::write(dir.join("a"), "changed\n").unwrap();
+    fs::write(dir.join("b"), "b\n").unwrap();
+    mygit(&dir, &["add", "a", "b"]);
+
+    let output = commit_with_editor(
+        &dir,
+        &["commit"],
+        "cp \"$1\" \"$SCRIPTS/template\"\n\
+         printf 'subject\\n# dropped\\n\\nbody\\n' >\"$1\"\n",
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        fs::read_to_string(scripts.join("template")).unwrap(),
+        "\n# Please enter the commit message for your changes. Lines starting\n\
+         # with '#' will be ignored, and an empty message aborts the commit.\n\
+         #\n\
+         # On branch main\n\
+         # Changes to be committed:\n\
+         #\tmodified:   a\n\
+         #\tnew file:   b\n\
+         #\n"
+    );
+    assert_eq!(head_message(&dir), "subject\n\nbody\n");
+
+    // Saving nothing but comments aborts, leaving HEAD where it was.
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    fs::write(dir.join("a"), "again\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    let output = commit_with_editor(&dir, &["commit"], "printf '# only\\n\\n' >\"$1\"\n");
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: Aborting commit due to empty commit message.\n"
+    );
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD"]), head);
+
+    // Nor is anything committed when the editor fails.
+    let output = commit_with_editor(&dir, &["commit"], "exit 1\n");
+    assert!(!output.status.success());
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD"]), head);
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_dir_all(&scripts).unwrap();
+}
+
+#[test]
+fn cleanup_modes_shape_the_message() {
+    let dir = repository("commit-cleanup");
+    let mut n = 0;
+    let mut commit = |args: &[&str]| {
+        n += 1;
+        fs::write(dir.join("a"), format!("{}\n", n)).unwrap();
+        mygit(&dir, &["add", "a"]);
+        mygit(&dir, &[&["commit"], args].concat());
+        head_message(&dir)
+    };
+    let message = "subject  \n# comment\n\n\n\nbody\n\n\n";
+    assert_eq!(commit(&["--cleanup=verbatim", "-m", message]), message);
+    assert_eq!(
+        commit(&["--cleanup=whitespace", "-m", message]),
+        "subject\n# comment\n\nbody\n"
+    );
+    assert_eq!(
+        commit(&["--cleanup=strip", "-m", message]),
+        "subject\n\nbody\n"
+    );
+    // Messages given with -m have no scissors line to cut at.
+    assert_eq!(
+        commit(&["--cleanup=scissors", "-m", message]),
+        "subject\n# comment\n\nbody\n"
+    );
+    assert_eq!(commit(&["-m", message]), "subject\n# comment\n\nbody\n");
+    fs::write(dir.join(".git/config"), "[commit]\n\tcleanup=verbatim\n").unwrap();
+    assert_eq!(commit(&["-m", message]), message);
+    assert_eq!(
+        commit(&["--cleanup=strip", "-m", message]),
+        "subject\n\nbody\n"
+    );
+
+    // Edited under scissors, the template's comments sit below the cut and
+    // comments above it stay.
+    fs::write(dir.join("a"), "edited\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    let output = commit_with_editor(
+        &dir,
+        &["commit", "--cleanup=scissors"],
+        "{ printf 'subject\\n# kept\\n'; cat \"$1\"; } >\"$SCRIPTS/edited\"\n\
+         cp \"$SCRIPTS/edited\" \"$1\"\n",
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(head_message(&dir), "subject\n# kept\n");
+
+    let output = run(&dir, &["commit", "--cleanup=bogus", "-m", "x"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: Invalid cleanup mode bogus\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_dir_all(dir.with_extension("editor")).unwrap();
+}
+
+#[test]
+fn the_user_comes_from_gitconfig_or_the_environment() {
+    let dir = repository("commit-user");
+    let author = || {
+        let log = mygit(&dir, &["log"]);
+        log.lines()
+            .find_map(|line| line.strip_prefix("Author: "))
+            .unwrap()
+            .to_string()
+    };
+    let commit = |message: &str| {
+        fs::write(dir.join("a"), message).unwrap();
+        mygit(&dir, &["add", "a"]);
+        command(&dir, &["commit", "-m", message])
+    };
+    let stderr = |output: &Output| String::from_utf8_lossy(&output.stderr).to_string();
+
+    let gitconfig = dir.join(".gitconfig");
+    for (config, error) in [
+        (
+            None,
+            format!(
+                "fatal: {} does not exist; create it with user.name and user.email set\n",
+                gitconfig.display()
+            ),
+        ),
+        (
+            Some("[user]\nemail = test@example.com\n"),
+            format!("fatal: user.name is not set in {}\n", gitconfig.display()),
+        ),
+        (
+            Some("[user]\nname = Test\n"),
+            format!("fatal: user.email is not set in {}\n", gitconfig.display()),
+        ),
+    ] {
+        match config {
+            Some(config) => fs::write(&gitconfig, config).unwrap(),
+            None => fs::remove_file(&gitconfig).unwrap(),
+        }
+        let output = commit("second").output().unwrap();
+        assert_eq!(output.status.code(), Some(128));
+        assert_eq!(stderr(&output), error);
+        assert_eq!(author(), "Test <test@example.com>");
+    }
+
+    // Each variable overrides its own half of the config.
+    let output = commit("env")
+        .env("GIT_AUTHOR_EMAIL", "env@example.com")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(author(), "Test <env@example.com>");
+
+    // With both set, no config is needed at all.
+    fs::remove_file(&gitconfig).unwrap();
+    let output = commit("both")
+        .env("GIT_AUTHOR_NAME", "Env")
+        .env("GIT_AUTHOR_EMAIL", "env@example.com")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(author(), "Env <env@example.com>");
+    fs::remove_dir_all(&dir).unwrap();
+}
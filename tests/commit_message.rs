@@ -0,0 +1,71 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::clock::FixedClock;
+use mygit::object::{Object, Timestamp, User};
+use mygit::repository::Repository;
+use std::fs;
+use std::process::Command;
+
+// Messages gain a final newline when they lack one, and the commits hash
+// as git's do.
+#[test]
+fn trailing_newlines_match_git() {
+    let dir = repository("commit-message");
+    let repo = Repository::open(&dir)
+        .unwrap()
+        .with_clock(FixedClock(Timestamp::new(1700000000, 3600)));
+    let user = User::new("Test", "test@example.com");
+    let tree = repo.write_object(&Object::Tree(Vec::new())).unwrap();
+
+    let git_dir = dir.join("git");
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(&git_dir)
+            .env("HOME", &dir)
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_AUTHOR_DATE", "1700000000 +0100")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_DATE", "1700000000 +0100")
+            .output()
+    };
+    fs::create_dir(&git_dir).unwrap();
+    let has_git = git(&["init", "-q"]).is_ok_and(|output| output.status.success());
+
+    for (message, stored) in [
+        ("subject", "subject\n"),
+        ("subject\n", "subject\n"),
+        ("subject\n\n\n", "subject\n\n\n"),
+        ("", ""),
+        ("subject\n\nbody", "subject\n\nbody\n"),
+        ("subject\n\nbody\n", "subject\n\nbody\n"),
+    ] {
+        let hash = repo
+            .commit_tree(&tree, Vec::new(), &user, message.to_string())
+            .unwrap();
+        let Object::Commit { message: read, .. } = repo.read_object(&hash).unwrap() else {
+            panic!("not a commit");
+        };
+        assert_eq!(read, stored, "{:?}", message);
+        assert!(mygit(&dir, &["cat-file", "-p", &hash]).ends_with(&format!("\n\n{}", stored)));
+
+        if has_git {
+            let output = git(&["commit-tree", &tree, "-m", message]).unwrap();
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout).trim(),
+                hash,
+                "{:?}",
+                message
+            );
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
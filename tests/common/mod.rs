@@ -0,0 +1,102 @@
+// Each test crate uses only some of these helpers.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+// The binary set up to run in `dir`, which also serves as $HOME so the user
+// comes from the .gitconfig `repository` writes there. Variables the binary
+// reads are cleared so the caller's environment cannot change the outcome.
+pub fn command(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_main"));
+    command.args(args).current_dir(dir).env("HOME", dir);
+    for name in [
+        "GIT_AUTHOR_NAME",
+        "GIT_AUTHOR_EMAIL",
+        "GIT_PAGER",
+        "PAGER",
+        "GIT_NO_REPLACE_OBJECTS",
+    ] {
+        command.env_remove(name);
+    }
+    command
+}
+
+pub fn run(dir: &Path, args: &[&str]) -> Output {
+    command(dir, args).output().unwrap()
+}
+
+// Like `run`, but the command must succeed; returns its stdout.
+pub fn mygit(dir: &Path, args: &[&str]) -> String {
+    let output = run(dir, args);
+    assert!(
+        output.status.success(),
+        "mygit {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Like `run`, with `input` fed to the command's stdin.
+pub fn run_with_input(dir: &Path, args: &[&str], input: &str) -> Output {
+    let mut child = command(dir, args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// Like `mygit`, with `input` fed to the command's stdin.
+pub fn mygit_with_input(dir: &Path, args: &[&str], input: &str) -> String {
+    let output = run_with_input(dir, args, input);
+    assert!(
+        output.status.success(),
+        "mygit {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// A fresh repository with one commit, "first", holding `a`.
+pub fn repository(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mygit-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join(".gitconfig"),
+        "[user]\nname = Test\nemail = test@example.com\n",
+    )
+    .unwrap();
+    fs::write(dir.join(".gitignore"), ".gitconfig\n").unwrap();
+    mygit(&dir, &["init"]);
+    fs::write(dir.join("a"), "a\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "first"]);
+    dir
+}
+
+// Writes `files`, deleting those without content, and commits them as
+// `message`; returns the new commit's hash.
+pub fn commit(dir: &Path, files: &[(&str, Option<&[u8]>)], message: &str) -> String {
+    for (path, content) in files {
+        match content {
+            Some(content) => fs::write(dir.join(path), content).unwrap(),
+            None => fs::remove_file(dir.join(path)).unwrap(),
+        }
+        mygit(dir, &["add", path]);
+    }
+    mygit(dir, &["commit", "-m", message]);
+    mygit(dir, &["rev-parse", "HEAD"]).trim().to_string()
+}
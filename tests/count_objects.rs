@@ -0,0 +1,40 @@
+mod common;
+
+use common::{mygit, repository};
+use std::fs;
+
+#[test]
+fn human_readable_histogram() {
+    let dir = repository("count-objects");
+    // Commit, tree and two small blobs so far.
+    for size in [5 << 10, 50 << 10, 2 << 20] {
+        fs::write(dir.join("blob"), "x".repeat(size)).unwrap();
+        mygit(&dir, &["hash-object", "-w", "blob"]);
+    }
+    fs::remove_file(dir.join("blob")).unwrap();
+
+    let output = mygit(&dir, &["count-objects", "-H"]);
+    let (summary, histogram) = output.split_once('\n').unwrap();
+    assert!(summary.starts_with("7 objects, "), "{}", summary);
+    assert!(summary.ends_with(" KiB"), "{}", summary);
+    assert_eq!(
+        histogram,
+        "size histogram:\n  \
+         < 1 KiB            4\n  \
+         1 KiB - 10 KiB     1\n  \
+         10 KiB - 100 KiB   1\n  \
+         100 KiB - 1 MiB    0\n  \
+         1 MiB - 10 MiB     1\n  \
+         >= 10 MiB          0\n"
+    );
+
+    let verbose = mygit(&dir, &["count-objects", "-v", "--human-readable"]);
+    assert!(verbose.starts_with("count: 7\nsize: "), "{}", verbose);
+    assert!(verbose.contains("\nsize-pack: 0 bytes\n"), "{}", verbose);
+    assert!(verbose.ends_with(histogram));
+
+    let plain = mygit(&dir, &["count-objects"]);
+    assert!(plain.starts_with("7 objects, ") && plain.ends_with(" kilobytes\n"));
+    assert!(!plain.contains("histogram"));
+    fs::remove_dir_all(&dir).unwrap();
+}
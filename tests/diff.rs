@@ -0,0 +1,189 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn blob(dir: &Path, content: &[u8]) -> String {
+    fs::write(dir.join("blob"), content).unwrap();
+    let hash = mygit(dir, &["hash-object", "-w", "blob"])
+        .trim()
+        .to_string();
+    fs::remove_file(dir.join("blob")).unwrap();
+    hash
+}
+
+#[test]
+fn blob_diffs() {
+    let dir = repository("diff-blobs");
+    let two = blob(&dir, b"a\nb\n");
+    let three = blob(&dir, b"a\nb\nc\n");
+    let diff = |a: &str, b: &str| mygit(&dir, &["--no-pager", "diff", a, b]);
+
+    assert_eq!(
+        diff(&two, &three),
+        format!(
+            "--- a/{}\n+++ b/{}\n@@ -1,2 +1,3 @@\n a\n b\n+c\n",
+            two, three
+        )
+    );
+    assert_eq!(
+        diff(&three, &two),
+        format!(
+            "--- a/{}\n+++ b/{}\n@@ -1,3 +1,2 @@\n a\n b\n-c\n",
+            three, two
+        )
+    );
+    assert_eq!(diff(&two, &two), "");
+
+    let binary = blob(&dir, b"a\0b\n");
+    assert_eq!(
+        diff(&two, &binary),
+        format!("Binary files a/{} and b/{} differ\n", two, binary)
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tree_diffs() {
+    let dir = repository("diff-trees");
+    fs::write(dir.join("a"), "changed\n").unwrap();
+    fs::create_dir(dir.join("d")).unwrap();
+    fs::write(dir.join("d/new"), "new\n").unwrap();
+    fs::remove_file(dir.join(".gitignore")).unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "second"]);
+
+    let old = mygit(&dir, &["rev-parse", "HEAD~1^{tree}"]);
+    let new = mygit(&dir, &["rev-parse", "HEAD^{tree}"]);
+    assert_eq!(
+        mygit(&dir, &["diff", "--name-status", old.trim(), new.trim()]),
+        "A\t.gitconfig\nD\t.gitignore\nM\ta\nA\td/new\n"
+    );
+    let patch = mygit(&dir, &["--no-pager", "diff", "HEAD~1", "HEAD"]);
+    assert!(patch.contains("diff --git a/a b/a\n"));
+    assert!(patch.contains("-a\n+changed\n"));
+    assert_eq!(mygit(&dir, &["diff", old.trim(), old.trim()]), "");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn context_lines() {
+    let dir = repository("diff-context");
+    let lines: String = (1..=10).map(|n| format!("{}\n", n)).collect();
+    let old = blob(&dir, lines.as_bytes());
+    let new = blob(&dir, lines.replace("5\n", "five\n").as_bytes());
+    let diff = |context: &str| mygit(&dir, &["--no-pager", "diff", context, &old, &new]);
+    let header = format!("--- a/{}\n+++ b/{}\n", old, new);
+
+    assert_eq!(diff("-U0"), format!("{}@@ -5 +5 @@\n-5\n+five\n", header));
+    assert_eq!(
+        diff("-U1"),
+        format!("{}@@ -4,3 +4,3 @@\n 4\n-5\n+five\n 6\n", header)
+    );
+    assert_eq!(
+        diff("-U5"),
+        format!(
+            "{}@@ -1,10 +1,10 @@\n 1\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n 9\n 10\n",
+            header
+        )
+    );
+    assert_eq!(diff("--unified=5"), diff("-U5"));
+    assert_eq!(
+        mygit(&dir, &["--no-pager", "diff", &old, &new]),
+        format!(
+            "{}@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n",
+            header
+        )
+    );
+
+    // Two changes four lines apart share a hunk with three lines of context
+    // but not with one.
+    let twice = blob(
+        &dir,
+        lines
+            .replace("2\n", "two\n")
+            .replace("7\n", "seven\n")
+            .as_bytes(),
+    );
+    let hunks = |context: &str| {
+        mygit(&dir, &["--no-pager", "diff", context, &old, &twice])
+            .matches("@@ -")
+            .count()
+    };
+    assert_eq!(hunks("-U3"), 1);
+    assert_eq!(hunks("-U1"), 2);
+    assert_eq!(hunks("-U0"), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Outside any repository, compared with git's own output where git is
+// installed.
+#[test]
+fn paths_on_disk_are_diffed_without_a_repository() {
+    let dir = std::env::temp_dir().join(format!("mygit-no-index-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("old/sub")).unwrap();
+    fs::create_dir_all(dir.join("new/sub")).unwrap();
+    fs::write(dir.join("old/f"), "one\ntwo\nthree\n").unwrap();
+    fs::write(dir.join("new/f"), "one\n2\nthree\n").unwrap();
+    fs::write(dir.join("old/sub/gone"), "gone\n").unwrap();
+    fs::write(dir.join("new/sub/added"), "added\n").unwrap();
+    fs::write(dir.join("old/same"), "same\n").unwrap();
+    fs::write(dir.join("new/same"), "same\n").unwrap();
+    let diff = |old: &str, new: &str| {
+        let output = run(&dir, &["--no-pager", "diff", "--no-index", old, new]);
+        let git = Command::new("git")
+            .args(["--no-pager", "diff", "--no-index", old, new])
+            .current_dir(&dir)
+            .env("HOME", &dir)
+            .output();
+        if let Ok(git) = git {
+            assert_eq!(output.stdout, git.stdout, "{} {}", old, new);
+            assert_eq!(output.status.code(), git.status.code(), "{} {}", old, new);
+        }
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    };
+
+    let file = "diff --git a/old/f b/new/f\n\
+                index 4cb29ea..f04eb26 100644\n\
+                --- a/old/f\n\
+                +++ b/new/f\n\
+                @@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n";
+    assert_eq!(diff("old/f", "new/f"), (Some(1), file.to_string()));
+    assert_eq!(diff("old/same", "new/same"), (Some(0), String::new()));
+    // A file against a directory is compared with the file of that name.
+    assert_eq!(diff("old/f", "new"), (Some(1), file.to_string()));
+
+    let (code, trees) = diff("old", "new");
+    assert_eq!(code, Some(1));
+    assert_eq!(
+        trees,
+        format!(
+            "{}\
+             diff --git a/new/sub/added b/new/sub/added\n\
+             new file mode 100644\n\
+             index 0000000..d5f7fc3\n\
+             --- /dev/null\n\
+             +++ b/new/sub/added\n\
+             @@ -0,0 +1 @@\n+added\n\
+             diff --git a/old/sub/gone b/old/sub/gone\n\
+             deleted file mode 100644\n\
+             index 286c5f5..0000000\n\
+             --- a/old/sub/gone\n\
+             +++ /dev/null\n\
+             @@ -1 +0,0 @@\n-gone\n",
+            file
+        )
+    );
+    assert_eq!(diff("old", "old"), (Some(0), String::new()));
+    assert_eq!(
+        run(&dir, &["diff", "--no-index", "old"]).status.code(),
+        Some(129)
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::{commit, mygit, mygit_with_input, repository, run};
+use std::fs;
+
+#[test]
+fn objects_and_remote_refs_arrive() {
+    let src = repository("fetch-src");
+    let dst = repository("fetch-dst");
+    // The first commit's objects are packed, the rest stay loose.
+    let first: Vec<String> = ["HEAD", "HEAD^{tree}", "HEAD:a", "HEAD:.gitignore"]
+        .iter()
+        .map(|rev| mygit(&src, &["rev-parse", rev]))
+        .collect();
+    fs::create_dir_all(src.join(".git/objects/pack")).unwrap();
+    mygit_with_input(
+        &src,
+        &["pack-objects", ".git/objects/pack/pack"],
+        &first.concat(),
+    );
+    mygit(&src, &["prune-packed"]);
+    commit(&src, &[("a", Some(b"second\n"))], "second");
+    mygit(&src, &["branch", "feature"]);
+    mygit(&src, &["tag", "v1"]);
+    fs::write(
+        dst.join(".git/config"),
+        format!("[remote \"origin\"]\n\turl = {}\n", src.display()),
+    )
+    .unwrap();
+
+    let main = mygit(&src, &["rev-parse", "main"]);
+    assert_eq!(
+        mygit(&dst, &["fetch", "origin"]),
+        format!(
+            "From {}\n \
+             * [new branch]      feature    -> origin/feature\n \
+             * [new branch]      main       -> origin/main\n \
+             * [new tag]         v1         -> v1\n",
+            src.display()
+        )
+    );
+    assert_eq!(mygit(&dst, &["rev-parse", "origin/main"]), main);
+    assert_eq!(
+        mygit(&dst, &["rev-parse", "refs/remotes/origin/feature"]),
+        main
+    );
+    assert_eq!(mygit(&dst, &["rev-parse", "v1"]), main);
+    assert_eq!(
+        mygit(&dst, &["cat-file", "-p", "origin/main:a"]),
+        "second\n"
+    );
+    assert_eq!(mygit(&dst, &["cat-file", "-p", "origin/main~1:a"]), "a\n");
+    assert!(mygit(&dst, &["fsck"]).is_empty());
+    // Local branches are left alone.
+    assert_ne!(mygit(&dst, &["rev-parse", "main"]), main);
+    assert!(!run(&dst, &["rev-parse", "feature"]).status.success());
+
+    // Only what changed is reported.
+    assert_eq!(
+        mygit(&dst, &["fetch", "origin"]),
+        format!("From {}\n", src.display())
+    );
+    commit(&src, &[("a", Some(b"third\n"))], "third");
+    let third = mygit(&src, &["rev-parse", "main"]);
+    fs::write(src.join(".git/refs/heads/feature"), &first[0]).unwrap();
+
+    // An unconfigured remote is named after its path.
+    fs::remove_file(dst.join(".git/refs/tags/v1")).unwrap();
+    assert_eq!(
+        mygit(&dst, &["fetch", &src.display().to_string()]),
+        format!(
+            "From {0}\n \
+             * [new branch]      feature    -> {1}/feature\n \
+             * [new branch]      main       -> {1}/main\n \
+             * [new tag]         v1         -> v1\n",
+            src.display(),
+            src.file_name().unwrap().to_string_lossy()
+        )
+    );
+    // A branch moved off its history is a forced update.
+    assert_eq!(
+        mygit(&dst, &["fetch", "origin"]),
+        format!(
+            "From {}\n \
+             + {}...{} feature    -> origin/feature  (forced update)\n   \
+             {}..{}  main       -> origin/main\n",
+            src.display(),
+            &main[..7],
+            &first[0][..7],
+            &main[..7],
+            &third[..7]
+        )
+    );
+    assert_eq!(mygit(&dst, &["rev-parse", "origin/main"]), third);
+
+    assert!(!run(&dst, &["fetch", "/no/such/repository"])
+        .status
+        .success());
+    assert_eq!(run(&dst, &["fetch"]).status.code(), Some(129));
+    fs::remove_dir_all(&src).unwrap();
+    fs::remove_dir_all(&dst).unwrap();
+}
+
+// A pack need not hold what its objects point at; the rest is still loose.
+#[test]
+fn partial_packs_are_walked_through() {
+    let src = repository("fetch-partial-src");
+    let dst = repository("fetch-partial-dst");
+    commit(&src, &[("a", Some(b"second\n"))], "second");
+    let head = mygit(&src, &["rev-parse", "HEAD"]);
+    fs::create_dir_all(src.join(".git/objects/pack")).unwrap();
+    mygit_with_input(&src, &["pack-objects", ".git/objects/pack/pack"], &head);
+    mygit(&src, &["prune-packed"]);
+
+    mygit(&dst, &["fetch", &src.display().to_string()]);
+    let remote = format!("{}/main", src.file_name().unwrap().to_string_lossy());
+    assert_eq!(mygit(&dst, &["rev-parse", &remote]), head);
+    assert_eq!(
+        mygit(&dst, &["cat-file", "-p", &format!("{}:a", remote)]),
+        "second\n"
+    );
+    assert!(mygit(&dst, &["fsck"]).is_empty());
+    fs::remove_dir_all(&src).unwrap();
+    fs::remove_dir_all(&dst).unwrap();
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn binary_changes_round_trip_through_format_patch_and_apply() {
+    let dir = repository("format-patch");
+    let old: Vec<u8> = (0..=255).collect();
+    // Nothing like the old content, so git too sends it whole rather than
+    // as a delta.
+    let new: Vec<u8> = old.iter().rev().map(|b| b ^ 0x5a).collect();
+    fs::write(dir.join("image"), &old).unwrap();
+    fs::write(dir.join("a"), "a\nb\n").unwrap();
+    mygit(&dir, &["add", "image", "a"]);
+    mygit(
+        &dir,
+        &[
+            "commit",
+            "-m",
+            "Add an image: the first one\n\nWith a body.",
+        ],
+    );
+    fs::write(dir.join("image"), &new).unwrap();
+    mygit(&dir, &["add", "image"]);
+    mygit(&dir, &["commit", "-m", "Touch up the image"]);
+
+    assert_eq!(
+        mygit(&dir, &["format-patch", "-o", "out", "-2"]),
+        "out/0001-Add-an-image-the-first-one.patch\nout/0002-Touch-up-the-image.patch\n"
+    );
+    let first = fs::read_to_string(dir.join("out/0001-Add-an-image-the-first-one.patch")).unwrap();
+    let head = mygit(&dir, &["rev-parse", "HEAD~1"]);
+    assert!(first.starts_with(&format!(
+        "From {} Mon Sep 17 00:00:00 2001\nFrom: Test <test@example.com>\nDate: ",
+        head.trim()
+    )));
+    assert!(first.contains(
+        "Subject: [PATCH 1/2] Add an image: the first one\n\n\
+         With a body.\n\
+         ---\n \
+         a     |   1 +\n \
+         image | Bin 0 -> 256 bytes\n \
+         2 files changed, 1 insertion(+)\n \
+         create mode 100644 image\n\n\
+         diff --git a/a b/a\n"
+    ));
+    assert!(first.contains("GIT binary patch\nliteral 256\n"));
+    // The same commits named by where they start.
+    let since = mygit(&dir, &["format-patch", "--stdout", "HEAD~2"]);
+    assert_eq!(since, mygit(&dir, &["format-patch", "--stdout", "-2"]));
+    let git = Command::new("git")
+        .args(["format-patch", "--stdout", "-2"])
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .output();
+    if let Some(git) = git.ok().filter(|output| output.status.success()) {
+        let version = format!("\n-- \n{}\n", env!("CARGO_PKG_VERSION"));
+        let git = String::from_utf8(git.stdout).unwrap();
+        let git_version = git.split("\n-- \n").nth(1).unwrap().lines().next().unwrap();
+        assert_eq!(
+            since.replace(&version, &format!("\n-- \n{}\n", git_version)),
+            git
+        );
+    }
+
+    // Back to the first commit's working tree, then patched up one by one.
+    fs::write(dir.join("a"), "a\n").unwrap();
+    fs::remove_file(dir.join("image")).unwrap();
+    mygit(
+        &dir,
+        &["apply", "out/0001-Add-an-image-the-first-one.patch"],
+    );
+    assert_eq!(fs::read(dir.join("image")).unwrap(), old);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\nb\n");
+    mygit(&dir, &["apply", "out/0002-Touch-up-the-image.patch"]);
+    assert_eq!(fs::read(dir.join("image")).unwrap(), new);
+    assert!(!run(&dir, &["apply", "out/0002-Touch-up-the-image.patch"])
+        .status
+        .success());
+
+    assert_eq!(run(&dir, &["format-patch"]).status.code(), Some(129));
+    assert_eq!(run(&dir, &["format-patch", "-o"]).status.code(), Some(129));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+
+#[test]
+fn fsck_reports_mismatched_and_missing_objects() {
+    let dir = repository("fsck");
+    assert_eq!(mygit(&dir, &["fsck"]), "");
+
+    let blob = mygit(&dir, &["rev-parse", "HEAD:a"]).trim().to_string();
+    let tree = mygit(&dir, &["rev-parse", "HEAD:"]).trim().to_string();
+    let object = |hash: &str| dir.join(".git/objects").join(&hash[..2]).join(&hash[2..]);
+
+    // Storing the blob under another name breaks both the copy and the
+    // tree that pointed at the original.
+    let wrong = format!(
+        "{}{}",
+        &blob[..39],
+        if blob.ends_with('0') { "1" } else { "0" }
+    );
+    fs::create_dir_all(object(&wrong).parent().unwrap()).unwrap();
+    fs::rename(object(&blob), object(&wrong)).unwrap();
+
+    let output = run(&dir, &["fsck"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "error: hash mismatch for {} (content hashes to {})\n",
+            wrong, blob
+        )),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains(&format!(
+            "error: broken link from tree {} to blob {}\n",
+            tree, blob
+        )),
+        "{}",
+        stderr
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
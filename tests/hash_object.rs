@@ -0,0 +1,50 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+use std::path::Path;
+
+// The hash printed, and whatever was warned about.
+fn hash_object(dir: &Path, args: &[&str]) -> (String, String) {
+    let output = run(dir, args);
+    assert!(output.status.success());
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn crlf_is_warned_about_only_when_asked() {
+    let dir = repository("hash-object-crlf");
+    fs::write(dir.join("dos.txt"), "one\r\ntwo\r\n").unwrap();
+    fs::write(dir.join("unix.txt"), "one\ntwo\n").unwrap();
+    fs::write(dir.join("mac.txt"), "one\rtwo\r").unwrap();
+    fs::write(dir.join("binary"), b"\0one\r\ntwo\r\n").unwrap();
+
+    let (hash, warning) = hash_object(&dir, &["hash-object", "--warn-crlf", "dos.txt"]);
+    assert_eq!(
+        warning,
+        "warning: dos.txt has CRLF line endings, which will be stored as-is\n"
+    );
+    // The content is hashed unchanged either way.
+    assert_eq!(
+        hash_object(&dir, &["hash-object", "dos.txt"]),
+        (hash, String::new())
+    );
+
+    for path in ["unix.txt", "mac.txt", "binary"] {
+        let (_, warning) = hash_object(&dir, &["hash-object", "--warn-crlf", path]);
+        assert_eq!(warning, "", "{}", path);
+    }
+
+    // Git converts line endings itself once core.autocrlf is set.
+    fs::write(dir.join(".git/config"), "[core]\n\tautocrlf = false\n").unwrap();
+    let (hash, warning) = hash_object(&dir, &["hash-object", "-w", "--warn-crlf", "dos.txt"]);
+    assert_eq!(warning, "");
+    assert_eq!(
+        mygit(&dir, &["cat-file", "-p", hash.trim()]),
+        "one\r\ntwo\r\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::object::Object;
+use mygit::repository::Repository;
+use std::fs;
+
+#[test]
+fn born_unborn_and_detached_heads() {
+    let dir = repository("head-commit");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    let repo = Repository::open(&dir).unwrap();
+    let Some((hash, Object::Commit { message, .. })) = repo.head_commit().unwrap() else {
+        panic!("no head commit");
+    };
+    assert_eq!(
+        (hash.as_str(), message.as_str()),
+        (head.as_str(), "first\n")
+    );
+
+    // Detached at the commit.
+    fs::write(dir.join(".git/HEAD"), format!("{}\n", head)).unwrap();
+    assert_eq!(repo.head_commit().unwrap().unwrap().0, head);
+
+    // A branch with no commits yet.
+    fs::write(dir.join(".git/HEAD"), "ref: refs/heads/unborn\n").unwrap();
+    assert!(repo.head_commit().unwrap().is_none());
+
+    // HEAD naming something other than a commit is an error, not unborn.
+    let tree = mygit(&dir, &["rev-parse", "main^{tree}"]);
+    fs::write(dir.join(".git/HEAD"), tree).unwrap();
+    assert!(repo.head_commit().is_err());
+
+    let empty = dir.join("empty");
+    fs::create_dir(&empty).unwrap();
+    mygit(&empty, &["init"]);
+    assert!(Repository::open(&empty)
+        .unwrap()
+        .head_commit()
+        .unwrap()
+        .is_none());
+    fs::remove_dir_all(&dir).unwrap();
+}
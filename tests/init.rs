@@ -0,0 +1,60 @@
+mod common;
+
+use common::{mygit, run};
+use std::fs;
+
+#[test]
+fn directories_are_created_and_reinitialized() {
+    let dir = std::env::temp_dir().join(format!("mygit-init-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let head = |repo: &str| fs::read_to_string(dir.join(repo).join(".git/HEAD")).unwrap();
+
+    // The directory is created if needed, with everything a repository needs.
+    assert_eq!(
+        mygit(&dir, &["init", "repo"]),
+        "Initialized git directory\n"
+    );
+    for path in ["objects", "refs/heads", "refs/tags"] {
+        assert!(dir.join("repo/.git").join(path).is_dir(), "{}", path);
+    }
+    assert_eq!(head("repo"), "ref: refs/heads/main\n");
+
+    // Initializing again keeps what is there.
+    fs::write(dir.join("repo/.git/HEAD"), "ref: refs/heads/other\n").unwrap();
+    fs::write(dir.join("repo/.git/refs/tags/v1"), "kept\n").unwrap();
+    assert_eq!(
+        mygit(&dir, &["init", "repo"]),
+        "Reinitialized existing git directory\n"
+    );
+    assert_eq!(head("repo"), "ref: refs/heads/other\n");
+    assert_eq!(
+        fs::read_to_string(dir.join("repo/.git/refs/tags/v1")).unwrap(),
+        "kept\n"
+    );
+    let output = run(&dir, &["init", "-b", "topic", "repo"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "warning: re-init: ignored --initial-branch\n"
+    );
+    assert_eq!(head("repo"), "ref: refs/heads/other\n");
+
+    // Without a directory the current one is initialized.
+    fs::create_dir(dir.join("here")).unwrap();
+    mygit(&dir.join("here"), &["init"]);
+    assert_eq!(head("here"), "ref: refs/heads/main\n");
+
+    // The initial branch can be chosen either way.
+    mygit(&dir, &["init", "-b", "topic", "short"]);
+    assert_eq!(head("short"), "ref: refs/heads/topic\n");
+    mygit(&dir, &["init", "--initial-branch=trunk", "long"]);
+    assert_eq!(head("long"), "ref: refs/heads/trunk\n");
+
+    let output = run(&dir, &["init", "-b", "", "empty"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(!dir.join("empty").exists());
+    assert_eq!(run(&dir, &["init", "-b"]).status.code(), Some(129));
+    assert_eq!(run(&dir, &["init", "a", "b"]).status.code(), Some(129));
+    fs::remove_dir_all(&dir).unwrap();
+}
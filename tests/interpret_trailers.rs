@@ -0,0 +1,43 @@
+mod common;
+
+use common::{mygit_with_input, repository, run};
+use std::fs;
+
+#[test]
+fn trailers_are_added_once() {
+    let dir = repository("interpret-trailers");
+    let trailers = |args: &[&str], input: &str| {
+        let mut all = vec!["interpret-trailers"];
+        all.extend(args);
+        mygit_with_input(&dir, &all, input)
+    };
+
+    assert_eq!(
+        trailers(&["--trailer", "Fixes: 1"], "Subject\n"),
+        "Subject\n\nFixes: 1\n"
+    );
+    assert_eq!(
+        trailers(
+            &["--trailer", "Fixes=2", "--trailer", "Acked-by: A"],
+            "Subject\n\nBody.\n\nFixes: 1\n"
+        ),
+        "Subject\n\nBody.\n\nFixes: 1\nFixes: 2\nAcked-by: A\n"
+    );
+    // The same key and value are not added twice.
+    assert_eq!(
+        trailers(
+            &["--trailer", "fixes: 1", "--trailer", "Fixes: 1"],
+            "Subject\n\nFixes: 1\n"
+        ),
+        "Subject\n\nFixes: 1\n"
+    );
+    assert_eq!(
+        trailers(&["--trim-empty"], "Subject\n\nFixes:\nAcked-by: A\n"),
+        "Subject\n\nAcked-by: A\n"
+    );
+    assert_eq!(trailers(&[], "Subject\n\n"), "Subject\n");
+
+    let output = run(&dir, &["interpret-trailers", "--trailer"]);
+    assert_eq!(output.status.code(), Some(129));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{mygit, mygit_with_input, repository};
+use flate2::write::ZlibEncoder;
+use mygit::object::hash_raw;
+use mygit::repository::Repository;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+fn entry(mode: &str, name: &str, hash: &str) -> Vec<u8> {
+    let mut entry = format!("{} {}\0", mode, name).into_bytes();
+    entry.extend(
+        (0..40)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hash[i..i + 2], 16).unwrap()),
+    );
+    entry
+}
+
+// Stores a tree exactly as given, as some old tools wrote them.
+fn write_raw_tree(dir: &Path, content: &[u8]) -> String {
+    let hash = hash_raw("tree", content);
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    write!(encoder, "tree {}\0", content.len()).unwrap();
+    encoder.write_all(content).unwrap();
+    let objects = dir.join(".git/objects").join(&hash[..2]);
+    fs::create_dir_all(&objects).unwrap();
+    fs::write(objects.join(&hash[2..]), encoder.finish().unwrap()).unwrap();
+    hash
+}
+
+#[test]
+fn legacy_modes_survive_a_rewrite() {
+    let dir = repository("legacy-modes");
+    let a = mygit(&dir, &["rev-parse", "HEAD:a"]).trim().to_string();
+    let sub = mygit(&dir, &["rev-parse", "HEAD^{tree}"])
+        .trim()
+        .to_string();
+    let raw = [entry("100664", "a", &a), entry("040000", "sub", &sub)].concat();
+    let legacy = write_raw_tree(&dir, &raw);
+
+    // Listed in the canonical form...
+    let listing = mygit(&dir, &["ls-tree", &legacy]);
+    assert_eq!(
+        listing,
+        format!("100644 blob {}\ta\n040000 tree {}\tsub\n", a, sub)
+    );
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "-r", "--name-only", &legacy]),
+        "a\nsub/.gitignore\nsub/a\n"
+    );
+    // ...but read back byte for byte, so the hash does not change.
+    let repo = Repository::open(&dir).unwrap();
+    let tree = repo.read_object(&legacy).unwrap();
+    assert_eq!(tree.content().unwrap(), raw);
+    assert_eq!(repo.write_object(&tree).unwrap(), legacy);
+    // Building the same tree afresh gives git's canonical one instead.
+    let canonical = mygit_with_input(&dir, &["mktree"], &listing);
+    assert_ne!(canonical.trim(), legacy);
+    assert_eq!(mygit(&dir, &["ls-tree", canonical.trim()]), listing);
+
+    let commit = mygit(
+        &dir,
+        &["commit-tree", &legacy, "-p", "HEAD", "-m", "legacy"],
+    );
+    fs::write(dir.join(".git/refs/heads/legacy"), &commit).unwrap();
+    let tip = repo
+        .rewrite_history("refs/heads/legacy", |tree| {
+            let object = repo.read_object(tree)?;
+            Ok(repo.write_object(&object)?)
+        })
+        .unwrap();
+    assert_eq!(tip, commit.trim());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::object::{Object, Timestamp, User};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// Writes a commit on the first commit's tree with the given author and
+// committer times.
+fn commit(dir: &Path, message: &str, parents: &[&str], author: i64, committer: i64) -> String {
+    let tree = mygit(dir, &["rev-parse", "HEAD^{tree}"]);
+    let user = User::new("Test", "test@example.com");
+    Object::Commit {
+        tree: tree.trim().to_string(),
+        parents: parents.iter().map(|parent| parent.to_string()).collect(),
+        author: user.clone(),
+        author_timestamp: Timestamp::new(author, 0),
+        committer: user,
+        committer_timestamp: Timestamp::new(committer, 0),
+        message: format!("{}\n", message),
+    }
+    .write_in(&dir.join(".git/objects"))
+    .unwrap()
+}
+
+fn subjects(dir: &Path, args: &[&str]) -> Vec<String> {
+    let mut all = vec!["--no-pager", "log"];
+    all.extend(args);
+    let log = mygit(dir, &all);
+    log.lines()
+        .filter_map(|line| line.strip_prefix("    "))
+        .map(str::to_string)
+        .collect()
+}
+
+// root - a - b
+//    \        \
+//     c - d - merge
+// The side branch a - b has the newest author dates but the oldest
+// committer dates.
+#[test]
+fn topo_and_date_orders_differ() {
+    let dir = repository("log-order");
+    let root = commit(&dir, "root", &[], 100, 100);
+    let a = commit(&dir, "a", &[&root], 600, 200);
+    let b = commit(&dir, "b", &[&a], 700, 400);
+    let c = commit(&dir, "c", &[&root], 300, 300);
+    let d = commit(&dir, "d", &[&c], 400, 500);
+    let merge = commit(&dir, "merge", &[&d, &b], 800, 800);
+
+    assert_eq!(
+        subjects(&dir, &[&merge]),
+        ["merge", "b", "a", "d", "c", "root"]
+    );
+    assert_eq!(
+        subjects(&dir, &["--topo-order", &merge]),
+        subjects(&dir, &[&merge])
+    );
+    assert_eq!(
+        subjects(&dir, &["--date-order", &merge]),
+        ["merge", "d", "b", "c", "a", "root"]
+    );
+    assert_eq!(
+        subjects(&dir, &["--author-date-order", &merge]),
+        ["merge", "b", "a", "d", "c", "root"]
+    );
+
+    // A parent is never listed before its children, whatever its date.
+    let late_root = commit(&dir, "late root", &[], 900, 900);
+    let child = commit(&dir, "child", &[&late_root], 850, 850);
+    assert_eq!(
+        subjects(&dir, &["--date-order", &child]),
+        ["child", "late root"]
+    );
+    assert_eq!(
+        subjects(&dir, &["--first-parent", &merge]),
+        ["merge", "d", "c", "root"]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn shallow_commits_and_grafts_end_history() {
+    let dir = repository("log-shallow");
+    let first = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    let second = commit(&dir, "second", &[&first], 200, 200);
+    let third = commit(&dir, "third", &[&second], 300, 300);
+    let other = commit(&dir, "other", &[], 50, 50);
+
+    // A shallow clone has the boundary commit but not its parents.
+    fs::write(dir.join(".git/shallow"), format!("{}\n", second)).unwrap();
+    fs::remove_file(dir.join(".git/objects").join(&first[..2]).join(&first[2..])).unwrap();
+    assert_eq!(subjects(&dir, &[&third]), ["third", "second"]);
+    assert_eq!(
+        subjects(&dir, &["--date-order", &third]),
+        ["third", "second"]
+    );
+    let shown = mygit(&dir, &["--no-pager", "show", &second]);
+    assert!(shown.contains("new file mode"));
+    fs::remove_file(dir.join(".git/shallow")).unwrap();
+
+    // Grafts replace a commit's parents.
+    fs::create_dir_all(dir.join(".git/info")).unwrap();
+    fs::write(
+        dir.join(".git/info/grafts"),
+        format!("# comment\n{} {}\n", third, other),
+    )
+    .unwrap();
+    assert_eq!(subjects(&dir, &[&third]), ["third", "other"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Compared with git's own output, in the topo order that is the default
+// here, where git is installed.
+#[test]
+fn output_matches_git() {
+    let dir = repository("log-format");
+    let root = commit(&dir, "root", &[], 1700000000, 1700000000);
+    let side = commit(
+        &dir,
+        "side\n\nwith a body",
+        &[&root],
+        1700000100,
+        1700000100,
+    );
+    let main = commit(&dir, "main", &[&root], 1700000200, 1700000200);
+    let merge = commit(&dir, "merge", &[&main, &side], 1700000300, 1700000300);
+
+    let log = mygit(&dir, &["--no-pager", "log", &merge]);
+    let entry = |hash: &str, extra: &str, date: &str, message: &str| {
+        format!(
+            "commit {}\n{}Author: Test <test@example.com>\nDate:   {} 2023 +0000\n\n{}\n",
+            hash, extra, date, message
+        )
+    };
+    assert_eq!(
+        log,
+        [
+            entry(
+                &merge,
+                &format!("Merge: {} {}\n", &main[..7], &side[..7]),
+                "Tue Nov 14 22:18:20",
+                "    merge",
+            ),
+            entry(
+                &side,
+                "",
+                "Tue Nov 14 22:15:00",
+                "    side\n    \n    with a body",
+            ),
+            entry(&main, "", "Tue Nov 14 22:16:40", "    main"),
+            entry(&root, "", "Tue Nov 14 22:13:20", "    root"),
+        ]
+        .join("\n")
+    );
+
+    let git = Command::new("git")
+        .args(["--no-pager", "log", "--no-decorate", "--topo-order", &merge])
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .output();
+    if let Some(git) = git.ok().filter(|output| output.status.success()) {
+        assert_eq!(log, String::from_utf8(git.stdout).unwrap());
+    }
+
+    // A missing parent ends the history instead of failing.
+    let objects = dir.join(".git/objects");
+    fs::remove_file(objects.join(&root[..2]).join(&root[2..])).unwrap();
+    assert_eq!(subjects(&dir, &[&side]), ["side", "", "with a body"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::{mygit, mygit_with_input, repository, run};
+use std::fs;
+
+// Lists a tree whose hashes, and so whose listing, git gives too.
+#[test]
+fn listing_matches_git() {
+    let dir = repository("ls-tree");
+    fs::write(dir.join("hello"), "hello\n").unwrap();
+    let hello = mygit(&dir, &["hash-object", "-w", "hello"]);
+    let sub = mygit_with_input(
+        &dir,
+        &["mktree"],
+        &format!("100644 blob {}\thello.txt\n", hello.trim()),
+    );
+    let tree = mygit_with_input(
+        &dir,
+        &["mktree"],
+        &format!(
+            "100644 blob 78981922613b2afb6025042ff6bd878ac1994e85\ta\n\
+             040000 tree {}\tsub\n\
+             100755 blob {}\trun.sh\n",
+            sub.trim(),
+            hello.trim()
+        ),
+    );
+    let tree = tree.trim();
+    assert_eq!(tree, "2c655dfe6fbef0029facf2a54e64d34571f8b02c");
+
+    assert_eq!(
+        mygit(&dir, &["ls-tree", tree]),
+        "100644 blob 78981922613b2afb6025042ff6bd878ac1994e85\ta\n\
+         100755 blob ce013625030ba8dba906f756967f9e9ca394464a\trun.sh\n\
+         040000 tree aaa96ced2d9a1c8e72c56b253a0e2fe78393feb7\tsub\n"
+    );
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "-r", tree]),
+        "100644 blob 78981922613b2afb6025042ff6bd878ac1994e85\ta\n\
+         100755 blob ce013625030ba8dba906f756967f9e9ca394464a\trun.sh\n\
+         100644 blob ce013625030ba8dba906f756967f9e9ca394464a\tsub/hello.txt\n"
+    );
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "--name-only", tree]),
+        "a\nrun.sh\nsub\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn paths_are_relative_to_the_current_directory() {
+    let dir = repository("ls-tree-cwd");
+    fs::create_dir_all(dir.join("sub/deeper")).unwrap();
+    fs::write(dir.join("sub/b"), "b\n").unwrap();
+    fs::write(dir.join("sub/deeper/c"), "c\n").unwrap();
+    mygit(&dir, &["add", "sub"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    let sub = dir.join("sub");
+
+    assert_eq!(
+        mygit(&sub, &["ls-tree", "--name-only", "HEAD"]),
+        "b\ndeeper\n"
+    );
+    assert_eq!(
+        mygit(&sub, &["ls-tree", "-r", "--name-only", "HEAD"]),
+        "b\ndeeper/c\n"
+    );
+    assert_eq!(
+        mygit(&sub, &["ls-tree", "--name-only", "HEAD", "b", "../a"]),
+        "../a\nb\n"
+    );
+    assert_eq!(
+        mygit(&sub, &["ls-tree", "--name-only", "HEAD", "deeper/"]),
+        "deeper/c\n"
+    );
+    assert_eq!(
+        mygit(
+            &sub.join("deeper"),
+            &["ls-tree", "--name-only", "HEAD", ".."]
+        ),
+        "../b\n../deeper\n"
+    );
+
+    // --full-tree lists from the root, whatever the current directory.
+    let full = mygit(&dir, &["ls-tree", "-r", "HEAD"]);
+    assert_eq!(mygit(&sub, &["ls-tree", "-r", "--full-tree", "HEAD"]), full);
+    assert_eq!(
+        mygit(
+            &sub,
+            &["ls-tree", "--full-tree", "--name-only", "HEAD", "sub/b"]
+        ),
+        "sub/b\n"
+    );
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "--full-tree", "--name-only", "HEAD"]),
+        mygit(&dir, &["ls-tree", "--name-only", "HEAD"])
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn trees_named_by_path_are_listed() {
+    let dir = repository("ls-tree-path");
+    fs::create_dir_all(dir.join("lib/sub")).unwrap();
+    fs::write(dir.join("lib/b"), "b\n").unwrap();
+    fs::write(dir.join("lib/sub/c"), "c\n").unwrap();
+    mygit(&dir, &["add", "lib"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "--name-only", "HEAD:lib"]),
+        "b\nsub\n"
+    );
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "-r", "--name-only", "main~0:lib/sub"]),
+        "c\n"
+    );
+    let sub = mygit(&dir, &["rev-parse", "HEAD:lib/sub"]);
+    assert_eq!(mygit(&dir, &["ls-tree", "--name-only", sub.trim()]), "c\n");
+    assert!(!run(&dir, &["ls-tree", "HEAD:lib/b"]).status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
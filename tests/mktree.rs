@@ -0,0 +1,69 @@
+mod common;
+
+use common::{mygit, mygit_with_input, repository, run_with_input};
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+
+#[test]
+fn listings_round_trip() {
+    let dir = repository("mktree");
+    fs::create_dir_all(dir.join("lib/sub")).unwrap();
+    fs::write(dir.join("lib/b"), "b\n").unwrap();
+    fs::write(dir.join("lib/sub/c"), "c\n").unwrap();
+    fs::write(dir.join("run.sh"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(dir.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    symlink("a", dir.join("link")).unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "second"]);
+
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"]);
+    let listing = mygit(&dir, &["ls-tree", "HEAD"]);
+    assert_eq!(mygit_with_input(&dir, &["mktree"], &listing), tree);
+    let lib = mygit(&dir, &["ls-tree", "HEAD:lib"]);
+    assert_eq!(
+        mygit_with_input(&dir, &["mktree"], &lib),
+        mygit(&dir, &["rev-parse", "HEAD:lib"])
+    );
+
+    // Order does not matter, and a submodule's commit need not be present.
+    let mut lines: Vec<&str> = listing.lines().rev().collect();
+    let module = format!("160000 commit {}\tmodule", "1".repeat(40));
+    lines.push(&module);
+    let with_module = mygit_with_input(&dir, &["mktree"], &(lines.join("\n") + "\n"));
+    let mut expected: Vec<&str> = listing.lines().collect();
+    expected.insert(4, &module);
+    assert_eq!(
+        mygit(&dir, &["ls-tree", with_module.trim()]),
+        expected.join("\n") + "\n"
+    );
+    assert_eq!(
+        mygit_with_input(&dir, &["mktree"], ""),
+        "4b825dc642cb6eb9a060e54bf8d69288fbee4904\n"
+    );
+
+    let blob = mygit(&dir, &["rev-parse", "HEAD:a"]);
+    let blob = blob.trim();
+    let missing = "2".repeat(40);
+    let invalid = [
+        (format!("100644 blob {} a", blob), "input format error"),
+        (format!("100644 blob {}\tx/y", blob), "contains slash"),
+        (format!("100600 blob {}\ta", blob), "invalid mode"),
+        (
+            format!("040000 blob {}\ta", blob),
+            "doesn't match mode type",
+        ),
+        (format!("040000 tree {}\ta", blob), "is a blob"),
+        (
+            format!("100644 blob {}\ta", &blob[..7]),
+            "input format error",
+        ),
+        (format!("100644 blob {}\ta", missing), "unavailable"),
+    ];
+    for (line, message) in invalid {
+        let output = run_with_input(&dir, &["mktree"], &(line.clone() + "\n"));
+        assert_eq!(output.status.code(), Some(128), "{}", line);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(message), "{}: {}", line, stderr);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+
+#[test]
+fn notes_are_attached_to_the_named_object() {
+    let dir = repository("notes");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    mygit(&dir, &["notes", "add", "-m", "hello", "HEAD"]);
+
+    assert_eq!(mygit(&dir, &["notes", "show", "HEAD"]), "hello\n");
+    assert_eq!(mygit(&dir, &["notes", "show", &head]), "hello\n");
+    assert_eq!(mygit(&dir, &["notes", "show", &head[..7]]), "hello\n");
+    assert_eq!(mygit(&dir, &["notes", "show"]), "hello\n");
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "--name-only", "refs/notes/commits"]),
+        format!("{}\n", head)
+    );
+
+    let output = run(&dir, &["notes", "add", "-m", "again", &head]);
+    assert!(!output.status.success());
+    mygit(&dir, &["notes", "add", "-f", "-m", "again", "main"]);
+    assert_eq!(mygit(&dir, &["notes", "show", "HEAD"]), "again\n");
+
+    let blob = mygit(&dir, &["rev-parse", "HEAD:a"]).trim().to_string();
+    assert!(!run(&dir, &["notes", "show", &blob]).status.success());
+    assert!(!run(&dir, &["notes", "show", "missing"]).status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,57 @@
+use mygit::object::{Object, ObjectBatch};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn stored(objects: &Path, hash: &str) -> Vec<u8> {
+    fs::read(objects.join(&hash[..2]).join(&hash[2..])).unwrap()
+}
+
+// A batch stores exactly what writing each object on its own would.
+#[test]
+fn batched_writes_match_individual_ones() {
+    let dir = env::temp_dir().join(format!("mygit-object-batch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let (single, batched) = (dir.join("single"), dir.join("batched"));
+    fs::create_dir_all(&single).unwrap();
+    fs::create_dir_all(&batched).unwrap();
+
+    let objects: Vec<Object> = (0..100)
+        .map(|n| Object::Blob(format!("blob {}\n", n % 60).into_bytes()))
+        .collect();
+    let hashes: Vec<String> = objects
+        .iter()
+        .map(|object| object.write_in(&single).unwrap())
+        .collect();
+
+    let mut batch = ObjectBatch::new();
+    let added: Vec<String> = objects
+        .iter()
+        .map(|object| batch.add(object).unwrap())
+        .collect();
+    assert_eq!(added, hashes);
+    assert!(
+        !batched.join(&hashes[0][..2]).exists(),
+        "written before flush"
+    );
+    batch.flush_into(&batched, true).unwrap();
+
+    for hash in &hashes {
+        assert_eq!(stored(&batched, hash), stored(&single, hash), "{}", hash);
+    }
+    let count = |objects: &Path| {
+        fs::read_dir(objects)
+            .unwrap()
+            .map(|dir| fs::read_dir(dir.unwrap().path()).unwrap().count())
+            .sum::<usize>()
+    };
+    assert_eq!(count(&batched), 60);
+    assert_eq!(count(&single), 60);
+
+    // Flushing again, or flushing objects already stored, changes nothing.
+    batch.flush_into(&batched, true).unwrap();
+    batch.add(&objects[0]).unwrap();
+    batch.flush_into(&batched, false).unwrap();
+    assert_eq!(count(&batched), 60);
+    fs::remove_dir_all(&dir).unwrap();
+}
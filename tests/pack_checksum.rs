@@ -0,0 +1,88 @@
+mod common;
+
+use common::{mygit, mygit_with_input, repository, run};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn written_packs_carry_checksums() {
+    let dir = repository("pack-checksum");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"])
+        .trim()
+        .to_string();
+    let a = mygit(&dir, &["rev-parse", "HEAD:a"]).trim().to_string();
+    let ignore = mygit(&dir, &["rev-parse", "HEAD:.gitignore"]);
+    fs::create_dir_all(dir.join(".git/objects/pack")).unwrap();
+    let name = mygit_with_input(
+        &dir,
+        &["pack-objects", ".git/objects/pack/pack"],
+        &format!("{}\n{}\n{}\n{}", head, tree, a, ignore),
+    );
+    let name = name.trim();
+    let base = dir.join(".git/objects/pack").join(format!("pack-{}", name));
+    let (idx, pack) = (base.with_extension("idx"), base.with_extension("pack"));
+    let pack_data = fs::read(&pack).unwrap();
+    // The pack is named after its trailer, which the index repeats.
+    assert_eq!(hex(&pack_data[pack_data.len() - 20..]), name);
+    let idx_data = fs::read(&idx).unwrap();
+    assert_eq!(
+        &idx_data[idx_data.len() - 40..idx_data.len() - 20],
+        &pack_data[pack_data.len() - 20..]
+    );
+
+    let idx_arg = format!(".git/objects/pack/pack-{}.idx", name);
+    assert_eq!(
+        mygit(&dir, &["verify-pack", &idx_arg]),
+        format!("{}: ok\n", pack.strip_prefix(&dir).unwrap().display())
+    );
+    // git itself accepts the pack, when it is around to ask.
+    if let Ok(output) = Command::new("git")
+        .args(["verify-pack", "-v"])
+        .arg(&idx)
+        .output()
+    {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let listing = String::from_utf8_lossy(&output.stdout);
+        for hash in [&head, &tree, &a] {
+            assert!(listing.contains(hash.as_str()), "{}", listing);
+        }
+    }
+
+    // A corrupted pack trailer fails verification, and prune-packed then
+    // keeps the loose copies.
+    let mut corrupted = pack_data.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    fs::write(&pack, &corrupted).unwrap();
+    let output = run(&dir, &["verify-pack", &idx_arg]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("checksum mismatch"));
+    assert!(!run(&dir, &["prune-packed"]).status.success());
+    assert!(dir
+        .join(".git/objects")
+        .join(&a[..2])
+        .join(&a[2..])
+        .exists());
+
+    // So does a corrupted index, which is checked whenever it is opened.
+    fs::write(&pack, &pack_data).unwrap();
+    mygit(&dir, &["prune-packed"]);
+    assert_eq!(mygit(&dir, &["cat-file", "-p", &a]), "a\n");
+    let mut corrupted = idx_data.clone();
+    corrupted[8] ^= 1;
+    fs::write(&idx, &corrupted).unwrap();
+    for name in [&a[..], &a[..7]] {
+        let output = run(&dir, &["cat-file", "-p", name]);
+        assert_eq!(output.status.code(), Some(128));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("checksum mismatch"));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use common::{mygit, repository, run};
+use mygit::pack::{Pack, PackIndex};
+use mygit::repository::Repository;
+use std::fs;
+use std::path::Path;
+
+// The fixture packs hold the same three commits of one file, made by git
+// with fixed dates; `first` and `second`'s versions of the file are stored
+// as deltas against `third`'s, one pack using OFS_DELTA entries and the
+// other REF_DELTA.
+const THIRD: &str = "c8509a4c55a5c58951a4ab1c3a3fc29c0bcdec41";
+const THIRD_TREE: &str = "97508bee08471f7ff0aad3396a44c8093d7d1d53";
+const FIRST_FILE: &str = "9d904a0e65bceeb68066d4987ae4a1cb77d3dbdc";
+const SECOND_FILE: &str = "02237bf456da8784f6d16a80a9239b0ed90cab8b";
+
+fn file(lines: usize, hundred: &str) -> String {
+    (1..=lines)
+        .map(|n| match n {
+            100 => format!("line {}\n", hundred),
+            n => format!("line {}\n", n),
+        })
+        .collect()
+}
+
+#[test]
+fn objects_are_read_from_fixture_packs() {
+    for name in ["pack-ofs-delta", "pack-ref-delta"] {
+        let dir = repository(name);
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        fs::create_dir_all(dir.join(".git/objects/pack")).unwrap();
+        for ext in ["pack", "idx"] {
+            let file = format!("{}.{}", name, ext);
+            fs::copy(
+                fixtures.join(&file),
+                dir.join(".git/objects/pack").join(&file),
+            )
+            .unwrap();
+        }
+
+        let commit = mygit(&dir, &["cat-file", "-p", &THIRD[..7]]);
+        assert!(
+            commit.starts_with(&format!("tree {}\n", THIRD_TREE)),
+            "{}",
+            name
+        );
+        assert!(commit.ends_with("\n\nthird\n"), "{}", name);
+        assert_eq!(mygit(&dir, &["cat-file", "-t", THIRD_TREE]), "tree\n");
+
+        assert_eq!(
+            mygit(&dir, &["cat-file", "-p", FIRST_FILE]),
+            file(200, "100"),
+            "{}",
+            name
+        );
+        assert_eq!(
+            mygit(&dir, &["cat-file", "-p", SECOND_FILE]),
+            file(200, "one hundred"),
+            "{}",
+            name
+        );
+        assert_eq!(
+            mygit(&dir, &["cat-file", "-p", &format!("{}~2:file", THIRD)]),
+            file(200, "100")
+        );
+        assert_eq!(
+            mygit(&dir, &["cat-file", "-s", &format!("{}:file", THIRD)]),
+            format!("{}\n", file(201, "one hundred").len())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+// With the `mmap` feature `Pack::open` maps the pack; every object, deltas
+// included, must come out as the buffered reader gives it.
+#[test]
+fn mapped_and_buffered_reads_agree() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for name in ["pack-ofs-delta", "pack-ref-delta"] {
+        let index = PackIndex::open(fixtures.join(format!("{}.idx", name))).unwrap();
+        let opened = Pack::open(index.pack_path()).unwrap();
+        let buffered = Pack::open_buffered(index.pack_path()).unwrap();
+        assert!(!index.hashes().is_empty());
+        for hash in index.hashes() {
+            assert_eq!(
+                opened.read(&index, hash, &fixtures).unwrap(),
+                buffered.read(&index, hash, &fixtures).unwrap(),
+                "{} in {}",
+                hash,
+                name
+            );
+        }
+    }
+}
+
+#[test]
+fn existence_is_checked_in_loose_and_packed_objects() {
+    let dir = repository("contains-all");
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    fs::create_dir_all(dir.join(".git/objects/pack")).unwrap();
+    for ext in ["pack", "idx"] {
+        let file = format!("pack-ofs-delta.{}", ext);
+        fs::copy(
+            fixtures.join(&file),
+            dir.join(".git/objects/pack").join(&file),
+        )
+        .unwrap();
+    }
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    let upper = FIRST_FILE.to_ascii_uppercase();
+    let absent = "0".repeat(40);
+
+    let repo = Repository::open(&dir).unwrap();
+    assert_eq!(
+        repo.contains_all(&[
+            head.trim(),
+            THIRD,
+            &absent,
+            SECOND_FILE,
+            &upper,
+            &THIRD[..7],
+            THIRD_TREE,
+        ])
+        .unwrap(),
+        vec![true, true, false, true, true, false, true]
+    );
+    assert!(repo.contains_all(&[]).unwrap().is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// `--pack` reads from the one pack named, even one outside the repository.
+#[test]
+fn objects_are_read_from_a_named_pack() {
+    let dir = repository("cat-file-pack");
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    assert!(!run(&dir, &["cat-file", "-p", FIRST_FILE]).status.success());
+    for name in ["pack-ofs-delta", "pack-ref-delta"] {
+        let idx = fixtures.join(format!("{}.idx", name)).display().to_string();
+        assert_eq!(
+            mygit(&dir, &["cat-file", "-p", FIRST_FILE, "--pack", &idx]),
+            file(200, "100"),
+            "{}",
+            name
+        );
+        assert_eq!(
+            mygit(&dir, &["cat-file", "-p", &SECOND_FILE[..7], "--pack", &idx]),
+            file(200, "one hundred"),
+            "{}",
+            name
+        );
+        let commit = mygit(&dir, &["cat-file", "-p", THIRD, "--pack", &idx]);
+        assert!(commit.starts_with(&format!("tree {}\n", THIRD_TREE)));
+        assert!(mygit(&dir, &["cat-file", "-p", THIRD_TREE, "--pack", &idx]).ends_with("\tfile\n"));
+
+        let head = mygit(&dir, &["rev-parse", "HEAD"]);
+        let output = run(&dir, &["cat-file", "-p", head.trim(), "--pack", &idx]);
+        assert_eq!(output.status.code(), Some(128));
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("does not name exactly one object in")
+        );
+        // Every hash starts with some hex digit, so an empty prefix matches
+        // them all.
+        assert!(!run(&dir, &["cat-file", "-p", "", "--pack", &idx])
+            .status
+            .success());
+    }
+    assert!(!run(
+        &dir,
+        &["cat-file", "-p", FIRST_FILE, "--pack", "missing.idx"]
+    )
+    .status
+    .success());
+    assert_eq!(
+        run(&dir, &["cat-file", "-p", FIRST_FILE, "--pack"])
+            .status
+            .code(),
+        Some(129)
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::{command, mygit, repository};
+use std::fs;
+
+// The pager would leave a file behind, so its absence shows that output
+// which is not going to a terminal bypasses it.
+#[test]
+fn output_that_is_not_a_terminal_is_not_paged() {
+    let dir = repository("pager");
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+
+    for args in [&["log"][..], &["diff", "HEAD~1", "HEAD"], &["show", "HEAD"]] {
+        for variable in ["GIT_PAGER", "PAGER"] {
+            let output = command(&dir, args)
+                .env(variable, "touch paged; cat")
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}", args);
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            assert!(!stdout.is_empty(), "{:?}", args);
+            assert_eq!(
+                stdout,
+                mygit(
+                    &dir,
+                    &["--no-pager"]
+                        .iter()
+                        .chain(args)
+                        .copied()
+                        .collect::<Vec<_>>()
+                ),
+                "{:?}",
+                args
+            );
+            assert!(!dir.join("paged").exists(), "{:?} paged", args);
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
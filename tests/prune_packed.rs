@@ -0,0 +1,49 @@
+mod common;
+
+use common::{mygit, mygit_with_input, repository};
+use std::fs;
+use std::path::Path;
+
+fn loose(dir: &Path, hash: &str) -> std::path::PathBuf {
+    dir.join(".git/objects").join(&hash[..2]).join(&hash[2..])
+}
+
+#[test]
+fn only_packed_loose_objects_are_pruned() {
+    let dir = repository("prune-packed");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    let a = mygit(&dir, &["rev-parse", "HEAD:a"]).trim().to_string();
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"])
+        .trim()
+        .to_string();
+    fs::create_dir_all(dir.join(".git/objects/pack")).unwrap();
+    mygit_with_input(
+        &dir,
+        &["pack-objects", ".git/objects/pack/pack"],
+        &format!("{}\n{}\n", head, a),
+    );
+
+    let dry_run = mygit(&dir, &["prune-packed", "-n"]);
+    let mut expected: Vec<String> = [&head, &a]
+        .iter()
+        .map(|hash| format!("rm -f .git/objects/{}/{}", &hash[..2], &hash[2..]))
+        .collect();
+    expected.sort();
+    let mut listed: Vec<&str> = dry_run.lines().collect();
+    listed.sort();
+    assert_eq!(listed, expected);
+    assert!(loose(&dir, &head).exists() && loose(&dir, &a).exists());
+
+    assert_eq!(mygit(&dir, &["prune-packed"]), "");
+    assert!(!loose(&dir, &head).exists());
+    assert!(!loose(&dir, &a).exists());
+    assert!(loose(&dir, &tree).exists());
+
+    // What was pruned is still read from the pack.
+    assert_eq!(mygit(&dir, &["cat-file", "-p", &a]), "a\n");
+    assert_eq!(mygit(&dir, &["cat-file", "-t", &head[..7]]), "commit\n");
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD:a"]).trim(), a);
+    assert!(mygit(&dir, &["log"]).contains("    first\n"));
+    assert_eq!(mygit(&dir, &["prune-packed", "--dry-run"]), "");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::{commit, mygit, repository, run};
+use std::fs;
+use std::path::Path;
+
+fn rev_parse(gitdir: &Path, name: &str) -> String {
+    fs::read_to_string(gitdir.join(name))
+        .unwrap()
+        .trim()
+        .to_string()
+}
+
+#[test]
+fn only_fast_forwards_are_pushed_unless_forced() {
+    let dir = repository("push");
+    let bare = dir.with_extension("git");
+    mygit(&dir, &["clone", "--bare", ".", &bare.display().to_string()]);
+    let url = fs::canonicalize(&bare).unwrap().display().to_string();
+    fs::write(
+        dir.join(".git/config"),
+        format!("[remote \"origin\"]\n\turl = {}\n", url),
+    )
+    .unwrap();
+    let first = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+
+    let second = commit(&dir, &[("a", Some(b"second\n"))], "second");
+    assert_eq!(
+        mygit(&dir, &["push", "origin", "main"]),
+        format!(
+            "To {}\n{:<20} main -> main\n",
+            url,
+            format!("   {}..{}", &first[..7], &second[..7])
+        )
+    );
+    assert_eq!(rev_parse(&bare, "refs/heads/main"), second);
+    assert_eq!(mygit(&dir, &["rev-parse", "origin/main"]).trim(), second);
+    // The pushed objects arrived with it.
+    let check = dir.with_extension("check");
+    mygit(&dir, &["clone", &url, &check.display().to_string()]);
+    assert_eq!(fs::read_to_string(check.join("a")).unwrap(), "second\n");
+    assert_eq!(mygit(&check, &["cat-file", "-p", "HEAD~1:a"]), "a\n");
+    fs::remove_dir_all(&check).unwrap();
+    assert_eq!(
+        mygit(&dir, &["push", "origin", "main"]),
+        format!("To {}\nEverything up-to-date\n", url)
+    );
+
+    // A commit that does not build on the remote's is refused.
+    mygit(&dir, &["switch", "-c", "other", &first]);
+    let other = commit(&dir, &[("a", Some(b"other\n"))], "other");
+    let output = run(&dir, &["push", "origin", "other:main"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "To {}\n ! [rejected]        other -> main (non-fast-forward)\n",
+            url
+        )
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("fatal: failed to push some refs to '{}'\n", url)
+    );
+    assert_eq!(rev_parse(&bare, "refs/heads/main"), second);
+
+    assert_eq!(
+        mygit(&dir, &["push", "--force", "origin", "other:main"]),
+        format!(
+            "To {}\n{:<20} other -> main\n",
+            url,
+            format!(" + {}...{}", &second[..7], &other[..7])
+        )
+    );
+    assert_eq!(rev_parse(&bare, "refs/heads/main"), other);
+
+    mygit(&dir, &["tag", "v1"]);
+    assert_eq!(
+        mygit(&dir, &["push", "origin", "v1"]),
+        format!("To {}\n * [new tag]         v1 -> v1\n", url)
+    );
+    assert_eq!(rev_parse(&bare, "refs/tags/v1"), other);
+    assert_eq!(
+        mygit(&dir, &["push", &url, "other"]),
+        format!("To {}\n * [new branch]      other -> other\n", url)
+    );
+    assert_eq!(rev_parse(&bare, "refs/heads/other"), other);
+
+    let output = run(&dir, &["push", "origin", "missing"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: src refspec refs/heads/missing does not match any\n"
+    );
+    fs::remove_dir_all(&bare).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// A branch checked out in a non-bare repository would no longer match its
+// working tree.
+#[test]
+fn checked_out_branches_are_not_pushed_to() {
+    let dir = repository("push-checked-out");
+    let clone = dir.with_extension("clone");
+    mygit(&dir, &["clone", ".", &clone.display().to_string()]);
+    fs::copy(dir.join(".gitconfig"), clone.join(".gitconfig")).unwrap();
+    commit(&clone, &[("a", Some(b"second\n"))], "second");
+    let first = mygit(&dir, &["rev-parse", "HEAD"]);
+
+    let output = run(&clone, &["push", "origin", "main"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains(" ! [remote rejected] main -> main (branch is currently checked out)\n"));
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD"]), first);
+
+    mygit(&clone, &["push", "origin", "main:pushed"]);
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "pushed"]),
+        mygit(&clone, &["rev-parse", "HEAD"])
+    );
+    fs::remove_dir_all(&clone).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
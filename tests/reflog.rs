@@ -0,0 +1,129 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 60 * 60;
+
+// A log line for a commit made `days` ago.
+fn entry(days: u64, message: &str) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    format!(
+        "{} {} Test <test@example.com> {} +0000\t{}\n",
+        "0".repeat(40),
+        "1".repeat(40),
+        now - days * DAY,
+        message
+    )
+}
+
+fn write_log(dir: &Path, name: &str, entries: &[String]) {
+    fs::write(dir.join(".git/logs").join(name), entries.concat()).unwrap();
+}
+
+#[test]
+fn expired_entries_are_dropped() {
+    let dir = repository("reflog-expire");
+    let entries = [
+        entry(200, "oldest"),
+        entry(100, "old"),
+        entry(40, "recent"),
+        entry(1, "newest"),
+    ];
+    let log = |name: &str| fs::read_to_string(dir.join(".git/logs").join(name)).unwrap();
+
+    write_log(&dir, "HEAD", &entries);
+    mygit(&dir, &["reflog", "expire", "--expire=30.days.ago", "HEAD"]);
+    assert_eq!(log("HEAD"), entries[3..].concat());
+
+    // The default keeps 90 days, and a short name means the branch.
+    write_log(&dir, "refs/heads/main", &entries);
+    mygit(&dir, &["reflog", "expire", "main"]);
+    assert_eq!(log("refs/heads/main"), entries[2..].concat());
+    mygit(&dir, &["reflog", "expire", "--expire=never", "main"]);
+    assert_eq!(log("refs/heads/main"), entries[2..].concat());
+    mygit(&dir, &["reflog", "expire", "--expire=2 weeks", "main"]);
+    assert_eq!(log("refs/heads/main"), entries[3..].concat());
+    mygit(&dir, &["reflog", "expire", "--expire=all", "main"]);
+    assert_eq!(log("refs/heads/main"), "");
+
+    // gc.reflogExpire replaces the default.
+    write_log(&dir, "HEAD", &entries);
+    fs::write(dir.join(".git/config"), "[gc]\n\treflogExpire = 150 days\n").unwrap();
+    mygit(&dir, &["reflog", "expire", "HEAD"]);
+    assert_eq!(log("HEAD"), entries[1..].concat());
+
+    for expire in [
+        "--expire=soon",
+        "--expire=99999999999999999.years.ago",
+        "--expire=-99999999999999999.years.ago",
+    ] {
+        let output = run(&dir, &["reflog", "expire", expire, "HEAD"]);
+        assert_eq!(output.status.code(), Some(128), "{}", expire);
+        assert_eq!(log("HEAD"), entries[1..].concat());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn earlier_values_are_named_by_position() {
+    let dir = repository("reflog-selectors");
+    let rev = |name: &str| mygit(&dir, &["rev-parse", name]).trim().to_string();
+    let first = rev("HEAD");
+    fs::write(dir.join("a"), "second\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    let second = rev("HEAD");
+    fs::write(dir.join("a"), "third\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "third"]);
+    let third = rev("HEAD");
+
+    assert_eq!(rev("HEAD@{0}"), third);
+    assert_eq!(rev("HEAD@{1}"), second);
+    assert_eq!(rev("HEAD@{2}"), first);
+    assert_eq!(rev("@@{1}"), second);
+    assert_eq!(rev("main@{1}"), second);
+    assert_eq!(rev("refs/heads/main@{2}"), first);
+    // Without a name, the current branch's log is read.
+    assert_eq!(rev("@{1}"), second);
+    assert_eq!(rev("HEAD@{1}~1"), first);
+    assert_eq!(rev("main@{1}^{tree}"), rev("HEAD~1^{tree}"));
+
+    // HEAD's log records switches, which the branch's does not.
+    mygit(&dir, &["switch", "-c", "feature", &first]);
+    assert_eq!(rev("HEAD@{1}"), third);
+    assert_eq!(rev("HEAD@{0}"), first);
+    assert_eq!(rev("main@{1}"), second);
+    assert_eq!(rev("@{-1}"), third);
+    mygit(&dir, &["switch", "main"]);
+    assert_eq!(rev("@{-1}"), first);
+    assert_eq!(rev("@{-2}"), third);
+
+    let output = run(&dir, &["rev-parse", "main@{5}"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("log for 'refs/heads/main' only has 3 entries"));
+    for name in [
+        "@{-5}",
+        "@{-0}",
+        "main@{-1}",
+        "missing@{1}",
+        "HEAD@{x}",
+        "HEAD@{18446744073709551615}",
+        "@{-18446744073709551615}",
+    ] {
+        assert_eq!(
+            run(&dir, &["rev-parse", name]).status.code(),
+            Some(128),
+            "{}",
+            name
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{command, commit, mygit, repository};
+use std::fs;
+
+fn subjects(log: &str) -> Vec<&str> {
+    log.lines()
+        .filter_map(|line| line.strip_prefix("    "))
+        .collect()
+}
+
+#[test]
+fn log_follows_replacements() {
+    let dir = repository("replace");
+    let second = commit(&dir, &[("a", Some(b"b\n"))], "second");
+    commit(&dir, &[("a", Some(b"c\n"))], "third");
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"]);
+    let other = mygit(&dir, &["commit-tree", tree.trim(), "-m", "other"]);
+    let other = other.trim();
+
+    mygit(&dir, &["replace", &second, other]);
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/refs/replace").join(&second)).unwrap(),
+        format!("{}\n", other)
+    );
+    let log = mygit(&dir, &["log"]);
+    assert_eq!(subjects(&log), ["third", "other"]);
+    assert!(mygit(&dir, &["cat-file", "-p", &second]).ends_with("\n\nother\n"));
+    // Names still resolve to the original hash; only its content changes.
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD~1"]).trim(), second);
+
+    let original = ["third", "second", "first"];
+    let log = mygit(&dir, &["--no-replace-objects", "log"]);
+    assert_eq!(subjects(&log), original);
+    let output = command(&dir, &["log"])
+        .env("GIT_NO_REPLACE_OBJECTS", "1")
+        .output()
+        .unwrap();
+    assert_eq!(subjects(&String::from_utf8_lossy(&output.stdout)), original);
+
+    // Replacements of replacements are followed too.
+    let another = mygit(&dir, &["commit-tree", tree.trim(), "-m", "another"]);
+    mygit(&dir, &["replace", other, another.trim()]);
+    assert_eq!(subjects(&mygit(&dir, &["log"])), ["third", "another"]);
+
+    mygit(&dir, &["replace", "-d", &second]);
+    assert_eq!(subjects(&mygit(&dir, &["log"])), original);
+    fs::remove_dir_all(&dir).unwrap();
+}
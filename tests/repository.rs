@@ -0,0 +1,73 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::object::{self, Object};
+use mygit::repository::Repository;
+use mygit::revision::Resolved;
+use std::env;
+use std::fs;
+
+#[test]
+fn repositories_outside_the_current_directory() {
+    let dir = repository("repository");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/b"), "b\n").unwrap();
+    mygit(&dir, &["add", "sub/b"]);
+
+    // Anything read from or written to the current directory instead of
+    // the opened repository would fail in this empty one. It is the only
+    // test in this binary, so changing directory disturbs no other.
+    let elsewhere = dir.with_extension("elsewhere");
+    fs::create_dir_all(&elsewhere).unwrap();
+    env::set_current_dir(&elsewhere).unwrap();
+
+    let repo = Repository::open(&dir).unwrap();
+    let head = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    assert_eq!(repo.resolve_ref("HEAD").unwrap(), head);
+    assert_eq!(repo.resolve_ref("refs/heads/main").unwrap(), head);
+    assert!(repo.resolve_ref("refs/heads/missing").is_err());
+    let Object::Commit { message, .. } = repo.read_object(&head[..7]).unwrap() else {
+        panic!("not a commit");
+    };
+    assert_eq!(message, "first\n");
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"])
+        .trim()
+        .to_string();
+    assert_eq!(
+        repo.resolve("main~0^{tree}").unwrap(),
+        Resolved::Tree(tree.clone())
+    );
+    assert_eq!(
+        repo.contains_all(&[&head, &tree, &"0".repeat(40)]).unwrap(),
+        vec![true, true, false]
+    );
+
+    let blob = repo.write_object(&Object::Blob(b"c\n".to_vec())).unwrap();
+    assert_eq!(mygit(&dir, &["cat-file", "-p", &blob]), "c\n");
+    let staged = repo.write_tree().unwrap();
+    assert_eq!(mygit(&dir, &["write-tree"]).trim(), staged);
+    assert!(Repository::open(dir.join("sub")).is_err());
+
+    let tip = repo
+        .rewrite_history("refs/heads/main", |_| Ok(staged.clone()))
+        .unwrap();
+    assert_eq!(mygit(&dir, &["rev-parse", "main"]).trim(), tip);
+    assert_eq!(mygit(&dir, &["rev-parse", "main^{tree}"]).trim(), staged);
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "refs/original/refs/heads/main"]).trim(),
+        head
+    );
+    assert!(fs::read_dir(&elsewhere).unwrap().next().is_none());
+    // The free functions act on the current directory's repository, which
+    // must have a .git of its own.
+    assert!(Repository::current().is_err());
+    assert!(object::find_objects("").is_err());
+
+    env::set_current_dir(dir.join("sub")).unwrap();
+    let repo = Repository::discover().unwrap();
+    assert_eq!(repo.workdir(), dir.canonicalize().unwrap());
+    assert_eq!(repo.gitdir(), dir.canonicalize().unwrap().join(".git"));
+    assert_eq!(repo.resolve_ref("HEAD").unwrap(), tip);
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_dir_all(&elsewhere).unwrap();
+}
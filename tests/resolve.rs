@@ -0,0 +1,131 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::repository::Repository;
+use mygit::revision::Resolved;
+use std::fs;
+
+// root - second - merge (main, tag v1 -> v2)
+//     \         /
+//       side --
+#[test]
+fn every_revision_syntax() {
+    let dir = repository("resolve");
+    let rev = |name: &str| mygit(&dir, &["rev-parse", name]).trim().to_string();
+    let root = rev("HEAD");
+    let root_tree = rev("HEAD^{tree}");
+    fs::create_dir(dir.join("d")).unwrap();
+    fs::write(dir.join("d/b"), "b\n").unwrap();
+    mygit(&dir, &["add", "d/b"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    let second = rev("HEAD");
+    let side = mygit(
+        &dir,
+        &["commit-tree", &root_tree, "-p", &root, "-m", "side"],
+    );
+    let side = side.trim().to_string();
+    let tree = rev("HEAD^{tree}");
+    let merge = mygit(
+        &dir,
+        &[
+            "commit-tree",
+            &tree,
+            "-p",
+            &second,
+            "-p",
+            &side,
+            "-m",
+            "merge",
+        ],
+    );
+    let merge = merge.trim().to_string();
+    fs::write(dir.join(".git/refs/heads/main"), format!("{}\n", merge)).unwrap();
+    mygit(&dir, &["tag", "-m", "one", "v1"]);
+    mygit(&dir, &["tag", "-m", "two", "v2", "v1"]);
+    mygit(&dir, &["tag", "light", &second]);
+    let (v1, v2) = (rev("v1"), rev("v2"));
+    let d = rev("HEAD:d");
+    let b = rev("HEAD:d/b");
+    let a = rev("HEAD:a");
+    fs::write(dir.join("a"), "staged\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    let staged = rev(":a");
+
+    let repo = Repository::open(&dir).unwrap();
+    let commit = |hash: &str| Resolved::Commit(hash.to_string());
+    for (name, expected) in [
+        // Names and hashes.
+        ("HEAD", commit(&merge)),
+        ("@", commit(&merge)),
+        ("main", commit(&merge)),
+        ("refs/heads/main", commit(&merge)),
+        ("heads/main", commit(&merge)),
+        ("light", commit(&second)),
+        (&merge[..], commit(&merge)),
+        (&merge[..7], commit(&merge)),
+        (&merge.to_uppercase()[..10], commit(&merge)),
+        (&a[..7], Resolved::Blob(a.clone())),
+        ("v1", Resolved::Tag(v1.clone())),
+        ("tags/v2", Resolved::Tag(v2.clone())),
+        // Ancestry.
+        ("HEAD~", commit(&second)),
+        ("HEAD~1", commit(&second)),
+        ("HEAD~2", commit(&root)),
+        ("HEAD^", commit(&second)),
+        ("HEAD^1", commit(&second)),
+        ("HEAD^2", commit(&side)),
+        ("HEAD^2~1", commit(&root)),
+        ("HEAD^^", commit(&root)),
+        ("HEAD~0", commit(&merge)),
+        ("HEAD^0", commit(&merge)),
+        ("v2~1", commit(&second)),
+        ("v2^2", commit(&side)),
+        // Peeling.
+        ("v2^{}", commit(&merge)),
+        ("v2^0", commit(&merge)),
+        ("v2^{tag}", Resolved::Tag(v2.clone())),
+        ("v2^{commit}", commit(&merge)),
+        ("v2^{tree}", Resolved::Tree(tree.clone())),
+        ("HEAD^{tree}", Resolved::Tree(tree.clone())),
+        ("HEAD~2^{tree}", Resolved::Tree(root_tree.clone())),
+        ("HEAD^{}", commit(&merge)),
+        // Paths.
+        ("HEAD:", Resolved::Tree(tree.clone())),
+        ("HEAD:a", Resolved::Blob(a.clone())),
+        ("HEAD:d", Resolved::Tree(d.clone())),
+        ("HEAD:d/b", Resolved::Blob(b.clone())),
+        ("v2:d/b", Resolved::Blob(b.clone())),
+        ("HEAD^{tree}:d/", Resolved::Tree(d.clone())),
+        ("HEAD~1:d/b", Resolved::Blob(b.clone())),
+        (":a", Resolved::Blob(staged.clone())),
+        (":./d/b", Resolved::Blob(b.clone())),
+    ] {
+        assert_eq!(repo.resolve(name).unwrap(), expected, "{}", name);
+    }
+
+    for name in [
+        "",
+        "missing",
+        "HEAD~3",
+        "HEAD^3",
+        "HEAD^2^2",
+        "HEAD^{blob}",
+        "HEAD^{tag}",
+        "HEAD^{nonsense}",
+        "HEAD^{tree",
+        "HEAD~x",
+        "HEAD^2x",
+        "v1^{}x",
+        "HEAD:missing",
+        "HEAD:a/b",
+        ":missing",
+        "missing:a",
+        "^{}",
+    ] {
+        assert!(repo.resolve(name).is_err(), "{}", name);
+    }
+    let Err(_) = repo.resolve(&format!("{}^{{commit}}", b)) else {
+        panic!("a blob peeled to a commit");
+    };
+    fs::remove_dir_all(&dir).unwrap();
+}
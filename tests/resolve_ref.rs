@@ -0,0 +1,53 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::refs::MAX_SYMREF_DEPTH;
+use mygit::repository::Repository;
+use std::fs;
+
+#[test]
+fn symbolic_refs_are_followed_to_a_hash() {
+    let dir = repository("resolve-ref");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    let repo = Repository::open(&dir).unwrap();
+    assert_eq!(repo.resolve_ref("HEAD").unwrap(), head);
+    assert_eq!(repo.resolve_ref("refs/heads/main").unwrap(), head);
+
+    // HEAD -> alias -> main.
+    fs::write(dir.join(".git/refs/heads/alias"), "ref: refs/heads/main\n").unwrap();
+    fs::write(dir.join(".git/HEAD"), "ref: refs/heads/alias\n").unwrap();
+    assert_eq!(repo.resolve_ref("HEAD").unwrap(), head);
+
+    // Detached, with the hash written in upper case.
+    fs::write(dir.join(".git/HEAD"), head.to_uppercase()).unwrap();
+    assert_eq!(repo.resolve_ref("HEAD").unwrap(), head);
+
+    let error = repo.resolve_ref("refs/heads/missing").unwrap_err();
+    assert_eq!(error.to_string(), "ref refs/heads/missing does not exist");
+    fs::write(dir.join(".git/HEAD"), "ref: refs/heads/unborn\n").unwrap();
+    let error = repo.resolve_ref("HEAD").unwrap_err();
+    assert_eq!(error.to_string(), "ref HEAD does not exist");
+    fs::write(dir.join(".git/refs/heads/bad"), "not a hash\n").unwrap();
+    let error = repo.resolve_ref("refs/heads/bad").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "ref refs/heads/bad holds neither a hash nor a symbolic ref"
+    );
+
+    // A chain MAX_SYMREF_DEPTH deep resolves; one link more does not, and
+    // neither does a cycle.
+    let link = |n: usize| format!("refs/heads/link{}", n);
+    fs::write(dir.join(".git").join(link(0)), format!("{}\n", head)).unwrap();
+    for n in 1..=MAX_SYMREF_DEPTH + 1 {
+        fs::write(
+            dir.join(".git").join(link(n)),
+            format!("ref: {}\n", link(n - 1)),
+        )
+        .unwrap();
+    }
+    assert_eq!(repo.resolve_ref(&link(MAX_SYMREF_DEPTH)).unwrap(), head);
+    assert!(repo.resolve_ref(&link(MAX_SYMREF_DEPTH + 1)).is_err());
+    fs::write(dir.join(".git/refs/heads/loop"), "ref: refs/heads/loop\n").unwrap();
+    assert!(repo.resolve_ref("refs/heads/loop").is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+use std::path::Path;
+
+fn read(dir: &Path, path: &str) -> String {
+    fs::read_to_string(dir.join(path)).unwrap()
+}
+
+#[test]
+fn only_the_named_paths_are_restored() {
+    let dir = repository("restore");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/b"), "b\n").unwrap();
+    fs::write(dir.join("sub/c"), "c\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+
+    fs::write(dir.join("a"), "changed\n").unwrap();
+    fs::write(dir.join("sub/b"), "changed\n").unwrap();
+    mygit(&dir, &["restore", "--source", "HEAD", "a"]);
+    assert_eq!(read(&dir, "a"), "a\n");
+    assert_eq!(read(&dir, "sub/b"), "changed\n");
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD"]), head);
+    assert_eq!(read(&dir, ".git/HEAD"), "ref: refs/heads/main\n");
+
+    // A directory restores everything under it.
+    fs::write(dir.join("sub/c"), "changed\n").unwrap();
+    mygit(&dir, &["restore", "sub/"]);
+    assert_eq!(read(&dir, "sub/b"), "b\n");
+    assert_eq!(read(&dir, "sub/c"), "c\n");
+
+    // From an older commit, into both the index and the working tree.
+    fs::write(dir.join("a"), "older\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "third"]);
+    mygit(
+        &dir,
+        &["restore", "--source=HEAD~1", "--staged", "--worktree", "a"],
+    );
+    assert_eq!(read(&dir, "a"), "a\n");
+    assert_eq!(
+        mygit(&dir, &["rev-parse", ":a"]),
+        mygit(&dir, &["rev-parse", "HEAD~1:a"])
+    );
+
+    let output = run(&dir, &["restore", "missing"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: pathspec 'missing' did not match any file(s) known to git\n"
+    );
+    let output = run(&dir, &["restore"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: you must specify path(s) to restore\n"
+    );
+    assert!(!run(&dir, &["restore", "--source", "nope", "a"])
+        .status
+        .success());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use common::{mygit, repository, run};
+use mygit::object::Object;
+use std::collections::HashMap;
+use std::fs;
+
+#[test]
+fn names_resolve_to_full_hashes() {
+    let dir = repository("rev-parse");
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    mygit(&dir, &["tag", "v1"]);
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    assert_eq!(head.trim().len(), 40);
+    let first = mygit(&dir, &["rev-parse", "HEAD~1"]);
+    assert_ne!(first, head);
+
+    for name in ["main", "refs/heads/main", "v1", "refs/tags/v1", head.trim()] {
+        assert_eq!(mygit(&dir, &["rev-parse", name]), head, "{}", name);
+    }
+    assert_eq!(mygit(&dir, &["rev-parse", "HEAD^"]), first);
+    assert_eq!(mygit(&dir, &["rev-parse", "main~1"]), first);
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "HEAD", "HEAD^"]),
+        format!("{}{}", head, first)
+    );
+
+    for name in [
+        "nope",
+        "HEAD~2",
+        "HEAD^^",
+        "0123456789abcdef0123456789abcdef01234567",
+    ] {
+        let output = run(&dir, &["rev-parse", name]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(128), "{}: {}", name, stderr);
+        assert_eq!(stderr, format!("fatal: unknown revision {}\n", name));
+        assert!(output.stdout.is_empty());
+    }
+    assert_eq!(run(&dir, &["rev-parse"]).status.code(), Some(129));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn names_outside_refs_are_not_refs() {
+    let dir = repository("rev-parse-ref-names");
+    fs::write(dir.join("x"), "ref: HEAD\n").unwrap();
+    for name in ["../x", "../../x", ".git/../x"] {
+        let output = run(&dir, &["rev-parse", name]);
+        assert_eq!(output.status.code(), Some(128), "{}", name);
+    }
+
+    let output = run(&dir, &["branch", "../../x"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(!dir.join(".git/x").exists());
+    assert_eq!(fs::read_to_string(dir.join("x")).unwrap(), "ref: HEAD\n");
+
+    fs::write(dir.join(".git/refs/heads/garbage"), "not a hash\n").unwrap();
+    let output = run(&dir, &["rev-parse", "garbage"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: ref refs/heads/garbage holds neither a hash nor a symbolic ref\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn verify_and_short() {
+    let dir = repository("rev-parse-short");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "--verify", "HEAD"]),
+        format!("{}\n", head)
+    );
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "--short", "HEAD"]),
+        format!("{}\n", &head[..7])
+    );
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "--short=10", "HEAD"]),
+        format!("{}\n", &head[..10])
+    );
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "--verify", "--short=4", &head[..9]]),
+        format!("{}\n", &head[..4])
+    );
+    for args in [
+        &["rev-parse", "--verify", "HEAD", "HEAD"][..],
+        &["rev-parse", "--verify", "nope"],
+        &["rev-parse", "--verify"],
+        &["rev-parse", "--short=x", "HEAD"],
+    ] {
+        assert_eq!(run(&dir, args).status.code(), Some(128), "{:?}", args);
+    }
+
+    // Store blobs until two share their first five characters.
+    let objects = dir.join(".git/objects");
+    let mut seen = HashMap::new();
+    let (first, second) = (0..)
+        .find_map(|i| {
+            let hash = Object::Blob(format!("{}\n", i).into_bytes())
+                .write_in(&objects)
+                .unwrap();
+            seen.insert(hash[..5].to_string(), hash.clone())
+                .map(|other| (other, hash))
+        })
+        .unwrap();
+    let shared = first
+        .chars()
+        .zip(second.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let output = run(&dir, &["rev-parse", "--verify", &first[..shared]]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("fatal: short object ID {} is ambiguous\n", &first[..shared])
+    );
+    // Abbreviations grow until they tell the two apart.
+    for hash in [&first, &second] {
+        let short = mygit(&dir, &["rev-parse", "--short=4", hash]);
+        assert_eq!(short, format!("{}\n", &hash[..shared + 1]));
+        assert_eq!(
+            mygit(&dir, &["rev-parse", "--verify", short.trim()]),
+            format!("{}\n", hash)
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn upstreams_come_from_branch_config() {
+    let dir = repository("rev-parse-upstream");
+    let rev = |name: &str| mygit(&dir, &["rev-parse", name]).trim().to_string();
+    let first = rev("HEAD");
+    fs::write(dir.join("a"), "second\n").unwrap();
+    mygit(&dir, &["add", "a"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    let second = rev("HEAD");
+    mygit(&dir, &["branch", "feature", &first]);
+    fs::create_dir_all(dir.join(".git/refs/remotes/origin")).unwrap();
+    fs::write(
+        dir.join(".git/refs/remotes/origin/main"),
+        format!("{}\n", first),
+    )
+    .unwrap();
+
+    let output = run(&dir, &["rev-parse", "@{u}"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: no upstream configured for branch 'main'\n"
+    );
+
+    fs::write(
+        dir.join(".git/config"),
+        "[branch \"main\"]\n\tremote = origin\n\tmerge = refs/heads/main\n\
+         [branch \"feature\"]\n\tremote = .\n\tmerge = refs/heads/main\n",
+    )
+    .unwrap();
+    for name in [
+        "@{u}",
+        "@{upstream}",
+        "HEAD@{u}",
+        "@@{u}",
+        "main@{u}",
+        "refs/heads/main@{upstream}",
+    ] {
+        assert_eq!(rev(name), first, "{}", name);
+    }
+    assert_eq!(rev("main@{u}^{tree}"), rev("HEAD~1^{tree}"));
+    // A remote of `.` tracks a local branch.
+    assert_eq!(rev("feature@{u}"), second);
+    mygit(&dir, &["switch", "feature"]);
+    assert_eq!(rev("@{u}"), second);
+
+    for name in ["other@{u}", "main@{upstreams}"] {
+        assert!(
+            !run(&dir, &["rev-parse", name]).status.success(),
+            "{}",
+            name
+        );
+    }
+    // The configured tracking ref must exist.
+    fs::remove_file(dir.join(".git/refs/remotes/origin/main")).unwrap();
+    assert!(!run(&dir, &["rev-parse", "main@{u}"]).status.success());
+    mygit(&dir, &["switch", "--detach", "main"]);
+    assert!(!run(&dir, &["rev-parse", "@{u}"]).status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
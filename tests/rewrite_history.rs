@@ -0,0 +1,54 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::object::{self, Object};
+use mygit::repository::Repository;
+use mygit::revwalk::{self, Order};
+use std::fs;
+
+#[test]
+fn removing_a_file_from_every_commit() {
+    let dir = repository("rewrite-history");
+    fs::write(dir.join("big"), "large\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "add big"]);
+    fs::write(dir.join("big"), "larger\n").unwrap();
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "change both"]);
+    let first = mygit(&dir, &["rev-parse", "HEAD~2"]).trim().to_string();
+    let old_tip = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+
+    let repo = Repository::open(&dir).unwrap();
+    let tip = repo
+        .rewrite_history("refs/heads/main", |tree| {
+            let mut files = object::flatten_tree_in(repo.gitdir(), tree)?;
+            files.remove("big");
+            let entries: Vec<(String, String, String)> = files
+                .into_iter()
+                .map(|(path, (mode, hash))| (path, mode, hash))
+                .collect();
+            repo.build_tree_from_paths(&entries)
+        })
+        .unwrap();
+
+    assert_eq!(mygit(&dir, &["rev-parse", "main"]).trim(), tip);
+    assert_eq!(
+        mygit(&dir, &["rev-parse", "refs/original/refs/heads/main"]).trim(),
+        old_tip
+    );
+    let commits = revwalk::walk_in(repo.gitdir(), &[tip], Order::Topo).unwrap();
+    assert_eq!(commits.len(), 3);
+    for (_, commit) in &commits {
+        let Object::Commit { tree, .. } = commit else {
+            panic!("not a commit");
+        };
+        let files = object::flatten_tree_in(repo.gitdir(), tree).unwrap();
+        assert!(!files.contains_key("big"));
+        assert!(files.contains_key("a"));
+    }
+    // The first commit never had the file, so it is left as it was.
+    assert_eq!(commits[2].0, first);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "b\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
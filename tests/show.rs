@@ -0,0 +1,78 @@
+mod common;
+
+use common::{mygit, repository};
+use std::fs;
+
+#[test]
+fn merges_are_shown_against_their_first_parent() {
+    let dir = repository("show-merge");
+    let base = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+    fs::write(dir.join("b"), "b\n").unwrap();
+    mygit(&dir, &["add", "b"]);
+    let tree = mygit(&dir, &["write-tree"]);
+    let side = mygit(
+        &dir,
+        &["commit-tree", tree.trim(), "-p", &base, "-m", "side"],
+    );
+
+    fs::remove_file(dir.join("b")).unwrap();
+    fs::write(dir.join("a"), "main\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "main"]);
+    let main = mygit(&dir, &["rev-parse", "HEAD"]).trim().to_string();
+
+    fs::write(dir.join("b"), "b\n").unwrap();
+    mygit(&dir, &["add", "b"]);
+    let tree = mygit(&dir, &["write-tree"]);
+    let merge = mygit(
+        &dir,
+        &[
+            "commit-tree",
+            tree.trim(),
+            "-p",
+            &main,
+            "-p",
+            side.trim(),
+            "-m",
+            "merge",
+        ],
+    );
+    let merge = merge.trim();
+
+    let shown = mygit(&dir, &["--no-pager", "show", merge]);
+    assert!(shown.starts_with(&format!(
+        "commit {} (from {})\nMerge: {} {}\n",
+        merge,
+        main,
+        &main[..7],
+        &side[..7]
+    )));
+    assert!(shown.contains("    merge\n"));
+    // Only what the side branch brought in differs from the first parent.
+    assert!(shown.contains("diff --git a/b b/b\nnew file mode 100644\n"));
+    assert!(!shown.contains("a/a"));
+
+    let stat = mygit(&dir, &["--no-pager", "show", "--stat", merge]);
+    assert!(
+        stat.ends_with("\n b | 1 +\n 1 file changed, 1 insertion(+)\n"),
+        "{}",
+        stat
+    );
+    assert!(!stat.contains("diff --git"));
+
+    // Ordinary commits name no parent, and the root commit shows every file.
+    let shown = mygit(&dir, &["--no-pager", "show", "--stat", &main]);
+    assert!(shown.starts_with(&format!("commit {}\nAuthor: ", main)));
+    assert!(
+        shown.ends_with(" a | 2 +-\n 1 file changed, 1 insertion(+), 1 deletion(-)\n"),
+        "{}",
+        shown
+    );
+    let shown = mygit(&dir, &["--no-pager", "show", "--stat", &base]);
+    assert!(
+        shown.contains(" .gitignore | 1 +\n a          | 1 +\n"),
+        "{}",
+        shown
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::{commit, mygit, repository};
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn branches_sharing_a_base() {
+    let dir = repository("show-branch");
+    mygit(&dir, &["checkout", "-b", "feature"]);
+    commit(&dir, &[("f", Some(b"1\n"))], "f1");
+    commit(&dir, &[("f", Some(b"2\n"))], "f2");
+    mygit(&dir, &["switch", "main"]);
+    // Tips are listed newest first, so make m1 strictly newer than f2
+    // rather than depending on whether they share a second.
+    let second = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let start = second(SystemTime::now());
+    while second(SystemTime::now()) == start {
+        thread::sleep(Duration::from_millis(10));
+    }
+    commit(&dir, &[("a", Some(b"m1\n"))], "m1");
+
+    assert_eq!(
+        mygit(&dir, &["show-branch", "main", "feature"]),
+        "* [main] m1\n \
+         ! [feature] f2\n\
+         --\n\
+         *  [main] m1\n \
+         + [feature] f2\n \
+         + [feature^] f1\n\
+         *+ [main^] first\n"
+    );
+    // Without names every branch is listed, in ref order.
+    assert_eq!(
+        mygit(&dir, &["show-branch"]),
+        "! [feature] f2\n \
+         * [main] m1\n\
+         --\n \
+         * [main] m1\n\
+         +  [feature] f2\n\
+         +  [feature^] f1\n\
+         +* [main^] first\n"
+    );
+
+    // A branch contained in another shares all of its commits.
+    mygit(&dir, &["branch", "old", "feature~1"]);
+    assert_eq!(
+        mygit(&dir, &["show-branch", "feature", "old"]),
+        "! [feature] f2\n \
+         ! [old] f1\n\
+         --\n\
+         +  [feature] f2\n\
+         ++ [old] f1\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
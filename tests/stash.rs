@@ -0,0 +1,37 @@
+mod common;
+
+use common::{mygit, repository};
+use std::fs;
+
+#[test]
+fn list_show_and_drop_two_stashes() {
+    let dir = repository("stash");
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["stash"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\n");
+    fs::write(dir.join("a"), "c\n").unwrap();
+    mygit(&dir, &["stash", "-m", "second"]);
+
+    let list = mygit(&dir, &["stash", "list"]);
+    let lines: Vec<&str> = list.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], "stash@{0}: On main: second");
+    assert!(lines[1].starts_with("stash@{1}: WIP on main: "));
+
+    let patch = mygit(&dir, &["--no-pager", "stash", "show", "-p", "1"]);
+    assert!(patch.contains("-a\n+b\n"));
+    let patch = mygit(&dir, &["--no-pager", "stash", "show", "-p"]);
+    assert!(patch.contains("-a\n+c\n"));
+
+    mygit(&dir, &["stash", "drop", "stash@{0}"]);
+    let list = mygit(&dir, &["stash", "list"]);
+    assert_eq!(list.lines().count(), 1);
+    assert!(list.starts_with("stash@{0}: WIP on main: "));
+    let patch = mygit(&dir, &["--no-pager", "stash", "show", "-p"]);
+    assert!(patch.contains("-a\n+b\n"));
+
+    mygit(&dir, &["stash", "drop"]);
+    assert_eq!(mygit(&dir, &["stash", "list"]), "");
+    assert!(!dir.join(".git/refs/stash").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
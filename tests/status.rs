@@ -0,0 +1,75 @@
+mod common;
+
+use common::{mygit, repository};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn staged_unstaged_and_untracked() {
+    let dir = repository("status");
+    assert!(mygit(&dir, &["status"]).ends_with("nothing to commit, working tree clean\n"));
+
+    fs::write(dir.join("a"), "b\n").unwrap();
+    fs::write(dir.join("new"), "new\n").unwrap();
+    mygit(&dir, &["add", "a", "new"]);
+    fs::write(dir.join("a"), "c\n").unwrap();
+    fs::write(dir.join("other"), "other\n").unwrap();
+    assert_eq!(
+        mygit(&dir, &["status"]),
+        "On branch main\n\
+         Changes to be committed:\n\
+         \tmodified:   a\n\
+         \tnew file:   new\n\
+         \n\
+         Changes not staged for commit:\n\
+         \tmodified:   a\n\
+         \n\
+         Untracked files:\n\
+         \tother\n\
+         \n"
+    );
+
+    mygit(&dir, &["add", "a", "other"]);
+    mygit(&dir, &["commit", "-m", "second"]);
+    assert_eq!(
+        mygit(&dir, &["status"]),
+        "On branch main\nnothing to commit, working tree clean\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn executable_bit_changes_are_modifications() {
+    let dir = repository("status-mode");
+    let path = dir.join("a");
+    let chmod = |mode: u32| fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    let unstaged = "On branch main\n\
+                    Changes not staged for commit:\n\
+                    \tmodified:   a\n\
+                    \n\
+                    no changes added to commit (use \"mygit add\")\n";
+
+    chmod(0o755);
+    assert_eq!(mygit(&dir, &["status"]), unstaged);
+    mygit(&dir, &["add", "a"]);
+    assert_eq!(
+        mygit(&dir, &["status"]),
+        "On branch main\n\
+         Changes to be committed:\n\
+         \tmodified:   a\n\
+         \n"
+    );
+    mygit(&dir, &["commit", "-m", "executable"]);
+    assert_eq!(
+        mygit(&dir, &["ls-tree", "HEAD", "a"]).split(' ').next(),
+        Some("100755")
+    );
+    assert!(mygit(&dir, &["status"]).ends_with("working tree clean\n"));
+
+    // Other permission bits are not tracked.
+    chmod(0o700);
+    assert!(mygit(&dir, &["status"]).ends_with("working tree clean\n"));
+    chmod(0o644);
+    assert_eq!(mygit(&dir, &["status"]), unstaged);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::{mygit, repository, run};
+use mygit::object::Object;
+use std::fs;
+use std::io::{self, Write};
+
+// Keeps a running checksum of what it is given instead of the bytes
+// themselves, remembering the largest single write.
+#[derive(Default)]
+struct Sink {
+    len: u64,
+    sum: u64,
+    largest_write: usize,
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.len += buf.len() as u64;
+        self.sum = buf.iter().fold(self.sum, |sum, &b| {
+            sum.wrapping_mul(31).wrapping_add(b as u64)
+        });
+        self.largest_write = self.largest_write.max(buf.len());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn large_blobs_stream_in_small_writes() {
+    let dir = repository("stream-blob");
+    let content: Vec<u8> = (0..8 * 1024 * 1024u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+        .collect();
+    fs::write(dir.join("large"), &content).unwrap();
+    let hash = mygit(&dir, &["hash-object", "-w", "large"]);
+    let hash = hash.trim();
+
+    let mut sink = Sink::default();
+    let written = Object::write_blob_to_in(&dir.join(".git"), hash, &mut sink).unwrap();
+    assert_eq!(written, content.len() as u64);
+    assert_eq!(sink.len, content.len() as u64);
+    let mut expected = Sink::default();
+    expected.write_all(&content).unwrap();
+    assert_eq!(sink.sum, expected.sum);
+    assert!(sink.largest_write <= 64 * 1024, "{}", sink.largest_write);
+
+    let output = run(&dir, &["cat-file", "-p", hash]);
+    assert!(output.status.success());
+    assert!(output.stdout == content);
+    fs::remove_dir_all(&dir).unwrap();
+}
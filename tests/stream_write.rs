@@ -0,0 +1,62 @@
+mod common;
+
+use common::{mygit, repository};
+use mygit::ignore::Ignore;
+use mygit::object::{self, FileMode, Object};
+use std::fs;
+
+#[test]
+fn streamed_blobs_hash_like_in_memory_ones() {
+    let dir = repository("stream-write");
+    let content: Vec<u8> = (0..24 * 1024 * 1024u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+        .collect();
+    fs::write(dir.join("large"), &content).unwrap();
+
+    let objects = dir.join(".git/objects");
+    let large = dir.join("large");
+    let expected = Object::Blob(content.clone()).hash().unwrap();
+    let mut file = fs::File::open(&large).unwrap();
+    let hash =
+        Object::write_blob_from_reader_in(&objects, &mut file, content.len() as u64).unwrap();
+    assert_eq!(hash, expected);
+    let mut file = fs::File::open(&large).unwrap();
+    assert_eq!(
+        object::hash_blob_from_reader(&mut file, content.len() as u64).unwrap(),
+        expected
+    );
+    assert_eq!(
+        object::hash_worktree_blob(&large).unwrap(),
+        (FileMode::Blob, expected.clone())
+    );
+    let scanned = object::scan_worktree(dir.to_str().unwrap(), &Ignore::new()).unwrap();
+    assert_eq!(scanned["large"], ("100644".to_string(), expected.clone()));
+    assert_eq!(
+        mygit(&dir, &["cat-file", "-s", &hash]),
+        format!("{}\n", content.len())
+    );
+
+    // A reader that runs out early leaves nothing behind.
+    let short = Object::write_blob_from_reader_in(&objects, &mut &content[..10], 11);
+    assert!(short.is_err());
+
+    // Trees built from the working directory stream their files too.
+    fs::remove_file(objects.join(&hash[..2]).join(&hash[2..])).unwrap();
+    mygit(&dir, &["add", "large"]);
+    let tree = mygit(&dir, &["write-tree"]);
+    assert_eq!(
+        mygit(&dir, &["rev-parse", &format!("{}:large", tree.trim())]),
+        format!("{}\n", expected)
+    );
+    assert_eq!(
+        mygit(&dir, &["hash-object", "large"]),
+        format!("{}\n", expected)
+    );
+    let leftovers: Vec<_> = fs::read_dir(&objects)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.starts_with("tmp_obj_"))
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::{mygit, repository};
+use std::fs;
+
+#[test]
+fn split_rewrites_a_subdirectory_history() {
+    let dir = repository("subtree-split");
+    fs::create_dir(dir.join("lib")).unwrap();
+    fs::write(dir.join("lib/x"), "1\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "add lib"]);
+    // Leaves lib alone, so it has no counterpart in the split.
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "change a"]);
+    fs::write(dir.join("lib/x"), "2\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "change lib"]);
+
+    let split = mygit(&dir, &["subtree", "split", "--prefix=lib"]);
+    let split = split.trim();
+    let tip = mygit(&dir, &["cat-file", "-p", split]);
+    assert!(tip.starts_with(&format!(
+        "tree {}",
+        mygit(&dir, &["rev-parse", "HEAD:lib"]).trim()
+    )));
+    assert!(tip.ends_with("\n\nchange lib\n"));
+
+    let parent = tip.lines().nth(1).unwrap().strip_prefix("parent ").unwrap();
+    let root = mygit(&dir, &["cat-file", "-p", parent]);
+    assert!(root.starts_with(&format!(
+        "tree {}",
+        mygit(&dir, &["rev-parse", "HEAD~2:lib"]).trim()
+    )));
+    assert!(!root.contains("\nparent "));
+    assert!(root.ends_with("\n\nadd lib\n"));
+
+    // The same history splits to the same commits.
+    assert_eq!(
+        mygit(&dir, &["subtree", "split", "--prefix=lib/", "HEAD"]).trim(),
+        split
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{mygit, repository, run};
+use std::fs;
+use std::path::Path;
+
+fn head(dir: &Path) -> String {
+    fs::read_to_string(dir.join(".git/HEAD")).unwrap()
+}
+
+#[test]
+fn the_working_tree_follows_the_branch() {
+    let dir = repository("switch");
+    assert_eq!(
+        mygit(&dir, &["switch", "-c", "feature"]),
+        "Switched to a new branch 'feature'\n"
+    );
+    fs::write(dir.join("a"), "feature\n").unwrap();
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/b"), "b\n").unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "on feature"]);
+
+    assert_eq!(
+        mygit(&dir, &["switch", "main"]),
+        "Switched to branch 'main'\n"
+    );
+    assert_eq!(head(&dir), "ref: refs/heads/main\n");
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\n");
+    assert!(!dir.join("sub").exists());
+    assert_eq!(
+        mygit(&dir, &["status"]),
+        "On branch main\nnothing to commit, working tree clean\n"
+    );
+
+    mygit(&dir, &["switch", "feature"]);
+    assert_eq!(head(&dir), "ref: refs/heads/feature\n");
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "feature\n");
+    assert_eq!(fs::read_to_string(dir.join("sub/b")).unwrap(), "b\n");
+    assert_eq!(
+        mygit(&dir, &["switch", "feature"]),
+        "Already on 'feature'\n"
+    );
+
+    // Local changes in the way stop the switch, and nothing moves.
+    fs::write(dir.join("a"), "local\n").unwrap();
+    assert!(!run(&dir, &["switch", "main"]).status.success());
+    assert_eq!(head(&dir), "ref: refs/heads/feature\n");
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "local\n");
+    fs::write(dir.join("a"), "feature\n").unwrap();
+
+    // Anything but a branch needs --detach, and paths are not switched to.
+    let output = run(&dir, &["switch", "main~0"]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("use --detach"));
+    let output = run(&dir, &["switch", "a"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "fatal: invalid reference: a\n"
+    );
+    let main = mygit(&dir, &["rev-parse", "main"]);
+    mygit(&dir, &["switch", "--detach", "main"]);
+    assert_eq!(head(&dir), main);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a\n");
+
+    let output = run(&dir, &["switch", "-c", "feature"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    assert_eq!(run(&dir, &["switch"]).status.code(), Some(129));
+    fs::remove_dir_all(&dir).unwrap();
+}
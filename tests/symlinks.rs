@@ -0,0 +1,69 @@
+mod common;
+
+use common::{mygit, repository};
+use std::fs;
+use std::os::unix::fs::symlink;
+
+#[test]
+fn symlinks_are_recorded_as_links_unless_dereferenced() {
+    let dir = repository("symlinks");
+    symlink("a", dir.join("link")).unwrap();
+    symlink("missing", dir.join("dangling")).unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "links"]);
+
+    // Git stores the link's target, "a" with no newline, as the blob.
+    let listing = mygit(&dir, &["ls-tree", "HEAD"]);
+    assert!(
+        listing.contains("120000 blob 2e65efe2a145dda7ee51d1741299f848e5bf752e\tlink\n"),
+        "{}",
+        listing
+    );
+    assert!(
+        listing.contains("120000 blob 6eab79a6ce25b19851f591e3e974e192c6858cf6\tdangling\n"),
+        "{}",
+        listing
+    );
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "HEAD:link"]), "a");
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "HEAD:dangling"]), "missing");
+
+    fs::write(dir.join("a"), "b\n").unwrap();
+    mygit(&dir, &["--dereference-symlinks", "add", "."]);
+    mygit(&dir, &["commit", "-m", "dereferenced"]);
+    let listing = mygit(&dir, &["ls-tree", "HEAD"]);
+    assert!(
+        listing.contains("100644 blob 61780798228d17af2d34fce4cfbdf35556832472\tlink\n"),
+        "{}",
+        listing
+    );
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "HEAD:link"]), "b\n");
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "HEAD:dangling"]), "missing");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn symlinked_directories_are_not_recursed_into() {
+    let dir = repository("symlinked-dirs");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/b"), "b\n").unwrap();
+    symlink("sub", dir.join("alias")).unwrap();
+    symlink("..", dir.join("sub/up")).unwrap();
+    mygit(&dir, &["add", "."]);
+    mygit(&dir, &["commit", "-m", "links"]);
+
+    let listing = mygit(&dir, &["ls-tree", "-r", "--name-only", "HEAD"]);
+    assert_eq!(listing, ".gitignore\na\nalias\nsub/b\nsub/up\n");
+    let listing = mygit(&dir, &["ls-tree", "HEAD"]);
+    assert!(
+        listing.contains("120000 blob 3de0f365ba57c94daac626bf53a7da269b65f57c\talias\n"),
+        "{}",
+        listing
+    );
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "HEAD:alias"]), "sub");
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "HEAD:sub/up"]), "..");
+    assert_eq!(
+        mygit(&dir, &["status"]).lines().last(),
+        Some("nothing to commit, working tree clean")
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::{mygit, repository, run};
+use flate2::write::ZlibEncoder;
+use mygit::object::MAX_TAG_DEPTH;
+use std::fs;
+use std::io::Write;
+
+#[test]
+fn tags_of_tags_peel_to_the_commit() {
+    let dir = repository("tag-of-tag");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    mygit(&dir, &["tag", "-m", "one", "v1"]);
+    mygit(&dir, &["tag", "-m", "two", "v2", "v1"]);
+
+    let v1 = mygit(&dir, &["rev-parse", "v1"]);
+    let v2 = mygit(&dir, &["cat-file", "-p", "v2"]);
+    assert!(v2.starts_with(&format!("object {}type tag\ntag v2\n", v1)));
+    assert_eq!(mygit(&dir, &["cat-file", "-t", "v2"]), "tag\n");
+    for rev in ["v2^{}", "v2^{commit}", "v2~0"] {
+        assert_eq!(mygit(&dir, &["rev-parse", rev]), head, "{}", rev);
+    }
+    let tree = mygit(&dir, &["rev-parse", "HEAD^{tree}"]);
+    assert_eq!(mygit(&dir, &["rev-parse", "v2^{tree}"]), tree);
+    assert_eq!(mygit(&dir, &["cat-file", "-p", "v2:a"]), "a\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tag_chains_are_bounded() {
+    let dir = repository("tag-chain");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    mygit(&dir, &["tag", "-m", "0", "t0"]);
+    for n in 1..MAX_TAG_DEPTH {
+        let (name, target) = (format!("t{}", n), format!("t{}", n - 1));
+        mygit(&dir, &["tag", "-m", &name, &name, &target]);
+    }
+    let deepest = format!("t{}", MAX_TAG_DEPTH - 1);
+    assert_eq!(
+        mygit(&dir, &["rev-parse", &format!("{}^{{}}", deepest)]),
+        head
+    );
+
+    let too_deep = format!("t{}", MAX_TAG_DEPTH);
+    mygit(&dir, &["tag", "-m", "deep", &too_deep, &deepest]);
+    let output = run(&dir, &["rev-parse", &format!("{}^{{commit}}", too_deep)]);
+    assert_eq!(output.status.code(), Some(128));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not peel"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// No real tag can name itself, since its hash covers its own content, so
+// the loop is stored under a made-up name.
+#[test]
+fn cyclic_tags_are_rejected() {
+    let dir = repository("tag-cycle");
+    let hash = "1".repeat(40);
+    let content = format!(
+        "object {}\ntype tag\ntag loop\ntagger Test <test@example.com> 0 +0000\n\nloop\n",
+        hash
+    );
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    write!(encoder, "tag {}\0{}", content.len(), content).unwrap();
+    fs::create_dir_all(dir.join(".git/objects/11")).unwrap();
+    fs::write(
+        dir.join(".git/objects/11").join(&hash[2..]),
+        encoder.finish().unwrap(),
+    )
+    .unwrap();
+    fs::write(dir.join(".git/refs/tags/loop"), format!("{}\n", hash)).unwrap();
+
+    assert_eq!(mygit(&dir, &["cat-file", "-t", "loop"]), "tag\n");
+    for rev in ["loop^{}", "loop^{commit}", "loop^{tree}", "loop~1"] {
+        let output = run(&dir, &["rev-parse", rev]);
+        assert_eq!(output.status.code(), Some(128), "{}", rev);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            format!("fatal: tag {} does not peel to a non-tag object\n", hash),
+            "{}",
+            rev
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn annotated_tags_are_stored_under_refs_tags() {
+    let dir = repository("tag-annotated");
+    let head = mygit(&dir, &["rev-parse", "HEAD"]);
+    mygit(&dir, &["tag", "-a", "v1", "-m", "release 1", "HEAD"]);
+    let tag = fs::read_to_string(dir.join(".git/refs/tags/v1")).unwrap();
+    assert_ne!(tag, head);
+    assert_eq!(mygit(&dir, &["cat-file", "-t", tag.trim()]), "tag\n");
+    let printed = mygit(&dir, &["cat-file", "-p", "v1"]);
+    assert!(
+        printed.starts_with(&format!(
+            "object {}type commit\ntag v1\ntagger Test <test@example.com> ",
+            head
+        )),
+        "{}",
+        printed
+    );
+    assert!(printed.ends_with("\n\nrelease 1\n"), "{}", printed);
+
+    // Any object can be tagged, and the tag records its type.
+    let blob = mygit(&dir, &["rev-parse", "HEAD:a"]);
+    mygit(&dir, &["tag", "-a", "-m", "just a", "blob-tag", "HEAD:a"]);
+    assert!(mygit(&dir, &["cat-file", "-p", "blob-tag"])
+        .starts_with(&format!("object {}type blob\ntag blob-tag\n", blob)));
+    assert_eq!(mygit(&dir, &["rev-parse", "blob-tag^{}"]), blob);
+
+    // Without -a or -m the tag is just a ref.
+    mygit(&dir, &["tag", "light"]);
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/refs/tags/light")).unwrap(),
+        head
+    );
+    assert!(!run(&dir, &["tag", "-a", "-m", "x", "bad", "missing"])
+        .status
+        .success());
+    assert!(!dir.join(".git/refs/tags/bad").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
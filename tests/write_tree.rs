@@ -0,0 +1,34 @@
+mod common;
+
+use common::{mygit, mygit_with_input, repository, run};
+use std::fs;
+
+#[test]
+fn prefix_writes_just_the_subtree() {
+    let dir = repository("write-tree-prefix");
+    fs::create_dir_all(dir.join("lib/sub")).unwrap();
+    fs::write(dir.join("lib/b"), "b\n").unwrap();
+    fs::write(dir.join("lib/sub/c"), "c\n").unwrap();
+
+    let b = mygit(&dir, &["hash-object", "-w", "lib/b"]);
+    let c = mygit(&dir, &["hash-object", "-w", "lib/sub/c"]);
+    let sub = mygit_with_input(&dir, &["mktree"], &format!("100644 blob {}\tc\n", c.trim()));
+    let lib = mygit_with_input(
+        &dir,
+        &["mktree"],
+        &format!(
+            "100644 blob {}\tb\n040000 tree {}\tsub\n",
+            b.trim(),
+            sub.trim()
+        ),
+    );
+
+    mygit(&dir, &["add", "lib"]);
+    // Only what was staged is written.
+    fs::write(dir.join("lib/b"), "changed\n").unwrap();
+    assert_eq!(mygit(&dir, &["write-tree", "--prefix=lib"]), lib);
+    assert_eq!(mygit(&dir, &["write-tree", "--prefix=lib/sub/"]), sub);
+    let output = run(&dir, &["write-tree", "--prefix=nope"]);
+    assert_eq!(output.status.code(), Some(128));
+    fs::remove_dir_all(&dir).unwrap();
+}